@@ -89,6 +89,9 @@ impl rend3_framework::App for CubeExample {
             mesh_kind: rend3::types::ObjectMeshKind::Static(mesh_handle),
             material: material_handle,
             transform: glam::Mat4::IDENTITY,
+            world_offset: glam::DVec3::ZERO,
+            sort_bias: 0,
+            debug_tint: None,
         };
         // Creating an object will hold onto both the mesh and the material
         // even if they are deleted.
@@ -116,6 +119,9 @@ impl rend3_framework::App for CubeExample {
             direction: glam::Vec3::new(-1.0, -4.0, 2.0),
             distance: 400.0,
             resolution: 2048,
+            casts_shadows: true,
+            snap_to_texels: true,
+            angular_size: 0.0,
         }));
 
         let lights = [
@@ -135,10 +141,8 @@ impl rend3_framework::App for CubeExample {
     }
 
     fn handle_redraw(&mut self, context: rend3_framework::RedrawContext<'_, ()>) {
-        // Swap the instruction buffers so that our frame's changes can be processed.
-        context.renderer.swap_instruction_buffers();
         // Evaluate our frame's world-change instructions
-        let mut eval_output = context.renderer.evaluate_instructions();
+        let mut eval_output = context.evaluate_instructions();
 
         // Lock the routines
         let pbr_routine = rend3_framework::lock(&context.routines.pbr);
@@ -163,16 +167,22 @@ impl rend3_framework::App for CubeExample {
                     pbr: &pbr_routine,
                     skybox: None,
                     tonemapping: &tonemapping_routine,
+                    color_grade: None,
+                    debug_draw: None,
                 },
                 target: rend3_routine::base::OutputRenderTarget {
                     handle: frame_handle,
                     resolution: context.resolution,
                     samples: SAMPLE_COUNT,
                 },
+                depth: None,
+                camera: None,
             },
             rend3_routine::base::BaseRenderGraphSettings {
                 ambient_color: glam::Vec4::ZERO,
-                clear_color: glam::Vec4::new(0.10, 0.05, 0.10, 1.0), // Nice scene-referred purple
+                // Nice scene-referred purple
+                background: rend3_routine::background::Background::SolidColor(glam::Vec4::new(0.10, 0.05, 0.10, 1.0)),
+                ..Default::default()
             },
         );
 
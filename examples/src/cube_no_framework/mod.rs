@@ -109,7 +109,7 @@ pub fn main() {
     let base_rendergraph = rend3_routine::base::BaseRenderGraph::new(&renderer, &spp);
 
     let mut data_core = renderer.data_core.lock();
-    let pbr_routine = rend3_routine::pbr::PbrRoutine::new(
+    let mut pbr_routine = rend3_routine::pbr::PbrRoutine::new(
         &renderer,
         &mut data_core,
         &spp,
@@ -122,6 +122,7 @@ pub fn main() {
         &spp,
         &base_rendergraph.interfaces,
         preferred_format,
+        rend3_routine::tonemapping::OutputTransfer::Srgb,
     );
 
     // Create mesh and calculate smooth normals based on vertices
@@ -145,6 +146,9 @@ pub fn main() {
         mesh_kind: rend3::types::ObjectMeshKind::Static(mesh_handle),
         material: material_handle,
         transform: glam::Mat4::IDENTITY,
+        world_offset: glam::DVec3::ZERO,
+        sort_bias: 0,
+        debug_tint: None,
     };
     // Creating an object will hold onto both the mesh and the material
     // even if they are deleted.
@@ -172,6 +176,9 @@ pub fn main() {
         direction: glam::Vec3::new(-1.0, -4.0, 2.0),
         distance: 400.0,
         resolution: 2048,
+        casts_shadows: true,
+        snap_to_texels: true,
+        angular_size: 0.0,
     });
 
     let mut resolution = glam::UVec2::new(window_size.width, window_size.height);
@@ -201,6 +208,8 @@ pub fn main() {
                 renderer.swap_instruction_buffers();
                 // Evaluate our frame's world-change instructions
                 let mut eval_output = renderer.evaluate_instructions();
+                // Evaluate changes to routines.
+                pbr_routine.evaluate(&renderer);
 
                 // Build a rendergraph
                 let mut graph = rend3::graph::RenderGraph::new();
@@ -221,16 +230,24 @@ pub fn main() {
                             pbr: &pbr_routine,
                             skybox: None,
                             tonemapping: &tonemapping_routine,
+                            color_grade: None,
+                            debug_draw: None,
                         },
                         target: rend3_routine::base::OutputRenderTarget {
                             handle: frame_handle,
                             resolution,
                             samples: rend3::types::SampleCount::One,
                         },
+                        depth: None,
+                        camera: None,
                     },
                     rend3_routine::base::BaseRenderGraphSettings {
                         ambient_color: glam::Vec4::ZERO,
-                        clear_color: glam::Vec4::new(0.10, 0.05, 0.10, 1.0), // Nice scene-referred purple
+                        // Nice scene-referred purple
+                        background: rend3_routine::background::Background::SolidColor(glam::Vec4::new(
+                            0.10, 0.05, 0.10, 1.0,
+                        )),
+                        ..Default::default()
                     },
                 );
 
@@ -48,7 +48,9 @@ impl rend3_framework::App for EguiExample {
         // Add PBR material with all defaults except a single color.
         let material = rend3_routine::pbr::PbrMaterial {
             albedo: rend3_routine::pbr::AlbedoComponent::Value(glam::Vec4::new(0.0, 0.5, 0.5, 1.0)),
-            transparency: rend3_routine::pbr::Transparency::Blend,
+            transparency: rend3_routine::pbr::Transparency::Blend {
+                mode: rend3_routine::pbr::BlendMode::StraightAlpha,
+            },
             ..rend3_routine::pbr::PbrMaterial::default()
         };
         let material_handle = context.renderer.add_material(material);
@@ -58,6 +60,9 @@ impl rend3_framework::App for EguiExample {
             mesh_kind: rend3::types::ObjectMeshKind::Static(mesh_handle),
             material: material_handle.clone(),
             transform: glam::Mat4::IDENTITY,
+            world_offset: glam::DVec3::ZERO,
+            sort_bias: 0,
+            debug_tint: None,
         };
 
         // Creating an object will hold onto both the mesh and the material
@@ -90,6 +95,9 @@ impl rend3_framework::App for EguiExample {
             direction: glam::Vec3::new(-1.0, -4.0, 2.0),
             distance: 400.0,
             resolution: 2048,
+            casts_shadows: true,
+            snap_to_texels: true,
+            angular_size: 0.0,
         });
 
         // Create the egui context
@@ -124,7 +132,8 @@ impl rend3_framework::App for EguiExample {
             &image_rgba,
             dimensions,
             Some("rust_logo_texture"),
-        );
+        )
+        .unwrap();
 
         let color: [f32; 4] = [0.0, 0.5, 0.5, 1.0];
 
@@ -185,7 +194,9 @@ impl rend3_framework::App for EguiExample {
                     &data.material_handle.clone(),
                     rend3_routine::pbr::PbrMaterial {
                         albedo: rend3_routine::pbr::AlbedoComponent::Value(glam::Vec4::from(data.color)),
-                        transparency: rend3_routine::pbr::Transparency::Blend,
+                        transparency: rend3_routine::pbr::Transparency::Blend {
+                            mode: rend3_routine::pbr::BlendMode::StraightAlpha,
+                        },
                         ..rend3_routine::pbr::PbrMaterial::default()
                     },
                 );
@@ -205,10 +216,8 @@ impl rend3_framework::App for EguiExample {
 
         let input = rend3_egui::Input { clipped_meshes: &paint_jobs, textures_delta, context: data.context.clone() };
 
-        // Swap the instruction buffers so that our frame's changes can be processed.
-        context.renderer.swap_instruction_buffers();
         // Evaluate our frame's world-change instructions
-        let mut eval_output = context.renderer.evaluate_instructions();
+        let mut eval_output = context.evaluate_instructions();
 
         // Lock the routines
         let pbr_routine = rend3_framework::lock(&context.routines.pbr);
@@ -233,16 +242,22 @@ impl rend3_framework::App for EguiExample {
                     pbr: &pbr_routine,
                     skybox: None,
                     tonemapping: &tonemapping_routine,
+                    color_grade: None,
+                    debug_draw: None,
                 },
                 target: rend3_routine::base::OutputRenderTarget {
                     handle: frame_handle,
                     resolution: context.resolution,
                     samples: SAMPLE_COUNT,
                 },
+                depth: None,
+                camera: None,
             },
             rend3_routine::base::BaseRenderGraphSettings {
                 ambient_color: glam::Vec4::ZERO,
-                clear_color: glam::Vec4::new(0.10, 0.05, 0.10, 1.0), // Nice scene-referred purple
+                // Nice scene-referred purple
+                background: rend3_routine::background::Background::SolidColor(glam::Vec4::new(0.10, 0.05, 0.10, 1.0)),
+                ..Default::default()
             },
         );
 
@@ -0,0 +1,64 @@
+//! Optional gamepad camera controls, enabled with the `gamepad` feature.
+
+use gilrs::{Axis, Button, Gilrs};
+use glam::Vec2;
+
+/// Stick deflection below this is treated as zero, to ignore drift.
+const DEADZONE: f32 = 0.15;
+
+/// Camera inputs read from the first connected gamepad this frame.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct GamepadState {
+    /// Left stick. Positive x is right, positive y is forward.
+    pub movement: Vec2,
+    /// Right stick. Positive x is right, positive y is up.
+    pub look: Vec2,
+    /// Left stick is clicked in.
+    pub run: bool,
+    /// Right shoulder is held.
+    pub up: bool,
+}
+
+pub struct GamepadInput {
+    gilrs: Gilrs,
+}
+
+impl GamepadInput {
+    pub fn new() -> Option<Self> {
+        match Gilrs::new() {
+            Ok(gilrs) => Some(Self { gilrs }),
+            Err(e) => {
+                log::warn!("Failed to initialize gamepad support: {e}");
+                None
+            }
+        }
+    }
+
+    /// Process all pending gamepad events and read the current state.
+    pub fn poll(&mut self) -> GamepadState {
+        // Events must be drained for gilrs to update its cached state.
+        while self.gilrs.next_event().is_some() {}
+
+        let Some((_, gamepad)) = self.gilrs.gamepads().next() else {
+            return GamepadState::default();
+        };
+
+        let stick = |x, y| apply_deadzone(Vec2::new(gamepad.value(x), gamepad.value(y)));
+
+        GamepadState {
+            movement: stick(Axis::LeftStickX, Axis::LeftStickY),
+            look: stick(Axis::RightStickX, Axis::RightStickY),
+            run: gamepad.is_pressed(Button::LeftThumb),
+            up: gamepad.is_pressed(Button::RightTrigger),
+        }
+    }
+}
+
+fn apply_deadzone(stick: Vec2) -> Vec2 {
+    let length = stick.length();
+    if length < DEADZONE {
+        return Vec2::ZERO;
+    }
+    // Rescale so motion starts from zero at the edge of the deadzone.
+    stick / length * ((length - DEADZONE) / (1.0 - DEADZONE)).min(1.0)
+}
@@ -23,6 +23,13 @@ use winit::{
     window::{Fullscreen, WindowBuilder},
 };
 
+#[cfg(feature = "gamepad")]
+mod gamepad;
+
+/// Radians of camera rotation per second at full right stick deflection.
+#[cfg(feature = "gamepad")]
+const GAMEPAD_LOOK_SPEED: f32 = 2.5;
+
 async fn load_skybox_image(loader: &rend3_framework::AssetLoader, data: &mut Vec<u8>, path: &str) {
     let request_response =
         loader.get_asset(AssetPath::Internal(path)).await.unwrap_or_else(|e| panic!("Error {}: {}", path, e));
@@ -121,6 +128,26 @@ fn button_pressed<Hash: BuildHasher>(map: &HashMap<KeyCode, bool, Hash>, key: Ke
     map.get(&key).map_or(false, |b| *b)
 }
 
+/// Move `current` towards `target`, taking `acceleration_time` seconds to
+/// reach a target speed from rest and `deceleration_time` seconds to come to
+/// rest. A time of zero changes the velocity instantly.
+fn accelerate(current: Vec3A, target: Vec3A, acceleration_time: f32, deceleration_time: f32, dt: f32) -> Vec3A {
+    let speeding_up = target.length_squared() >= current.length_squared();
+    let (time, speed) =
+        if speeding_up { (acceleration_time, target.length()) } else { (deceleration_time, current.length()) };
+    if time <= 0.0 {
+        return target;
+    }
+
+    let difference = target - current;
+    let max_step = speed / time * dt;
+    if difference.length() <= max_step {
+        target
+    } else {
+        current + difference.normalize() * max_step
+    }
+}
+
 fn extract_backend(value: &str) -> Result<Backend, &'static str> {
     Ok(match value.to_lowercase().as_str() {
         "vulkan" | "vk" => Backend::Vulkan,
@@ -260,6 +287,8 @@ Assets:
 Controls:
   --walk <speed>               Walk speed (speed without holding shift) in units/second (typically meters). Default 10.
   --run  <speed>               Run speed (speed while holding shift) in units/second (typically meters). Default 50.
+  --accel-time <seconds>       Time taken to ramp up to full speed. Default 0 (instant).
+  --decel-time <seconds>       Time taken to come to a stop. Default 0 (instant).
   --camera x,y,z,pitch,yaw     Spawns the camera at the given position. Press Period to get the current camera position.
 
 Debug:
@@ -274,6 +303,8 @@ pub struct SceneViewer {
     file_to_load: Option<String>,
     walk_speed: f32,
     run_speed: f32,
+    acceleration_time: f32,
+    deceleration_time: f32,
     gltf_settings: rend3_gltf::GltfLoadSettings,
     directional_light_direction: Option<Vec3>,
     directional_light_intensity: f32,
@@ -287,9 +318,12 @@ pub struct SceneViewer {
     loading_reciever: Option<Receiver<anyhow::Result<(LoadedGltfScene, GltfSceneInstance)>>>,
 
     scancode_status: FastHashMap<KeyCode, bool>,
+    #[cfg(feature = "gamepad")]
+    gamepad: Option<gamepad::GamepadInput>,
     camera_pitch: f32,
     camera_yaw: f32,
     camera_location: Vec3A,
+    camera_velocity: Vec3A,
     previous_profiling_stats: Option<RendererStatistics>,
     last_mouse_delta: Option<DVec2>,
 
@@ -308,6 +342,8 @@ impl Default for SceneViewer {
             file_to_load: None,
             walk_speed: 10.0,
             run_speed: 50.0,
+            acceleration_time: 0.0,
+            deceleration_time: 0.0,
             gltf_settings: GltfLoadSettings::default(),
             directional_light_direction: None,
             directional_light_intensity: 1.0,
@@ -319,11 +355,14 @@ impl Default for SceneViewer {
             wait_for_load: false,
             loading_reciever: None,
             scancode_status: HashMap::default(),
+            #[cfg(feature = "gamepad")]
+            gamepad: None,
 
             // Camera settings for the default scene
             camera_pitch: -0.08869916,
             camera_yaw: 5.899576,
             camera_location: Vec3A::new(-2.9936655, 2.189423, 5.308956),
+            camera_velocity: Vec3A::ZERO,
 
             previous_profiling_stats: None,
             last_mouse_delta: None,
@@ -334,6 +373,22 @@ impl Default for SceneViewer {
     }
 }
 impl SceneViewer {
+    /// Turn the camera, where positive yaw turns right and positive pitch turns
+    /// down.
+    fn rotate_camera(&mut self, yaw: f32, pitch: f32) {
+        const TAU: f32 = std::f32::consts::PI * 2.0;
+
+        self.camera_yaw -= yaw;
+        self.camera_pitch -= pitch;
+        if self.camera_yaw < 0.0 {
+            self.camera_yaw += TAU;
+        } else if self.camera_yaw >= TAU {
+            self.camera_yaw -= TAU;
+        }
+        self.camera_pitch =
+            self.camera_pitch.clamp(-std::f32::consts::FRAC_PI_2 + 0.0001, std::f32::consts::FRAC_PI_2 - 0.0001)
+    }
+
     pub fn from_args() -> Self {
         #[cfg(feature = "tracy")]
         tracy_client::Client::start();
@@ -393,6 +448,12 @@ impl SceneViewer {
         if let Some(run_speed) = option_arg(args.opt_value_from_str("--run")) {
             app.run_speed = run_speed;
         }
+        if let Some(acceleration_time) = option_arg(args.opt_value_from_str("--accel-time")) {
+            app.acceleration_time = acceleration_time;
+        }
+        if let Some(deceleration_time) = option_arg(args.opt_value_from_str("--decel-time")) {
+            app.deceleration_time = deceleration_time;
+        }
 
         let camera_default =
             [app.camera_location.x, app.camera_location.y, app.camera_location.z, app.camera_pitch, app.camera_yaw];
@@ -451,6 +512,16 @@ impl rend3_framework::App for SceneViewer {
         self.samples
     }
 
+    fn create_base_rendergraph(
+        &mut self,
+        renderer: &Arc<Renderer>,
+        spp: &rend3::ShaderPreProcessor,
+    ) -> rend3_routine::base::BaseRenderGraph {
+        let mut base_rendergraph = rend3_routine::base::BaseRenderGraph::new(renderer, spp);
+        base_rendergraph.samplers.set_anisotropy(renderer, 16);
+        base_rendergraph
+    }
+
     fn present_mode(&self) -> rend3::types::PresentMode {
         self.present_mode
     }
@@ -462,6 +533,11 @@ impl rend3_framework::App for SceneViewer {
     fn setup(&mut self, context: rend3_framework::SetupContext<'_>) {
         self.grabber = context.windowing.map(|windowing| rend3_framework::Grabber::new(windowing.window));
 
+        #[cfg(feature = "gamepad")]
+        {
+            self.gamepad = gamepad::GamepadInput::new();
+        }
+
         if let Some(direction) = self.directional_light_direction {
             self.directional_light = Some(context.renderer.add_directional_light(DirectionalLight {
                 color: Vec3::splat(1.0),
@@ -469,10 +545,13 @@ impl rend3_framework::App for SceneViewer {
                 direction,
                 distance: self.gltf_settings.directional_light_shadow_distance,
                 resolution: 2048,
+                casts_shadows: true,
+                snap_to_texels: true,
+                angular_size: 0.0,
             }));
         }
 
-        let gltf_settings = self.gltf_settings;
+        let gltf_settings = self.gltf_settings.clone();
         let file_to_load = self.file_to_load.take();
         let renderer = Arc::clone(context.renderer);
         let routines = Arc::clone(context.routines);
@@ -552,8 +631,6 @@ impl rend3_framework::App for SceneViewer {
                     return;
                 }
 
-                const TAU: f32 = std::f32::consts::PI * 2.0;
-
                 let mouse_delta = if self.absolute_mouse {
                     let prev = self.last_mouse_delta.replace(DVec2::new(delta_x, delta_y));
                     if let Some(prev) = prev {
@@ -565,15 +642,7 @@ impl rend3_framework::App for SceneViewer {
                     DVec2::new(delta_x, delta_y)
                 };
 
-                self.camera_yaw -= (mouse_delta.x / 1000.0) as f32;
-                self.camera_pitch -= (mouse_delta.y / 1000.0) as f32;
-                if self.camera_yaw < 0.0 {
-                    self.camera_yaw += TAU;
-                } else if self.camera_yaw >= TAU {
-                    self.camera_yaw -= TAU;
-                }
-                self.camera_pitch =
-                    self.camera_pitch.clamp(-std::f32::consts::FRAC_PI_2 + 0.0001, std::f32::consts::FRAC_PI_2 - 0.0001)
+                self.rotate_camera((mouse_delta.x / 1000.0) as f32, (mouse_delta.y / 1000.0) as f32);
             }
             _ => {}
         }
@@ -595,23 +664,51 @@ impl rend3_framework::App for SceneViewer {
         let forward = -rotation.z_axis;
         let up = rotation.y_axis;
         let side = -rotation.x_axis;
-        let velocity =
+        let speed =
             if button_pressed(&self.scancode_status, KeyCode::ShiftLeft) { self.run_speed } else { self.walk_speed };
+
+        // The velocity the inputs are asking for. The camera accelerates towards this.
+        let mut target_velocity = Vec3A::ZERO;
+
+        #[cfg(feature = "gamepad")]
+        if let Some(gamepad) = &mut self.gamepad {
+            let state = gamepad.poll();
+            let speed = if state.run { self.run_speed } else { self.walk_speed };
+
+            target_velocity += forward * state.movement.y * speed;
+            target_velocity -= side * state.movement.x * speed;
+            if state.up {
+                target_velocity += up * speed;
+            }
+
+            let look = state.look * GAMEPAD_LOOK_SPEED * context.delta_t_seconds;
+            self.rotate_camera(look.x, -look.y);
+        }
+
         if button_pressed(&self.scancode_status, KeyCode::KeyW) {
-            self.camera_location += forward * velocity * context.delta_t_seconds;
+            target_velocity += forward * speed;
         }
         if button_pressed(&self.scancode_status, KeyCode::KeyS) {
-            self.camera_location -= forward * velocity * context.delta_t_seconds;
+            target_velocity -= forward * speed;
         }
         if button_pressed(&self.scancode_status, KeyCode::KeyA) {
-            self.camera_location += side * velocity * context.delta_t_seconds;
+            target_velocity += side * speed;
         }
         if button_pressed(&self.scancode_status, KeyCode::KeyD) {
-            self.camera_location -= side * velocity * context.delta_t_seconds;
+            target_velocity -= side * speed;
         }
         if button_pressed(&self.scancode_status, KeyCode::KeyQ) {
-            self.camera_location += up * velocity * context.delta_t_seconds;
+            target_velocity += up * speed;
         }
+
+        self.camera_velocity = accelerate(
+            self.camera_velocity,
+            target_velocity,
+            self.acceleration_time,
+            self.deceleration_time,
+            context.delta_t_seconds,
+        );
+        self.camera_location += self.camera_velocity * context.delta_t_seconds;
         if button_pressed(&self.scancode_status, KeyCode::Period) {
             println!(
                 "{x},{y},{z},{pitch},{yaw}",
@@ -644,15 +741,14 @@ impl rend3_framework::App for SceneViewer {
             .renderer
             .set_camera_data(Camera { projection: CameraProjection::Perspective { vfov: 60.0, near: 0.1 }, view });
 
+        // Evaluate our frame's world-change instructions
+        let mut eval_output = context.evaluate_instructions();
+
         // Lock all the routines
         let pbr_routine = lock(&context.routines.pbr);
         let mut skybox_routine = lock(&context.routines.skybox);
         let tonemapping_routine = lock(&context.routines.tonemapping);
 
-        // Swap the instruction buffers so that our frame's changes can be processed.
-        context.renderer.swap_instruction_buffers();
-        // Evaluate our frame's world-change instructions
-        let mut eval_output = context.renderer.evaluate_instructions();
         // Evaluate changes to routines.
         skybox_routine.evaluate(context.renderer);
 
@@ -674,16 +770,22 @@ impl rend3_framework::App for SceneViewer {
                     pbr: &pbr_routine,
                     skybox: Some(&skybox_routine),
                     tonemapping: &tonemapping_routine,
+                    color_grade: None,
+                    debug_draw: None,
                 },
                 target: rend3_routine::base::OutputRenderTarget {
                     handle: frame_handle,
                     resolution: context.resolution,
                     samples: self.samples,
                 },
+                depth: None,
+                camera: None,
             },
             rend3_routine::base::BaseRenderGraphSettings {
                 ambient_color: Vec3::splat(self.ambient_light_level).extend(1.0),
-                clear_color: glam::Vec4::new(0.0, 0.0, 0.0, 1.0),
+                background: rend3_routine::background::Background::Skybox,
+                occlusion_culling: true,
+                ..Default::default()
             },
         );
 
@@ -104,16 +104,17 @@ impl rend3_framework::App for SkinningExample {
             direction: glam::Vec3::new(-1.0, -4.0, 2.0),
             distance: 400.0,
             resolution: 2048,
+            casts_shadows: true,
+            snap_to_texels: true,
+            angular_size: 0.0,
         }));
     }
 
     fn handle_redraw(&mut self, context: rend3_framework::RedrawContext<'_, ()>) {
         self.update_skeleton(context.renderer);
 
-        // Swap the instruction buffers so that our frame's changes can be processed.
-        context.renderer.swap_instruction_buffers();
         // Evaluate our frame's world-change instructions
-        let mut eval_output = context.renderer.evaluate_instructions();
+        let mut eval_output = context.evaluate_instructions();
 
         // Lock the routines
         let pbr_routine = rend3_framework::lock(&context.routines.pbr);
@@ -137,16 +138,22 @@ impl rend3_framework::App for SkinningExample {
                     pbr: &pbr_routine,
                     skybox: None,
                     tonemapping: &tonemapping_routine,
+                    color_grade: None,
+                    debug_draw: None,
                 },
                 target: rend3_routine::base::OutputRenderTarget {
                     handle: frame_handle,
                     resolution: context.resolution,
                     samples: SAMPLE_COUNT,
                 },
+                depth: None,
+                camera: None,
             },
             rend3_routine::base::BaseRenderGraphSettings {
                 ambient_color: glam::Vec4::ZERO,
-                clear_color: glam::Vec4::new(0.10, 0.05, 0.10, 1.0), // Nice scene-referred purple
+                // Nice scene-referred purple
+                background: rend3_routine::background::Background::SolidColor(glam::Vec4::new(0.10, 0.05, 0.10, 1.0)),
+                ..Default::default()
             },
         );
 
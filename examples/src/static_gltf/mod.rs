@@ -67,6 +67,9 @@ impl rend3_framework::App for StaticGltfExample {
             mesh_kind: rend3::types::ObjectMeshKind::Static(mesh),
             material,
             transform: glam::Mat4::from_scale(glam::Vec3::new(1.0, 1.0, -1.0)),
+            world_offset: glam::DVec3::ZERO,
+            sort_bias: 0,
+            debug_tint: None,
         };
         // We need to keep the object alive.
         self.object_handle = Some(context.renderer.add_object(object));
@@ -91,14 +94,15 @@ impl rend3_framework::App for StaticGltfExample {
             direction: glam::Vec3::new(-1.0, -4.0, 2.0),
             distance: 20.0,
             resolution: 2048,
+            casts_shadows: true,
+            snap_to_texels: true,
+            angular_size: 0.0,
         }));
     }
 
     fn handle_redraw(&mut self, context: rend3_framework::RedrawContext<'_, ()>) {
-        // Swap the instruction buffers so that our frame's changes can be processed.
-        context.renderer.swap_instruction_buffers();
         // Evaluate our frame's world-change instructions
-        let mut eval_output = context.renderer.evaluate_instructions();
+        let mut eval_output = context.evaluate_instructions();
 
         // Lock the routines
         let pbr_routine = rend3_framework::lock(&context.routines.pbr);
@@ -123,16 +127,22 @@ impl rend3_framework::App for StaticGltfExample {
                     pbr: &pbr_routine,
                     skybox: None,
                     tonemapping: &tonemapping_routine,
+                    color_grade: None,
+                    debug_draw: None,
                 },
                 target: rend3_routine::base::OutputRenderTarget {
                     handle: frame_handle,
                     resolution: context.resolution,
                     samples: SAMPLE_COUNT,
                 },
+                depth: None,
+                camera: None,
             },
             rend3_routine::base::BaseRenderGraphSettings {
                 ambient_color: glam::Vec4::ZERO,
-                clear_color: glam::Vec4::new(0.10, 0.05, 0.10, 1.0), // Nice scene-referred purple
+                // Nice scene-referred purple
+                background: rend3_routine::background::Background::SolidColor(glam::Vec4::new(0.10, 0.05, 0.10, 1.0)),
+                ..Default::default()
             },
         );
         // Dispatch a render using the built up rendergraph!
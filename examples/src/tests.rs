@@ -42,6 +42,7 @@ pub async fn test_app<A: App<T>, T: 'static>(mut config: TestConfiguration<A>) -
             &spp,
             &base_rendergraph.interfaces,
             wgpu::TextureFormat::Rgba8UnormSrgb,
+            rend3_routine::tonemapping::OutputTransfer::Srgb,
         )),
     });
     drop(data_core);
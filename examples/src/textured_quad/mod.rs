@@ -87,6 +87,9 @@ impl rend3_framework::App for TexturedQuadExample {
                 glam::Quat::from_euler(glam::EulerRot::XYZ, 0.0, 0.0, 0.0),
                 glam::Vec3::new(0.0, 0.0, 0.0),
             ),
+            world_offset: glam::DVec3::ZERO,
+            sort_bias: 0,
+            debug_tint: None,
         };
 
         // Creating an object will hold onto both the mesh and the material
@@ -102,7 +105,9 @@ impl rend3_framework::App for TexturedQuadExample {
         // Set camera's location
         context.renderer.set_camera_data(rend3::types::Camera {
             projection: rend3::types::CameraProjection::Orthographic {
-                size: glam::Vec3A::new(context.resolution.x as f32, context.resolution.y as f32, CAMERA_DEPTH),
+                size: context.resolution.as_vec2(),
+                near: 0.0,
+                far: CAMERA_DEPTH,
             },
             view,
         });
@@ -116,7 +121,9 @@ impl rend3_framework::App for TexturedQuadExample {
             // Reset camera
             context.renderer.set_camera_data(rend3::types::Camera {
                 projection: rend3::types::CameraProjection::Orthographic {
-                    size: glam::Vec3A::new(size.x as f32, size.y as f32, CAMERA_DEPTH),
+                    size: size.as_vec2(),
+                    near: 0.0,
+                    far: CAMERA_DEPTH,
                 },
                 view: self.data.as_ref().unwrap().view,
             });
@@ -124,10 +131,8 @@ impl rend3_framework::App for TexturedQuadExample {
     }
 
     fn handle_redraw(&mut self, context: rend3_framework::RedrawContext<'_, ()>) {
-        // Swap the instruction buffers so that our frame's changes can be processed.
-        context.renderer.swap_instruction_buffers();
         // Evaluate our frame's world-change instructions
-        let mut eval_output = context.renderer.evaluate_instructions();
+        let mut eval_output = context.evaluate_instructions();
 
         // Lock the routines
         let pbr_routine = rend3_framework::lock(&context.routines.pbr);
@@ -152,16 +157,22 @@ impl rend3_framework::App for TexturedQuadExample {
                     pbr: &pbr_routine,
                     skybox: None,
                     tonemapping: &tonemapping_routine,
+                    color_grade: None,
+                    debug_draw: None,
                 },
                 target: rend3_routine::base::OutputRenderTarget {
                     handle: frame_handle,
                     resolution: context.resolution,
                     samples: SAMPLE_COUNT,
                 },
+                depth: None,
+                camera: None,
             },
             rend3_routine::base::BaseRenderGraphSettings {
                 ambient_color: glam::Vec4::ZERO,
-                clear_color: glam::Vec4::new(0.10, 0.05, 0.10, 1.0), // Nice scene-referred purple
+                // Nice scene-referred purple
+                background: rend3_routine::background::Background::SolidColor(glam::Vec4::new(0.10, 0.05, 0.10, 1.0)),
+                ..Default::default()
             },
         );
 
@@ -1,6 +1,8 @@
 //! Render routine integrating egui into a rend3 rendergraph.
 //!
 //! Call [`EguiRenderRoutine::add_to_graph`] to add it to the graph.
+//!
+//! [`EguiSceneWidget`] shows a rend3 scene inside the UI.
 
 use std::{mem, sync::Arc};
 
@@ -11,8 +13,21 @@ use rend3::{
     types::SampleCount,
     Renderer,
 };
+use thiserror::Error;
 use wgpu::TextureFormat;
 
+mod scene_widget;
+
+pub use scene_widget::{EguiSceneWidget, OrbitControls};
+
+#[derive(Debug, Error)]
+pub enum EguiTextureError {
+    #[error("{0:?} can't be written to from the cpu")]
+    UnsupportedFormat(TextureFormat),
+    #[error("Texture of {width}x{height} needs {expected} bytes of data, but {actual} bytes were given")]
+    WrongDataLength { width: u32, height: u32, expected: usize, actual: usize },
+}
+
 pub struct EguiRenderRoutine {
     pub internal: egui_wgpu::Renderer,
     screen_descriptor: egui_wgpu::ScreenDescriptor,
@@ -94,6 +109,10 @@ impl EguiRenderRoutine {
     }
 
     /// Creates an egui texture from the given image data, format, and dimensions.
+    ///
+    /// `image_rgba` holds tightly packed rows of blocks of the format, and
+    /// must be exactly as long as the texture. The texture is sampled with
+    /// linear filtering and clamped to the edge.
     pub fn create_egui_texture(
         internal: &mut egui_wgpu::Renderer,
         renderer: &Arc<rend3::Renderer>,
@@ -101,7 +120,33 @@ impl EguiRenderRoutine {
         image_rgba: &[u8],
         dimensions: (u32, u32),
         label: Option<&str>,
-    ) -> egui::TextureId {
+    ) -> Result<egui::TextureId, EguiTextureError> {
+        EguiRenderRoutine::create_egui_texture_with_sampling(
+            internal,
+            renderer,
+            format,
+            image_rgba,
+            dimensions,
+            label,
+            wgpu::FilterMode::Linear,
+            wgpu::AddressMode::ClampToEdge,
+        )
+    }
+
+    /// [`Self::create_egui_texture`] sampled with the given filter and address
+    /// mode. Use [`wgpu::FilterMode::Nearest`] for pixel art.
+    #[allow(clippy::too_many_arguments)]
+    pub fn create_egui_texture_with_sampling(
+        internal: &mut egui_wgpu::Renderer,
+        renderer: &Arc<rend3::Renderer>,
+        format: wgpu::TextureFormat,
+        image_rgba: &[u8],
+        dimensions: (u32, u32),
+        label: Option<&str>,
+        filter: wgpu::FilterMode,
+        address_mode: wgpu::AddressMode,
+    ) -> Result<egui::TextureId, EguiTextureError> {
+        let block_size = format.block_copy_size(None).ok_or(EguiTextureError::UnsupportedFormat(format))?;
         let texture_size = wgpu::Extent3d { width: dimensions.0, height: dimensions.1, depth_or_array_layers: 1 };
 
         let image_texture = renderer.device.create_texture(&wgpu::TextureDescriptor {
@@ -115,18 +160,23 @@ impl EguiRenderRoutine {
             view_formats: &[],
         });
 
-        EguiRenderRoutine::wgpu_texture_to_egui(
+        EguiRenderRoutine::wgpu_texture_to_egui_with_sampling(
             internal,
             renderer,
             image_texture,
             image_rgba,
             dimensions,
             format.block_dimensions(),
-            format.block_copy_size(None).unwrap(),
+            block_size,
+            filter,
+            address_mode,
         )
     }
 
     /// Creates egui::TextureId with wgpu backend with existing wgpu::Texture
+    ///
+    /// Errors if `image_rgba` isn't exactly as long as the blocks of the
+    /// texture. The texture is sampled with linear filtering and clamped to the edge.
     pub fn wgpu_texture_to_egui(
         internal: &mut egui_wgpu::Renderer,
         renderer: &Arc<rend3::Renderer>,
@@ -135,10 +185,51 @@ impl EguiRenderRoutine {
         dimensions: (u32, u32),
         block_dimensions: (u32, u32),
         block_size: u32,
-    ) -> egui::TextureId {
+    ) -> Result<egui::TextureId, EguiTextureError> {
+        EguiRenderRoutine::wgpu_texture_to_egui_with_sampling(
+            internal,
+            renderer,
+            image_texture,
+            image_rgba,
+            dimensions,
+            block_dimensions,
+            block_size,
+            wgpu::FilterMode::Linear,
+            wgpu::AddressMode::ClampToEdge,
+        )
+    }
+
+    /// [`Self::wgpu_texture_to_egui`] sampled with the given filter and
+    /// address mode.
+    #[allow(clippy::too_many_arguments)]
+    pub fn wgpu_texture_to_egui_with_sampling(
+        internal: &mut egui_wgpu::Renderer,
+        renderer: &Arc<rend3::Renderer>,
+        image_texture: wgpu::Texture,
+        image_rgba: &[u8],
+        dimensions: (u32, u32),
+        block_dimensions: (u32, u32),
+        block_size: u32,
+        filter: wgpu::FilterMode,
+        address_mode: wgpu::AddressMode,
+    ) -> Result<egui::TextureId, EguiTextureError> {
         let device = &renderer.device;
         let queue = &renderer.queue;
 
+        // Partial blocks at the edges still take up a whole block.
+        let blocks_wide = (dimensions.0 + block_dimensions.0 - 1) / block_dimensions.0;
+        let blocks_high = (dimensions.1 + block_dimensions.1 - 1) / block_dimensions.1;
+        let bytes_per_row = blocks_wide * block_size;
+        let expected = bytes_per_row as usize * blocks_high as usize;
+        if image_rgba.len() != expected {
+            return Err(EguiTextureError::WrongDataLength {
+                width: dimensions.0,
+                height: dimensions.1,
+                expected,
+                actual: image_rgba.len(),
+            });
+        }
+
         let texture_size = wgpu::Extent3d { width: dimensions.0, height: dimensions.1, depth_or_array_layers: 1 };
 
         queue.write_texture(
@@ -149,22 +240,25 @@ impl EguiRenderRoutine {
                 aspect: wgpu::TextureAspect::All,
             },
             image_rgba,
-            wgpu::ImageDataLayout {
-                offset: 0,
-                bytes_per_row: Some((dimensions.0 / block_dimensions.0) * block_size),
-                rows_per_image: None,
-            },
+            wgpu::ImageDataLayout { offset: 0, bytes_per_row: Some(bytes_per_row), rows_per_image: None },
             texture_size,
         );
 
-        internal.register_native_texture(
+        Ok(internal.register_native_texture_with_sampler_options(
             device,
             &image_texture.create_view(&wgpu::TextureViewDescriptor {
                 dimension: Some(wgpu::TextureViewDimension::D2),
                 ..Default::default()
             }),
-            wgpu::FilterMode::Linear,
-        )
+            wgpu::SamplerDescriptor {
+                label: Some("egui user texture sampler"),
+                address_mode_u: address_mode,
+                address_mode_v: address_mode,
+                mag_filter: filter,
+                min_filter: filter,
+                ..Default::default()
+            },
+        ))
     }
 }
 
@@ -0,0 +1,169 @@
+//! Widget showing a rend3 scene inside an egui UI.
+
+use std::f32::consts::FRAC_PI_2;
+
+use egui::{Color32, Pos2, Rect, Response, Sense, Ui};
+use glam::{Mat4, UVec2, Vec3};
+use rend3::{
+    graph::{RenderGraph, RenderTargetHandle, ViewportRect},
+    types::{Camera, CameraProjection, Handedness},
+    Renderer,
+};
+
+/// Radians the camera turns per point dragged.
+const ORBIT_SENSITIVITY: f32 = 0.01;
+/// Fraction the distance changes per point scrolled.
+const ZOOM_SENSITIVITY: f32 = 0.002;
+
+/// Camera orbiting around a target, driven by dragging and scrolling over an
+/// [`EguiSceneWidget`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OrbitControls {
+    /// Point the camera looks at and orbits around.
+    pub target: Vec3,
+    /// Distance of the camera from the target.
+    pub distance: f32,
+    /// Rotation around the Y axis in radians.
+    pub yaw: f32,
+    /// Angle above the XZ plane in radians.
+    pub pitch: f32,
+    /// Vertical field of view in degrees.
+    pub vfov: f32,
+}
+
+impl Default for OrbitControls {
+    fn default() -> Self {
+        Self { target: Vec3::ZERO, distance: 5.0, yaw: 0.0, pitch: 0.3, vfov: 60.0 }
+    }
+}
+
+impl OrbitControls {
+    /// Position of the camera in world space.
+    pub fn eye(&self) -> Vec3 {
+        let (yaw_sin, yaw_cos) = self.yaw.sin_cos();
+        let (pitch_sin, pitch_cos) = self.pitch.sin_cos();
+        self.target + Vec3::new(pitch_cos * yaw_sin, pitch_sin, -pitch_cos * yaw_cos) * self.distance
+    }
+
+    /// Camera looking from [`Self::eye`] at the target.
+    pub fn camera(&self, handedness: Handedness) -> Camera {
+        let view = match handedness {
+            Handedness::Left => Mat4::look_at_lh(self.eye(), self.target, Vec3::Y),
+            Handedness::Right => Mat4::look_at_rh(self.eye(), self.target, Vec3::Y),
+        };
+        Camera { projection: CameraProjection::Perspective { vfov: self.vfov, near: 0.1 }, view }
+    }
+
+    fn handle_input(&mut self, ui: &Ui, response: &Response) {
+        if response.dragged() {
+            let delta = response.drag_delta();
+            self.yaw -= delta.x * ORBIT_SENSITIVITY;
+            self.pitch = (self.pitch + delta.y * ORBIT_SENSITIVITY).clamp(-FRAC_PI_2 + 0.01, FRAC_PI_2 - 0.01);
+        }
+        if response.hovered() {
+            let scroll = ui.input(|i| i.smooth_scroll_delta.y);
+            self.distance = (self.distance * (1.0 - scroll * ZOOM_SENSITIVITY)).max(0.01);
+        }
+    }
+}
+
+/// Shows a rend3 scene rendered to a texture inside a UI, with orbit camera
+/// controls.
+///
+/// Each frame, call [`Self::ui`] to lay out the widget, then render the scene
+/// as seen from [`Self::camera`] into [`Self::add_render_target`], in its own
+/// graph execution. The camera can be passed as the camera override of the
+/// rend3-routine base rendergraph. A scene with different objects lives in a
+/// second [`Renderer`] sharing the device of the main one, like the thumbnail
+/// renderer in rend3-routine does.
+///
+/// The texture is `Rgba8UnormSrgb`, so the scene should be tonemapped with an
+/// sRGB output transfer.
+pub struct EguiSceneWidget {
+    /// Camera controlled by the widget.
+    pub orbit: OrbitControls,
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+    texture_id: egui::TextureId,
+}
+
+impl EguiSceneWidget {
+    /// Format of the texture the scene is rendered into.
+    pub const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+    pub fn new(internal: &mut egui_wgpu::Renderer, renderer: &Renderer) -> Self {
+        let (texture, view) = create_texture(renderer, UVec2::ONE);
+        let texture_id = internal.register_native_texture(&renderer.device, &view, wgpu::FilterMode::Linear);
+
+        Self { orbit: OrbitControls::default(), texture, view, texture_id }
+    }
+
+    /// Lays out the widget over all the available space and handles its input.
+    ///
+    /// The texture follows the size of the widget in physical pixels, and is
+    /// recreated when it changes.
+    pub fn ui(&mut self, ui: &mut Ui, internal: &mut egui_wgpu::Renderer, renderer: &Renderer) -> Response {
+        let (rect, response) = ui.allocate_exact_size(ui.available_size(), Sense::click_and_drag());
+
+        let size = (rect.size() * ui.ctx().pixels_per_point()).round();
+        let size = UVec2::new(size.x as u32, size.y as u32).max(UVec2::ONE);
+        if size != self.size() {
+            (self.texture, self.view) = create_texture(renderer, size);
+            internal.update_egui_texture_from_wgpu_texture(
+                &renderer.device,
+                &self.view,
+                wgpu::FilterMode::Linear,
+                self.texture_id,
+            );
+        }
+
+        self.orbit.handle_input(ui, &response);
+
+        let uv = Rect::from_min_max(Pos2::ZERO, Pos2::new(1.0, 1.0));
+        ui.painter().image(self.texture_id, rect, uv, Color32::WHITE);
+
+        response
+    }
+
+    /// Size of the texture in pixels.
+    pub fn size(&self) -> UVec2 {
+        UVec2::new(self.texture.width(), self.texture.height())
+    }
+
+    /// Aspect ratio to give the renderer drawing the scene.
+    pub fn aspect_ratio(&self) -> f32 {
+        let size = self.size();
+        size.x as f32 / size.y as f32
+    }
+
+    /// Camera to give the renderer drawing the scene.
+    pub fn camera(&self, handedness: Handedness) -> Camera {
+        self.orbit.camera(handedness)
+    }
+
+    /// Imports the texture of the widget into the graph, to render the scene
+    /// into.
+    pub fn add_render_target<'node>(&'node self, graph: &mut RenderGraph<'node>) -> RenderTargetHandle {
+        graph.add_imported_render_target(&self.texture, 0..1, 0..1, ViewportRect::from_size(self.size()))
+    }
+
+    /// Frees the texture from egui. The widget can't be used afterwards.
+    pub fn free(self, internal: &mut egui_wgpu::Renderer) {
+        internal.free_texture(&self.texture_id);
+    }
+}
+
+fn create_texture(renderer: &Renderer, size: UVec2) -> (wgpu::Texture, wgpu::TextureView) {
+    let texture = renderer.device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("egui scene widget"),
+        size: wgpu::Extent3d { width: size.x, height: size.y, depth_or_array_layers: 1 },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: EguiSceneWidget::FORMAT,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        view_formats: &[],
+    });
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    (texture, view)
+}
@@ -1,14 +1,15 @@
 #![cfg_attr(target_arch = "wasm32", allow(clippy::arc_with_non_send_sync))]
 
-use std::{future::Future, pin::Pin, sync::Arc};
+use std::{collections::VecDeque, future::Future, pin::Pin, sync::Arc};
 
 use glam::UVec2;
 use rend3::{
+    graph::InstructionEvaluationOutput,
     types::{Handedness, SampleCount, Surface, TextureFormat},
     InstanceAdapterDevice, Renderer, ShaderPreProcessor,
 };
 use rend3_routine::base::BaseRenderGraph;
-use wgpu::{Instance, PresentMode, SurfaceError};
+use wgpu::{Instance, Maintain, PresentMode, SubmissionIndex, SurfaceError};
 use winit::{
     error::EventLoopError,
     event::Event,
@@ -62,6 +63,23 @@ pub struct RedrawContext<'a, T: 'static = ()> {
     pub delta_t_seconds: f32,
 }
 
+impl<'a, T: 'static> RedrawContext<'a, T> {
+    /// Swaps the instruction buffers and evaluates them, applying the changes
+    /// made since the last frame, then evaluates the changes made to the PBR
+    /// routine, like a new environment. This is the first thing a redraw
+    /// does, and must be done before locking the routines.
+    ///
+    /// The output describes the frame about to be rendered, such as the
+    /// cameras of the shadow maps in [`InstructionEvaluationOutput::shadows`],
+    /// so the graph can be built around it before being executed with it.
+    pub fn evaluate_instructions(&self) -> InstructionEvaluationOutput {
+        self.renderer.swap_instruction_buffers();
+        let eval_output = self.renderer.evaluate_instructions();
+        lock(&self.routines.pbr).evaluate(self.renderer);
+        eval_output
+    }
+}
+
 pub trait App<T: 'static = ()> {
     /// The handedness of the coordinate system of the renderer.
     const HANDEDNESS: Handedness;
@@ -131,6 +149,17 @@ pub trait App<T: 'static = ()> {
         rend3::types::PresentMode::Fifo
     }
 
+    /// Maximum number of frames the CPU may get ahead of the GPU. Before
+    /// starting a frame, the framework waits for the GPU to finish the frame
+    /// this many frames back, so 1 waits for the previous frame. Lower values
+    /// reduce input latency at the cost of throughput.
+    ///
+    /// `None` never waits, leaving pacing to the surface. This may change
+    /// dynamically.
+    fn max_frames_in_flight(&self) -> Option<usize> {
+        None
+    }
+
     /// Determines the scale factor used
     fn scale_factor(&self) -> f32 {
         1.0
@@ -146,7 +175,9 @@ pub trait App<T: 'static = ()> {
         let _ = (context, event);
     }
 
-    /// Handle a redraw event.
+    /// Handle a redraw event. Start by calling
+    /// [`RedrawContext::evaluate_instructions`], then build and execute a
+    /// graph with its output.
     fn handle_redraw(&mut self, context: RedrawContext<'_, T>);
 
     /// Called after each redraw for post-processing, if needed.
@@ -239,6 +270,7 @@ pub async fn async_start<A: App<T> + 'static, T: 'static>(mut app: A, window_bui
             &spp,
             &base_rendergraph.interfaces,
             format,
+            rend3_routine::tonemapping::OutputTransfer::Srgb,
         )),
     });
     drop(data_core);
@@ -275,6 +307,7 @@ pub async fn async_start<A: App<T> + 'static, T: 'static>(mut app: A, window_bui
     }
 
     let mut previous_time = web_time::Instant::now();
+    let mut frames_in_flight = VecDeque::new();
 
     // On native this is a result, but on wasm it's a unit type.
     #[allow(clippy::let_unit_value)]
@@ -315,6 +348,8 @@ pub async fn async_start<A: App<T> + 'static, T: 'static>(mut app: A, window_bui
                     return;
                 };
 
+                wait_for_frames_in_flight(&renderer, &mut frames_in_flight, app.max_frames_in_flight());
+
                 if stored_surface_info.requires_reconfigure {
                     rend3::configure_surface(
                         surface,
@@ -358,6 +393,8 @@ pub async fn async_start<A: App<T> + 'static, T: 'static>(mut app: A, window_bui
                 });
 
                 surface_texture.present();
+                // An empty submission completes once everything submitted this frame has.
+                frames_in_flight.push_back(renderer.queue.submit(None));
 
                 app.handle_redraw_done(&window); // standard action is to redraw, but that can be overridden.
             } else {
@@ -381,6 +418,25 @@ pub async fn async_start<A: App<T> + 'static, T: 'static>(mut app: A, window_bui
     );
 }
 
+/// Blocks until at most `max_frames_in_flight - 1` of the given frames are
+/// still running on the GPU, so the next frame brings it to the limit.
+fn wait_for_frames_in_flight(
+    renderer: &Renderer,
+    frames_in_flight: &mut VecDeque<SubmissionIndex>,
+    max_frames_in_flight: Option<usize>,
+) {
+    let Some(max_frames_in_flight) = max_frames_in_flight else {
+        frames_in_flight.clear();
+        return;
+    };
+
+    while frames_in_flight.len() >= max_frames_in_flight.max(1) {
+        let frame = frames_in_flight.pop_front().unwrap();
+        profiling::scope!("Waiting for frame in flight");
+        renderer.device.poll(Maintain::WaitForSubmissionIndex(frame));
+    }
+}
+
 struct StoredSurfaceInfo {
     size: UVec2,
     scale_factor: f32,
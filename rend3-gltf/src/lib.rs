@@ -5,7 +5,8 @@
 //! represent.
 //!
 //! To "just load a gltf/glb", look at the documentation for [`load_gltf`] and
-//! use the default [`filesystem_io_func`].
+//! use the default [`filesystem_io_func`]. When loading untrusted files, use
+//! [`filesystem_io_func_with_limit`] to cap the size of embedded data.
 //!
 //! Individual components of a gltf can be loaded with the other functions in
 //! this crate.
@@ -14,6 +15,14 @@
 //! - `KHR_punctual_lights`
 //! - `KHR_texture_transform`
 //! - `KHR_material_unlit`
+//! - `KHR_materials_anisotropy`
+//! - `KHR_materials_transmission`
+//! - `KHR_materials_sheen`
+//! - `KHR_materials_ior`
+//!
+//! # Features
+//! - `ddsfile` and `ktx2` (default): load textures from dds and ktx2 files.
+//! - `serde_json`: parse node extras with [`Node::extras_json`].
 //!
 //! # Known Limitations
 //! - Only the albedo texture's transform from `KHR_texture_transform` will be
@@ -22,14 +31,21 @@
 
 use std::{
     borrow::Cow,
-    collections::{hash_map::Entry, BTreeMap, HashMap, VecDeque},
+    collections::{
+        hash_map::{DefaultHasher, Entry},
+        BTreeMap, HashMap, VecDeque,
+    },
     future::Future,
+    hash::{Hash, Hasher},
+    iter::zip,
     path::Path,
+    pin::Pin,
     sync::Arc,
+    task::{Context, Poll},
 };
 
 use base64::Engine;
-use glam::{Mat3, Mat4, Quat, UVec2, Vec2, Vec3, Vec4};
+use glam::{DVec3, Mat3, Mat4, Quat, UVec2, Vec2, Vec3, Vec4};
 use gltf::buffer::Source;
 use rend3::{
     types::{self, Handedness, MeshValidationError, ObjectHandle, ObjectMeshKind, Skeleton, SkeletonHandle},
@@ -54,6 +70,11 @@ impl<T> Labeled<T> {
     }
 }
 
+/// Index of the first item labeled `name`.
+fn index_by_label<T>(items: &[Labeled<T>], name: &str) -> Option<usize> {
+    items.iter().position(|item| item.label.as_deref() == Some(name))
+}
+
 /// A single sub-mesh of a gltf.
 #[derive(Debug)]
 pub struct MeshPrimitive {
@@ -107,6 +128,16 @@ pub struct Node {
     pub object: Option<Labeled<Object>>,
     /// Directional light for this node.
     pub directional_light: Option<types::DirectionalLightHandle>,
+    /// The node's application specific `extras`, as raw JSON text.
+    pub extras: Option<String>,
+}
+
+impl Node {
+    /// Parse the node's `extras` as JSON, if it has any.
+    #[cfg(feature = "serde_json")]
+    pub fn extras_json(&self) -> Option<serde_json::Result<serde_json::Value>> {
+        self.extras.as_deref().map(serde_json::from_str)
+    }
 }
 
 /// Hashmap key for caching images.
@@ -118,13 +149,53 @@ pub struct ImageKey {
     pub srgb: bool,
 }
 
+/// Hashmap key for caching meshes by their contents.
+///
+/// Meshes with the same key are very likely identical, but hashes can
+/// collide, so a [`MeshMap`] also compares their data. Build one with
+/// [`MeshKey::new`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct MeshKey {
+    pub vertex_count: usize,
+    pub index_count: usize,
+    /// Hash of the indices and the data of every vertex attribute.
+    pub hash: u64,
+}
+
+impl MeshKey {
+    pub fn new(mesh: &types::Mesh) -> Self {
+        let mut hasher = DefaultHasher::new();
+        mesh.indices.hash(&mut hasher);
+        for attribute in &mesh.attributes {
+            attribute.id().name().hash(&mut hasher);
+            attribute.untyped_data().hash(&mut hasher);
+        }
+
+        Self { vertex_count: mesh.vertex_count, index_count: mesh.indices.len(), hash: hasher.finish() }
+    }
+}
+
 /// A uploaded texture and its format.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Texture {
     pub handle: types::Texture2DHandle,
+    /// Format the texture was uploaded with. This is the source of truth for
+    /// the texture's color space, see [`Texture::is_srgb`].
     pub format: types::TextureFormat,
 }
 
+impl Texture {
+    /// Returns true if the texture was uploaded with an sRGB format, so
+    /// sampling it yields linear values.
+    ///
+    /// This may be false even if the texture was loaded with `srgb` requested
+    /// in its [`ImageKey`], as some formats (such as float formats in ktx2 or
+    /// dds files) have no sRGB variant and are uploaded as-is.
+    pub fn is_srgb(&self) -> bool {
+        self.format.is_srgb()
+    }
+}
+
 #[derive(Debug)]
 pub struct Joint {
     pub node_idx: usize,
@@ -170,6 +241,31 @@ pub struct Animation {
 /// Hashmap which stores a mapping from [`ImageKey`] to a labeled handle.
 pub type ImageMap = FastHashMap<ImageKey, Labeled<Texture>>;
 
+/// A mesh uploaded through a [`MeshMap`], along with a copy of the data it
+/// was uploaded from.
+#[derive(Debug, Clone)]
+pub struct CachedMesh {
+    pub handle: types::MeshHandle,
+    indices: Vec<u32>,
+    attributes: Vec<(&'static str, Vec<u8>)>,
+}
+
+impl CachedMesh {
+    /// Returns if `mesh` has exactly the data this mesh was uploaded from.
+    pub fn matches(&self, mesh: &types::Mesh) -> bool {
+        self.indices == mesh.indices
+            && self.attributes.len() == mesh.attributes.len()
+            && zip(&self.attributes, &mesh.attributes).all(|((name, data), attribute)| {
+                *name == attribute.id().name() && data[..] == *attribute.untyped_data()
+            })
+    }
+}
+
+/// Hashmap which stores a mapping from [`MeshKey`] to the uploaded meshes
+/// with that key. It keeps a copy of the data of every mesh, so meshes are
+/// only shared if their data is equal.
+pub type MeshMap = FastHashMap<MeshKey, Vec<CachedMesh>>;
+
 /// Loaded data on a gltf scene that can be reused across multiple instances of
 /// the same set of objects.
 #[derive(Debug)]
@@ -182,6 +278,18 @@ pub struct LoadedGltfScene {
     pub animations: Vec<Labeled<Animation>>,
 }
 
+impl LoadedGltfScene {
+    /// Index into [`Self::materials`] of the first material called `name`.
+    pub fn material_by_name(&self, name: &str) -> Option<usize> {
+        index_by_label(&self.materials, name)
+    }
+
+    /// Index into [`Self::meshes`] of the first mesh called `name`.
+    pub fn mesh_by_name(&self, name: &str) -> Option<usize> {
+        index_by_label(&self.meshes, name)
+    }
+}
+
 /// Data specific to each instance of a gltf scene.
 pub struct GltfSceneInstance {
     /// The flat list of nodes in the scene. Each node points to a list of
@@ -193,6 +301,13 @@ pub struct GltfSceneInstance {
     pub topological_order: Vec<usize>,
 }
 
+impl GltfSceneInstance {
+    /// Index into [`Self::nodes`] of the first node called `name`.
+    pub fn node_by_name(&self, name: &str) -> Option<usize> {
+        index_by_label(&self.nodes, name)
+    }
+}
+
 /// Describes how loading gltf failed.
 #[derive(Debug, Error)]
 pub enum GltfLoadError<E: std::error::Error + 'static> {
@@ -227,6 +342,10 @@ pub enum GltfLoadError<E: std::error::Error + 'static> {
     MissingSkin(usize),
     #[error("Gltf file references material {0} but material does not exist")]
     MissingMaterial(usize),
+    #[error("Mesh {mesh} has {primitives} primitives, but {overrides} material overrides were given")]
+    MaterialOverrideCountMismatch { mesh: usize, primitives: usize, overrides: usize },
+    #[error("Buffer {0} refers to the glb blob, but there is no blob")]
+    MissingBlob(usize),
     #[error("Mesh {0} primitive {1} uses unsupported mode {2:?}. Only triangles are supported")]
     UnsupportedPrimitiveMode(usize, usize, gltf::mesh::Mode),
     #[error("Mesh {0} failed validation")]
@@ -246,8 +365,8 @@ pub enum GltfLoadError<E: std::error::Error + 'static> {
 unsafe impl<E: std::error::Error + 'static> Send for GltfLoadError<E> {}
 unsafe impl<E: std::error::Error + 'static> Sync for GltfLoadError<E> {}
 
-/// Tries to decode data as base64, failing if the prefix doesn't match.
-pub fn try_load_base64(uri: &str) -> Option<Vec<u8>> {
+/// Splits the base64 payload out of a data URI.
+fn base64_payload(uri: &str) -> Option<&str> {
     let octet_stream_header = "data:";
 
     let base64_data = uri.strip_prefix(octet_stream_header)?;
@@ -258,6 +377,20 @@ pub fn try_load_base64(uri: &str) -> Option<Vec<u8>> {
         return None;
     }
 
+    Some(data)
+}
+
+/// Size in bytes the data URI decodes to, if it is a base64 data URI. This
+/// doesn't decode the data.
+pub fn base64_decoded_len(uri: &str) -> Option<usize> {
+    let data = base64_payload(uri)?;
+    Some(data.trim_end_matches('=').len() * 3 / 4)
+}
+
+/// Tries to decode data as base64, failing if the prefix doesn't match.
+pub fn try_load_base64(uri: &str) -> Option<Vec<u8>> {
+    let data = base64_payload(uri)?;
+
     log::info!("loading {} bytes of base64 data", data.len());
     profiling::scope!("decoding base64 uri");
     // TODO: errors
@@ -281,9 +414,80 @@ pub async fn filesystem_io_func(parent_directory: impl AsRef<Path>, uri: &str) -
     std::fs::read(path_resolved)
 }
 
+/// [`filesystem_io_func`] which refuses to decode data URIs larger than
+/// `max_embedded_bytes`, for loading untrusted files. Embedded data is
+/// decoded in one allocation, so a huge data URI could otherwise exhaust
+/// memory.
+pub async fn filesystem_io_func_with_limit(
+    parent_directory: impl AsRef<Path>,
+    uri: &str,
+    max_embedded_bytes: usize,
+) -> Result<Vec<u8>, std::io::Error> {
+    if let Some(len) = base64_decoded_len(uri) {
+        if len > max_embedded_bytes {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("embedded data is {len} bytes, over the limit of {max_embedded_bytes} bytes"),
+            ));
+        }
+    }
+
+    filesystem_io_func(parent_directory, uri).await
+}
+
+/// Tweaks applied to a material after it is read from the gltf, to fix assets
+/// without re-exporting them. Fields left as `None` keep the value from the
+/// gltf.
+#[derive(Debug, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(default))]
+pub struct MaterialOverride {
+    /// Replaces how the textures of the material are sampled.
+    pub sample_type: Option<pbr::SampleType>,
+    /// Multiplies the emissive color.
+    pub emissive_multiplier: Option<f32>,
+    /// Replaces whether the material is unlit.
+    pub unlit: Option<bool>,
+    /// Replaces the alpha cutoff of masked materials. Has no effect on other
+    /// materials.
+    pub alpha_cutoff: Option<f32>,
+}
+
+impl MaterialOverride {
+    /// Applies the override to a material read from the gltf.
+    pub fn apply(&self, material: &mut pbr::PbrMaterial) {
+        if let Some(sample_type) = self.sample_type {
+            material.sample_type = sample_type;
+        }
+        if let Some(multiplier) = self.emissive_multiplier {
+            material.emissive = match material.emissive {
+                pbr::MaterialComponent::None => pbr::MaterialComponent::None,
+                pbr::MaterialComponent::Value(value) => pbr::MaterialComponent::Value(value * multiplier),
+                pbr::MaterialComponent::Texture(ref texture) => {
+                    pbr::MaterialComponent::TextureValue { texture: texture.clone(), value: Vec3::splat(multiplier) }
+                }
+                pbr::MaterialComponent::TextureValue { ref texture, value } => {
+                    pbr::MaterialComponent::TextureValue { texture: texture.clone(), value: value * multiplier }
+                }
+            };
+        }
+        if let Some(unlit) = self.unlit {
+            material.unlit = unlit;
+        }
+        if let (Some(alpha_cutoff), pbr::Transparency::Cutout { cutout }) =
+            (self.alpha_cutoff, &mut material.transparency)
+        {
+            *cutout = alpha_cutoff;
+        }
+    }
+}
+
+/// Overrides of materials, by the name of the material in the gltf.
+pub type MaterialOverrides = HashMap<String, MaterialOverride>;
+
 /// Determines parameters that are given to various parts of the gltf world that
 /// cannot be specified by gltf alone.
-#[derive(Copy, Clone)]
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(default))]
 pub struct GltfLoadSettings {
     /// Global scale applied to all objects (default: 1)
     pub scale: f32,
@@ -295,6 +499,11 @@ pub struct GltfLoadSettings {
     pub normal_direction: pbr::NormalTextureYDirection,
     /// Enable built-in directional lights (default true)
     pub enable_directional: bool,
+    /// Upload primitives with identical geometry only once, sharing their
+    /// [`types::MeshHandle`] (default false)
+    pub deduplicate_meshes: bool,
+    /// Tweaks applied to materials with matching names (default empty)
+    pub material_overrides: MaterialOverrides,
 }
 
 impl Default for GltfLoadSettings {
@@ -305,6 +514,8 @@ impl Default for GltfLoadSettings {
             directional_light_resolution: 2048,
             normal_direction: pbr::NormalTextureYDirection::Up,
             enable_directional: true,
+            deduplicate_meshes: false,
+            material_overrides: MaterialOverrides::new(),
         }
     }
 }
@@ -317,7 +528,8 @@ impl Default for GltfLoadSettings {
 /// **Must** keep the [`LoadedGltfScene`] alive for the scene to remain.
 ///
 /// See [`load_gltf_data`] and [`instance_loaded_scene`] if you need more
-/// fine-grained control about how and when the scene data is instanced.
+/// fine-grained control about how and when the scene data is instanced, and
+/// [`GltfLoadTask`] if you need to be able to cancel the load.
 ///
 /// ```no_run
 /// # use std::path::Path;
@@ -337,7 +549,7 @@ pub async fn load_gltf<F, Fut, E>(
     data: &[u8],
     settings: &GltfLoadSettings,
     io_func: F,
-) -> Result<(LoadedGltfScene, GltfSceneInstance), GltfLoadError<E>>
+) -> GltfLoadResult<E>
 where
     F: FnMut(SsoString) -> Fut,
     Fut: Future<Output = Result<Vec<u8>, E>>,
@@ -351,6 +563,7 @@ where
     };
 
     let loaded = load_gltf_data(renderer, &mut file, settings, io_func).await?;
+    YieldNow(false).await;
 
     if file.scenes().len() != 1 {
         return Err(GltfLoadError::GltfSingleSceneOnly);
@@ -371,6 +584,83 @@ where
     Ok((loaded, instance))
 }
 
+/// Result of loading and instancing a gltf with [`load_gltf`] or a
+/// [`GltfLoadTask`].
+pub type GltfLoadResult<E> = Result<(LoadedGltfScene, GltfSceneInstance), GltfLoadError<E>>;
+
+/// A cancellable [`load_gltf`].
+///
+/// The task is a future which drives the load when polled. Dropping it before
+/// it completes cancels the load, dropping the handles to any meshes,
+/// textures, and materials already uploaded so the renderer frees them. Loading
+/// yields between the buffer, mesh, material, and skin stages, so a task
+/// which is no longer polled stops at the next stage boundary at the latest.
+///
+/// The task is `Send`, so it can be spawned on multithreaded executors. This
+/// requires the io function and the futures it returns to be `Send` as well.
+///
+/// ```no_run
+/// # use std::path::Path;
+/// # let renderer = unimplemented!();
+/// let path = Path::new("some/path/scene.gltf");
+/// let gltf_data = std::fs::read(&path).unwrap();
+/// let parent_directory = path.parent().unwrap();
+/// let task = rend3_gltf::GltfLoadTask::new(
+///     &renderer,
+///     &gltf_data,
+///     rend3_gltf::GltfLoadSettings::default(),
+///     |p| async move { rend3_gltf::filesystem_io_func(&parent_directory, &p).await }
+/// );
+/// // The user picked a different file, throw away the old load.
+/// task.cancel();
+/// ```
+#[must_use = "the gltf is only loaded while the task is polled"]
+pub struct GltfLoadTask<'a, E: std::error::Error + 'static> {
+    future: Pin<Box<dyn Future<Output = GltfLoadResult<E>> + Send + 'a>>,
+}
+
+impl<'a, E: std::error::Error + Send + 'static> GltfLoadTask<'a, E> {
+    /// Start loading the given gltf. See [`load_gltf`] for the meaning of the
+    /// arguments.
+    pub fn new<F, Fut>(renderer: &Arc<Renderer>, data: &'a [u8], settings: GltfLoadSettings, io_func: F) -> Self
+    where
+        F: FnMut(SsoString) -> Fut + Send + 'a,
+        Fut: Future<Output = Result<Vec<u8>, E>> + Send + 'a,
+    {
+        let renderer = Arc::clone(renderer);
+        Self { future: Box::pin(async move { load_gltf(&renderer, data, &settings, io_func).await }) }
+    }
+
+    /// Cancel the load, freeing everything loaded so far. This is the same as
+    /// dropping the task.
+    pub fn cancel(self) {}
+}
+
+impl<'a, E: std::error::Error + 'static> Future for GltfLoadTask<'a, E> {
+    type Output = GltfLoadResult<E>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.future.as_mut().poll(cx)
+    }
+}
+
+/// Future which returns pending once before completing, letting the executor
+/// drop the loading future between stages.
+struct YieldNow(bool);
+
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.0 {
+            return Poll::Ready(());
+        }
+        self.0 = true;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
 /// Load a given gltf's data, like meshes and materials, without yet adding
 /// any of the nodes to the scene.
 ///
@@ -393,11 +683,19 @@ where
     let blob = file.blob.take();
 
     let buffers = load_buffers(file.buffers(), blob, &mut io_func).await?;
+    YieldNow(false).await;
 
     let default_material = load_default_material(renderer);
-    let meshes = load_meshes(renderer, file.meshes(), &buffers)?;
+    let mut mesh_cache = MeshMap::default();
+    let meshes =
+        load_meshes(renderer, file.meshes(), &buffers, settings.deduplicate_meshes.then_some(&mut mesh_cache))?;
+    YieldNow(false).await;
+
     let (materials, images) =
-        load_materials_and_textures(renderer, file.materials(), &buffers, settings, &mut io_func).await?;
+        load_materials_and_textures(renderer, &file.document, file.materials(), &buffers, settings, &mut io_func)
+            .await?;
+    YieldNow(false).await;
+
     let skins = load_skins(file.skins(), &buffers)?;
     let animations = load_animations(file.animations(), &buffers)?;
 
@@ -415,9 +713,51 @@ pub fn add_mesh_by_index<E: std::error::Error + 'static>(
     name: Option<&str>,
     skin_index: Option<usize>,
     transform: Mat4,
+) -> Result<Labeled<Object>, GltfLoadError<E>> {
+    add_mesh_by_index_inner(renderer, loaded, mesh_index, name, skin_index, transform, None)
+}
+
+/// Adds a single mesh from the [`LoadedGltfScene`] found by its index, as an
+/// object to the scene, drawing its primitives with other materials.
+///
+/// `materials` has one entry per primitive of the mesh. Primitives with a
+/// `Some` entry use that material instead of the one they were loaded with.
+/// This allows reusing loaded geometry with different materials, like team
+/// colors or damage states.
+pub fn add_mesh_by_index_with_materials<E: std::error::Error + 'static>(
+    renderer: &Arc<Renderer>,
+    loaded: &LoadedGltfScene,
+    mesh_index: usize,
+    name: Option<&str>,
+    skin_index: Option<usize>,
+    transform: Mat4,
+    materials: &[Option<types::MaterialHandle>],
+) -> Result<Labeled<Object>, GltfLoadError<E>> {
+    add_mesh_by_index_inner(renderer, loaded, mesh_index, name, skin_index, transform, Some(materials))
+}
+
+fn add_mesh_by_index_inner<E: std::error::Error + 'static>(
+    renderer: &Arc<Renderer>,
+    loaded: &LoadedGltfScene,
+    mesh_index: usize,
+    name: Option<&str>,
+    skin_index: Option<usize>,
+    transform: Mat4,
+    material_overrides: Option<&[Option<types::MaterialHandle>]>,
 ) -> Result<Labeled<Object>, GltfLoadError<E>> {
     let mesh_handle = loaded.meshes.get(mesh_index).ok_or(GltfLoadError::MissingMesh(mesh_index))?;
 
+    if let Some(overrides) = material_overrides {
+        let primitives = mesh_handle.inner.primitives.len();
+        if overrides.len() != primitives {
+            return Err(GltfLoadError::MaterialOverrideCountMismatch {
+                mesh: mesh_index,
+                primitives,
+                overrides: overrides.len(),
+            });
+        }
+    }
+
     let mut primitives = Vec::new();
     let mut skeletons = Vec::new();
 
@@ -428,11 +768,20 @@ pub fn add_mesh_by_index<E: std::error::Error + 'static>(
         None
     };
 
-    for prim in &mesh_handle.inner.primitives {
-        let mat_idx = prim.material;
-        let mat = mat_idx
-            .map_or_else(|| Some(&loaded.default_material), |mat_idx| loaded.materials.get(mat_idx).map(|m| &m.inner))
-            .ok_or_else(|| GltfLoadError::MissingMaterial(mat_idx.expect("Could not find default material")))?;
+    for (prim_idx, prim) in mesh_handle.inner.primitives.iter().enumerate() {
+        let mat_override = material_overrides.and_then(|overrides| overrides[prim_idx].as_ref());
+        let mat = match mat_override {
+            Some(mat) => mat,
+            None => {
+                let mat_idx = prim.material;
+                mat_idx
+                    .map_or_else(
+                        || Some(&loaded.default_material),
+                        |mat_idx| loaded.materials.get(mat_idx).map(|m| &m.inner),
+                    )
+                    .ok_or_else(|| GltfLoadError::MissingMaterial(mat_idx.expect("Could not find default material")))?
+            }
+        };
 
         let mesh_kind = if let Some(skin) = skin {
             let skeleton = renderer.add_skeleton(Skeleton {
@@ -440,6 +789,7 @@ pub fn add_mesh_by_index<E: std::error::Error + 'static>(
                 // joint matrix is inv_bind_pose * bind_pose, thus the identity matrix.
                 joint_matrices: vec![Mat4::IDENTITY; skin.inner.inverse_bind_matrices.len()],
                 mesh: prim.handle.clone(),
+                skinning_method: types::SkinningMethod::LinearBlend,
             })?;
             skeletons.push(skeleton.clone());
             ObjectMeshKind::Animated(skeleton)
@@ -447,7 +797,14 @@ pub fn add_mesh_by_index<E: std::error::Error + 'static>(
             ObjectMeshKind::Static(prim.handle.clone())
         };
 
-        primitives.push(renderer.add_object(types::Object { mesh_kind, material: mat.clone(), transform }));
+        primitives.push(renderer.add_object(types::Object {
+            mesh_kind,
+            material: mat.clone(),
+            transform,
+            world_offset: DVec3::ZERO,
+            sort_bias: 0,
+            debug_tint: None,
+        }));
     }
 
     Ok(Labeled::new(
@@ -490,6 +847,9 @@ fn node_indices_topological_sort(nodes: &[gltf::Node]) -> (Vec<usize>, BTreeMap<
 ///
 /// You need to hold onto the returned value from this function to make sure the
 /// objects don't get deleted.
+///
+/// Large scenes can be instanced over several frames with
+/// [`instance_scene_step`] instead.
 pub fn instance_loaded_scene<E: std::error::Error + 'static>(
     renderer: &Arc<Renderer>,
     loaded: &LoadedGltfScene,
@@ -497,22 +857,88 @@ pub fn instance_loaded_scene<E: std::error::Error + 'static>(
     settings: &GltfLoadSettings,
     parent_transform: Mat4,
 ) -> Result<GltfSceneInstance, GltfLoadError<E>> {
-    let (topological_order, parents) = node_indices_topological_sort(&nodes);
+    let mut partial = PartialSceneInstance::new(nodes, parent_transform);
+    instance_scene_step(renderer, loaded, &mut partial, settings, usize::MAX)?;
+    Ok(partial.finish().expect("all nodes were instanced"))
+}
+
+/// A scene being instanced a few nodes at a time by [`instance_scene_step`].
+///
+/// Objects and lights of the nodes instanced so far are owned by this, so
+/// dropping it before finishing removes them again.
+pub struct PartialSceneInstance<'a> {
+    nodes: Vec<gltf::Node<'a>>,
+    parents: BTreeMap<usize, usize>,
+    parent_transform: Mat4,
+    node_transforms: Vec<Mat4>,
+    final_nodes: Vec<Labeled<Node>>,
+    topological_order: Vec<usize>,
+    /// Number of nodes of `topological_order` which are instanced.
+    instanced: usize,
+}
+
+impl<'a> PartialSceneInstance<'a> {
+    /// Prepares instancing `nodes`, with `parent_transform` applied to the root
+    /// nodes. No objects are created until [`instance_scene_step`] is called.
+    pub fn new(nodes: Vec<gltf::Node<'a>>, parent_transform: Mat4) -> Self {
+        let (topological_order, parents) = node_indices_topological_sort(&nodes);
 
-    let num_nodes = nodes.len();
+        let num_nodes = nodes.len();
+
+        debug_assert_eq!(topological_order.len(), num_nodes);
+
+        Self {
+            nodes,
+            parents,
+            parent_transform,
+            node_transforms: vec![Mat4::IDENTITY; num_nodes],
+            final_nodes: vec![Labeled::new(Node::default(), None); num_nodes],
+            topological_order,
+            instanced: 0,
+        }
+    }
 
-    debug_assert_eq!(topological_order.len(), num_nodes);
+    /// Number of nodes which are still to be instanced.
+    pub fn remaining(&self) -> usize {
+        self.topological_order.len() - self.instanced
+    }
 
-    let mut node_transforms = vec![Mat4::IDENTITY; num_nodes];
+    /// Whether every node has been instanced.
+    pub fn is_finished(&self) -> bool {
+        self.remaining() == 0
+    }
 
-    let mut final_nodes = vec![Labeled::new(Node::default(), None); nodes.len()];
-    for node_idx in topological_order.iter() {
-        let node = &nodes[*node_idx];
+    /// The finished instance, or `None` if there are nodes left to instance.
+    pub fn finish(self) -> Option<GltfSceneInstance> {
+        self.is_finished()
+            .then(|| GltfSceneInstance { nodes: self.final_nodes, topological_order: self.topological_order })
+    }
+}
+
+/// Instances up to `max_nodes` more nodes of a scene loaded using
+/// [`load_gltf_data`], so a large scene can be spread over several frames.
+/// Returns whether the scene is finished, after which
+/// [`PartialSceneInstance::finish`] gives the instance.
+///
+/// Nodes are instanced parents first, so the nodes instanced so far always
+/// form a complete part of the hierarchy.
+pub fn instance_scene_step<E: std::error::Error + 'static>(
+    renderer: &Arc<Renderer>,
+    loaded: &LoadedGltfScene,
+    partial: &mut PartialSceneInstance<'_>,
+    settings: &GltfLoadSettings,
+    max_nodes: usize,
+) -> Result<bool, GltfLoadError<E>> {
+    let end = partial.instanced.saturating_add(max_nodes).min(partial.topological_order.len());
+    while partial.instanced < end {
+        let node_idx = partial.topological_order[partial.instanced];
+        let node = &partial.nodes[node_idx];
 
         let local_transform = Mat4::from_cols_array_2d(&node.transform().matrix());
-        let parent_transform = parents.get(&node.index()).map(|p| node_transforms[*p]).unwrap_or(parent_transform);
+        let parent_transform =
+            partial.parents.get(&node.index()).map(|p| partial.node_transforms[*p]).unwrap_or(partial.parent_transform);
         let transform = parent_transform * local_transform;
-        node_transforms[*node_idx] = transform;
+        partial.node_transforms[node_idx] = transform;
 
         let object = if let Some(mesh) = node.mesh() {
             Some(add_mesh_by_index(
@@ -537,6 +963,9 @@ pub fn instance_loaded_scene<E: std::error::Error + 'static>(
                         direction,
                         distance: settings.directional_light_shadow_distance,
                         resolution: settings.directional_light_resolution,
+                        casts_shadows: true,
+                        snap_to_texels: true,
+                        angular_size: 0.0,
                     }))
                 }
                 _ => None,
@@ -547,18 +976,20 @@ pub fn instance_loaded_scene<E: std::error::Error + 'static>(
 
         let children = node.children().map(|node| node.index()).collect();
 
-        final_nodes[*node_idx] = Labeled::new(
+        partial.final_nodes[node_idx] = Labeled::new(
             Node {
-                parent: parents.get(&node.index()).cloned(),
+                parent: partial.parents.get(&node.index()).cloned(),
                 children,
                 local_transform,
                 object,
                 directional_light: light,
+                extras: node.extras().as_ref().map(|extras| extras.get().to_owned()),
             },
             node.name(),
-        )
+        );
+        partial.instanced += 1;
     }
-    Ok(GltfSceneInstance { nodes: final_nodes, topological_order })
+    Ok(partial.is_finished())
 }
 
 /// Loads buffers from a [`gltf::Buffer`] iterator, calling io_func to resolve
@@ -566,12 +997,10 @@ pub fn instance_loaded_scene<E: std::error::Error + 'static>(
 ///
 /// If the gltf came from a .glb, the glb's blob should be provided.
 ///
-/// # Panics
-///
-/// Panics if buffers requires a blob but no blob was given.
+/// If a buffer refers to the blob but no blob was given, this is an error.
 pub async fn load_buffers<F, Fut, E>(
     file: impl ExactSizeIterator<Item = gltf::Buffer<'_>>,
-    blob: Option<Vec<u8>>,
+    mut blob: Option<Vec<u8>>,
     mut io_func: F,
 ) -> Result<Vec<Vec<u8>>, GltfLoadError<E>>
 where
@@ -581,22 +1010,15 @@ where
 {
     // profiling::scope!("loading buffers");
     let mut buffers = Vec::with_capacity(file.len());
-    let mut blob_index = None;
     for b in file {
         let data = match b.source() {
-            Source::Bin => {
-                blob_index = Some(b.index());
-                Vec::new()
-            }
+            Source::Bin => blob.take().ok_or(GltfLoadError::MissingBlob(b.index()))?,
             Source::Uri(uri) => {
                 io_func(SsoString::from(uri)).await.map_err(|e| GltfLoadError::BufferIo(SsoString::from(uri), e))?
             }
         };
         buffers.push(data);
     }
-    if let Some(blob_index) = blob_index {
-        buffers[blob_index] = blob.expect("glb blob not found, but gltf expected it");
-    }
     Ok(buffers)
 }
 
@@ -604,10 +1026,15 @@ where
 ///
 /// All binary data buffers must be provided. Call this with
 /// [`gltf::Document::meshes`] as the mesh argument.
+///
+/// If a [`MeshMap`] is given, it is used as a cache: primitives whose geometry
+/// is already in it reuse the existing handle instead of being uploaded again.
+/// The same map can be passed to multiple loads to share meshes between them.
 pub fn load_meshes<'a, E: std::error::Error + 'static>(
     renderer: &Arc<Renderer>,
     meshes: impl Iterator<Item = gltf::Mesh<'a>>,
     buffers: &[Vec<u8>],
+    mut mesh_cache: Option<&mut MeshMap>,
 ) -> Result<Vec<Labeled<Mesh>>, GltfLoadError<E>> {
     profiling::scope!("loading meshes");
     meshes
@@ -650,6 +1077,7 @@ pub fn load_meshes<'a, E: std::error::Error + 'static>(
                     builder = builder.with_vertex_texture_coordinates_1(uvs.into_f32().map(Vec2::from).collect())
                 }
 
+                // glTF vertex colors are linear, so materials don't decode them as sRGB.
                 if let Some(colors) = reader.read_colors(0) {
                     builder = builder.with_vertex_color_0(colors.into_rgba_u8().collect())
                 }
@@ -666,9 +1094,18 @@ pub fn load_meshes<'a, E: std::error::Error + 'static>(
                     builder = builder.with_vertex_joint_weights(joint_weights.into_f32().map(Vec4::from).collect())
                 }
 
+                // Only the first 8 joint influences are supported.
+                if let Some(joint_indices) = reader.read_joints(1) {
+                    builder = builder.with_vertex_joint_indices_1(joint_indices.into_u16().collect())
+                }
+
+                if let Some(joint_weights) = reader.read_weights(1) {
+                    builder = builder.with_vertex_joint_weights_1(joint_weights.into_f32().map(Vec4::from).collect())
+                }
+
                 let mesh = builder.build().map_err(|valid| GltfLoadError::MeshValidationError(mesh.index(), valid))?;
 
-                let handle = renderer.add_mesh(mesh)?;
+                let handle = add_mesh_cached(mesh_cache.as_deref_mut(), mesh, |mesh| renderer.add_mesh(mesh))?;
 
                 res_prims.push(MeshPrimitive { handle, material: prim.material().index() })
             }
@@ -677,6 +1114,30 @@ pub fn load_meshes<'a, E: std::error::Error + 'static>(
         .collect()
 }
 
+/// Uploads `mesh` with `add_mesh`, unless `mesh_cache` is given and already
+/// holds a mesh with the same data.
+fn add_mesh_cached<E>(
+    mesh_cache: Option<&mut MeshMap>,
+    mesh: types::Mesh,
+    add_mesh: impl FnOnce(types::Mesh) -> Result<types::MeshHandle, E>,
+) -> Result<types::MeshHandle, E> {
+    let Some(cache) = mesh_cache else {
+        return add_mesh(mesh);
+    };
+
+    let cached = cache.entry(MeshKey::new(&mesh)).or_default();
+    if let Some(existing) = cached.iter().find(|existing| existing.matches(&mesh)) {
+        return Ok(existing.handle.clone());
+    }
+
+    let indices = mesh.indices.clone();
+    let attributes =
+        mesh.attributes.iter().map(|attribute| (attribute.id().name(), attribute.untyped_data().to_vec())).collect();
+    let handle = add_mesh(mesh)?;
+    cached.push(CachedMesh { handle: handle.clone(), indices, attributes });
+    Ok(handle)
+}
+
 fn load_skins<E: std::error::Error + 'static>(
     skins: gltf::iter::Skins,
     buffers: &[Vec<u8>],
@@ -780,6 +1241,7 @@ pub fn load_default_material(renderer: &Arc<Renderer>) -> types::MaterialHandle
         albedo: pbr::AlbedoComponent::Value(Vec4::splat(1.0)),
         transparency: pbr::Transparency::Opaque,
         normal: pbr::NormalTexture::None,
+        parallax: pbr::ParallaxTexture::None,
         aomr_textures: pbr::AoMRTextures::None,
         ao_factor: Some(1.0),
         metallic_factor: Some(1.0),
@@ -789,22 +1251,101 @@ pub fn load_default_material(renderer: &Arc<Renderer>) -> types::MaterialHandle
         clearcoat_roughness_factor: Some(1.0),
         emissive: pbr::MaterialComponent::None,
         reflectance: pbr::MaterialComponent::None,
+        ior: None,
         anisotropy: pbr::MaterialComponent::None,
+        anisotropy_rotation: 0.0,
+        transmission: pbr::MaterialComponent::None,
+        sheen_color: pbr::MaterialComponent::None,
+        sheen_roughness: pbr::MaterialComponent::None,
+        lightmap: None,
         uv_transform0: Mat3::IDENTITY,
         uv_transform1: Mat3::IDENTITY,
+        uv_sets: pbr::UvSets::default(),
         unlit: false,
         sample_type: pbr::SampleType::Linear,
+        culling: types::Culling::Back,
+    })
+}
+
+/// Texture info of a material extension the gltf crate doesn't parse itself.
+struct ExtensionTexture<'a> {
+    texture: Option<gltf::Texture<'a>>,
+    tex_coord: u8,
+}
+
+fn read_extension_texture<'a>(
+    document: &'a gltf::Document,
+    extension: &gltf::json::Value,
+    key: &str,
+) -> ExtensionTexture<'a> {
+    let texture_info = extension.get(key);
+    ExtensionTexture {
+        texture: texture_info
+            .and_then(|info| info.get("index")?.as_u64())
+            .and_then(|index| document.textures().nth(index as usize)),
+        tex_coord: texture_info.and_then(|info| info.get("texCoord")?.as_u64()).unwrap_or(0) as u8,
+    }
+}
+
+fn read_extension_number(extension: &gltf::json::Value, key: &str, default: f64) -> f32 {
+    extension.get(key).and_then(|v| v.as_f64()).unwrap_or(default) as f32
+}
+
+/// `KHR_materials_anisotropy`, which the gltf crate doesn't parse itself.
+struct AnisotropyExtension<'a> {
+    strength: f32,
+    rotation: f32,
+    texture: ExtensionTexture<'a>,
+}
+
+fn read_anisotropy_extension<'a>(
+    document: &'a gltf::Document,
+    material: &gltf::Material<'_>,
+) -> Option<AnisotropyExtension<'a>> {
+    let extension = material.extensions()?.get("KHR_materials_anisotropy")?;
+
+    Some(AnisotropyExtension {
+        strength: read_extension_number(extension, "anisotropyStrength", 0.0),
+        rotation: read_extension_number(extension, "anisotropyRotation", 0.0),
+        texture: read_extension_texture(document, extension, "anisotropyTexture"),
+    })
+}
+
+/// `KHR_materials_sheen`, which the gltf crate doesn't parse itself.
+struct SheenExtension<'a> {
+    color: Vec3,
+    roughness: f32,
+    color_texture: ExtensionTexture<'a>,
+    roughness_texture: ExtensionTexture<'a>,
+}
+
+fn read_sheen_extension<'a>(document: &'a gltf::Document, material: &gltf::Material<'_>) -> Option<SheenExtension<'a>> {
+    let extension = material.extensions()?.get("KHR_materials_sheen")?;
+    let color = extension
+        .get("sheenColorFactor")
+        .and_then(|v| v.as_array())
+        .filter(|v| v.len() == 3)
+        .map_or(Vec3::ZERO, |v| Vec3::from_array([0, 1, 2].map(|i| v[i].as_f64().unwrap_or(0.0) as f32)));
+
+    Some(SheenExtension {
+        color,
+        roughness: read_extension_number(extension, "sheenRoughnessFactor", 0.0),
+        color_texture: read_extension_texture(document, extension, "sheenColorTexture"),
+        roughness_texture: read_extension_texture(document, extension, "sheenRoughnessTexture"),
     })
 }
 
 /// Loads materials and textures from a [`gltf::Material`] iterator.
 ///
 /// All binary data buffers must be provided. Call this with
-/// [`gltf::Document::materials`] as the materials argument.
+/// [`gltf::Document::materials`] as the materials argument, and the
+/// document they came from, which is used to find textures referenced by
+/// extensions.
 ///
 /// io_func determines how URIs are resolved into their underlying data.
 pub async fn load_materials_and_textures<F, Fut, E>(
     renderer: &Arc<Renderer>,
+    document: &gltf::Document,
     materials: impl ExactSizeIterator<Item = gltf::Material<'_>>,
     buffers: &[Vec<u8>],
     settings: &GltfLoadSettings,
@@ -832,6 +1373,10 @@ where
         let roughness_factor = pbr.roughness_factor();
         let metallic_factor = pbr.metallic_factor();
         let metallic_roughness = pbr.metallic_roughness_texture();
+        let anisotropy = read_anisotropy_extension(document, &material);
+        let sheen = read_sheen_extension(document, &material);
+        let transmission = material.transmission();
+        let transmission_texture = transmission.as_ref().and_then(|t| t.transmission_texture());
 
         let nearest = albedo
             .as_ref()
@@ -854,6 +1399,21 @@ where
             })
             .unwrap_or(Mat3::IDENTITY);
 
+        let uv_set = |tex_coord: Option<u32>| tex_coord.unwrap_or(0) as u8;
+        let uv_sets = pbr::UvSets {
+            albedo: uv_set(albedo.as_ref().map(|i| i.tex_coord())),
+            normal: uv_set(normals.as_ref().map(|i| i.tex_coord())),
+            roughness: uv_set(metallic_roughness.as_ref().map(|i| i.tex_coord())),
+            metallic: uv_set(metallic_roughness.as_ref().map(|i| i.tex_coord())),
+            emissive: uv_set(emissive.as_ref().map(|i| i.tex_coord())),
+            ambient_occlusion: uv_set(occlusion.as_ref().map(|i| i.tex_coord())),
+            anisotropy: anisotropy.as_ref().map_or(0, |a| a.texture.tex_coord),
+            transmission: uv_set(transmission_texture.as_ref().map(|i| i.tex_coord())),
+            sheen_color: sheen.as_ref().map_or(0, |s| s.color_texture.tex_coord),
+            sheen_roughness: sheen.as_ref().map_or(0, |s| s.roughness_texture.tex_coord),
+            ..pbr::UvSets::default()
+        };
+
         let albedo_tex = util::texture_option_resolve(
             albedo.map(|i| load_image_cached(renderer, &mut images, i.texture().source(), true, buffers, io_func)),
         )
@@ -875,8 +1435,34 @@ where
                 .map(|i| load_image_cached(renderer, &mut images, i.texture().source(), false, buffers, io_func)),
         )
         .await?;
+        let transmission_tex = util::texture_option_resolve(
+            transmission_texture
+                .map(|i| load_image_cached(renderer, &mut images, i.texture().source(), false, buffers, io_func)),
+        )
+        .await?;
+        let anisotropy_tex = util::texture_option_resolve(
+            anisotropy
+                .as_ref()
+                .and_then(|a| a.texture.texture.as_ref())
+                .map(|t| load_image_cached(renderer, &mut images, t.source(), false, buffers, io_func)),
+        )
+        .await?;
+        let sheen_color_tex = util::texture_option_resolve(
+            sheen
+                .as_ref()
+                .and_then(|s| s.color_texture.texture.as_ref())
+                .map(|t| load_image_cached(renderer, &mut images, t.source(), true, buffers, io_func)),
+        )
+        .await?;
+        let sheen_roughness_tex = util::texture_option_resolve(
+            sheen
+                .as_ref()
+                .and_then(|s| s.roughness_texture.texture.as_ref())
+                .map(|t| load_image_cached(renderer, &mut images, t.source(), false, buffers, io_func)),
+        )
+        .await?;
 
-        let handle = renderer.add_material(pbr::PbrMaterial {
+        let mut pbr_material = pbr::PbrMaterial {
             albedo: match albedo_tex {
                 Some(tex) => pbr::AlbedoComponent::TextureVertexValue {
                     texture: tex.handle,
@@ -890,7 +1476,7 @@ where
                 gltf::material::AlphaMode::Mask => {
                     pbr::Transparency::Cutout { cutout: material.alpha_cutoff().unwrap_or(0.5) }
                 }
-                gltf::material::AlphaMode::Blend => pbr::Transparency::Blend,
+                gltf::material::AlphaMode::Blend => pbr::Transparency::Blend { mode: pbr::BlendMode::StraightAlpha },
             },
             normal: match normals_tex {
                 Some(tex) if util::format_components(tex.format) == Some(2) => {
@@ -927,12 +1513,44 @@ where
                 }
                 None => pbr::MaterialComponent::Value(Vec3::from(emissive_factor)),
             },
+            anisotropy: match (anisotropy_tex, &anisotropy) {
+                (Some(tex), Some(a)) => pbr::MaterialComponent::TextureValue { texture: tex.handle, value: a.strength },
+                (None, Some(a)) => pbr::MaterialComponent::Value(a.strength),
+                (_, None) => pbr::MaterialComponent::None,
+            },
+            ior: material.ior(),
+            anisotropy_rotation: anisotropy.as_ref().map_or(0.0, |a| a.rotation),
+            transmission: match (transmission_tex, &transmission) {
+                (Some(tex), Some(t)) => {
+                    pbr::MaterialComponent::TextureValue { texture: tex.handle, value: t.transmission_factor() }
+                }
+                (None, Some(t)) => pbr::MaterialComponent::Value(t.transmission_factor()),
+                (_, None) => pbr::MaterialComponent::None,
+            },
+            sheen_color: match (sheen_color_tex, &sheen) {
+                (Some(tex), Some(s)) => pbr::MaterialComponent::TextureValue { texture: tex.handle, value: s.color },
+                (None, Some(s)) => pbr::MaterialComponent::Value(s.color),
+                (_, None) => pbr::MaterialComponent::None,
+            },
+            sheen_roughness: match (sheen_roughness_tex, &sheen) {
+                (Some(tex), Some(s)) => {
+                    pbr::MaterialComponent::TextureValue { texture: tex.handle, value: s.roughness }
+                }
+                (None, Some(s)) => pbr::MaterialComponent::Value(s.roughness),
+                (_, None) => pbr::MaterialComponent::None,
+            },
             uv_transform0: uv_transform,
             uv_transform1: uv_transform,
+            uv_sets,
             unlit: material.unlit(),
             sample_type: nearest,
+            culling: if material.double_sided() { types::Culling::None } else { types::Culling::Back },
             ..pbr::PbrMaterial::default()
-        });
+        };
+        if let Some(material_override) = material.name().and_then(|name| settings.material_overrides.get(name)) {
+            material_override.apply(&mut pbr_material);
+        }
+        let handle = renderer.add_material(pbr_material);
 
         result.push(Labeled::new(handle, material.name()));
     }
@@ -1007,6 +1625,24 @@ where
         }
     };
 
+    let texture = decode_image(&data, uri, image.name(), srgb)?;
+    let format = texture.format;
+    let handle = renderer.add_texture_2d(texture)?;
+
+    Ok(Labeled::new(Texture { handle, format }, image.name()))
+}
+
+/// Decodes an image file into a texture, without uploading it.
+///
+/// ktx2 and dds files are used as-is when the respective features are
+/// enabled. Anything else is decoded with the `image` crate and given
+/// generated mipmaps. `uri` is only used in errors.
+pub fn decode_image<E: std::error::Error + 'static>(
+    data: &[u8],
+    uri: SsoString,
+    label: Option<&str>,
+    srgb: bool,
+) -> Result<types::Texture, GltfLoadError<E>> {
     let mut uri = Some(uri);
     let mut texture = None;
 
@@ -1042,7 +1678,7 @@ where
         }
 
         texture = Some(types::Texture {
-            label: image.name().map(str::to_owned),
+            label: label.map(str::to_owned),
             format,
             size: UVec2::new(header.pixel_width, header.pixel_height),
             data,
@@ -1089,7 +1725,7 @@ where
             let data = dds.get_data(0).map_err(|_| GltfLoadError::TextureTooManyLayers(uri.take().unwrap()))?;
 
             texture = Some(types::Texture {
-                label: image.name().map(str::to_owned),
+                label: label.map(str::to_owned),
                 format,
                 size: UVec2::new(dds.get_width(), dds.get_height()),
                 data: data.to_vec(),
@@ -1105,13 +1741,12 @@ where
 
     if texture.is_none() {
         profiling::scope!("decoding image");
-        let parsed =
-            image::load_from_memory(&data).map_err(|e| GltfLoadError::TextureDecode(uri.take().unwrap(), e))?;
+        let parsed = image::load_from_memory(data).map_err(|e| GltfLoadError::TextureDecode(uri.take().unwrap(), e))?;
         let size = UVec2::new(parsed.width(), parsed.height());
         let (data, format) = util::convert_dynamic_image(parsed, srgb);
 
         texture = Some(types::Texture {
-            label: image.name().map(str::to_owned),
+            label: label.map(str::to_owned),
             format,
             size,
             data,
@@ -1120,11 +1755,7 @@ where
         })
     };
 
-    let texture = texture.unwrap();
-    let format = texture.format;
-    let handle = renderer.add_texture_2d(texture)?;
-
-    Ok(Labeled::new(Texture { handle, format }, image.name()))
+    Ok(texture.unwrap())
 }
 
 /// Implementation utilities.
@@ -1629,3 +2260,161 @@ pub mod util {
         Some(tex.components())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec3;
+    use rend3::types::{Handedness, Mesh, MeshBuilder, MeshHandle, Texture2DHandle};
+    use rend3_routine::pbr::{BlendMode, MaterialComponent, PbrMaterial, SampleType, Transparency};
+
+    use super::{
+        add_mesh_cached, load_buffers, CachedMesh, GltfLoadError, GltfLoadTask, MaterialOverride, MeshKey, MeshMap,
+    };
+
+    fn triangle(offset: f32) -> Mesh {
+        let positions = vec![Vec3::new(offset, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), Vec3::new(1.0, 0.0, 0.0)];
+        MeshBuilder::new(positions, Handedness::Left).build().unwrap()
+    }
+
+    /// Adds `mesh` through the cache, counting how many meshes are actually uploaded.
+    fn add(cache: Option<&mut MeshMap>, mesh: Mesh, uploads: &mut usize) -> MeshHandle {
+        add_mesh_cached(cache, mesh, |_| {
+            *uploads += 1;
+            Ok::<_, ()>(MeshHandle::new(|_| {}, *uploads))
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn deduplicates_identical_meshes() {
+        let mut cache = MeshMap::default();
+        let mut uploads = 0;
+        let first = add(Some(&mut cache), triangle(0.0), &mut uploads);
+        let second = add(Some(&mut cache), triangle(0.0), &mut uploads);
+        let different = add(Some(&mut cache), triangle(0.5), &mut uploads);
+
+        assert_eq!(uploads, 2);
+        assert_eq!(first.get_raw(), second.get_raw());
+        assert_ne!(first.get_raw(), different.get_raw());
+    }
+
+    #[test]
+    fn uploads_every_mesh_without_cache() {
+        let mut uploads = 0;
+        let first = add(None, triangle(0.0), &mut uploads);
+        let second = add(None, triangle(0.0), &mut uploads);
+
+        assert_eq!(uploads, 2);
+        assert_ne!(first.get_raw(), second.get_raw());
+    }
+
+    #[test]
+    fn colliding_keys_are_not_shared() {
+        let mut cache = MeshMap::default();
+        let mut uploads = 0;
+        let first = add(Some(&mut cache), triangle(0.0), &mut uploads);
+
+        // Pretend another mesh hashed to the same key as the first.
+        let other = triangle(0.5);
+        let cached = cache.values().next().unwrap().clone();
+        cache.insert(MeshKey::new(&other), cached);
+
+        let second = add(Some(&mut cache), other, &mut uploads);
+        assert_eq!(uploads, 2);
+        assert_ne!(first.get_raw(), second.get_raw());
+        assert_eq!(cache.values().map(Vec::<CachedMesh>::len).sum::<usize>(), 3);
+    }
+
+    #[test]
+    fn override_scales_emissive_texture() {
+        let texture = Texture2DHandle::new(|_| {}, 0);
+        let mut material = PbrMaterial { emissive: MaterialComponent::Texture(texture), ..Default::default() };
+        let over = MaterialOverride { emissive_multiplier: Some(4.0), ..Default::default() };
+        over.apply(&mut material);
+
+        match material.emissive {
+            MaterialComponent::TextureValue { value, .. } => assert_eq!(value, Vec3::splat(4.0)),
+            other => panic!("Expected a texture scaled by a value, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn override_replaces_fields() {
+        let mut material = PbrMaterial {
+            emissive: MaterialComponent::Value(Vec3::splat(0.5)),
+            transparency: Transparency::Cutout { cutout: 0.5 },
+            ..Default::default()
+        };
+        let over = MaterialOverride {
+            sample_type: Some(SampleType::Nearest),
+            emissive_multiplier: Some(2.0),
+            unlit: Some(true),
+            alpha_cutoff: Some(0.25),
+        };
+        over.apply(&mut material);
+
+        assert_eq!(material.sample_type, SampleType::Nearest);
+        assert!(matches!(material.emissive, MaterialComponent::Value(value) if value == Vec3::ONE));
+        assert!(material.unlit);
+        assert_eq!(material.transparency, Transparency::Cutout { cutout: 0.25 });
+    }
+
+    #[test]
+    fn override_cutoff_ignored_without_cutout() {
+        for transparency in [Transparency::Opaque, Transparency::Blend { mode: BlendMode::default() }] {
+            let mut material = PbrMaterial { transparency, ..Default::default() };
+            MaterialOverride { alpha_cutoff: Some(0.25), ..Default::default() }.apply(&mut material);
+            assert_eq!(material.transparency, transparency);
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn load_settings_round_trip() {
+        use super::{GltfLoadSettings, MaterialOverrides};
+
+        let settings = GltfLoadSettings {
+            scale: 2.0,
+            deduplicate_meshes: true,
+            material_overrides: MaterialOverrides::from([(
+                "glass".to_owned(),
+                MaterialOverride { emissive_multiplier: Some(3.0), unlit: Some(true), ..Default::default() },
+            )]),
+            ..Default::default()
+        };
+
+        let json = serde_json::to_string(&settings).unwrap();
+        let loaded: GltfLoadSettings = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.scale, settings.scale);
+        assert_eq!(loaded.directional_light_shadow_distance, settings.directional_light_shadow_distance);
+        assert_eq!(loaded.directional_light_resolution, settings.directional_light_resolution);
+        assert_eq!(loaded.normal_direction, settings.normal_direction);
+        assert_eq!(loaded.enable_directional, settings.enable_directional);
+        assert_eq!(loaded.deduplicate_meshes, settings.deduplicate_meshes);
+        assert_eq!(loaded.material_overrides, settings.material_overrides);
+
+        // Missing fields fall back to their defaults.
+        let partial: GltfLoadSettings = serde_json::from_str(r#"{"scale": 0.5}"#).unwrap();
+        assert_eq!(partial.scale, 0.5);
+        assert_eq!(partial.directional_light_resolution, 2048);
+        assert!(partial.material_overrides.is_empty());
+    }
+
+    #[test]
+    fn load_task_is_send() {
+        fn assert_send<T: Send>() {}
+        assert_send::<GltfLoadTask<'static, std::io::Error>>();
+    }
+
+    #[test]
+    fn missing_blob_is_an_error() {
+        // A buffer without a uri refers to the glb blob, whatever its name.
+        let gltf = gltf::Gltf::from_slice(
+            br#"{"asset": {"version": "2.0"}, "buffers": [{"byteLength": 4, "name": "scene.bin"}]}"#,
+        )
+        .unwrap();
+        let result =
+            pollster::block_on(load_buffers(gltf.buffers(), None, |uri| async move { panic!("tried to load {uri}") }));
+        assert!(matches!(result, Err(GltfLoadError::<std::io::Error>::MissingBlob(0))));
+    }
+}
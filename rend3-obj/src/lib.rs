@@ -0,0 +1,330 @@
+//! obj and mtl model loader for rend3.
+//!
+//! Loads the meshes of an obj file, along with the materials of the mtl
+//! libraries it references, into the renderer. Objects aren't created, so the
+//! meshes can be placed however the user likes.
+//!
+//! URIs of material libraries and textures are resolved with an io function,
+//! the same as in rend3-gltf, so [`rend3_gltf::filesystem_io_func`] can be
+//! used to load from the directory of the obj file. Textures are decoded the
+//! same way as gltf textures.
+//!
+//! # Known Limitations
+//! - Only the diffuse and normal textures of a material are used.
+//! - Materials are mapped to PBR with a non-metallic surface, whose roughness
+//!   is derived from the specular exponent.
+//! - Texture options, such as `-bm`, are not supported.
+
+use std::{collections::HashMap, future::Future, io::Cursor, sync::Arc};
+
+use glam::{Vec2, Vec3};
+use rend3::{
+    managers::{MeshCreationError, TextureCreationError},
+    types::{self, Handedness, MeshValidationError},
+    util::typedefs::SsoString,
+    Renderer,
+};
+use rend3_gltf::{GltfLoadError, Labeled};
+use rend3_routine::pbr;
+use thiserror::Error;
+
+/// Describes how loading an obj failed.
+#[derive(Debug, Error)]
+pub enum ObjLoadError<E: std::error::Error + 'static> {
+    #[error("Obj parsing error")]
+    Obj(#[source] tobj::LoadError),
+    #[error("Material library {0} failed to be loaded from the fs")]
+    MaterialLibraryIo(SsoString, #[source] E),
+    #[error("Material library parsing error")]
+    MaterialLibrary(#[source] tobj::LoadError),
+    #[error("Texture {0} failed to be loaded from the fs")]
+    TextureIo(SsoString, #[source] E),
+    #[error("Texture {0} failed to be decoded")]
+    TextureDecode(SsoString, #[source] GltfLoadError<E>),
+    #[error("Mesh {0} failed validation")]
+    MeshValidationError(usize, #[source] MeshValidationError),
+    #[error("Failed to create a mesh")]
+    MeshCreation(#[from] MeshCreationError),
+    #[error("Failed to create a texture")]
+    TextureCreation(#[from] TextureCreationError),
+}
+
+/// A single mesh of an obj file, one per `o` or `g` statement.
+#[derive(Debug, Clone)]
+pub struct ObjMesh {
+    pub handle: types::MeshHandle,
+    /// Index into [`LoadedObj::materials`].
+    pub material: Option<usize>,
+}
+
+/// Meshes and materials loaded from an obj file.
+#[derive(Debug, Clone)]
+pub struct LoadedObj {
+    pub meshes: Vec<Labeled<ObjMesh>>,
+    pub materials: Vec<Labeled<types::MaterialHandle>>,
+}
+
+impl LoadedObj {
+    /// Index of the first material with the given name.
+    pub fn material_by_name(&self, name: &str) -> Option<usize> {
+        self.materials.iter().position(|material| material.label.as_deref() == Some(name))
+    }
+
+    /// Index of the first mesh with the given name.
+    pub fn mesh_by_name(&self, name: &str) -> Option<usize> {
+        self.meshes.iter().position(|mesh| mesh.label.as_deref() == Some(name))
+    }
+}
+
+/// Load the meshes and materials of an obj file into the renderer.
+///
+/// io_func determines how the URIs of material libraries and textures are
+/// resolved into their underlying data.
+///
+/// ```no_run
+/// # use std::path::Path;
+/// # let renderer = unimplemented!();
+/// let path = Path::new("some/path/model.obj");
+/// let obj_data = std::fs::read(&path).unwrap();
+/// let parent_directory = path.parent().unwrap();
+/// let _loaded = pollster::block_on(rend3_obj::load_obj(
+///     &renderer,
+///     &obj_data,
+///     |p| async move { rend3_gltf::filesystem_io_func(&parent_directory, &p).await }
+/// ));
+/// ```
+pub async fn load_obj<F, Fut, E>(
+    renderer: &Arc<Renderer>,
+    data: &[u8],
+    mut io_func: F,
+) -> Result<LoadedObj, ObjLoadError<E>>
+where
+    F: FnMut(SsoString) -> Fut,
+    Fut: Future<Output = Result<Vec<u8>, E>>,
+    E: std::error::Error + 'static,
+{
+    profiling::scope!("loading obj");
+
+    // tobj loads material libraries synchronously, so fetch them all up front.
+    let mut libraries = HashMap::new();
+    for uri in material_library_uris(data) {
+        let library =
+            io_func(SsoString::from(uri)).await.map_err(|e| ObjLoadError::MaterialLibraryIo(uri.into(), e))?;
+        libraries.insert(uri.to_owned(), library);
+    }
+
+    let options = tobj::LoadOptions { single_index: true, triangulate: true, ..Default::default() };
+    let (models, materials) =
+        tobj::load_obj_buf(&mut Cursor::new(data), &options, |path| match libraries.get(&*path.to_string_lossy()) {
+            Some(library) => tobj::load_mtl_buf(&mut Cursor::new(library)),
+            None => Err(tobj::LoadError::OpenFileFailed),
+        })
+        .map_err(ObjLoadError::Obj)?;
+    let materials = materials.map_err(ObjLoadError::MaterialLibrary)?;
+
+    let materials = load_materials(renderer, &materials, &mut io_func).await?;
+    let meshes = load_meshes(renderer, &models)?;
+
+    Ok(LoadedObj { meshes, materials })
+}
+
+/// URIs of the material libraries an obj file references, as tobj will ask for
+/// them.
+fn material_library_uris(data: &[u8]) -> Vec<&str> {
+    data.split(|&b| b == b'\n')
+        .filter_map(|line| std::str::from_utf8(line).ok())
+        .filter_map(|line| line.trim().strip_prefix("mtllib"))
+        .filter(|rest| rest.starts_with(char::is_whitespace))
+        .map(str::trim)
+        .filter(|uri| !uri.is_empty())
+        .collect()
+}
+
+/// Uploads the meshes of the given models.
+///
+/// obj models are right handed, so with a left handed renderer they are
+/// mirrored along z and have their winding order flipped.
+pub fn load_meshes<E: std::error::Error + 'static>(
+    renderer: &Arc<Renderer>,
+    models: &[tobj::Model],
+) -> Result<Vec<Labeled<ObjMesh>>, ObjLoadError<E>> {
+    profiling::scope!("loading meshes");
+
+    let flip = renderer.handedness == Handedness::Left;
+    let vec3 = |v: &[f32]| if flip { Vec3::new(v[0], v[1], -v[2]) } else { Vec3::from_slice(v) };
+
+    models
+        .iter()
+        .enumerate()
+        .map(|(index, model)| {
+            let mesh = &model.mesh;
+
+            let mut builder =
+                types::MeshBuilder::new(mesh.positions.chunks_exact(3).map(vec3).collect(), renderer.handedness);
+            if flip {
+                builder = builder.with_flip_winding_order();
+            }
+
+            if !mesh.normals.is_empty() {
+                builder = builder.with_vertex_normals(mesh.normals.chunks_exact(3).map(vec3).collect());
+            }
+
+            if !mesh.texcoords.is_empty() {
+                // obj texture coordinates start at the bottom left, ours at the top left.
+                let uvs = mesh.texcoords.chunks_exact(2).map(|uv| Vec2::new(uv[0], 1.0 - uv[1])).collect();
+                builder = builder.with_vertex_texture_coordinates_0(uvs);
+            }
+
+            if !mesh.vertex_color.is_empty() {
+                let colors = mesh
+                    .vertex_color
+                    .chunks_exact(3)
+                    .map(|c| [c[0], c[1], c[2], 1.0].map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8))
+                    .collect();
+                builder = builder.with_vertex_color_0(colors);
+            }
+
+            let built = builder
+                .with_indices(mesh.indices.clone())
+                .build()
+                .map_err(|valid| ObjLoadError::MeshValidationError(index, valid))?;
+            let handle = renderer.add_mesh(built)?;
+
+            Ok(Labeled::new(ObjMesh { handle, material: mesh.material_id }, Some(model.name.as_str())))
+        })
+        .collect()
+}
+
+/// Uploads the given materials, and the textures they reference.
+///
+/// io_func determines how texture URIs are resolved into their underlying data.
+pub async fn load_materials<F, Fut, E>(
+    renderer: &Arc<Renderer>,
+    materials: &[tobj::Material],
+    io_func: &mut F,
+) -> Result<Vec<Labeled<types::MaterialHandle>>, ObjLoadError<E>>
+where
+    F: FnMut(SsoString) -> Fut,
+    Fut: Future<Output = Result<Vec<u8>, E>>,
+    E: std::error::Error + 'static,
+{
+    profiling::scope!("loading materials");
+
+    let mut textures = HashMap::new();
+    let mut result = Vec::with_capacity(materials.len());
+    for material in materials {
+        let diffuse = material.diffuse.map_or(Vec3::ONE, Vec3::from);
+        let dissolve = material.dissolve.unwrap_or(1.0);
+        let albedo_value = diffuse.extend(dissolve);
+
+        let albedo = match material.diffuse_texture {
+            Some(ref uri) => pbr::AlbedoComponent::TextureValue {
+                texture: load_texture_cached(renderer, &mut textures, uri, true, io_func).await?,
+                value: albedo_value,
+            },
+            None => pbr::AlbedoComponent::Value(albedo_value),
+        };
+
+        let normal = match material.normal_texture {
+            Some(ref uri) => pbr::NormalTexture::Tricomponent(
+                load_texture_cached(renderer, &mut textures, uri, false, io_func).await?,
+                pbr::NormalTextureYDirection::Up,
+            ),
+            None => pbr::NormalTexture::None,
+        };
+
+        let emissive = material
+            .unknown_param
+            .get("Ke")
+            .and_then(|ke| parse_vec3(ke))
+            .map_or(pbr::MaterialComponent::None, pbr::MaterialComponent::Value);
+
+        let handle = renderer.add_material(pbr::PbrMaterial {
+            albedo,
+            transparency: if dissolve < 1.0 {
+                pbr::Transparency::Blend { mode: pbr::BlendMode::StraightAlpha }
+            } else {
+                pbr::Transparency::Opaque
+            },
+            normal,
+            metallic_factor: Some(0.0),
+            roughness_factor: material.shininess.map(shininess_to_roughness),
+            emissive,
+            // Illumination model 0 is a flat color without lighting.
+            unlit: material.illumination_model == Some(0),
+            ..pbr::PbrMaterial::default()
+        });
+
+        result.push(Labeled::new(handle, Some(material.name.as_str())));
+    }
+
+    Ok(result)
+}
+
+async fn load_texture_cached<F, Fut, E>(
+    renderer: &Arc<Renderer>,
+    textures: &mut HashMap<(String, bool), types::Texture2DHandle>,
+    uri: &str,
+    srgb: bool,
+    io_func: &mut F,
+) -> Result<types::Texture2DHandle, ObjLoadError<E>>
+where
+    F: FnMut(SsoString) -> Fut,
+    Fut: Future<Output = Result<Vec<u8>, E>>,
+    E: std::error::Error + 'static,
+{
+    if let Some(handle) = textures.get(&(uri.to_owned(), srgb)) {
+        return Ok(handle.clone());
+    }
+
+    let data = io_func(SsoString::from(uri)).await.map_err(|e| ObjLoadError::TextureIo(uri.into(), e))?;
+    let texture = rend3_gltf::decode_image(&data, uri.into(), Some(uri), srgb)
+        .map_err(|e| ObjLoadError::TextureDecode(uri.into(), e))?;
+    let handle = renderer.add_texture_2d(texture)?;
+
+    textures.insert((uri.to_owned(), srgb), handle.clone());
+
+    Ok(handle)
+}
+
+fn parse_vec3(value: &str) -> Option<Vec3> {
+    let mut components = value.split_whitespace().map(str::parse::<f32>);
+    let x = components.next()?.ok()?;
+    // A single value applies to all channels.
+    match (components.next(), components.next()) {
+        (Some(y), Some(z)) => Some(Vec3::new(x, y.ok()?, z.ok()?)),
+        (None, _) => Some(Vec3::splat(x)),
+        _ => None,
+    }
+}
+
+/// Converts a Blinn-Phong specular exponent into perceptual roughness.
+pub fn shininess_to_roughness(shininess: f32) -> f32 {
+    (2.0 / (shininess.max(0.0) + 2.0)).sqrt().sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{material_library_uris, parse_vec3, shininess_to_roughness};
+    use glam::Vec3;
+
+    #[test]
+    fn finds_material_libraries() {
+        let obj = b"# comment\nmtllib a.mtl\r\nv 0 0 0\n  mtllib my lib.mtl\nmtllibs b.mtl\n";
+        assert_eq!(material_library_uris(obj), ["a.mtl", "my lib.mtl"]);
+    }
+
+    #[test]
+    fn parses_colors() {
+        assert_eq!(parse_vec3("0.5 0.25 1"), Some(Vec3::new(0.5, 0.25, 1.0)));
+        assert_eq!(parse_vec3("0.5"), Some(Vec3::splat(0.5)));
+        assert_eq!(parse_vec3("0.5 0.25"), None);
+        assert_eq!(parse_vec3("red"), None);
+    }
+
+    #[test]
+    fn roughness_decreases_with_shininess() {
+        assert!((shininess_to_roughness(0.0) - 1.0).abs() < 1e-6);
+        assert!(shininess_to_roughness(1000.0) < shininess_to_roughness(10.0));
+    }
+}
@@ -28,4 +28,10 @@ fn fs_main_scene(vout: VertexOutput) -> @location(0) vec4<f32> {
 fn fs_main_monitor(vout: VertexOutput) -> @location(0) vec4<f32> {
     var sampled = textureSample(source, primary_sampler, vout.tex_coords);
     return vec4<f32>(srgb_scene_to_display(sampled.rgb), sampled.a);
+}
+
+@fragment
+fn fs_main_linear(vout: VertexOutput) -> @location(0) vec4<f32> {
+    var sampled = textureSample(source, primary_sampler, vout.tex_coords);
+    return clamp(sampled, vec4<f32>(0.0), vec4<f32>(1.0));
 }
\ No newline at end of file
@@ -0,0 +1,56 @@
+{{include "rend3-routine/math/color.wgsl"}}
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+}
+
+struct ColorGradeUniforms {
+    strength: f32,
+}
+
+@group(0) @binding(0)
+var source: texture_2d<f32>;
+@group(0) @binding(1)
+var lut: texture_3d<f32>;
+@group(0) @binding(2)
+var linear_sampler: sampler;
+@group(0) @binding(3)
+var<uniform> uniforms: ColorGradeUniforms;
+
+@vertex
+fn vs_main(@builtin(vertex_index) id: u32) -> VertexOutput {
+    var output: VertexOutput;
+    output.position = vec4<f32>(f32(id / 2u) * 4.0 - 1.0, f32(id % 2u) * 4.0 - 1.0, 0.0, 1.0);
+    output.tex_coords = vec2<f32>(f32(id / 2u) * 2.0, 1.0 - (f32(id % 2u) * 2.0));
+    return output;
+}
+
+// Looks up a display encoded color in the lut, blending towards the ungraded color by the strength.
+fn grade(display: vec3<f32>) -> vec3<f32> {
+    // Remap so that 0 and 1 land on the centers of the outermost texels.
+    let size = vec3<f32>(textureDimensions(lut));
+    let coords = saturate3(display) * ((size - 1.0) / size) + (0.5 / size);
+    let graded = textureSampleLevel(lut, linear_sampler, coords, 0.0).rgb;
+    return mix(display, graded, uniforms.strength);
+}
+
+fn saturate3(v: vec3<f32>) -> vec3<f32> {
+    return clamp(v, vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+// The source and target are sRGB textures, so we see linear values and need to encode them
+// for the lut, which is authored against display values.
+@fragment
+fn fs_main_scene(vout: VertexOutput) -> @location(0) vec4<f32> {
+    let sampled = textureSampleLevel(source, linear_sampler, vout.tex_coords, 0.0);
+    let graded = grade(srgb_scene_to_display(sampled.rgb));
+    return vec4<f32>(srgb_display_to_scene(graded), sampled.a);
+}
+
+// The tonemapper already wrote display values to the source.
+@fragment
+fn fs_main_monitor(vout: VertexOutput) -> @location(0) vec4<f32> {
+    let sampled = textureSampleLevel(source, linear_sampler, vout.tex_coords, 0.0);
+    return vec4<f32>(grade(sampled.rgb), sampled.a);
+}
@@ -1,5 +1,6 @@
 {{include "rend3-routine/structures.wgsl"}}
 {{include "rend3-routine/structures_object.wgsl"}}
+{{include "rend3-routine/math/depth.wgsl"}}
 
 @group(0) @binding(0)
 var<storage> vertex_buffer: array<u32>;
@@ -240,7 +241,8 @@ fn save_culling_results(global_invocation: u32, lid: u32) {
     }
 }
 
-fn textureSampleMin(texture: texture_depth_2d, uv: vec2<f32>, mipmap: f32) -> f32 {
+// Samples the farthest depth of the 4 texels around the given uv.
+fn textureSampleFarthest(texture: texture_depth_2d, uv: vec2<f32>, mipmap: f32) -> f32 {
     let int_mipmap = i32(mipmap);
     let mip_resolution = vec2<f32>(textureDimensions(texture, int_mipmap).xy);
 
@@ -254,16 +256,17 @@ fn textureSampleMin(texture: texture_depth_2d, uv: vec2<f32>, mipmap: f32) -> f3
     let bottom_left = vec2<u32>(low.x, high.y);
     let bottom_right = vec2<u32>(high.x, high.y);
 
-    var minval = textureLoad(texture, top_left, int_mipmap);
-    minval = min(minval, textureLoad(texture, top_right, int_mipmap));
-    minval = min(minval, textureLoad(texture, bottom_left, int_mipmap));
-    minval = min(minval, textureLoad(texture, bottom_right, int_mipmap));
-    return minval;
+    var farthest = textureLoad(texture, top_left, int_mipmap);
+    farthest = farther_depth(farthest, textureLoad(texture, top_right, int_mipmap));
+    farthest = farther_depth(farthest, textureLoad(texture, bottom_left, int_mipmap));
+    farthest = farther_depth(farthest, textureLoad(texture, bottom_right, int_mipmap));
+    return farthest;
 }
 
 fn execute_culling(
     model_view_proj: mat4x4<f32>,
     vertices: TriangleVertices,
+    culling: u32,
 ) -> bool {
     let position0 = model_view_proj * vec4<f32>(vertices[0], 1.0);
     let position1 = model_view_proj * vec4<f32>(vertices[1], 1.0);
@@ -271,11 +274,15 @@ fn execute_culling(
 
     let det = determinant(mat3x3<f32>(position0.xyw, position1.xyw, position2.xyw));
 
-    if (per_camera_uniform.flags & PCU_FLAGS_AREA_VISIBLE_MASK) == PCU_FLAGS_POSITIVE_AREA_VISIBLE && det <= 0.0 {
-        return false;
-    }
-    if (per_camera_uniform.flags & PCU_FLAGS_AREA_VISIBLE_MASK) == PCU_FLAGS_NEGATIVE_AREA_VISIBLE && det >= 0.0 {
-        return false;
+    if culling != CULLING_NONE {
+        // Front face culling flips which area is visible.
+        let positive_area_visible = ((per_camera_uniform.flags & PCU_FLAGS_AREA_VISIBLE_MASK) == PCU_FLAGS_POSITIVE_AREA_VISIBLE) != (culling == CULLING_FRONT);
+        if positive_area_visible && det <= 0.0 {
+            return false;
+        }
+        if !positive_area_visible && det >= 0.0 {
+            return false;
+        }
     }
 
     let ndc0 = position0.xyz / position0.w;
@@ -297,10 +304,13 @@ fn execute_culling(
         }
     }
 
-    // We skip hi-z calculation if we're doing a shadow pass
+    // We skip hi-z calculation if we're doing a shadow pass or it isn't enabled
     if per_camera_uniform.shadow_index != 0xFFFFFFFFu {
         return true;
     }
+    if (per_camera_uniform.flags & PCU_FLAGS_OCCLUSION_CULLING_MASK) == PCU_FLAGS_OCCLUSION_CULLING_DISABLED {
+        return true;
+    }
 
     var min_tex_coords = (min_ndc_xy + 1.0) / 2.0;
     var max_tex_coords = (max_ndc_xy + 1.0) / 2.0;
@@ -313,10 +323,10 @@ fn execute_culling(
     let longest_edge = max(edges.x, edges.y);
     let mip = ceil(log2(max(longest_edge, 1.0)));
 
-    let depth = max(max(ndc0.z, ndc1.z), ndc2.z);
-    let occlusion_depth = textureSampleMin(hirearchical_z_buffer, uv, mip);
+    let depth = closer_depth(closer_depth(ndc0.z, ndc1.z), ndc2.z);
+    let occlusion_depth = textureSampleFarthest(hirearchical_z_buffer, uv, mip);
 
-    if depth < occlusion_depth {
+    if is_farther(depth, occlusion_depth) {
         return false;
     }
 
@@ -357,7 +367,7 @@ fn cs_main(
 
         let model_view_proj = per_camera_uniform.objects[object_info.object_id].model_view_proj;
 
-        let passes_culling = execute_culling(model_view_proj, triangle.vertices);
+        let passes_culling = execute_culling(model_view_proj, triangle.vertices, object_info.culling);
 
         if object_info.atomic_capable == 1u {
             if passes_culling {
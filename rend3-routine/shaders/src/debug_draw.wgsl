@@ -0,0 +1,99 @@
+{{include "rend3-routine/structures.wgsl"}}
+
+@group(0) @binding(3)
+var<uniform> uniforms: UniformData;
+
+struct SegmentInput {
+    @location(0) start: vec3<f32>,
+    @location(1) end: vec3<f32>,
+    @location(2) start_color: vec4<f32>,
+    @location(3) end_color: vec4<f32>,
+    @location(4) width: f32,
+}
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+    // Ends of the segment in pixels.
+    @location(1) @interpolate(flat) start: vec2<f32>,
+    @location(2) @interpolate(flat) end: vec2<f32>,
+    @location(3) @interpolate(flat) radius: f32,
+}
+
+// Segments are clipped to this clip space w, so the parts behind the camera don't wrap around.
+const MIN_W: f32 = 0.00001;
+
+fn clip_to_pixels(clip: vec4<f32>) -> vec2<f32> {
+    let ndc = clip.xy / clip.w;
+    return vec2<f32>(ndc.x * 0.5 + 0.5, 0.5 - ndc.y * 0.5) * vec2<f32>(uniforms.resolution);
+}
+
+fn pixels_to_ndc(pixels: vec2<f32>) -> vec2<f32> {
+    let uv = pixels / vec2<f32>(uniforms.resolution);
+    return vec2<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0);
+}
+
+// Each segment is an instance of two triangles covering it in screen space, with room for round caps and an
+// antialiased edge. Points are segments with both ends in the same place.
+@vertex
+fn vs_main(@builtin(vertex_index) id: u32, segment: SegmentInput) -> VertexOutput {
+    var start = uniforms.view_proj * vec4<f32>(segment.start, 1.0);
+    var end = uniforms.view_proj * vec4<f32>(segment.end, 1.0);
+    var start_color = segment.start_color;
+    var end_color = segment.end_color;
+
+    var vs_out: VertexOutput;
+    if (start.w < MIN_W && end.w < MIN_W) {
+        vs_out.position = vec4<f32>(0.0);
+        return vs_out;
+    }
+    if (start.w < MIN_W) {
+        let t = (MIN_W - start.w) / (end.w - start.w);
+        start = mix(start, end, t);
+        start_color = mix(start_color, end_color, t);
+    } else if (end.w < MIN_W) {
+        let t = (MIN_W - end.w) / (start.w - end.w);
+        end = mix(end, start, t);
+        end_color = mix(end_color, start_color, t);
+    }
+
+    let start_pixels = clip_to_pixels(start);
+    let end_pixels = clip_to_pixels(end);
+    let delta = end_pixels - start_pixels;
+    var direction = vec2<f32>(1.0, 0.0);
+    if (dot(delta, delta) > 0.0) {
+        direction = normalize(delta);
+    }
+    let normal = vec2<f32>(-direction.y, direction.x);
+
+    let radius = segment.width * 0.5;
+    let extent = radius + 1.0;
+
+    let at_end = id == 1u || id == 4u || id == 5u;
+    let side = select(-1.0, 1.0, id == 2u || id == 3u || id == 5u);
+    let clip = select(start, end, at_end);
+    let base = select(start_pixels - direction * extent, end_pixels + direction * extent, at_end);
+    let pixels = base + normal * extent * side;
+
+    vs_out.position = vec4<f32>(pixels_to_ndc(pixels) * clip.w, clip.z, clip.w);
+    vs_out.color = select(start_color, end_color, at_end);
+    vs_out.start = start_pixels;
+    vs_out.end = end_pixels;
+    vs_out.radius = radius;
+    return vs_out;
+}
+
+@fragment
+fn fs_main(vs_out: VertexOutput) -> @location(0) vec4<f32> {
+    // Distance in pixels from the center of this pixel to the segment.
+    let to_pixel = vs_out.position.xy - vs_out.start;
+    let delta = vs_out.end - vs_out.start;
+    let t = saturate(dot(to_pixel, delta) / max(dot(delta, delta), 0.00001));
+    let distance = length(to_pixel - delta * t);
+
+    let coverage = saturate(vs_out.radius + 0.5 - distance);
+    if (coverage <= 0.0) {
+        discard;
+    }
+    return vec4<f32>(vs_out.color.rgb, vs_out.color.a * coverage);
+}
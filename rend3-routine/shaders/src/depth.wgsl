@@ -38,6 +38,7 @@ var albedo_tex: texture_2d<f32>;
 
     position
     texture_coords_0
+    texture_coords_1
     color_0
 }}
 
@@ -46,6 +47,7 @@ struct VertexOutput {
     @location(0) coords0: vec2<f32>,
     @location(1) alpha: f32,
     @location(2) @interpolate(flat) material: u32,
+    @location(3) coords1: vec2<f32>,
 }
 
 @vertex
@@ -80,6 +82,7 @@ fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
     var vs_out: VertexOutput;
     vs_out.material = data.material_index;
     vs_out.coords0 = vs_in.texture_coords_0;
+    vs_out.coords1 = vs_in.texture_coords_1;
     vs_out.alpha = vs_in.color_0.a;
     vs_out.position = model_view_proj * position_vec4;
 
@@ -105,7 +108,10 @@ fn fs_main(vs_out: VertexOutput) {
     {{#if discard}}
     var material = materials[vs_out.material];
 
-    let coords = vs_out.coords0;
+    var coords = (material.uv_transform0 * vec3<f32>(vs_out.coords0, 1.0)).xy;
+    if (material_uses_uv1(material.uv_sets, UV_SET_ALBEDO)) {
+        coords = (material.uv_transform1 * vec3<f32>(vs_out.coords1, 1.0)).xy;
+    }
     let uvdx = dpdx(coords);
     let uvdy = dpdx(coords);
 
@@ -120,7 +126,7 @@ fn fs_main(vs_out: VertexOutput) {
     }
     alpha *= material.albedo.a;
 
-    if (alpha < material.alpha_cutout) {
+    if (alpha < material.alpha_cutout + uniforms.cutout_bias) {
         discard;
     }
     {{/if}}
@@ -0,0 +1,28 @@
+{{include "rend3-routine/math/depth.wgsl"}}
+
+struct VertexOutput {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) clip_position: vec2<f32>,
+}
+
+struct GradientUniforms {
+    top: vec4<f32>,
+    bottom: vec4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> uniforms: GradientUniforms;
+
+// Drawn at the far plane, so it only covers what no opaque object did.
+@vertex
+fn vs_main(@builtin(vertex_index) id: u32) -> VertexOutput {
+    let clip_position = vec2<f32>(f32(id / 2u) * 4.0 - 1.0, f32(id % 2u) * 4.0 - 1.0);
+
+    return VertexOutput(vec4<f32>(clip_position, FAR_DEPTH, 1.0), clip_position);
+}
+
+@fragment
+fn fs_main(vout: VertexOutput) -> @location(0) vec4<f32> {
+    let t = saturate(vout.clip_position.y * 0.5 + 0.5);
+    return mix(uniforms.bottom, uniforms.top, t);
+}
@@ -1,3 +1,5 @@
+{{include "rend3-routine/math/depth.wgsl"}}
+
 @group(0) @binding(0)
 var source: texture_depth_2d;
 
@@ -22,12 +24,12 @@ fn fs_main(vout: VertexOutput) -> @builtin(frag_depth) f32 {
 
     let count_odd = vout.resolution & vec2<u32>(1u);
 
-    var nearest = 1.0;
+    var farthest = NEAR_DEPTH;
     for (var x = 0u; x < 2u + count_odd.x; x += 1u) {
         for (var y = 0u; y < 2u + count_odd.y; y += 1u) {
-            nearest = min(nearest, textureLoad(source, previous_base_tex_coord + vec2<u32>(x, y), 0));
+            farthest = farther_depth(farthest, textureLoad(source, previous_base_tex_coord + vec2<u32>(x, y), 0));
         }
     }
 
-    return nearest;
+    return farthest;
 }
\ No newline at end of file
@@ -0,0 +1,78 @@
+{{include "rend3-routine/structures.wgsl"}}
+{{include "rend3-routine/math/depth.wgsl"}}
+
+@group(0) @binding(0)
+var<uniform> clusters: LightClusterUniforms;
+@group(0) @binding(1)
+var<storage> point_lights: PointLightData;
+@group(0) @binding(2)
+var<storage, read_write> cluster_lights: array<ClusterLightList>;
+
+const MAX_LIGHTS_PER_CLUSTER: u32 = 63u;
+
+// Finds the view space position of a point on the screen at the given view depth.
+fn cluster_corner(ndc: vec2<f32>, depth: f32) -> vec3<f32> {
+    // Unproject onto the near plane, then slide along the view ray.
+    let near_undiv = clusters.inv_proj * vec4<f32>(ndc, NEAR_DEPTH, 1.0);
+    let near_position = near_undiv.xyz / near_undiv.w;
+    return near_position * (depth / abs(near_position.z));
+}
+
+fn sphere_intersects_aabb(center: vec3<f32>, radius: f32, aabb_min: vec3<f32>, aabb_max: vec3<f32>) -> bool {
+    let closest = clamp(center, aabb_min, aabb_max);
+    let delta = center - closest;
+    return dot(delta, delta) <= radius * radius;
+}
+
+@compute @workgroup_size(64)
+fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let dims = clusters.dimensions;
+    let index = global_id.x;
+    if (index >= dims.x * dims.y * dims.z) {
+        return;
+    }
+
+    let x = index % dims.x;
+    let y = (index / dims.x) % dims.y;
+    let z = index / (dims.x * dims.y);
+
+    // Cluster y = 0 is at the top of the screen, matching framebuffer coordinates.
+    let ndc_min = vec2<f32>(f32(x) / f32(dims.x) * 2.0 - 1.0, 1.0 - f32(y + 1u) / f32(dims.y) * 2.0);
+    let ndc_max = vec2<f32>(f32(x + 1u) / f32(dims.x) * 2.0 - 1.0, 1.0 - f32(y) / f32(dims.y) * 2.0);
+
+    let near_depth = cluster_slice_depth(clusters, z);
+    let far_depth = cluster_slice_depth(clusters, z + 1u);
+
+    var corners = array<vec3<f32>, 8>(
+        cluster_corner(vec2<f32>(ndc_min.x, ndc_min.y), near_depth),
+        cluster_corner(vec2<f32>(ndc_max.x, ndc_min.y), near_depth),
+        cluster_corner(vec2<f32>(ndc_min.x, ndc_max.y), near_depth),
+        cluster_corner(vec2<f32>(ndc_max.x, ndc_max.y), near_depth),
+        cluster_corner(vec2<f32>(ndc_min.x, ndc_min.y), far_depth),
+        cluster_corner(vec2<f32>(ndc_max.x, ndc_min.y), far_depth),
+        cluster_corner(vec2<f32>(ndc_min.x, ndc_max.y), far_depth),
+        cluster_corner(vec2<f32>(ndc_max.x, ndc_max.y), far_depth),
+    );
+
+    var aabb_min = corners[0];
+    var aabb_max = corners[0];
+    for (var i = 1; i < 8; i += 1) {
+        aabb_min = min(aabb_min, corners[i]);
+        aabb_max = max(aabb_max, corners[i]);
+    }
+
+    var count = 0u;
+    for (var i = 0u; i < point_lights.count; i += 1u) {
+        let light = point_lights.data[i];
+        let center = (clusters.view * light.position).xyz;
+
+        if (sphere_intersects_aabb(center, light.radius, aabb_min, aabb_max)) {
+            cluster_lights[index].indices[count] = i;
+            count += 1u;
+            if (count == MAX_LIGHTS_PER_CLUSTER) {
+                break;
+            }
+        }
+    }
+    cluster_lights[index].count = count;
+}
@@ -13,11 +13,35 @@ const FLAGS_CC_GLTF_SPLIT: u32        = 0x0800u;
 const FLAGS_CC_BW_SPLIT: u32          = 0x1000u;
 const FLAGS_UNLIT: u32                = 0x2000u;
 const FLAGS_NEAREST: u32              = 0x4000u;
+const FLAGS_BLEND_PREMULTIPLY: u32    = 0x8000u;
+const FLAGS_BLEND_ADDITIVE: u32       = 0x10000u;
+const FLAGS_OPAQUE_ALPHA: u32         = 0x20000u;
+const FLAGS_DOUBLE_SIDED: u32         = 0x40000u;
 
 fn extract_material_flag(data: u32, flag: u32) -> bool {
     return bool(data & flag);
 }
 
+// Bits of `uv_sets`. A set bit means the texture samples the second set of texture coordinates.
+const UV_SET_ALBEDO: u32               = 0u;
+const UV_SET_NORMAL: u32               = 1u;
+const UV_SET_ROUGHNESS: u32            = 2u;
+const UV_SET_METALLIC: u32             = 3u;
+const UV_SET_REFLECTANCE: u32          = 4u;
+const UV_SET_CLEAR_COAT: u32           = 5u;
+const UV_SET_CLEAR_COAT_ROUGHNESS: u32 = 6u;
+const UV_SET_EMISSIVE: u32             = 7u;
+const UV_SET_ANISOTROPY: u32           = 8u;
+const UV_SET_AMBIENT_OCCLUSION: u32    = 9u;
+const UV_SET_HEIGHT: u32               = 10u;
+const UV_SET_TRANSMISSION: u32         = 11u;
+const UV_SET_SHEEN_COLOR: u32          = 12u;
+const UV_SET_SHEEN_ROUGHNESS: u32      = 13u;
+
+fn material_uses_uv1(uv_sets: u32, texture: u32) -> bool {
+    return bool((uv_sets >> texture) & 1u);
+}
+
 struct GpuMaterialData {
     albedo_tex: u32,
     normal_tex: u32,
@@ -31,9 +55,13 @@ struct GpuMaterialData {
     // -- 16 --
     anisotropy_tex: u32,
     ambient_occlusion_tex: u32,
+    height_tex: u32,
+    lightmap_tex: u32,
+    // -- 16 --
+    transmission_tex: u32,
+    sheen_color_tex: u32,
+    sheen_roughness_tex: u32,
     _padding0: u32,
-    _padding1: u32,
-    
     // -- 16 --
 
     uv_transform0: mat3x3<f32>,
@@ -54,6 +82,19 @@ struct GpuMaterialData {
     ambient_occlusion: f32,
     alpha_cutout: f32,
     flags: u32,
+    // -- 16 --
+    parallax_scale: f32,
+    uv_sets: u32,
+    anisotropy_rotation: f32,
+    transmission: f32,
+    // -- 16 --
+    sheen_color: vec3<f32>,
+    sheen_roughness: f32,
+    // -- 16 --
+    ior: f32,
+    _padding1: f32,
+    _padding2: f32,
+    _padding3: f32,
 }
 
 struct CpuMaterialData {
@@ -75,6 +116,19 @@ struct CpuMaterialData {
     ambient_occlusion: f32,
     alpha_cutout: f32,
     flags: u32,
+    // -- 16 --
+    parallax_scale: f32,
+    uv_sets: u32,
+    anisotropy_rotation: f32,
+    transmission: f32,
+    // -- 16 --
+    sheen_color: vec3<f32>,
+    sheen_roughness: f32,
+    // -- 16 --
+    ior: f32,
+    _padding1: f32,
+    _padding2: f32,
+    _padding3: f32,
     
     // -- 16 --
     texture_enable: u32,
@@ -31,3 +31,35 @@ fn brdf_v_smith_ggx_correlated(nov: f32, nol: f32, a: f32) -> f32 {
     let ggxv = nol * sqrt((-nov * a2 + nov) * nov + a2);
     return 0.5 / (ggxl + ggxv);
 }
+
+// Anisotropic variants, with roughness `at` along the tangent and `ab` along the bitangent.
+//
+// Source: Physically Based Rendering in Filament, section 4.8.2
+fn brdf_d_ggx_anisotropic(noh: f32, toh: f32, boh: f32, at: f32, ab: f32) -> f32 {
+    let a2 = at * ab;
+    let d = vec3<f32>(ab * toh, at * boh, a2 * noh);
+    let d2 = dot(d, d);
+    let b2 = a2 / d2;
+    return a2 * b2 * b2 * (1.0 / PI);
+}
+
+fn brdf_v_smith_ggx_correlated_anisotropic(at: f32, ab: f32, tov: f32, bov: f32, tol: f32, bol: f32, nov: f32, nol: f32) -> f32 {
+    let lambda_v = nol * length(vec3<f32>(at * tov, ab * bov, nov));
+    let lambda_l = nov * length(vec3<f32>(at * tol, ab * bol, nol));
+    return 0.5 / (lambda_v + lambda_l);
+}
+
+// Sheen lobe for cloth like materials, as used by KHR_materials_sheen.
+//
+// Source: Production Friendly Microfacet Sheen BRDF, Estevez and Kulla 2017
+fn brdf_d_charlie(noh: f32, a: f32) -> f32 {
+    let inv_a = 1.0 / a;
+    let cos2h = noh * noh;
+    let sin2h = max(1.0 - cos2h, 0.0078125);
+    return (2.0 + inv_a) * pow(sin2h, inv_a * 0.5) / (2.0 * PI);
+}
+
+// Source: Physically Based Rendering in Filament, section 4.12.2, after Neubelt and Pettineo 2013
+fn brdf_v_neubelt(nov: f32, nol: f32) -> f32 {
+    return saturate(1.0 / (4.0 * (nol + nov - nol * nov)));
+}
@@ -0,0 +1,39 @@
+// Helpers which abstract over the depth convention in use.
+//
+// With reverse depth (the default) the near plane is at 1 and the far plane at 0.
+// With forward depth (`forward_z`) the near plane is at 0 and the far plane at 1.
+
+{{#if forward_z}}
+const NEAR_DEPTH: f32 = 0.0;
+const FAR_DEPTH: f32 = 1.0;
+{{else}}
+const NEAR_DEPTH: f32 = 1.0;
+const FAR_DEPTH: f32 = 0.0;
+{{/if}}
+
+// Returns whichever depth is farther from the camera.
+fn farther_depth(a: f32, b: f32) -> f32 {
+    {{#if forward_z}}
+    return max(a, b);
+    {{else}}
+    return min(a, b);
+    {{/if}}
+}
+
+// Returns whichever depth is closer to the camera.
+fn closer_depth(a: f32, b: f32) -> f32 {
+    {{#if forward_z}}
+    return min(a, b);
+    {{else}}
+    return max(a, b);
+    {{/if}}
+}
+
+// Returns true if depth `a` is strictly farther from the camera than depth `b`.
+fn is_farther(a: f32, b: f32) -> bool {
+    {{#if forward_z}}
+    return a > b;
+    {{else}}
+    return a < b;
+    {{/if}}
+}
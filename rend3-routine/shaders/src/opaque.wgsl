@@ -5,6 +5,7 @@
 {{include "rend3-routine/math/color.wgsl"}}
 {{include "rend3-routine/math/matrix.wgsl"}}
 {{include "rend3-routine/shadow/pcf.wgsl"}}
+{{include "rend3-routine/shadow/pcss.wgsl"}}
 
 @group(0) @binding(0)
 var primary_sampler: sampler;
@@ -20,6 +21,10 @@ var<storage> directional_lights: DirectionalLightData;
 var<storage> point_lights: PointLightData;
 @group(0) @binding(6)
 var shadows: texture_depth_2d;
+@group(0) @binding(7)
+var<uniform> light_clusters: LightClusterUniforms;
+@group(0) @binding(8)
+var<storage> cluster_lights: array<ClusterLightList>;
 
 @group(1) @binding(0)
 var<storage> object_buffer: array<Object>;
@@ -60,6 +65,33 @@ var emissive_tex: texture_2d<f32>;
 var anisotropy_tex: texture_2d<f32>;
 @group(2) @binding(9)
 var ambient_occlusion_tex: texture_2d<f32>;
+@group(2) @binding(10)
+var height_tex: texture_2d<f32>;
+// The lightmap, transmission and sheen textures would go over the sampled texture limit, so aren't bound.
+{{/if}}
+
+struct EnvironmentData {
+    // Rotates world space directions into the space of the cubemaps.
+    inv_rotation: mat3x3<f32>,
+    // 1 if an image based lighting environment is bound, 0 to use the flat ambient.
+    enabled: u32,
+}
+
+@group(3) @binding(0)
+var<uniform> environment: EnvironmentData;
+@group(3) @binding(1)
+var environment_sampler: sampler;
+@group(3) @binding(2)
+var environment_irradiance: texture_cube<f32>;
+@group(3) @binding(3)
+var environment_prefiltered: texture_cube<f32>;
+@group(3) @binding(4)
+var environment_brdf_lut: texture_2d<f32>;
+
+{{#if transmission}}
+// Copy of the opaque scene, made before transparent objects are drawn.
+@group(4) @binding(0)
+var transmission_background: texture_2d<f32>;
 {{/if}}
 
 {{
@@ -74,17 +106,21 @@ var ambient_occlusion_tex: texture_2d<f32>;
     texture_coords_0
     texture_coords_1
     color_0
+    packed_normal
+    packed_tangent
 }}
 
 struct VertexOutput {
     @builtin(position) position: vec4<f32>,
     @location(0) view_position: vec4<f32>,
     @location(1) normal: vec3<f32>,
-    @location(2) tangent: vec3<f32>,
+    // w holds the handedness of the tangent frame.
+    @location(2) tangent: vec4<f32>,
     @location(3) coords0: vec2<f32>,
     @location(4) coords1: vec2<f32>,
     @location(6) color: vec4<f32>,
     @location(7) @interpolate(flat) material: u32,
+    @location(8) @interpolate(flat) debug_tint: vec4<f32>,
 }
 
 
@@ -123,9 +159,13 @@ fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
 
     var vs_out: VertexOutput;
     vs_out.material = data.material_index;
+    vs_out.debug_tint = data.debug_tint;
     vs_out.view_position = model_view * position_vec4;
-    vs_out.normal = normalize(mv_mat3 * (inv_scale_sq * vs_in.normal));
-    vs_out.tangent = normalize(mv_mat3 * (inv_scale_sq * vs_in.tangent));
+    // Meshes have either full precision or packed normals and tangents. Missing attributes read as zero.
+    let normal = vs_in.normal + vs_in.packed_normal;
+    let tangent = vs_in.tangent + vs_in.packed_tangent;
+    vs_out.normal = normalize(mv_mat3 * (inv_scale_sq * normal));
+    vs_out.tangent = vec4<f32>(normalize(mv_mat3 * (inv_scale_sq * tangent)), 1.0);
     vs_out.color = vs_in.color_0;
     vs_out.coords0 = vs_in.texture_coords_0;
     vs_out.coords1 = vs_in.texture_coords_1;
@@ -134,6 +174,13 @@ fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
     return vs_out;
 }
 
+// A set of texture coordinates along with their screen space derivatives.
+struct UvCoords {
+    coords: vec2<f32>,
+    ddx: vec2<f32>,
+    ddy: vec2<f32>,
+}
+
 {{#if (eq profile "GpuDriven")}}
 alias Material = GpuMaterialData;
 
@@ -147,17 +194,27 @@ fn has_clear_coat_roughness_texture(material: ptr<function, Material>) -> bool {
 fn has_emissive_texture(material: ptr<function, Material>) -> bool { return (*material).emissive_tex != 0u; }
 fn has_anisotropy_texture(material: ptr<function, Material>) -> bool { return (*material).anisotropy_tex != 0u; }
 fn has_ambient_occlusion_texture(material: ptr<function, Material>) -> bool { return (*material).ambient_occlusion_tex != 0u; }
-
-fn albedo_texture(material: ptr<function, Material>, samp: sampler, coords: vec2<f32>, ddx: vec2<f32>, ddy: vec2<f32>) -> vec4<f32> { return textureSampleGrad(textures[(*material).albedo_tex - 1u], samp, coords, ddx, ddy); }
-fn normal_texture(material: ptr<function, Material>, samp: sampler, coords: vec2<f32>, ddx: vec2<f32>, ddy: vec2<f32>) -> vec4<f32> { return textureSampleGrad(textures[(*material).normal_tex - 1u], samp, coords, ddx, ddy); }
-fn roughness_texture(material: ptr<function, Material>, samp: sampler, coords: vec2<f32>, ddx: vec2<f32>, ddy: vec2<f32>) -> vec4<f32> { return textureSampleGrad(textures[(*material).roughness_tex - 1u], samp, coords, ddx, ddy); }
-fn metallic_texture(material: ptr<function, Material>, samp: sampler, coords: vec2<f32>, ddx: vec2<f32>, ddy: vec2<f32>) -> vec4<f32> { return textureSampleGrad(textures[(*material).metallic_tex - 1u], samp, coords, ddx, ddy); }
-fn reflectance_texture(material: ptr<function, Material>, samp: sampler, coords: vec2<f32>, ddx: vec2<f32>, ddy: vec2<f32>) -> vec4<f32> { return textureSampleGrad(textures[(*material).reflectance_tex - 1u], samp, coords, ddx, ddy); }
-fn clear_coat_texture(material: ptr<function, Material>, samp: sampler, coords: vec2<f32>, ddx: vec2<f32>, ddy: vec2<f32>) -> vec4<f32> { return textureSampleGrad(textures[(*material).clear_coat_tex - 1u], samp, coords, ddx, ddy); }
-fn clear_coat_roughness_texture(material: ptr<function, Material>, samp: sampler, coords: vec2<f32>, ddx: vec2<f32>, ddy: vec2<f32>) -> vec4<f32> { return textureSampleGrad(textures[(*material).clear_coat_roughness_tex - 1u], samp, coords, ddx, ddy); }
-fn emissive_texture(material: ptr<function, Material>, samp: sampler, coords: vec2<f32>, ddx: vec2<f32>, ddy: vec2<f32>) -> vec4<f32> { return textureSampleGrad(textures[(*material).emissive_tex - 1u], samp, coords, ddx, ddy); }
-fn anisotropy_texture(material: ptr<function, Material>, samp: sampler, coords: vec2<f32>, ddx: vec2<f32>, ddy: vec2<f32>) -> vec4<f32> { return textureSampleGrad(textures[(*material).anisotropy_tex - 1u], samp, coords, ddx, ddy); }
-fn ambient_occlusion_texture(material: ptr<function, Material>, samp: sampler, coords: vec2<f32>, ddx: vec2<f32>, ddy: vec2<f32>) -> vec4<f32> { return textureSampleGrad(textures[(*material).ambient_occlusion_tex - 1u], samp, coords, ddx, ddy); }
+fn has_height_texture(material: ptr<function, Material>) -> bool { return (*material).height_tex != 0u; }
+fn has_lightmap_texture(material: ptr<function, Material>) -> bool { return (*material).lightmap_tex != 0u; }
+fn has_transmission_texture(material: ptr<function, Material>) -> bool { return (*material).transmission_tex != 0u; }
+fn has_sheen_color_texture(material: ptr<function, Material>) -> bool { return (*material).sheen_color_tex != 0u; }
+fn has_sheen_roughness_texture(material: ptr<function, Material>) -> bool { return (*material).sheen_roughness_tex != 0u; }
+
+fn albedo_texture(material: ptr<function, Material>, samp: sampler, uv: UvCoords) -> vec4<f32> { return textureSampleGrad(textures[(*material).albedo_tex - 1u], samp, uv.coords, uv.ddx, uv.ddy); }
+fn normal_texture(material: ptr<function, Material>, samp: sampler, uv: UvCoords) -> vec4<f32> { return textureSampleGrad(textures[(*material).normal_tex - 1u], samp, uv.coords, uv.ddx, uv.ddy); }
+fn roughness_texture(material: ptr<function, Material>, samp: sampler, uv: UvCoords) -> vec4<f32> { return textureSampleGrad(textures[(*material).roughness_tex - 1u], samp, uv.coords, uv.ddx, uv.ddy); }
+fn metallic_texture(material: ptr<function, Material>, samp: sampler, uv: UvCoords) -> vec4<f32> { return textureSampleGrad(textures[(*material).metallic_tex - 1u], samp, uv.coords, uv.ddx, uv.ddy); }
+fn reflectance_texture(material: ptr<function, Material>, samp: sampler, uv: UvCoords) -> vec4<f32> { return textureSampleGrad(textures[(*material).reflectance_tex - 1u], samp, uv.coords, uv.ddx, uv.ddy); }
+fn clear_coat_texture(material: ptr<function, Material>, samp: sampler, uv: UvCoords) -> vec4<f32> { return textureSampleGrad(textures[(*material).clear_coat_tex - 1u], samp, uv.coords, uv.ddx, uv.ddy); }
+fn clear_coat_roughness_texture(material: ptr<function, Material>, samp: sampler, uv: UvCoords) -> vec4<f32> { return textureSampleGrad(textures[(*material).clear_coat_roughness_tex - 1u], samp, uv.coords, uv.ddx, uv.ddy); }
+fn emissive_texture(material: ptr<function, Material>, samp: sampler, uv: UvCoords) -> vec4<f32> { return textureSampleGrad(textures[(*material).emissive_tex - 1u], samp, uv.coords, uv.ddx, uv.ddy); }
+fn anisotropy_texture(material: ptr<function, Material>, samp: sampler, uv: UvCoords) -> vec4<f32> { return textureSampleGrad(textures[(*material).anisotropy_tex - 1u], samp, uv.coords, uv.ddx, uv.ddy); }
+fn ambient_occlusion_texture(material: ptr<function, Material>, samp: sampler, uv: UvCoords) -> vec4<f32> { return textureSampleGrad(textures[(*material).ambient_occlusion_tex - 1u], samp, uv.coords, uv.ddx, uv.ddy); }
+fn height_texture(material: ptr<function, Material>, samp: sampler, uv: UvCoords) -> vec4<f32> { return textureSampleGrad(textures[(*material).height_tex - 1u], samp, uv.coords, uv.ddx, uv.ddy); }
+fn lightmap_texture(material: ptr<function, Material>, samp: sampler, uv: UvCoords) -> vec4<f32> { return textureSampleGrad(textures[(*material).lightmap_tex - 1u], samp, uv.coords, uv.ddx, uv.ddy); }
+fn transmission_texture(material: ptr<function, Material>, samp: sampler, uv: UvCoords) -> vec4<f32> { return textureSampleGrad(textures[(*material).transmission_tex - 1u], samp, uv.coords, uv.ddx, uv.ddy); }
+fn sheen_color_texture(material: ptr<function, Material>, samp: sampler, uv: UvCoords) -> vec4<f32> { return textureSampleGrad(textures[(*material).sheen_color_tex - 1u], samp, uv.coords, uv.ddx, uv.ddy); }
+fn sheen_roughness_texture(material: ptr<function, Material>, samp: sampler, uv: UvCoords) -> vec4<f32> { return textureSampleGrad(textures[(*material).sheen_roughness_tex - 1u], samp, uv.coords, uv.ddx, uv.ddy); }
 {{else}}
 alias Material = CpuMaterialData;
 
@@ -171,17 +228,27 @@ fn has_clear_coat_roughness_texture(material: ptr<function, Material>) -> bool {
 fn has_emissive_texture(material: ptr<function, Material>) -> bool { return bool(((*material).texture_enable >> 7u) & 0x1u); }
 fn has_anisotropy_texture(material: ptr<function, Material>) -> bool { return bool(((*material).texture_enable >> 8u) & 0x1u); }
 fn has_ambient_occlusion_texture(material: ptr<function, Material>) -> bool { return bool(((*material).texture_enable >> 9u) & 0x1u); }
-
-fn albedo_texture(material: ptr<function, Material>, samp: sampler, coords: vec2<f32>, ddx: vec2<f32>, ddy: vec2<f32>) -> vec4<f32> { return textureSampleGrad(albedo_tex, samp, coords, ddx, ddy); }
-fn normal_texture(material: ptr<function, Material>, samp: sampler, coords: vec2<f32>, ddx: vec2<f32>, ddy: vec2<f32>) -> vec4<f32> { return textureSampleGrad(normal_tex, samp, coords, ddx, ddy); }
-fn roughness_texture(material: ptr<function, Material>, samp: sampler, coords: vec2<f32>, ddx: vec2<f32>, ddy: vec2<f32>) -> vec4<f32> { return textureSampleGrad(roughness_tex, samp, coords, ddx, ddy); }
-fn metallic_texture(material: ptr<function, Material>, samp: sampler, coords: vec2<f32>, ddx: vec2<f32>, ddy: vec2<f32>) -> vec4<f32> { return textureSampleGrad(metallic_tex, samp, coords, ddx, ddy); }
-fn reflectance_texture(material: ptr<function, Material>, samp: sampler, coords: vec2<f32>, ddx: vec2<f32>, ddy: vec2<f32>) -> vec4<f32> { return textureSampleGrad(reflectance_tex, samp, coords, ddx, ddy); }
-fn clear_coat_texture(material: ptr<function, Material>, samp: sampler, coords: vec2<f32>, ddx: vec2<f32>, ddy: vec2<f32>) -> vec4<f32> { return textureSampleGrad(clear_coat_tex, samp, coords, ddx, ddy); }
-fn clear_coat_roughness_texture(material: ptr<function, Material>, samp: sampler, coords: vec2<f32>, ddx: vec2<f32>, ddy: vec2<f32>) -> vec4<f32> { return textureSampleGrad(clear_coat_roughness_tex, samp, coords, ddx, ddy); }
-fn emissive_texture(material: ptr<function, Material>, samp: sampler, coords: vec2<f32>, ddx: vec2<f32>, ddy: vec2<f32>) -> vec4<f32> { return textureSampleGrad(emissive_tex, samp, coords, ddx, ddy); }
-fn anisotropy_texture(material: ptr<function, Material>, samp: sampler, coords: vec2<f32>, ddx: vec2<f32>, ddy: vec2<f32>) -> vec4<f32> { return textureSampleGrad(anisotropy_tex, samp, coords, ddx, ddy); }
-fn ambient_occlusion_texture(material: ptr<function, Material>, samp: sampler, coords: vec2<f32>, ddx: vec2<f32>, ddy: vec2<f32>) -> vec4<f32> { return textureSampleGrad(ambient_occlusion_tex, samp, coords, ddx, ddy); }
+fn has_height_texture(material: ptr<function, Material>) -> bool { return bool(((*material).texture_enable >> 10u) & 0x1u); }
+fn has_lightmap_texture(material: ptr<function, Material>) -> bool { return false; }
+fn has_transmission_texture(material: ptr<function, Material>) -> bool { return false; }
+fn has_sheen_color_texture(material: ptr<function, Material>) -> bool { return false; }
+fn has_sheen_roughness_texture(material: ptr<function, Material>) -> bool { return false; }
+
+fn albedo_texture(material: ptr<function, Material>, samp: sampler, uv: UvCoords) -> vec4<f32> { return textureSampleGrad(albedo_tex, samp, uv.coords, uv.ddx, uv.ddy); }
+fn normal_texture(material: ptr<function, Material>, samp: sampler, uv: UvCoords) -> vec4<f32> { return textureSampleGrad(normal_tex, samp, uv.coords, uv.ddx, uv.ddy); }
+fn roughness_texture(material: ptr<function, Material>, samp: sampler, uv: UvCoords) -> vec4<f32> { return textureSampleGrad(roughness_tex, samp, uv.coords, uv.ddx, uv.ddy); }
+fn metallic_texture(material: ptr<function, Material>, samp: sampler, uv: UvCoords) -> vec4<f32> { return textureSampleGrad(metallic_tex, samp, uv.coords, uv.ddx, uv.ddy); }
+fn reflectance_texture(material: ptr<function, Material>, samp: sampler, uv: UvCoords) -> vec4<f32> { return textureSampleGrad(reflectance_tex, samp, uv.coords, uv.ddx, uv.ddy); }
+fn clear_coat_texture(material: ptr<function, Material>, samp: sampler, uv: UvCoords) -> vec4<f32> { return textureSampleGrad(clear_coat_tex, samp, uv.coords, uv.ddx, uv.ddy); }
+fn clear_coat_roughness_texture(material: ptr<function, Material>, samp: sampler, uv: UvCoords) -> vec4<f32> { return textureSampleGrad(clear_coat_roughness_tex, samp, uv.coords, uv.ddx, uv.ddy); }
+fn emissive_texture(material: ptr<function, Material>, samp: sampler, uv: UvCoords) -> vec4<f32> { return textureSampleGrad(emissive_tex, samp, uv.coords, uv.ddx, uv.ddy); }
+fn anisotropy_texture(material: ptr<function, Material>, samp: sampler, uv: UvCoords) -> vec4<f32> { return textureSampleGrad(anisotropy_tex, samp, uv.coords, uv.ddx, uv.ddy); }
+fn ambient_occlusion_texture(material: ptr<function, Material>, samp: sampler, uv: UvCoords) -> vec4<f32> { return textureSampleGrad(ambient_occlusion_tex, samp, uv.coords, uv.ddx, uv.ddy); }
+fn height_texture(material: ptr<function, Material>, samp: sampler, uv: UvCoords) -> vec4<f32> { return textureSampleGrad(height_tex, samp, uv.coords, uv.ddx, uv.ddy); }
+fn lightmap_texture(material: ptr<function, Material>, samp: sampler, uv: UvCoords) -> vec4<f32> { return vec4<f32>(0.0); }
+fn transmission_texture(material: ptr<function, Material>, samp: sampler, uv: UvCoords) -> vec4<f32> { return vec4<f32>(0.0); }
+fn sheen_color_texture(material: ptr<function, Material>, samp: sampler, uv: UvCoords) -> vec4<f32> { return vec4<f32>(0.0); }
+fn sheen_roughness_texture(material: ptr<function, Material>, samp: sampler, uv: UvCoords) -> vec4<f32> { return vec4<f32>(0.0); }
 {{/if}}
 
 fn compute_diffuse_color(base_color: vec3<f32>, metallic: f32) -> vec3<f32> {
@@ -196,23 +263,93 @@ fn compute_dielectric_f0(reflectance: f32) -> f32 {
     return 0.16 * reflectance * reflectance;
 }
 
+// F0 at normal incidence from air into a dielectric with the given index of refraction.
+fn compute_dielectric_f0_from_ior(ior: f32) -> f32 {
+    let r = (ior - 1.0) / (ior + 1.0);
+    return r * r;
+}
+
 fn perceptual_roughness_to_roughness(perceptual_roughness: f32) -> f32 {
     return perceptual_roughness * perceptual_roughness;
 }
 
+fn material_uv(material: ptr<function, Material>, texture: u32, uv0: UvCoords, uv1: UvCoords) -> UvCoords {
+    if (material_uses_uv1((*material).uv_sets, texture)) {
+        return uv1;
+    }
+    return uv0;
+}
+
+const PARALLAX_MIN_LAYERS: f32 = 8.0;
+const PARALLAX_MAX_LAYERS: f32 = 32.0;
+
+// Steps along the view ray through the height field until it goes below the surface,
+// then interpolates between the last two samples to find the intersection.
+//
+// Source: https://learnopengl.com/Advanced-Lighting/Parallax-Mapping
+fn parallax_occlusion_mapping(material: ptr<function, Material>, s: sampler, uv: UvCoords, view_tangent: vec3<f32>) -> vec2<f32> {
+    // Take more samples at grazing angles, where the ray travels further across the surface.
+    let layer_count = mix(PARALLAX_MAX_LAYERS, PARALLAX_MIN_LAYERS, abs(view_tangent.z));
+    let layer_depth = 1.0 / layer_count;
+    let delta_coords = (view_tangent.xy / max(view_tangent.z, 0.05)) * ((*material).parallax_scale / layer_count);
+
+    var current_coords = uv.coords;
+    var current_layer_depth = 0.0;
+    var current_depth = 1.0 - height_texture(material, s, UvCoords(current_coords, uv.ddx, uv.ddy)).r;
+    for (var i = 0; i < i32(PARALLAX_MAX_LAYERS); i += 1) {
+        if (current_layer_depth >= current_depth) {
+            break;
+        }
+        current_coords -= delta_coords;
+        current_depth = 1.0 - height_texture(material, s, UvCoords(current_coords, uv.ddx, uv.ddy)).r;
+        current_layer_depth += layer_depth;
+    }
+
+    let previous_coords = current_coords + delta_coords;
+    let after_depth = current_depth - current_layer_depth;
+    let before_depth = (1.0 - height_texture(material, s, UvCoords(previous_coords, uv.ddx, uv.ddy)).r) - current_layer_depth + layer_depth;
+    let denominator = after_depth - before_depth;
+    if (abs(denominator) < 0.00001) {
+        return current_coords;
+    }
+    let weight = after_depth / denominator;
+
+    return mix(current_coords, previous_coords, weight);
+}
+
 fn get_pixel_data_inner(material_arg: Material, s: sampler, vs_out: VertexOutput) -> PixelData {
     var material = material_arg;
     var pixel: PixelData;
 
-    let coords = (material.uv_transform0 * vec3<f32>(vs_out.coords0, 1.0)).xy;
-    let uvdx = dpdx(coords);
-    let uvdy = dpdy(coords);
+    let coords0 = (material.uv_transform0 * vec3<f32>(vs_out.coords0, 1.0)).xy;
+    let coords1 = (material.uv_transform1 * vec3<f32>(vs_out.coords1, 1.0)).xy;
+    var uv0 = UvCoords(coords0, dpdx(coords0), dpdy(coords0));
+    var uv1 = UvCoords(coords1, dpdx(coords1), dpdy(coords1));
+
+    // --- PARALLAX ---
+
+    if (has_height_texture(&material)) {
+        let normal_norm = normalize(vs_out.normal);
+        let tangent_norm = normalize(vs_out.tangent.xyz);
+        let bitangent = cross(normal_norm, tangent_norm) * vs_out.tangent.w;
+
+        // Transpose of an orthonormal TBN is its inverse, taking the view vector into tangent space.
+        let view_dir = -normalize(vs_out.view_position.xyz);
+        let view_tangent = vec3<f32>(dot(view_dir, tangent_norm), dot(view_dir, bitangent), dot(view_dir, normal_norm));
+
+        // Only the uv set of the height texture is displaced.
+        if (material_uses_uv1(material.uv_sets, UV_SET_HEIGHT)) {
+            uv1.coords = parallax_occlusion_mapping(&material, s, uv1, view_tangent);
+        } else {
+            uv0.coords = parallax_occlusion_mapping(&material, s, uv0, view_tangent);
+        }
+    }
 
     // --- ALBEDO ---
 
     if (extract_material_flag(material.flags, FLAGS_ALBEDO_ACTIVE)) {
         if (has_albedo_texture(&material)) {
-            pixel.albedo = albedo_texture(&material, s, coords, uvdx, uvdy);
+            pixel.albedo = albedo_texture(&material, s, material_uv(&material, UV_SET_ALBEDO, uv0, uv1));
         } else {
             pixel.albedo = vec4<f32>(1.0);
         }
@@ -229,10 +366,18 @@ fn get_pixel_data_inner(material_arg: Material, s: sampler, vs_out: VertexOutput
     pixel.albedo *= material.albedo;
 
     {{#if discard}}
-    if (pixel.albedo.a < material.alpha_cutout) {
+    {{#if alpha_to_coverage}}
+    // When multisampling, the cutout is done through coverage in fs_main.
+    let multisampled = (per_camera_uniform.flags & PCU_FLAGS_MULTISAMPLE_MASK) == PCU_FLAGS_MULTISAMPLE_ENABLED;
+    if (!multisampled && pixel.albedo.a < material.alpha_cutout + uniforms.cutout_bias) {
+        discard;
+    }
+    {{else}}
+    if (pixel.albedo.a < material.alpha_cutout + uniforms.cutout_bias) {
         discard;
     }
     {{/if}}
+    {{/if}}
 
     // --- STOP IF UNLIT ---
 
@@ -244,7 +389,7 @@ fn get_pixel_data_inner(material_arg: Material, s: sampler, vs_out: VertexOutput
     // --- NORMAL TEXTURE ---
 
     if (has_normal_texture(&material)) {
-        let texture_read = normal_texture(&material, s, coords, uvdx, uvdy);
+        let texture_read = normal_texture(&material, s, material_uv(&material, UV_SET_NORMAL, uv0, uv1));
         var normal: vec3<f32>;
         if (extract_material_flag(material.flags, FLAGS_BICOMPONENT_NORMAL)) {
             var bicomp: vec2<f32>;
@@ -264,8 +409,8 @@ fn get_pixel_data_inner(material_arg: Material, s: sampler, vs_out: VertexOutput
             normal.y = -normal.y;
         }
         let normal_norm = normalize(vs_out.normal);
-        let tangent_norm = normalize(vs_out.tangent);
-        let bitangent = cross(normal_norm, tangent_norm);
+        let tangent_norm = normalize(vs_out.tangent.xyz);
+        let bitangent = cross(normal_norm, tangent_norm) * vs_out.tangent.w;
 
         let tbn = mat3x3(tangent_norm, bitangent, normal_norm);
 
@@ -283,7 +428,7 @@ fn get_pixel_data_inner(material_arg: Material, s: sampler, vs_out: VertexOutput
         // Green: Roughness
         // Blue: Metallic
         if (has_roughness_texture(&material)) {
-            let aomr = roughness_texture(&material, s, coords, uvdx, uvdy);
+            let aomr = roughness_texture(&material, s, material_uv(&material, UV_SET_ROUGHNESS, uv0, uv1));
             pixel.ambient_occlusion = material.ambient_occlusion * aomr[0];
             pixel.perceptual_roughness = material.roughness * aomr[1];
             pixel.metallic = material.metallic * aomr[2];
@@ -300,19 +445,19 @@ fn get_pixel_data_inner(material_arg: Material, s: sampler, vs_out: VertexOutput
         // In roughness texture:
         // Red: Roughness
         if (has_roughness_texture(&material)) {
-            pixel.perceptual_roughness = material.roughness * roughness_texture(&material, s, coords, uvdx, uvdy).r;
+            pixel.perceptual_roughness = material.roughness * roughness_texture(&material, s, material_uv(&material, UV_SET_ROUGHNESS, uv0, uv1)).r;
         } else {
             pixel.perceptual_roughness = material.roughness;
         }
 
         if (has_metallic_texture(&material)) {
-            pixel.metallic = material.metallic * metallic_texture(&material, s, coords, uvdx, uvdy).r;
+            pixel.metallic = material.metallic * metallic_texture(&material, s, material_uv(&material, UV_SET_METALLIC, uv0, uv1)).r;
         } else {
             pixel.metallic = material.metallic;
         }
 
         if (has_ambient_occlusion_texture(&material)) {
-            pixel.ambient_occlusion = material.ambient_occlusion * ambient_occlusion_texture(&material, s, coords, uvdx, uvdy).r;
+            pixel.ambient_occlusion = material.ambient_occlusion * ambient_occlusion_texture(&material, s, material_uv(&material, UV_SET_AMBIENT_OCCLUSION, uv0, uv1)).r;
         } else {
             pixel.ambient_occlusion = material.ambient_occlusion;
         }
@@ -328,7 +473,7 @@ fn get_pixel_data_inner(material_arg: Material, s: sampler, vs_out: VertexOutput
         // Green: Roughness
         // Blue: Metallic
         if (has_roughness_texture(&material)) {
-            let texture_read = roughness_texture(&material, s, coords, uvdx, uvdy);
+            let texture_read = roughness_texture(&material, s, material_uv(&material, UV_SET_ROUGHNESS, uv0, uv1));
             var rm: vec2<f32>;
             if (extract_material_flag(material.flags, FLAGS_AOMR_SWIZZLED_SPLIT)) {
                 rm = texture_read.gb;
@@ -343,7 +488,7 @@ fn get_pixel_data_inner(material_arg: Material, s: sampler, vs_out: VertexOutput
         }
 
         if (has_ambient_occlusion_texture(&material)) {
-            let texture_read = ambient_occlusion_texture(&material, s, coords, uvdx, uvdy);
+            let texture_read = ambient_occlusion_texture(&material, s, material_uv(&material, UV_SET_AMBIENT_OCCLUSION, uv0, uv1));
             pixel.ambient_occlusion = material.ambient_occlusion * texture_read.r;
         } else {
             pixel.ambient_occlusion = material.ambient_occlusion;
@@ -353,7 +498,7 @@ fn get_pixel_data_inner(material_arg: Material, s: sampler, vs_out: VertexOutput
     // --- REFLECTANCE ---
 
     if (has_reflectance_texture(&material)) {
-        pixel.reflectance = material.reflectance * reflectance_texture(&material, s, coords, uvdx, uvdy).r;
+        pixel.reflectance = material.reflectance * reflectance_texture(&material, s, material_uv(&material, UV_SET_REFLECTANCE, uv0, uv1)).r;
     } else {
         pixel.reflectance = material.reflectance;
     }
@@ -362,7 +507,7 @@ fn get_pixel_data_inner(material_arg: Material, s: sampler, vs_out: VertexOutput
 
     if (extract_material_flag(material.flags, FLAGS_CC_GLTF_COMBINED)) {
         if (has_clear_coat_texture(&material)) {
-            let texture_read = clear_coat_texture(&material, s, coords, uvdx, uvdy);
+            let texture_read = clear_coat_texture(&material, s, material_uv(&material, UV_SET_CLEAR_COAT, uv0, uv1));
             pixel.clear_coat = material.clear_coat * texture_read.r;
             pixel.clear_coat_perceptual_roughness = material.clear_coat_roughness * texture_read.g;
         } else {
@@ -371,13 +516,13 @@ fn get_pixel_data_inner(material_arg: Material, s: sampler, vs_out: VertexOutput
         }
     } else {
         if (has_clear_coat_texture(&material)) {
-            pixel.clear_coat = material.clear_coat * clear_coat_texture(&material, s, coords, uvdx, uvdy).r;
+            pixel.clear_coat = material.clear_coat * clear_coat_texture(&material, s, material_uv(&material, UV_SET_CLEAR_COAT, uv0, uv1)).r;
         } else {
             pixel.clear_coat = material.clear_coat;
         }
 
         if (has_clear_coat_roughness_texture(&material)) {
-            let texture_read = clear_coat_roughness_texture(&material, s, coords, uvdx, uvdy);
+            let texture_read = clear_coat_roughness_texture(&material, s, material_uv(&material, UV_SET_CLEAR_COAT_ROUGHNESS, uv0, uv1));
 
             if (extract_material_flag(material.flags, FLAGS_CC_GLTF_SPLIT)) {
                 pixel.clear_coat_perceptual_roughness = material.clear_coat_roughness * texture_read.g;
@@ -392,25 +537,77 @@ fn get_pixel_data_inner(material_arg: Material, s: sampler, vs_out: VertexOutput
     // --- EMISSIVE ---
 
     if (has_emissive_texture(&material)) {
-        pixel.emissive = material.emissive * emissive_texture(&material, s, coords, uvdx, uvdy).rgb;
+        pixel.emissive = material.emissive * emissive_texture(&material, s, material_uv(&material, UV_SET_EMISSIVE, uv0, uv1)).rgb;
     } else {
         pixel.emissive = material.emissive;
     }
 
     // --- ANISOTROPY ---
 
+    // In anisotropy texture:
+    // Red, Green: Direction in tangent space
+    // Blue: Strength
+    var anisotropy_direction = vec2<f32>(1.0, 0.0);
     if (has_anisotropy_texture(&material)) {
-        pixel.anisotropy = material.anisotropy * anisotropy_texture(&material, s, coords, uvdx, uvdy).r;
+        let texture_read = anisotropy_texture(&material, s, material_uv(&material, UV_SET_ANISOTROPY, uv0, uv1));
+        anisotropy_direction = texture_read.rg * 2.0 - 1.0;
+        pixel.anisotropy = material.anisotropy * texture_read.b;
     } else {
         pixel.anisotropy = material.anisotropy;
     }
+    if (pixel.anisotropy != 0.0) {
+        let rotation_cos = cos(material.anisotropy_rotation);
+        let rotation_sin = sin(material.anisotropy_rotation);
+        let direction = mat2x2<f32>(rotation_cos, rotation_sin, -rotation_sin, rotation_cos) * anisotropy_direction;
+
+        let normal_norm = normalize(vs_out.normal);
+        let tangent_norm = normalize(vs_out.tangent.xyz);
+        let bitangent = cross(normal_norm, tangent_norm) * vs_out.tangent.w;
+        let tangent = tangent_norm * direction.x + bitangent * direction.y;
+
+        // Keep the direction perpendicular to the normal mapped normal.
+        pixel.anisotropy_tangent = normalize(tangent - pixel.normal * dot(tangent, pixel.normal));
+    }
+
+    // --- LIGHTMAP ---
+
+    if (has_lightmap_texture(&material)) {
+        pixel.lightmap = lightmap_texture(&material, s, uv1).rgb;
+    }
+
+    // --- TRANSMISSION ---
+
+    if (has_transmission_texture(&material)) {
+        pixel.transmission = material.transmission * transmission_texture(&material, s, material_uv(&material, UV_SET_TRANSMISSION, uv0, uv1)).r;
+    } else {
+        pixel.transmission = material.transmission;
+    }
+
+    // --- SHEEN ---
+
+    if (has_sheen_color_texture(&material)) {
+        pixel.sheen_color = material.sheen_color * sheen_color_texture(&material, s, material_uv(&material, UV_SET_SHEEN_COLOR, uv0, uv1)).rgb;
+    } else {
+        pixel.sheen_color = material.sheen_color;
+    }
+    if (has_sheen_roughness_texture(&material)) {
+        pixel.sheen_perceptual_roughness = material.sheen_roughness * sheen_roughness_texture(&material, s, material_uv(&material, UV_SET_SHEEN_ROUGHNESS, uv0, uv1)).a;
+    } else {
+        pixel.sheen_perceptual_roughness = material.sheen_roughness;
+    }
 
     // --- COMPUTATIONS---
 
-    pixel.diffuse_color = compute_diffuse_color(pixel.albedo.xyz, pixel.metallic);
+    // Transmitted light replaces diffuse reflection.
+    pixel.diffuse_color = compute_diffuse_color(pixel.albedo.xyz, pixel.metallic) * (1.0 - pixel.transmission);
 
-    // Assumes an interface from air to an IOR of 1.5 for dielectrics
-    let reflectance = compute_dielectric_f0(pixel.reflectance);
+    // Without an explicit IOR, reflectance 0.5 corresponds to an interface from air to an IOR of 1.5 for dielectrics
+    var reflectance: f32;
+    if (material.ior != 0.0) {
+        reflectance = compute_dielectric_f0_from_ior(material.ior);
+    } else {
+        reflectance = compute_dielectric_f0(pixel.reflectance);
+    }
     pixel.f0 = compute_f0(pixel.albedo.rgb, pixel.metallic, reflectance);
 
     if (pixel.clear_coat != 0.0) {
@@ -419,6 +616,8 @@ fn get_pixel_data_inner(material_arg: Material, s: sampler, vs_out: VertexOutput
         pixel.clear_coat_roughness = perceptual_roughness_to_roughness(pixel.clear_coat_perceptual_roughness);
     }
     pixel.roughness = perceptual_roughness_to_roughness(pixel.perceptual_roughness);
+    // The charlie distribution diverges as roughness goes to zero.
+    pixel.sheen_roughness = perceptual_roughness_to_roughness(max(pixel.sheen_perceptual_roughness, 0.07));
 
     return pixel;
 }
@@ -448,9 +647,21 @@ fn surface_shading(light_dir: vec3<f32>, intensity: vec3<f32>, pixel: PixelData,
 
     let f90 = saturate(dot(pixel.f0, vec3<f32>(50.0 * 0.33)));
 
-    let d = brdf_d_ggx(noh, pixel.roughness);
+    var d: f32;
+    var v: f32;
+    if (pixel.anisotropy != 0.0) {
+        // Roughness is stretched along the direction of anisotropy, as in KHR_materials_anisotropy.
+        let t = pixel.anisotropy_tangent;
+        let b = cross(n, t);
+        let at = mix(pixel.roughness, 1.0, pixel.anisotropy * pixel.anisotropy);
+        let ab = pixel.roughness;
+        d = brdf_d_ggx_anisotropic(noh, dot(t, h), dot(b, h), at, ab);
+        v = brdf_v_smith_ggx_correlated_anisotropic(at, ab, dot(t, view_pos), dot(b, view_pos), dot(t, light_dir), dot(b, light_dir), nov, nol);
+    } else {
+        d = brdf_d_ggx(noh, pixel.roughness);
+        v = brdf_v_smith_ggx_correlated(nov, nol, pixel.roughness);
+    }
     let f = brdf_f_schlick_vec3(loh, pixel.f0, f90);
-    let v = brdf_v_smith_ggx_correlated(nov, nol, pixel.roughness);
 
     // TODO: figure out how they generate their lut
     let energy_comp = 1.0;
@@ -460,23 +671,90 @@ fn surface_shading(light_dir: vec3<f32>, intensity: vec3<f32>, pixel: PixelData,
     // diffuse
     let fd = pixel.diffuse_color * brdf_fd_lambert();
 
-    let color = fd + fr * energy_comp;
+    var color = fd + fr * energy_comp;
+
+    if (any(pixel.sheen_color != vec3<f32>(0.0))) {
+        // The sheen lobe sits on top of the base layer, which is darkened by the light the sheen reflects.
+        // The directional albedo of the sheen would come from a lookup table, we use a rough constant instead.
+        let sheen_d = brdf_d_charlie(noh, pixel.sheen_roughness);
+        let sheen_v = brdf_v_neubelt(nov, nol);
+        let sheen_scaling = 1.0 - max(pixel.sheen_color.r, max(pixel.sheen_color.g, pixel.sheen_color.b)) * 0.157;
+        color = color * sheen_scaling + pixel.sheen_color * (sheen_d * sheen_v);
+    }
 
     let light_attenuation = 1.0;
 
     return (color * intensity) * (light_attenuation * nol * occlusion);
 }
 
-@fragment
-fn fs_main(vs_out: VertexOutput) -> @location(0) vec4<f32> {
-    let material = materials[vs_out.material];
+fn point_light_shading(light: PointLight, pixel: PixelData, view_position: vec3<f32>, v: vec3<f32>) -> vec3<f32> {
+    // Delta to light
+    let delta = (uniforms.view * light.position).xyz - view_position;
+
+    // Distance
+    let d2 = dot(delta, delta);
+    let d = sqrt(d2);
+
+    // Inverse square falloff, windowed so it reaches zero with a zero derivative at the radius.
+    // The distance is clamped to 1cm to avoid the singularity at the light's position.
+    // Source: Real Shading in Unreal Engine 4, Karis 2013
+    let s = saturate(d / light.radius);
+    let s2 = s * s;
+    let window = saturate(1.0 - s2 * s2);
+    let att = window * window / max(d2, 0.0001);
+    let intensity = light.color * att;
+
+    // Calculate light source vector
+    let l = delta / d;
 
+    return max(surface_shading(l, intensity, pixel, v, pixel.ambient_occlusion), vec3<f32>(0.0));
+}
+
+// Split-sum image based lighting, using world space directions to match the skybox.
+fn environment_shading(pixel: PixelData, view_pos: vec3<f32>) -> vec3<f32> {
+    let inv_view_mat3 = mat3x3<f32>(uniforms.inv_view[0].xyz, uniforms.inv_view[1].xyz, uniforms.inv_view[2].xyz);
+    let n = normalize(inv_view_mat3 * pixel.normal);
+    let v = normalize(inv_view_mat3 * view_pos);
+    let r = environment.inv_rotation * reflect(-v, n);
+    let nov = saturate(dot(n, v));
+
+    let irradiance = textureSampleLevel(environment_irradiance, environment_sampler, environment.inv_rotation * n, 0.0).rgb;
+    let diffuse = irradiance * pixel.diffuse_color;
+
+    let max_lod = f32(textureNumLevels(environment_prefiltered) - 1u);
+    let prefiltered = textureSampleLevel(environment_prefiltered, environment_sampler, r, pixel.perceptual_roughness * max_lod).rgb;
+    let brdf = textureSampleLevel(environment_brdf_lut, environment_sampler, vec2<f32>(nov, pixel.perceptual_roughness), 0.0).rg;
+    let specular = prefiltered * (pixel.f0 * brdf.x + brdf.y);
+
+    return (diffuse + specular) * pixel.ambient_occlusion;
+}
+
+fn shade(material: Material, vs_out: VertexOutput) -> vec4<f32> {
+    {{#if alpha_to_coverage}}
+    var pixel = get_pixel_data(material, vs_out);
+
+    // Sharpen alpha around the cutout so coverage goes from none to full over about a pixel.
+    let alpha_width = max(fwidth(pixel.albedo.a), 0.0001);
+    if ((per_camera_uniform.flags & PCU_FLAGS_MULTISAMPLE_MASK) == PCU_FLAGS_MULTISAMPLE_ENABLED) {
+        pixel.albedo.a = saturate((pixel.albedo.a - material.alpha_cutout - uniforms.cutout_bias) / alpha_width + 0.5);
+    }
+    {{else}}
     let pixel = get_pixel_data(material, vs_out);
+    {{/if}}
 
+    {{#if unlit}}
+    return pixel.albedo;
+    {{else}}
     if (extract_material_flag(material.flags, FLAGS_UNLIT)) {
         return pixel.albedo;
     }
 
+    return light_pixel(pixel, vs_out);
+    {{/if}}
+}
+
+// Lights the pixel with the scene's lights, shadows, lightmap and environment.
+fn light_pixel(pixel: PixelData, vs_out: VertexOutput) -> vec4<f32> {
     // View vector
     let v = -normalize(vs_out.view_position.xyz);
 
@@ -497,22 +775,39 @@ fn fs_main(vs_out: VertexOutput) -> @location(0) vec4<f32> {
         var top_right = top_left + light.size;
         let shadow_coords = mix(top_left, top_right, shadow_local_coords);
 
-        // The shadow is stored in an atlas, so we need to make sure we don't linear blend
-        // across atlasses. We move our conditional borders in a half a pixel for standard
-        // linear blending (so we're hitting texel centers on the edge). We move it an additional
-        // pixel in so that our pcf5 offsets don't move off the edge of the atlasses.
+        // Every light's shadow is stored in its own region of a shared atlas, so we need to make
+        // sure we don't linear blend into the neighboring lights' shadows. We move our conditional
+        // borders in a half a pixel for standard linear blending (so we're hitting texel centers
+        // on the edge). We move it an additional pixel in so that our pcf5 offsets don't move off
+        // the edge of this light's region. The test is done in atlas space, as that's where the
+        // borders are.
         let shadow_border = light.inv_resolution * 1.5;
         top_left += shadow_border;
         top_right -= shadow_border;
 
         var shadow_value = 1.0;
         if (
-            any(shadow_flipped >= top_left) && // XY lower
-            any(shadow_flipped <= top_right) && // XY upper
+            light.casts_shadows != 0u &&
+            all(shadow_coords >= top_left) && // XY lower
+            all(shadow_coords <= top_right) && // XY upper
             shadow_ndc.z >= 0.0 && // Z lower
             shadow_ndc.z <= 1.0 // Z upper
         ) {
-            shadow_value = shadow_sample_pcf5(shadows, comparison_sampler, shadow_coords, shadow_ndc.z);
+            if (light.penumbra_scale > 0.0) {
+                shadow_value = shadow_sample_pcss(
+                    shadows,
+                    comparison_sampler,
+                    shadow_coords,
+                    shadow_ndc.z,
+                    light.inv_resolution,
+                    top_left,
+                    top_right,
+                    light.penumbra_scale,
+                    light.depth_to_distance,
+                );
+            } else {
+                shadow_value = shadow_sample_pcf5(shadows, comparison_sampler, shadow_coords, shadow_ndc.z);
+            }
         }
 
         // Calculate light source vector
@@ -521,31 +816,70 @@ fn fs_main(vs_out: VertexOutput) -> @location(0) vec4<f32> {
         color += surface_shading(l, light.color, pixel, v, shadow_value * pixel.ambient_occlusion);
     }
 
-    for (var i = 0; i < i32(point_lights.count); i += 1) {
-        let light = point_lights.data[i];
-
-        // Delta to light
-        let delta = (uniforms.view * light.position).xyz - vs_out.view_position.xyz;
-
-        // Distance
-        let d = length(delta);
+    if (light_clusters.enabled != 0u) {
+        // Only iterate the lights which were binned into this pixel's cluster.
+        let cluster = cluster_index(light_clusters, vs_out.position.xy, abs(vs_out.view_position.z));
+        let count = cluster_lights[cluster].count;
+        for (var i = 0u; i < count; i += 1u) {
+            let light = point_lights.data[cluster_lights[cluster].indices[i]];
+            color += point_light_shading(light, pixel, vs_out.view_position.xyz, v);
+        }
+    } else {
+        for (var i = 0; i < i32(point_lights.count); i += 1) {
+            color += point_light_shading(point_lights.data[i], pixel, vs_out.view_position.xyz, v);
+        }
+    }
 
-        // Attenuate from light and cusp at radius
-        // Derivative is 0 at both d = 0 and d = radius
-        // Source: https://lisyarus.github.io/blog/graphics/2022/07/30/point-light-attenuation.html
-        let s = saturate(d / light.radius);
-        let s2 = s * s;
-        let inv_s2 = 1.0 - s2;
-        let att = inv_s2 * inv_s2 / (1.0 + s2);
-        let intensity = light.color * att;
+    // Baked lighting already includes its own occlusion and shadowing.
+    color += pixel.lightmap * pixel.diffuse_color;
 
-        // Calculate light source vector
-        let l = delta / d;
+    {{#if transmission}}
+    // Thin surfaces let the scene directly behind them through, tinted by their base color.
+    if (pixel.transmission != 0.0) {
+        let background_coords = vs_out.position.xy / vec2<f32>(textureDimensions(transmission_background));
+        let background = textureSampleLevel(transmission_background, environment_sampler, background_coords, 0.0).rgb;
+        color += background * pixel.albedo.rgb * (1.0 - pixel.metallic) * pixel.transmission;
+    }
+    {{/if}}
 
-        color += max(surface_shading(l, intensity, pixel, v, pixel.ambient_occlusion), vec3<f32>(0.0));
+    if (environment.enabled != 0u) {
+        color += environment_shading(pixel, v);
+        return vec4<f32>(color, pixel.albedo.a);
     }
 
     let ambient = uniforms.ambient * pixel.albedo;
     let shaded = vec4<f32>(color, pixel.albedo.a);
     return max(ambient, shaded);
 }
+
+@fragment
+fn fs_main(vs_in: VertexOutput, @builtin(front_facing) front_facing: bool) -> @location(0) vec4<f32> {
+    let material = materials[vs_in.material];
+
+    // Back faces of double sided materials face away from their normal. Negating the
+    // whole tangent frame, including its handedness, turns it over without mirroring
+    // normal maps.
+    var vs_out = vs_in;
+    if (!front_facing && extract_material_flag(material.flags, FLAGS_DOUBLE_SIDED)) {
+        vs_out.normal = -vs_out.normal;
+        vs_out.tangent = -vs_out.tangent;
+    }
+
+    var color = shade(material, vs_out) * vs_out.debug_tint;
+
+    // Blended materials are drawn with premultiplied alpha blending, so convert the
+    // output to match their blend mode.
+    if (extract_material_flag(material.flags, FLAGS_BLEND_PREMULTIPLY)) {
+        color = vec4<f32>(color.rgb * color.a, color.a);
+    }
+    if (extract_material_flag(material.flags, FLAGS_BLEND_ADDITIVE)) {
+        color.a = 0.0;
+    }
+    {{#if transmission}}
+    // Transmissive materials which aren't blended are drawn in the blend pass, and must fully cover what is behind them.
+    if (extract_material_flag(material.flags, FLAGS_OPAQUE_ALPHA)) {
+        color.a = 1.0;
+    }
+    {{/if}}
+    return color;
+}
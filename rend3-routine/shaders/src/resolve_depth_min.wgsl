@@ -1,3 +1,5 @@
+{{include "rend3-routine/math/depth.wgsl"}}
+
 @group(0) @binding(0)
 var source: texture_depth_multisampled_2d;
 
@@ -17,11 +19,11 @@ fn vs_main(@builtin(vertex_index) id: u32) -> VertexOutput {
 
 @fragment
 fn fs_main(vout: VertexOutput) -> @builtin(frag_depth) f32 {
-    var nearest: f32 = 1.0;
+    var farthest: f32 = NEAR_DEPTH;
 
     for (var sample = 0; sample < SAMPLES; sample += 1) {
-        nearest = min(nearest, textureLoad(source, vec2u(vout.position.xy), sample));
+        farthest = farther_depth(farthest, textureLoad(source, vec2u(vout.position.xy), sample));
     }
 
-    return nearest;
+    return farthest;
 }
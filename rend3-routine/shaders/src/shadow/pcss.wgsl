@@ -0,0 +1,50 @@
+// Percentage closer soft shadows. The penumbra widens with the distance between the receiver and
+// the average blocker found around it, so shadows are sharp where they touch their caster.
+//
+// All coordinates are in atlas space. Samples are clamped to [min_coords, max_coords] so they stay
+// within this light's region of the atlas.
+fn shadow_sample_pcss(
+    tex: texture_depth_2d,
+    samp: sampler_comparison,
+    coords: vec2<f32>,
+    depth: f32,
+    texel_size: vec2<f32>,
+    min_coords: vec2<f32>,
+    max_coords: vec2<f32>,
+    penumbra_scale: f32,
+    depth_to_distance: f32,
+) -> f32 {
+    let dimensions = vec2<f32>(textureDimensions(tex));
+    let search_radius = texel_size * 6.0;
+
+    // Find the average distance from the receiver to whatever is blocking it.
+    var blocker_distance = 0.0;
+    var blocker_count = 0.0;
+    for (var y = -2; y <= 2; y += 1) {
+        for (var x = -2; x <= 2; x += 1) {
+            let sample_coords = clamp(coords + vec2<f32>(f32(x), f32(y)) * 0.5 * search_radius, min_coords, max_coords);
+            let sample_depth = textureLoad(tex, vec2<i32>(sample_coords * dimensions), 0);
+            let distance = (sample_depth - depth) * depth_to_distance;
+            if (distance > 0.0) {
+                blocker_distance += distance;
+                blocker_count += 1.0;
+            }
+        }
+    }
+
+    if (blocker_count == 0.0) {
+        return 1.0;
+    }
+
+    let penumbra = blocker_distance / blocker_count * penumbra_scale;
+    let radius = clamp(vec2<f32>(penumbra * 0.5), texel_size, search_radius);
+
+    var result = 0.0;
+    for (var y = 0; y < 4; y += 1) {
+        for (var x = 0; x < 4; x += 1) {
+            let offset = (vec2<f32>(f32(x), f32(y)) - 1.5) / 1.5 * radius;
+            result += textureSampleCompareLevel(tex, samp, clamp(coords + offset, min_coords, max_coords), depth);
+        }
+    }
+    return result / 16.0;
+}
@@ -11,6 +11,10 @@ struct SkinningInput {
     joint_indices_offset: u32,
     /// Byte offset into vertex buffer of joint weights of mesh.
     joint_weight_offset: u32,
+    /// Byte offset into vertex buffer of the second set of joint indices of mesh.
+    joint_indices_1_offset: u32,
+    /// Byte offset into vertex buffer of the second set of joint weights of mesh.
+    joint_weight_1_offset: u32,
     /// Byte offset into vertex buffer of position attribute of skinned mesh.
     updated_position_offset: u32,
     /// Byte offset into vertex buffer of normal attribute of skinned mesh.
@@ -22,6 +26,8 @@ struct SkinningInput {
     joint_matrix_base_offset: u32,
     /// Count of vertices in this mesh.
     vertex_count: u32,
+    /// 0 for linear blend skinning, 1 for dual quaternion skinning.
+    dual_quaternion: u32,
 }
 
 @group(0) @binding(0)
@@ -34,6 +40,10 @@ var<storage> joint_matrices: array<mat4x4<f32>>;
 {{include "rend3/vertex_attributes.wgsl"}}
 {{include "rend3/vertex_attributes_store.wgsl"}}
 
+fn quat_rotate(q: vec4<f32>, v: vec3<f32>) -> vec3<f32> {
+    return v + 2.0 * cross(q.xyz, cross(q.xyz, v) + q.w * v);
+}
+
 @compute @workgroup_size(256)
 fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
     let idx = global_id.x;
@@ -42,8 +52,25 @@ fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
         return;
     }
 
-    let joint_indices = extract_attribute_vec4_u16(input.joint_indices_offset, idx);
-    let joint_weights = extract_attribute_vec4_f32(input.joint_weight_offset, idx);
+    // Meshes with a second set of joints have up to 8 influences, everything else only pays for 4.
+    var joint_indices: array<u32, 8>;
+    var joint_weights: array<f32, 8>;
+    var influence_count = 4;
+    let joint_indices_0 = extract_attribute_vec4_u16(input.joint_indices_offset, idx);
+    let joint_weights_0 = extract_attribute_vec4_f32(input.joint_weight_offset, idx);
+    for (var i = 0; i < 4; i++) {
+        joint_indices[i] = joint_indices_0[i];
+        joint_weights[i] = joint_weights_0[i];
+    }
+    if (input.joint_indices_1_offset != 0xFFFFFFFFu) {
+        let joint_indices_1 = extract_attribute_vec4_u16(input.joint_indices_1_offset, idx);
+        let joint_weights_1 = extract_attribute_vec4_f32(input.joint_weight_1_offset, idx);
+        for (var i = 0; i < 4; i++) {
+            joint_indices[i + 4] = joint_indices_1[i];
+            joint_weights[i + 4] = joint_weights_1[i];
+        }
+        influence_count = 8;
+    }
 
     // Compute the skinned position
     var pos_acc = vec3<f32>(0.0);
@@ -63,18 +90,48 @@ fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
         tangent = extract_attribute_vec3_f32(input.base_tangent_offset, idx);
     }
     
-    for (var i = 0; i < 4; i++) {
-        let weight = joint_weights[i];
+    if (input.dual_quaternion != 0u) {
+        // Each joint "matrix" holds the rotation, dual part, and scale of the joint in its first three columns.
+        var real_acc = vec4<f32>(0.0);
+        var dual_acc = vec4<f32>(0.0);
+        var scale_acc = vec3<f32>(0.0);
+        let first_real = joint_matrices[input.joint_matrix_base_offset + joint_indices[0]][0];
+        for (var i = 0; i < influence_count; i++) {
+            let weight = joint_weights[i];
+
+            if (weight > 0.0) {
+                let joint_index = joint_indices[i];
+                let joint = joint_matrices[input.joint_matrix_base_offset + joint_index];
+                // q and -q are the same rotation, blend along the shortest path.
+                let signed_weight = select(weight, -weight, dot(joint[0], first_real) < 0.0);
+                real_acc += joint[0] * signed_weight;
+                dual_acc += joint[1] * signed_weight;
+                scale_acc += joint[2].xyz * weight;
+            }
+        }
+
+        let inv_len = 1.0 / length(real_acc);
+        let real = real_acc * inv_len;
+        let dual = dual_acc * inv_len;
+        let translation = 2.0 * (real.w * dual.xyz - dual.w * real.xyz + cross(real.xyz, dual.xyz));
+
+        pos_acc = quat_rotate(real, pos * scale_acc) + translation;
+        norm_acc = quat_rotate(real, normal / scale_acc);
+        tang_acc = quat_rotate(real, tangent / scale_acc);
+    } else {
+        for (var i = 0; i < influence_count; i++) {
+            let weight = joint_weights[i];
 
-        if (weight > 0.0) {
-            let joint_index = joint_indices[i];
-            let joint_matrix = joint_matrices[input.joint_matrix_base_offset + joint_index];
-            let joint_matrix3 = mat3x3<f32>(joint_matrix[0].xyz, joint_matrix[1].xyz, joint_matrix[2].xyz);
-            pos_acc += (joint_matrix * vec4<f32>(pos, 1.0)).xyz * weight;
-            
-            let inv_scale_sq = mat3_inv_scale_squared(joint_matrix3);
-            norm_acc += (joint_matrix3 * (inv_scale_sq * normal)) * weight;
-            tang_acc += (joint_matrix3 * (inv_scale_sq * tangent)) * weight;
+            if (weight > 0.0) {
+                let joint_index = joint_indices[i];
+                let joint_matrix = joint_matrices[input.joint_matrix_base_offset + joint_index];
+                let joint_matrix3 = mat3x3<f32>(joint_matrix[0].xyz, joint_matrix[1].xyz, joint_matrix[2].xyz);
+                pos_acc += (joint_matrix * vec4<f32>(pos, 1.0)).xyz * weight;
+                
+                let inv_scale_sq = mat3_inv_scale_squared(joint_matrix3);
+                norm_acc += (joint_matrix3 * (inv_scale_sq * normal)) * weight;
+                tang_acc += (joint_matrix3 * (inv_scale_sq * tangent)) * weight;
+            }
         }
     }
 
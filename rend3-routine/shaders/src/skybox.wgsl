@@ -1,4 +1,5 @@
 {{include "rend3-routine/structures.wgsl"}}
+{{include "rend3-routine/math/depth.wgsl"}}
 
 struct VertexOutput {
     @builtin(position) pos: vec4<f32>,
@@ -9,7 +10,7 @@ struct VertexOutput {
 fn vs_main(@builtin(vertex_index) id: u32) -> VertexOutput {
     let clip_position = vec2<f32>(f32(id / 2u) * 4.0 - 1.0, f32(id % 2u) * 4.0 - 1.0);
 
-    return VertexOutput(vec4<f32>(clip_position, 0.0, 1.0), clip_position);
+    return VertexOutput(vec4<f32>(clip_position, FAR_DEPTH, 1.0), clip_position);
 }
 
 @group(0) @binding(0)
@@ -19,16 +20,65 @@ var<uniform> uniforms: UniformData;
 @group(1) @binding(0)
 var skybox: texture_cube<f32>;
 
+struct SkyboxData {
+    // Rotates world space directions into the space of the cubemap.
+    inv_rotation: mat3x3<f32>,
+    intensity: f32,
+    mip_bias: f32,
+}
+
+@group(1) @binding(1)
+var<uniform> skybox_data: SkyboxData;
+@group(1) @binding(2)
+var skybox_sampler: sampler;
+
 @fragment
 fn fs_main(output: VertexOutput) -> @location(0) vec4<f32> {
     // We use the near plane as depth here, as if we used the far plane, it would all NaN out. Doesn't _really_ matter,
-    // but 1.0 is a nice round number and results in a depth of 0.1 with my near plane. Good 'nuf.
-    let clip = vec4<f32>(output.clip_position, 1.0, 1.0);
+    // but it results in a depth of 0.1 with my near plane. Good 'nuf.
+    let clip = vec4<f32>(output.clip_position, NEAR_DEPTH, 1.0);
     let world_undiv = uniforms.inv_origin_view_proj * clip;
     let world = world_undiv.xyz / world_undiv.w;
     let world_dir = normalize(world);
+    let skybox_dir = skybox_data.inv_rotation * world_dir;
+
+    let background = textureSampleBias(skybox, skybox_sampler, skybox_dir, skybox_data.mip_bias).rgb * skybox_data.intensity;
+
+    return vec4<f32>(background, 1.0);
+}
+
+struct CaptureOutput {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) clip_position: vec2<f32>,
+    @location(1) @interpolate(flat) face: u32,
+}
+
+// Draws one face of a cubemap per instance, used to capture the skybox into a cubemap.
+@vertex
+fn vs_capture(@builtin(vertex_index) id: u32, @builtin(instance_index) face: u32) -> CaptureOutput {
+    let clip_position = vec2<f32>(f32(id / 2u) * 4.0 - 1.0, f32(id % 2u) * 4.0 - 1.0);
+
+    return CaptureOutput(vec4<f32>(clip_position, 0.0, 1.0), clip_position, face);
+}
+
+// Direction through a point on a cubemap face, in the face order and orientation wgpu samples cubemaps with.
+fn cube_face_direction(face: u32, clip: vec2<f32>) -> vec3<f32> {
+    switch face {
+        case 0u: { return vec3<f32>(1.0, clip.y, -clip.x); }
+        case 1u: { return vec3<f32>(-1.0, clip.y, clip.x); }
+        case 2u: { return vec3<f32>(clip.x, 1.0, -clip.y); }
+        case 3u: { return vec3<f32>(clip.x, -1.0, clip.y); }
+        case 4u: { return vec3<f32>(clip.x, clip.y, 1.0); }
+        default: { return vec3<f32>(-clip.x, clip.y, -1.0); }
+    }
+}
+
+@fragment
+fn fs_capture(output: CaptureOutput) -> @location(0) vec4<f32> {
+    let world_dir = normalize(cube_face_direction(output.face, output.clip_position));
+    let skybox_dir = skybox_data.inv_rotation * world_dir;
 
-    let background = textureSample(skybox, primary_sampler, world_dir).rgb;
+    let background = textureSampleLevel(skybox, primary_sampler, skybox_dir, 0.0).rgb * skybox_data.intensity;
 
     return vec4<f32>(background, 1.0);
 }
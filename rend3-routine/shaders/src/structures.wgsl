@@ -28,6 +28,7 @@ struct IndirectCall {
 struct UniformData {
     view: mat4x4<f32>,
     view_proj: mat4x4<f32>,
+    previous_view_proj: mat4x4<f32>,
     origin_view_proj: mat4x4<f32>,
     inv_view: mat4x4<f32>,
     inv_view_proj: mat4x4<f32>,
@@ -35,6 +36,8 @@ struct UniformData {
     frustum: Frustum,
     ambient: vec4<f32>,
     resolution: vec2<u32>,
+    // Added to the alpha cutout of every material.
+    cutout_bias: f32,
 }
 
 struct PerCameraUniformObjectData {
@@ -71,6 +74,11 @@ const PCU_FLAGS_MULTISAMPLE_MASK: u32 = 0x2u;
 const PCU_FLAGS_MULTISAMPLE_DISABLED: u32 = 0x0u;
 const PCU_FLAGS_MULTISAMPLE_ENABLED: u32 = 0x2u;
 
+// Occlusion culling against the hi-z buffer
+const PCU_FLAGS_OCCLUSION_CULLING_MASK: u32 = 0x4u;
+const PCU_FLAGS_OCCLUSION_CULLING_DISABLED: u32 = 0x0u;
+const PCU_FLAGS_OCCLUSION_CULLING_ENABLED: u32 = 0x4u;
+
 struct DirectionalLight {
     /// View/Projection of directional light. Shadow rendering uses viewports
     /// so this always outputs [-1, 1] no matter where in the atlast the shadow is.
@@ -85,6 +93,13 @@ struct DirectionalLight {
     offset: vec2<f32>,
     /// [0, 1] size of the shadow map in the atlas.
     size: vec2<f32>,
+    /// 1 if the light has a shadow map, 0 if not.
+    casts_shadows: u32,
+    /// Width of the penumbra in [0, 1] atlas units per world unit between
+    /// the caster and receiver. 0 uses a fixed size filter.
+    penumbra_scale: f32,
+    /// World units towards the light per unit of shadow map depth.
+    depth_to_distance: f32,
 }
 
 struct DirectionalLightData {
@@ -95,9 +110,9 @@ struct DirectionalLightData {
 struct PointLight {
     /// The position of the light in world space.
     position: vec4<f32>,
-    // Color/intensity of the light.
+    // Color of the light, premultiplied by its intensity in candela.
     color: vec3<f32>,
-    /// The radius of the light.
+    /// Distance at which the light's contribution reaches zero.
     radius: f32,
 }
 
@@ -106,6 +121,45 @@ struct PointLightData {
     data: array<PointLight>,
 }
 
+struct LightClusterUniforms {
+    view: mat4x4<f32>,
+    inv_proj: mat4x4<f32>,
+    /// Top left corner of the viewport the clusters cover, in framebuffer pixels.
+    origin: vec2<f32>,
+    /// Resolution of the viewport the clusters cover.
+    resolution: vec2<f32>,
+    /// View space depth of the near plane.
+    near: f32,
+    /// View space depth of the far edge of the second to last slice. The last slice extends to infinity.
+    far: f32,
+    /// Amount of clusters in each dimension.
+    dimensions: vec3<u32>,
+    /// 1 if clustering is enabled, 0 if all lights should be iterated.
+    enabled: u32,
+}
+
+struct ClusterLightList {
+    count: u32,
+    /// Indices into the point light array. Size must match MAX_LIGHTS_PER_CLUSTER.
+    indices: array<u32, 63>,
+}
+
+fn cluster_slice_depth(clusters: LightClusterUniforms, slice: u32) -> f32 {
+    if (slice >= clusters.dimensions.z) {
+        return 1.0e20;
+    }
+    return clusters.near * pow(clusters.far / clusters.near, f32(slice) / f32(clusters.dimensions.z));
+}
+
+fn cluster_index(clusters: LightClusterUniforms, frag_coord: vec2<f32>, view_depth: f32) -> u32 {
+    let tile = vec2<u32>(clamp((frag_coord - clusters.origin) / clusters.resolution, vec2<f32>(0.0), vec2<f32>(1.0)) * vec2<f32>(clusters.dimensions.xy));
+    let slice_f = log(max(view_depth, clusters.near) / clusters.near) / log(clusters.far / clusters.near);
+    let slice = u32(slice_f * f32(clusters.dimensions.z));
+
+    let clamped = min(vec3<u32>(tile, slice), clusters.dimensions - 1u);
+    return clamped.x + clamped.y * clusters.dimensions.x + clamped.z * clusters.dimensions.x * clusters.dimensions.y;
+}
+
 struct PixelData {
     albedo: vec4<f32>,
     diffuse_color: vec3<f32>,
@@ -122,4 +176,11 @@ struct PixelData {
     anisotropy: f32,
     ambient_occlusion: f32,
     material_flags: u32,
+    lightmap: vec3<f32>,
+    // Direction of anisotropy in view space, perpendicular to the normal.
+    anisotropy_tangent: vec3<f32>,
+    transmission: f32,
+    sheen_color: vec3<f32>,
+    sheen_perceptual_roughness: f32,
+    sheen_roughness: f32,
 }
@@ -2,6 +2,7 @@
 
 struct Object {
     transform: mat4x4<f32>,
+    previous_transform: mat4x4<f32>,
     bounding_sphere: Sphere,
     first_index: u32,
     index_count: u32,
@@ -9,6 +10,8 @@ struct Object {
     vertex_attribute_start_offsets: array<u32, {{vertex_array_counts}}>,
     // 1 if enabled, 0 if disabled
     enabled: u32,
+    // Multiplies the shaded color, one if there is no tint.
+    debug_tint: vec4<f32>,
 }
 
 struct ObjectCullingInformation {
@@ -20,8 +23,15 @@ struct ObjectCullingInformation {
     local_region_id: u32,
     previous_global_invocation: u32,
     atomic_capable: u32,
+    // Uses CULLING_* constants
+    culling: u32,
 }
 
+// Faces culled by the material, relative to what the camera culls.
+const CULLING_BACK: u32 = 0u;
+const CULLING_FRONT: u32 = 1u;
+const CULLING_NONE: u32 = 2u;
+
 struct BatchData {
     total_objects: u32,
     total_invocations: u32,
@@ -0,0 +1,147 @@
+{{include "rend3-routine/structures.wgsl"}}
+{{include "rend3-routine/structures_object.wgsl"}}
+{{include "rend3-routine/material.wgsl"}}
+
+@group(0) @binding(0)
+var primary_sampler: sampler;
+@group(0) @binding(3)
+var<uniform> uniforms: UniformData;
+
+@group(1) @binding(0)
+var<storage> object_buffer: array<Object>;
+@group(1) @binding(1)
+var<storage> batch_data: BatchData;
+@group(1) @binding(2)
+var<storage> vertex_buffer: array<u32>;
+@group(1) @binding(3)
+var<storage> per_camera_uniform: PerCameraUniform;
+
+{{#if (eq profile "GpuDriven")}}
+@group(1) @binding(4)
+var<storage> materials: array<GpuMaterialData>;
+@group(2) @binding(0)
+var textures: binding_array<texture_2d<f32>>;
+{{/if}}
+
+{{#if (eq profile "CpuDriven")}}
+@group(1) @binding(4)
+var<storage> materials: array<CpuMaterialData>;
+@group(2) @binding(0)
+var albedo_tex: texture_2d<f32>;
+{{/if}}
+
+{{
+    vertex_fetch
+    
+    object_buffer
+    batch_data
+
+    position
+    texture_coords_0
+    texture_coords_1
+    color_0
+}}
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) coords0: vec2<f32>,
+    @location(1) alpha: f32,
+    @location(2) @interpolate(flat) material: u32,
+    @location(3) current_clip: vec4<f32>,
+    @location(4) previous_clip: vec4<f32>,
+    @location(5) coords1: vec2<f32>,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    // If the vertex index is our sentinel invalid value, return a degenerate triangle.
+    //
+    // This is used by the culling shader to discard triangles when the ordering of the
+    // triangles are important, and atomics can't be used.
+    if vertex_index == INVALID_VERTEX {
+        var vs_out: VertexOutput;
+        vs_out.position = vec4<f32>(0.0);
+        return vs_out;
+    }
+    let indices = unpack_vertex_index(vertex_index);
+    
+    let data = object_buffer[indices.object];
+    // If the object is disabled, return a degenerate triangle.
+    //
+    // This happens when the object is deleted, and we're rendering last-frame's objects.
+    if data.enabled == 0u {
+        var vs_out: VertexOutput;
+        vs_out.position = vec4<f32>(0.0);
+        return vs_out;
+    }
+
+    let vs_in = get_vertices(indices);
+
+    let model_view_proj = per_camera_uniform.objects[indices.object].model_view_proj;
+    let previous_model_view_proj = uniforms.previous_view_proj * data.previous_transform;
+
+    let position_vec4 = vec4<f32>(vs_in.position, 1.0);
+
+    var vs_out: VertexOutput;
+    vs_out.material = data.material_index;
+    vs_out.coords0 = vs_in.texture_coords_0;
+    vs_out.coords1 = vs_in.texture_coords_1;
+    vs_out.alpha = vs_in.color_0.a;
+    vs_out.position = model_view_proj * position_vec4;
+    vs_out.current_clip = vs_out.position;
+    vs_out.previous_clip = previous_model_view_proj * position_vec4;
+
+    return vs_out;
+}
+
+{{#if (eq profile "GpuDriven")}}
+alias Material = GpuMaterialData;
+
+fn has_albedo_texture(material: ptr<function, Material>) -> bool { return (*material).albedo_tex != 0u; }
+
+fn albedo_texture(material: ptr<function, Material>, samp: sampler, coords: vec2<f32>, ddx: vec2<f32>, ddy: vec2<f32>) -> vec4<f32> { return textureSampleGrad(textures[(*material).albedo_tex - 1u], samp, coords, ddx, ddy); }
+{{else}}
+alias Material = CpuMaterialData;
+
+fn has_albedo_texture(material: ptr<function, Material>) -> bool { return bool(((*material).texture_enable >> 0u) & 0x1u); }
+
+fn albedo_texture(material: ptr<function, Material>, samp: sampler, coords: vec2<f32>, ddx: vec2<f32>, ddy: vec2<f32>) -> vec4<f32> { return textureSampleGrad(albedo_tex, samp, coords, ddx, ddy); }
+{{/if}}
+
+// Writes the screen space motion of the surface since last frame, in uv units.
+//
+// Sampling the previous frame at `uv - velocity` finds where this pixel was.
+@fragment
+fn fs_main(vs_out: VertexOutput) -> @location(0) vec2<f32> {
+    {{#if discard}}
+    var material = materials[vs_out.material];
+
+    var coords = (material.uv_transform0 * vec3<f32>(vs_out.coords0, 1.0)).xy;
+    if (material_uses_uv1(material.uv_sets, UV_SET_ALBEDO)) {
+        coords = (material.uv_transform1 * vec3<f32>(vs_out.coords1, 1.0)).xy;
+    }
+    let uvdx = dpdx(coords);
+    let uvdy = dpdy(coords);
+
+    var alpha = 1.0;
+    if (extract_material_flag(material.flags, FLAGS_ALBEDO_ACTIVE)) {
+        if (has_albedo_texture(&material)) {
+            alpha = albedo_texture(&material, primary_sampler, coords, uvdx, uvdy).a;
+        }
+        if (extract_material_flag(material.flags, FLAGS_ALBEDO_BLEND)) {
+            alpha *= vs_out.alpha;
+        }
+    }
+    alpha *= material.albedo.a;
+
+    if (alpha < material.alpha_cutout + uniforms.cutout_bias) {
+        discard;
+    }
+    {{/if}}
+
+    let current = vs_out.current_clip.xy / vs_out.current_clip.w;
+    let previous = vs_out.previous_clip.xy / vs_out.previous_clip.w;
+
+    // Clip space y points up, uv space y points down.
+    return (current - previous) * vec2<f32>(0.5, -0.5);
+}
@@ -0,0 +1,173 @@
+//! Backgrounds behind the scene, where no opaque object was drawn.
+
+use std::borrow::Cow;
+
+use encase::{ShaderSize, ShaderType, UniformBuffer};
+use glam::Vec4;
+use rend3::{
+    graph::{NodeResourceUsage, RenderGraph, RenderPassTargets},
+    types::SampleCount,
+    util::bind_merge::{BindGroupBuilder, BindGroupLayoutBuilder},
+    Renderer, ShaderConfig, ShaderPreProcessor,
+};
+use wgpu::{
+    BindGroup, Buffer, BufferBindingType, BufferDescriptor, BufferUsages, ColorTargetState, ColorWrites,
+    DepthBiasState, DepthStencilState, FragmentState, FrontFace, MultisampleState, PipelineLayoutDescriptor,
+    PolygonMode, PrimitiveState, PrimitiveTopology, RenderPipeline, RenderPipelineDescriptor, ShaderModuleDescriptor,
+    ShaderSource, ShaderStages, StencilState, VertexState,
+};
+
+use crate::common::WholeFrameInterfaces;
+
+/// What fills the parts of the screen not covered by opaque objects.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Background {
+    /// A single scene-referred color.
+    SolidColor(Vec4),
+    /// Blend from `top` at the top of the screen to `bottom` at the bottom.
+    VerticalGradient { top: Vec4, bottom: Vec4 },
+    /// The texture of the [`SkyboxRoutine`](crate::skybox::SkyboxRoutine),
+    /// if one is given to the rendergraph. Opaque black otherwise.
+    Skybox,
+    /// Fully transparent black, for compositing the output over other
+    /// content.
+    #[default]
+    Transparent,
+}
+
+impl Background {
+    /// Color the scene is cleared with before anything is drawn.
+    pub fn clear_color(&self) -> Vec4 {
+        match *self {
+            Self::SolidColor(color) => color,
+            Self::Skybox => Vec4::W,
+            Self::VerticalGradient { .. } | Self::Transparent => Vec4::ZERO,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, ShaderType)]
+struct GradientUniforms {
+    top: Vec4,
+    bottom: Vec4,
+}
+
+/// Draws [`Background::VerticalGradient`].
+pub struct GradientRoutine {
+    pipeline_s1: RenderPipeline,
+    pipeline_s4: RenderPipeline,
+    uniform_buffer: Buffer,
+    bg: BindGroup,
+}
+
+impl GradientRoutine {
+    /// Create the routine.
+    pub fn new(renderer: &Renderer, spp: &ShaderPreProcessor, interfaces: &WholeFrameInterfaces) -> Self {
+        profiling::scope!("GradientRoutine::new");
+
+        let bgl = BindGroupLayoutBuilder::new()
+            .append_buffer(
+                ShaderStages::FRAGMENT,
+                BufferBindingType::Uniform,
+                false,
+                GradientUniforms::SHADER_SIZE.get(),
+            )
+            .build(&renderer.device, Some("gradient bgl"));
+
+        let uniform_buffer = renderer.device.create_buffer(&BufferDescriptor {
+            label: Some("gradient uniforms"),
+            size: GradientUniforms::SHADER_SIZE.get(),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let bg = BindGroupBuilder::new().append_buffer(&uniform_buffer).build(&renderer.device, Some("gradient"), &bgl);
+
+        let module = renderer.device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("gradient"),
+            source: ShaderSource::Wgsl(Cow::Owned(
+                spp.render_shader(
+                    "rend3-routine/gradient.wgsl",
+                    &ShaderConfig { forward_z: !renderer.depth_convention.is_reverse(), ..Default::default() },
+                    None,
+                )
+                .unwrap(),
+            )),
+        });
+
+        let pll = renderer.device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("gradient pass"),
+            bind_group_layouts: &[&bgl],
+            push_constant_ranges: &[],
+        });
+
+        let inner = |samples: SampleCount| {
+            renderer.device.create_render_pipeline(&RenderPipelineDescriptor {
+                label: Some("gradient pass"),
+                layout: Some(&pll),
+                vertex: VertexState { module: &module, entry_point: "vs_main", buffers: &[] },
+                primitive: PrimitiveState {
+                    topology: PrimitiveTopology::TriangleList,
+                    strip_index_format: None,
+                    front_face: FrontFace::Cw,
+                    cull_mode: None,
+                    unclipped_depth: false,
+                    polygon_mode: PolygonMode::Fill,
+                    conservative: false,
+                },
+                depth_stencil: interfaces.depth_format.map(|format| DepthStencilState {
+                    format,
+                    depth_write_enabled: false,
+                    depth_compare: renderer.depth_convention.closer_equal(),
+                    stencil: StencilState::default(),
+                    bias: DepthBiasState::default(),
+                }),
+                multisample: MultisampleState { count: samples as u32, ..Default::default() },
+                fragment: Some(FragmentState {
+                    module: &module,
+                    entry_point: "fs_main",
+                    targets: &[Some(ColorTargetState {
+                        format: interfaces.hdr_format,
+                        blend: None,
+                        write_mask: ColorWrites::all(),
+                    })],
+                }),
+                multiview: None,
+            })
+        };
+
+        Self { pipeline_s1: inner(SampleCount::One), pipeline_s4: inner(SampleCount::Four), uniform_buffer, bg }
+    }
+
+    /// Add drawing the gradient behind the opaque objects of `renderpass` to
+    /// the rendergraph.
+    pub fn add_to_graph<'node>(
+        &'node self,
+        graph: &mut RenderGraph<'node>,
+        renderpass: RenderPassTargets,
+        samples: SampleCount,
+        top: Vec4,
+        bottom: Vec4,
+    ) {
+        let mut builder = graph.add_node("Background Gradient");
+
+        let rpass_handle = builder.add_renderpass(renderpass, NodeResourceUsage::InputOutput);
+
+        builder.build(move |mut ctx| {
+            let rpass = ctx.encoder_or_pass.take_rpass(rpass_handle);
+
+            let mut data = UniformBuffer::new(Vec::new());
+            data.write(&GradientUniforms { top, bottom }).unwrap();
+            ctx.renderer.queue.write_buffer(&self.uniform_buffer, 0, &data.into_inner());
+
+            let pipeline = match samples {
+                SampleCount::One => &self.pipeline_s1,
+                SampleCount::Four => &self.pipeline_s4,
+            };
+
+            rpass.set_pipeline(pipeline);
+            rpass.set_bind_group(0, &self.bg, &[]);
+            rpass.draw(0..3, 0..1);
+        });
+    }
+}
@@ -8,6 +8,9 @@
 //! This will allow you to insert your own routines and customize the behavior
 //! of the existing routines.
 //!
+//! Passes working on the HDR image, like post effects, can be added without
+//! copying anything through [`BaseRenderGraph::add_to_graph_with_hdr_passes`].
+//!
 //! [`BaseRenderGraphIntermediateState`] intentionally has all of its members
 //! public. If you want to change what rendergraph image things are rendering
 //! to, or muck with any of the data in there, you are free to, and the
@@ -22,13 +25,16 @@ use rend3::{
         self, DataHandle, InstructionEvaluationOutput, RenderGraph, RenderPassTargets, RenderTargetDescriptor,
         RenderTargetHandle, ViewportRect,
     },
-    types::{SampleCount, TextureFormat, TextureUsages},
+    managers::CameraState,
+    types::{Camera, DepthConvention, Handedness, SampleCount, TextureFormat, TextureUsages},
     Renderer, ShaderPreProcessor, INTERNAL_SHADOW_DEPTH_FORMAT,
 };
-use wgpu::{BindGroup, Buffer};
+use thiserror::Error;
+use wgpu::{BindGroup, Buffer, Features};
 
 use crate::{
-    clear,
+    background::{Background, GradientRoutine},
+    clear, clustering,
     common::{self, CameraSpecifier},
     culling,
     forward::{self, ForwardRoutineArgs},
@@ -42,14 +48,14 @@ pub struct DepthTargets {
 }
 
 impl DepthTargets {
-    pub fn new(graph: &mut RenderGraph<'_>, resolution: UVec2, samples: SampleCount) -> Self {
+    pub fn new(graph: &mut RenderGraph<'_>, resolution: UVec2, samples: SampleCount, format: TextureFormat) -> Self {
         let single_sample_mipped = graph.add_render_target(RenderTargetDescriptor {
             label: Some("hdr depth".into()),
             resolution,
             depth: 1,
             mip_levels: None,
             samples: SampleCount::One,
-            format: TextureFormat::Depth32Float,
+            format,
             usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
         });
 
@@ -60,7 +66,7 @@ impl DepthTargets {
                 depth: 1,
                 mip_levels: Some(1),
                 samples,
-                format: TextureFormat::Depth32Float,
+                format,
                 usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
             })
         });
@@ -68,59 +74,321 @@ impl DepthTargets {
         Self { single_sample_mipped, multi_sample }
     }
 
+    /// Use an imported depth target as the one rendered into. See
+    /// [`BaseRenderGraphInputs::depth`] for its requirements.
+    pub fn new_imported(
+        graph: &mut RenderGraph<'_>,
+        resolution: UVec2,
+        samples: SampleCount,
+        format: TextureFormat,
+        imported: RenderTargetHandle,
+    ) -> Self {
+        if !samples.needs_resolve() {
+            return Self { single_sample_mipped: imported, multi_sample: None };
+        }
+
+        let single_sample_mipped = graph.add_render_target(RenderTargetDescriptor {
+            label: Some("hdr depth".into()),
+            resolution,
+            depth: 1,
+            mip_levels: None,
+            samples: SampleCount::One,
+            format,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+        });
+
+        Self { single_sample_mipped, multi_sample: Some(imported) }
+    }
+
     pub fn rendering_target(&self) -> RenderTargetHandle {
         self.multi_sample.unwrap_or(self.single_sample_mipped.set_mips(0..1))
     }
 }
 
+/// Scene targets of the base rendergraph, for routines which read or write
+/// the HDR image before it is tonemapped.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct BaseRenderGraphTargets {
+    /// Target the scene is rendered into. Multisampled if the output is.
+    pub hdr_color: RenderTargetHandle,
+    /// Single sampled HDR image, which is what tonemapping reads. The same
+    /// as `hdr_color` if the output isn't multisampled.
+    pub resolved_color: RenderTargetHandle,
+    /// `None` if the graph renders without a depth buffer.
+    pub hdr_depth: Option<DepthTargets>,
+}
+
+/// Target the base rendergraph tonemaps the scene into.
+///
+/// The scene is rendered at `resolution` and drawn into the viewport of
+/// `handle`, which is also the scissor rect. To render several views into one
+/// surface, such as for split-screen, execute one graph per view, each with a
+/// [`BaseRenderGraphInputs::camera`] and a target made by
+/// [`OutputRenderTarget::with_viewport`]. Import the surface with
+/// [`RenderGraph::add_preserved_imported_render_target`] so views don't clear
+/// each other.
 pub struct OutputRenderTarget {
     pub handle: RenderTargetHandle,
     pub resolution: UVec2,
     pub samples: SampleCount,
 }
 
+impl OutputRenderTarget {
+    /// Target covering `viewport` of `handle`, with a matching resolution.
+    pub fn with_viewport(handle: RenderTargetHandle, viewport: ViewportRect, samples: SampleCount) -> Self {
+        Self { handle: handle.set_viewport(viewport), resolution: viewport.size, samples }
+    }
+}
+
 pub struct BaseRenderGraphRoutines<'node> {
     pub pbr: &'node crate::pbr::PbrRoutine,
     pub skybox: Option<&'node crate::skybox::SkyboxRoutine>,
     pub tonemapping: &'node crate::tonemapping::TonemappingRoutine,
+    /// Grades the tonemapped image. Only runs if the routine has a lookup
+    /// table set.
+    pub color_grade: Option<&'node crate::color_grade::ColorGradeRoutine>,
+    /// Draws its queued lines and points after the forward passes.
+    pub debug_draw: Option<&'node crate::debug_draw::DebugDrawRoutine>,
 }
 
 pub struct BaseRenderGraphInputs<'a, 'node> {
     pub eval_output: &'a InstructionEvaluationOutput,
     pub routines: BaseRenderGraphRoutines<'node>,
     pub target: OutputRenderTarget,
+    /// External depth buffer to depth test against, made with
+    /// [`RenderGraph::add_imported_depth_target`]. Its contents are loaded
+    /// instead of cleared, and the scene's depth is written into it.
+    ///
+    /// It must have the depth format of the [`BaseRenderGraph`], the render
+    /// resolution, the sample count of the output, and `RENDER_ATTACHMENT |
+    /// TEXTURE_BINDING` usage. Depth must follow the
+    /// [`DepthConvention`] of the renderer. When single sampled with
+    /// [`BaseRenderGraphSettings::occlusion_culling`] enabled, it must have a
+    /// full mip chain, which is overwritten with the depth pyramid.
+    ///
+    /// Ignored by a graph made with [`BaseRenderGraph::new_without_depth`].
+    pub depth: Option<RenderTargetHandle>,
+    /// Camera to render from instead of the renderer's, with the aspect ratio
+    /// of the output resolution. A view matrix with a negative determinant
+    /// mirrors the scene, such as for planar reflections, and face culling is
+    /// flipped to match.
+    ///
+    /// Shadows still follow the renderer's camera, and the previous frame's
+    /// view is taken to be the same, so there is no camera motion in the
+    /// velocity buffer. Per-camera state of the culler is uploaded through the
+    /// queue, so views with different cameras must each be rendered in their
+    /// own graph execution.
+    pub camera: Option<InputCamera>,
 }
 
-#[derive(Debug, Default)]
+/// Camera a [`BaseRenderGraph`] renders from instead of the renderer's.
+#[derive(Debug, Copy, Clone)]
+pub struct InputCamera {
+    pub camera: Camera,
+    /// Identifies the camera to the culler, which keeps state between frames
+    /// for each camera, like the objects visible last frame. Every camera
+    /// rendered in a frame needs its own id, which it should keep across
+    /// frames.
+    pub id: u32,
+}
+
+#[derive(Debug, Clone)]
 pub struct BaseRenderGraphSettings {
     pub ambient_color: Vec4,
-    pub clear_color: Vec4,
+    /// What is visible behind the opaque objects.
+    pub background: Background,
+    /// Bin point lights into view space clusters so each pixel only shades the
+    /// lights which can reach it. Worthwhile once there are more than a handful
+    /// of point lights.
+    pub clustered_lighting: bool,
+    /// Render per-pixel motion vectors of opaque and cutout objects into an
+    /// `Rg16Float` target, available through
+    /// [`BaseRenderGraphIntermediateState::velocity_renderpass`].
+    pub velocity: bool,
+    /// Build a hierarchical depth pyramid from the depth of the triangles
+    /// predicted visible from last frame, and cull triangles hidden behind it
+    /// before the second forward pass. Worthwhile in dense, heavily occluded
+    /// scenes.
+    pub occlusion_culling: bool,
+    /// Multiplier of the output resolution the scene is rendered at, before
+    /// being resampled by tonemapping. Below 1.0 trades sharpness for speed,
+    /// above 1.0 supersamples. Defaults to 1.0.
+    pub render_scale: f32,
+    /// Render shadow maps. When disabled, the shadow maps stay clear and
+    /// nothing is shadowed. Defaults to true.
+    pub shadows: bool,
+    /// Render opaque and cutout objects, along with their motion vectors.
+    /// Defaults to true.
+    pub opaque: bool,
+    /// Render blended objects. Defaults to true.
+    pub transparent: bool,
+}
+
+impl Default for BaseRenderGraphSettings {
+    fn default() -> Self {
+        Self {
+            ambient_color: Vec4::ZERO,
+            background: Background::Transparent,
+            clustered_lighting: false,
+            velocity: false,
+            occlusion_culling: false,
+            render_scale: 1.0,
+            shadows: true,
+            opaque: true,
+            transparent: true,
+        }
+    }
 }
 
+/// Formats the base rendergraph can render HDR color in.
+pub const HDR_FORMATS: [TextureFormat; 2] = [TextureFormat::Rgba16Float, TextureFormat::Rg11b10Float];
+
+#[derive(Debug, Error)]
+pub enum FormatError {
+    #[error("{0:?} has no depth aspect")]
+    NotDepth(TextureFormat),
+    #[error("{0:?} is not one of the HDR formats {HDR_FORMATS:?}")]
+    NotHdr(TextureFormat),
+    #[error("{format:?} requires device features {missing:?}")]
+    MissingFeatures { format: TextureFormat, missing: Features },
+}
+
+pub type DepthFormatError = FormatError;
+
 /// Starter RenderGraph.
 ///
 /// See module for documentation.
 pub struct BaseRenderGraph {
     pub interfaces: common::WholeFrameInterfaces,
+    /// Created without anisotropic filtering. Use
+    /// [`Samplers::set_anisotropy`](common::Samplers::set_anisotropy) to
+    /// enable it.
     pub samplers: common::Samplers,
     pub gpu_culler: culling::GpuCuller,
     pub gpu_skinner: skinning::GpuSkinner,
+    pub light_clustering: clustering::LightClusteringRoutine,
+    /// Draws [`Background::VerticalGradient`].
+    pub gradient: GradientRoutine,
+    /// Depth convention of the renderer this graph was created with.
+    pub depth_convention: DepthConvention,
+    /// Handedness of the renderer this graph was created with.
+    pub handedness: Handedness,
 }
 
 impl BaseRenderGraph {
+    /// Create the base rendergraph with a `Depth32Float` depth buffer.
     pub fn new(renderer: &Arc<Renderer>, spp: &ShaderPreProcessor) -> Self {
+        Self::new_with_depth_format(renderer, spp, TextureFormat::Depth32Float)
+            .expect("Depth32Float is always supported")
+    }
+
+    /// Create the base rendergraph with the given depth buffer format, for
+    /// example a depth-stencil format to use the stencil in custom routines.
+    ///
+    /// Routines drawing into the depth buffer must be created with the
+    /// resulting [`interfaces`](Self::interfaces), which carry the format.
+    pub fn new_with_depth_format(
+        renderer: &Arc<Renderer>,
+        spp: &ShaderPreProcessor,
+        depth_format: TextureFormat,
+    ) -> Result<Self, DepthFormatError> {
+        Self::new_with_formats(renderer, spp, depth_format, TextureFormat::Rgba16Float)
+    }
+
+    /// Create the base rendergraph with the given depth buffer and HDR color
+    /// formats. The HDR format must be one of [`HDR_FORMATS`].
+    /// `Rg11b10Float` halves the bandwidth of `Rgba16Float` at the cost of
+    /// precision and alpha, so [`Background::Transparent`] renders opaque. It
+    /// requires [`Features::RG11B10UFLOAT_RENDERABLE`].
+    ///
+    /// Routines drawing into the HDR target must be created with the
+    /// resulting [`interfaces`](Self::interfaces), which carry the format.
+    pub fn new_with_formats(
+        renderer: &Arc<Renderer>,
+        spp: &ShaderPreProcessor,
+        depth_format: TextureFormat,
+        hdr_format: TextureFormat,
+    ) -> Result<Self, FormatError> {
+        if !depth_format.has_depth_aspect() {
+            return Err(FormatError::NotDepth(depth_format));
+        }
+        let missing = depth_format.required_features() - renderer.features;
+        if !missing.is_empty() {
+            return Err(FormatError::MissingFeatures { format: depth_format, missing });
+        }
+
+        Self::new_inner(renderer, spp, Some(depth_format), hdr_format)
+    }
+
+    /// Create the base rendergraph without a depth buffer, for 2D scenes and
+    /// overlays which are drawn in order. The HDR format must be one of
+    /// [`HDR_FORMATS`].
+    ///
+    /// Nothing is depth tested or written, so later draws cover earlier ones.
+    /// The background is drawn first, then opaque objects and then blended
+    /// objects sorted back to front. Opaque objects are drawn grouped by
+    /// material key (opaque, cutout and their unlit variants), so an object
+    /// can only be placed over another one with the same key, by giving it a
+    /// higher [`Object::sort_bias`](rend3::types::Object::sort_bias).
+    ///
+    /// Velocity and occlusion culling need depth, so
+    /// [`BaseRenderGraphSettings::velocity`] and
+    /// [`BaseRenderGraphSettings::occlusion_culling`] are ignored.
+    ///
+    /// Routines drawing into the HDR target must be created with the
+    /// resulting [`interfaces`](Self::interfaces), which have no depth format.
+    pub fn new_without_depth(
+        renderer: &Arc<Renderer>,
+        spp: &ShaderPreProcessor,
+        hdr_format: TextureFormat,
+    ) -> Result<Self, FormatError> {
+        Self::new_inner(renderer, spp, None, hdr_format)
+    }
+
+    fn new_inner(
+        renderer: &Arc<Renderer>,
+        spp: &ShaderPreProcessor,
+        depth_format: Option<TextureFormat>,
+        hdr_format: TextureFormat,
+    ) -> Result<Self, FormatError> {
         profiling::scope!("DefaultRenderGraphData::new");
 
-        let interfaces = common::WholeFrameInterfaces::new(&renderer.device);
+        if !HDR_FORMATS.contains(&hdr_format) {
+            return Err(FormatError::NotHdr(hdr_format));
+        }
+        let required = match hdr_format {
+            TextureFormat::Rg11b10Float => Features::RG11B10UFLOAT_RENDERABLE,
+            _ => Features::empty(),
+        };
+        let missing = required - renderer.features;
+        if !missing.is_empty() {
+            return Err(FormatError::MissingFeatures { format: hdr_format, missing });
+        }
 
-        let samplers = common::Samplers::new(&renderer.device);
+        let interfaces = common::WholeFrameInterfaces::new(&renderer.device, depth_format, hdr_format);
+
+        let samplers = common::Samplers::new(renderer, 1);
 
         // TODO: Support more materials
         let gpu_culler = culling::GpuCuller::new::<pbr::PbrMaterial>(renderer, spp);
 
         let gpu_skinner = skinning::GpuSkinner::new(&renderer.device, spp);
 
-        Self { interfaces, samplers, gpu_culler, gpu_skinner }
+        let light_clustering =
+            clustering::LightClusteringRoutine::new(&renderer.device, spp, renderer.depth_convention);
+
+        let gradient = GradientRoutine::new(renderer, spp, &interfaces);
+
+        Ok(Self {
+            interfaces,
+            samplers,
+            gpu_culler,
+            gpu_skinner,
+            light_clustering,
+            gradient,
+            depth_convention: renderer.depth_convention,
+            handedness: renderer.handedness,
+        })
     }
 
     /// Add this to the rendergraph. This is the function you should start
@@ -131,9 +399,30 @@ impl BaseRenderGraph {
         graph: &mut RenderGraph<'node>,
         inputs: BaseRenderGraphInputs<'_, 'node>,
         settings: BaseRenderGraphSettings,
-    ) {
+    ) -> BaseRenderGraphTargets {
+        self.add_to_graph_with_hdr_passes(graph, inputs, settings, |_, _| {})
+    }
+
+    /// Add this to the rendergraph, calling `hdr_passes` to add custom nodes
+    /// after the scene is rendered and before it is tonemapped. Nodes added
+    /// there can read and write the HDR image through the given targets.
+    pub fn add_to_graph_with_hdr_passes<'node>(
+        &'node self,
+        graph: &mut RenderGraph<'node>,
+        inputs: BaseRenderGraphInputs<'_, 'node>,
+        settings: BaseRenderGraphSettings,
+        hdr_passes: impl FnOnce(&mut RenderGraph<'node>, &BaseRenderGraphTargets),
+    ) -> BaseRenderGraphTargets {
         // Create the data and handles for the graph.
-        let mut state = BaseRenderGraphIntermediateState::new(graph, inputs, settings);
+        let mut state = BaseRenderGraphIntermediateState::new(
+            graph,
+            inputs,
+            settings,
+            self.depth_convention,
+            self.handedness,
+            self.interfaces.depth_format,
+            self.interfaces.hdr_format,
+        );
 
         // Clear the shadow buffers. This, as an explicit node, must be done as a limitation of the graph dependency system.
         state.clear_shadow_buffers();
@@ -155,10 +444,22 @@ impl BaseRenderGraph {
         // Upload the uniforms for the objects in the forward pass.
         state.object_uniform_upload(self);
 
+        // Bin the point lights into clusters for the forward pass.
+        state.light_clustering(self);
+
+        // Without a depth buffer, nothing keeps the background from covering the
+        // scene, so it goes first.
+        if state.depth.is_none() {
+            state.background(self);
+        }
+
         // Do the first pass, rendering the predicted triangles from last frame.
         state.pbr_render_opaque_predicted_triangles();
 
-        // Create the hi-z buffer.
+        // Write the motion vectors of the predicted triangles, if enabled.
+        state.pbr_render_velocity_predicted_triangles();
+
+        // Create the hi-z buffer, if occlusion culling is enabled.
         state.hi_z();
 
         // Perform culling for the objects in the forward pass.
@@ -171,8 +472,16 @@ impl BaseRenderGraph {
         // Do the second pass, rendering the residual triangles.
         state.pbr_render_opaque_residual_triangles();
 
-        // Render the skybox.
-        state.skybox();
+        // Write the motion vectors of the residual triangles, if enabled.
+        state.pbr_render_velocity_residual_triangles();
+
+        // Render the skybox or gradient behind the opaque objects.
+        if state.depth.is_some() {
+            state.background(self);
+        }
+
+        // Copy the opaque scene for transmissive materials to see through.
+        state.transmission_background();
 
         // Render all transparent objects.
         //
@@ -180,8 +489,20 @@ impl BaseRenderGraph {
         // considered "residual".
         state.pbr_forward_rendering_transparent();
 
+        // Draw the queued debug lines and points over the scene, if given.
+        state.debug_draw();
+
+        // Run the user's passes on the finished HDR image.
+        let targets = state.targets();
+        hdr_passes(&mut *state.graph, &targets);
+
         // Tonemap the HDR inner buffer to the output buffer.
         state.tonemapping();
+
+        // Apply color grading to the tonemapped image, if enabled.
+        state.color_grade();
+
+        targets
     }
 }
 
@@ -193,6 +514,17 @@ pub struct BaseRenderGraphIntermediateState<'a, 'node> {
     pub graph: &'a mut RenderGraph<'node>,
     pub inputs: BaseRenderGraphInputs<'a, 'node>,
     pub settings: BaseRenderGraphSettings,
+    /// Decides the clear value of every depth target.
+    pub depth_convention: DepthConvention,
+    /// Resolution of the scene targets, the output resolution multiplied by
+    /// [`BaseRenderGraphSettings::render_scale`].
+    pub render_resolution: UVec2,
+    /// State of [`BaseRenderGraphInputs::camera`], replacing the renderer's
+    /// camera if set.
+    pub camera: Option<CameraState>,
+    /// Camera the scene is culled and drawn for, [`CameraSpecifier::Custom`]
+    /// if [`BaseRenderGraphInputs::camera`] is set.
+    pub camera_specifier: CameraSpecifier,
 
     pub pre_cull: DataHandle<Buffer>,
     pub shadow_cull: Vec<DataHandle<Arc<culling::DrawCallSet>>>,
@@ -200,10 +532,21 @@ pub struct BaseRenderGraphIntermediateState<'a, 'node> {
 
     pub shadow_uniform_bg: DataHandle<BindGroup>,
     pub forward_uniform_bg: DataHandle<BindGroup>,
+    /// Bind group holding the copy of the opaque scene, used by transmissive
+    /// materials.
+    pub transmission_bg: DataHandle<BindGroup>,
 
     pub shadow: RenderTargetHandle,
-    pub depth: DepthTargets,
+    /// `None` if the graph renders without a depth buffer.
+    pub depth: Option<DepthTargets>,
     pub primary_renderpass: RenderPassTargets,
+    /// Renderpass writing motion vectors. Only present if
+    /// [`BaseRenderGraphSettings::velocity`] is set and there is a depth
+    /// buffer.
+    pub velocity_renderpass: Option<RenderPassTargets>,
+    /// Target tonemapping renders into. This is an intermediate LDR target if
+    /// post-tonemapping routines need to run, otherwise the output.
+    pub tonemapped: RenderTargetHandle,
 
     pub pre_skinning_buffers: DataHandle<skinning::PreSkinningBuffers>,
 }
@@ -213,13 +556,27 @@ impl<'a, 'node> BaseRenderGraphIntermediateState<'a, 'node> {
         graph: &'a mut RenderGraph<'node>,
         inputs: BaseRenderGraphInputs<'a, 'node>,
         settings: BaseRenderGraphSettings,
+        depth_convention: DepthConvention,
+        handedness: Handedness,
+        depth_format: Option<TextureFormat>,
+        hdr_format: TextureFormat,
     ) -> Self {
         // We need to know how many shadows we need to render
         let shadow_count = inputs.eval_output.shadows.len();
 
+        let render_resolution =
+            (inputs.target.resolution.as_vec2() * settings.render_scale).round().as_uvec2().max(UVec2::ONE);
+
+        let camera = inputs.camera.map(|input| {
+            let aspect_ratio = inputs.target.resolution.x as f32 / inputs.target.resolution.y as f32;
+            CameraState::new(input.camera, handedness, depth_convention, Some(aspect_ratio))
+        });
+        let camera_specifier = inputs.camera.map_or(CameraSpecifier::Viewport, |input| CameraSpecifier::Custom(input.id));
+
         // Create global bind group information
         let shadow_uniform_bg = graph.add_data::<BindGroup>();
         let forward_uniform_bg = graph.add_data::<BindGroup>();
+        let transmission_bg = graph.add_data::<BindGroup>();
 
         // Shadow render target
         let shadow = graph.add_render_target(RenderTargetDescriptor {
@@ -235,34 +592,87 @@ impl<'a, 'node> BaseRenderGraphIntermediateState<'a, 'node> {
         // Make the actual render targets we want to render to.
         let color = graph.add_render_target(RenderTargetDescriptor {
             label: Some("hdr color".into()),
-            resolution: inputs.target.resolution,
+            resolution: render_resolution,
             depth: 1,
             samples: inputs.target.samples,
             mip_levels: Some(1),
-            format: TextureFormat::Rgba16Float,
-            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+            format: hdr_format,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_SRC,
         });
         let resolve = inputs.target.samples.needs_resolve().then(|| {
             graph.add_render_target(RenderTargetDescriptor {
                 label: Some("hdr resolve".into()),
-                resolution: inputs.target.resolution,
+                resolution: render_resolution,
                 depth: 1,
                 mip_levels: Some(1),
                 samples: SampleCount::One,
-                format: TextureFormat::Rgba16Float,
-                usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+                format: hdr_format,
+                usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_SRC,
             })
         });
-        let depth = DepthTargets::new(graph, inputs.target.resolution, inputs.target.samples);
+        let depth = depth_format.map(|depth_format| match inputs.depth {
+            Some(imported) => {
+                DepthTargets::new_imported(graph, render_resolution, inputs.target.samples, depth_format, imported)
+            }
+            None => DepthTargets::new(graph, render_resolution, inputs.target.samples, depth_format),
+        });
         let primary_renderpass = graph::RenderPassTargets {
-            targets: vec![graph::RenderPassTarget { color, resolve, clear: settings.clear_color }],
-            depth_stencil: Some(graph::RenderPassDepthTarget {
+            targets: vec![graph::RenderPassTarget { color, resolve, clear: settings.background.clear_color() }],
+            depth_stencil: depth.map(|depth| graph::RenderPassDepthTarget {
                 target: depth.rendering_target(),
-                depth_clear: Some(0.0),
+                depth_clear: Some(depth_convention.clear_depth()),
                 stencil_clear: None,
             }),
         };
 
+        let velocity_renderpass = depth.filter(|_| settings.velocity).map(|depth| {
+            let velocity = graph.add_render_target(RenderTargetDescriptor {
+                label: Some("velocity".into()),
+                resolution: render_resolution,
+                depth: 1,
+                samples: inputs.target.samples,
+                mip_levels: Some(1),
+                format: TextureFormat::Rg16Float,
+                usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+            });
+            let velocity_resolve = inputs.target.samples.needs_resolve().then(|| {
+                graph.add_render_target(RenderTargetDescriptor {
+                    label: Some("velocity resolve".into()),
+                    resolution: render_resolution,
+                    depth: 1,
+                    mip_levels: Some(1),
+                    samples: SampleCount::One,
+                    format: TextureFormat::Rg16Float,
+                    usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+                })
+            });
+            graph::RenderPassTargets {
+                targets: vec![graph::RenderPassTarget {
+                    color: velocity,
+                    resolve: velocity_resolve,
+                    clear: Vec4::ZERO,
+                }],
+                depth_stencil: Some(graph::RenderPassDepthTarget {
+                    target: depth.rendering_target(),
+                    depth_clear: Some(depth_convention.clear_depth()),
+                    stencil_clear: None,
+                }),
+            }
+        });
+
+        let tonemapped = match inputs.routines.color_grade.filter(|color_grade| color_grade.is_active()) {
+            Some(color_grade) => graph.add_render_target(RenderTargetDescriptor {
+                label: Some("ldr color".into()),
+                resolution: inputs.target.resolution,
+                depth: 1,
+                mip_levels: Some(1),
+                samples: SampleCount::One,
+                format: color_grade.output_format(),
+                usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+            }),
+            None => inputs.target.handle,
+        };
+
         let pre_skinning_buffers = graph.add_data::<skinning::PreSkinningBuffers>();
 
         let pre_cull = graph.add_data();
@@ -273,6 +683,10 @@ impl<'a, 'node> BaseRenderGraphIntermediateState<'a, 'node> {
             graph,
             inputs,
             settings,
+            depth_convention,
+            render_resolution,
+            camera,
+            camera_specifier,
 
             pre_cull,
             shadow_cull,
@@ -280,18 +694,30 @@ impl<'a, 'node> BaseRenderGraphIntermediateState<'a, 'node> {
 
             shadow_uniform_bg,
             forward_uniform_bg,
+            transmission_bg,
 
             shadow,
             depth,
             primary_renderpass,
+            velocity_renderpass,
+            tonemapped,
 
             pre_skinning_buffers,
         }
     }
 
+    /// Named handles of the scene targets.
+    pub fn targets(&self) -> BaseRenderGraphTargets {
+        BaseRenderGraphTargets {
+            hdr_color: self.primary_renderpass.targets[0].color,
+            resolved_color: self.primary_renderpass.resolved_color(0),
+            hdr_depth: self.depth,
+        }
+    }
+
     /// Clear the shadow buffers. This, as an explicit node, must be done as a limitation of the graph dependency system.
     fn clear_shadow_buffers(&mut self) {
-        clear::add_depth_clear_to_graph(self.graph, self.shadow, 0.0);
+        clear::add_depth_clear_to_graph(self.graph, self.shadow, self.depth_convention.clear_depth());
     }
 
     /// Create all the uniforms all the shaders in this graph need.
@@ -306,32 +732,46 @@ impl<'a, 'node> BaseRenderGraphIntermediateState<'a, 'node> {
             },
             uniforms::UniformInformation {
                 samplers: &base.samplers,
+                light_clustering: &base.light_clustering,
                 ambient: self.settings.ambient_color,
-                resolution: self.inputs.target.resolution,
+                resolution: self.render_resolution,
+                cutout_bias: self.inputs.routines.pbr.cutout_bias(),
+                camera: self.camera.clone(),
             },
         );
     }
 
+    /// Does nothing unless [`BaseRenderGraphSettings::shadows`] is set.
     pub fn shadow_object_uniform_upload(&mut self, base: &'node BaseRenderGraph) {
+        if !self.settings.shadows {
+            return;
+        }
         for (shadow_index, shadow) in self.inputs.eval_output.shadows.iter().enumerate() {
             base.gpu_culler.add_object_uniform_upload_to_graph::<pbr::PbrMaterial>(
                 self.graph,
                 CameraSpecifier::Shadow(shadow_index as u32),
+                None,
                 UVec2::splat(shadow.map.size),
                 SampleCount::One,
+                false,
                 &format_sso!("Shadow Culling S{}", shadow_index),
             );
         }
     }
 
-    /// Does all shadow culling for the PBR materials.
+    /// Does all shadow culling for the PBR materials. Does nothing unless
+    /// [`BaseRenderGraphSettings::shadows`] is set.
     pub fn pbr_shadow_culling(&mut self, base: &'node BaseRenderGraph) {
+        if !self.settings.shadows {
+            return;
+        }
         for (shadow_index, &shadow_culled) in self.shadow_cull.iter().enumerate() {
             base.gpu_culler.add_culling_to_graph::<pbr::PbrMaterial>(
                 self.graph,
                 shadow_culled,
                 self.shadow,
                 CameraSpecifier::Shadow(shadow_index as u32),
+                None,
                 &format_sso!("Shadow Culling S{}", shadow_index),
             );
         }
@@ -344,26 +784,46 @@ impl<'a, 'node> BaseRenderGraphIntermediateState<'a, 'node> {
     pub fn object_uniform_upload(&mut self, base: &'node BaseRenderGraph) {
         base.gpu_culler.add_object_uniform_upload_to_graph::<pbr::PbrMaterial>(
             self.graph,
-            CameraSpecifier::Viewport,
-            self.inputs.target.resolution,
+            self.camera_specifier,
+            self.camera.clone(),
+            self.render_resolution,
             self.inputs.target.samples,
+            self.settings.occlusion_culling && self.depth.is_some(),
             "Uniform Bake",
         );
     }
 
+    /// Bins the point lights into clusters, if enabled.
+    pub fn light_clustering(&mut self, base: &'node BaseRenderGraph) {
+        base.light_clustering.add_to_graph(
+            self.graph,
+            self.camera.clone(),
+            self.primary_renderpass.targets[0].color.viewport(),
+            self.settings.clustered_lighting,
+        );
+    }
+
     /// Does all culling for the forward PBR materials.
     pub fn pbr_culling(&mut self, base: &'node BaseRenderGraph) {
+        // The culler always binds a hi-z texture. Without a depth buffer occlusion culling is off, so the shadow
+        // map stands in.
+        let hi_z = self.depth.map_or(self.shadow, |depth| depth.single_sample_mipped);
         base.gpu_culler.add_culling_to_graph::<pbr::PbrMaterial>(
             self.graph,
             self.cull,
-            self.depth.single_sample_mipped,
-            CameraSpecifier::Viewport,
+            hi_z,
+            self.camera_specifier,
+            self.camera.clone(),
             "Primary Culling",
         );
     }
 
-    /// Render all shadows for the PBR materials.
+    /// Render all shadows for the PBR materials. Does nothing unless
+    /// [`BaseRenderGraphSettings::shadows`] is set.
     pub fn pbr_shadow_rendering(&mut self) {
+        if !self.settings.shadows {
+            return;
+        }
         let iter = zip(&self.shadow_cull, &self.inputs.eval_output.shadows);
         for (shadow_index, (shadow_cull, desc)) in iter.enumerate() {
             let target = self.shadow.set_viewport(ViewportRect::new(desc.map.offset, UVec2::splat(desc.map.size)));
@@ -371,7 +831,7 @@ impl<'a, 'node> BaseRenderGraphIntermediateState<'a, 'node> {
                 targets: vec![],
                 depth_stencil: Some(graph::RenderPassDepthTarget {
                     target,
-                    depth_clear: Some(0.0),
+                    depth_clear: Some(self.depth_convention.clear_depth()),
                     stencil_clear: None,
                 }),
             };
@@ -386,86 +846,191 @@ impl<'a, 'node> BaseRenderGraphIntermediateState<'a, 'node> {
                         whole_frame_uniform_bg: self.shadow_uniform_bg,
                         per_material_bgl: &self.inputs.routines.pbr.per_material,
                         extra_bgs: None,
+                        extra_graph_bg: None,
                     },
                     culling_source: forward::CullingSource::Residual(*shadow_cull),
                     samples: SampleCount::One,
                     renderpass: renderpass.clone(),
+                    stencil_reference: 0,
                 });
             }
         }
     }
 
-    /// Render the skybox.
-    pub fn skybox(&mut self) {
-        if let Some(skybox) = self.inputs.routines.skybox {
-            skybox.add_to_graph(
+    /// Render the background, if it isn't a plain clear.
+    pub fn background(&mut self, base: &'node BaseRenderGraph) {
+        match self.settings.background {
+            Background::Skybox => {
+                if let Some(skybox) = self.inputs.routines.skybox {
+                    skybox.add_to_graph(
+                        self.graph,
+                        self.primary_renderpass.clone(),
+                        self.forward_uniform_bg,
+                        self.inputs.target.samples,
+                    );
+                }
+            }
+            Background::VerticalGradient { top, bottom } => base.gradient.add_to_graph(
                 self.graph,
                 self.primary_renderpass.clone(),
-                self.forward_uniform_bg,
                 self.inputs.target.samples,
-            );
+                top,
+                bottom,
+            ),
+            Background::SolidColor(_) | Background::Transparent => {}
         }
     }
 
-    /// Render the PBR materials.
+    /// Render the PBR materials. Only clears the primary renderpass unless
+    /// [`BaseRenderGraphSettings::opaque`] is set.
     pub fn pbr_render_opaque_predicted_triangles(&mut self) {
-        let routines = [&self.inputs.routines.pbr.opaque_routine, &self.inputs.routines.pbr.cutout_routine];
+        if !self.settings.opaque {
+            clear::add_renderpass_clear_to_graph(self.graph, self.primary_renderpass.clone());
+            return;
+        }
+        let pbr = self.inputs.routines.pbr;
+        let routines = [&pbr.opaque_routine, &pbr.cutout_routine, &pbr.unlit_opaque_routine, &pbr.unlit_cutout_routine];
         for routine in routines {
             routine.add_forward_to_graph(ForwardRoutineArgs {
                 graph: self.graph,
                 label: "PBR Forward Pass 1",
-                camera: CameraSpecifier::Viewport,
+                camera: self.camera_specifier,
                 binding_data: forward::ForwardRoutineBindingData {
                     whole_frame_uniform_bg: self.forward_uniform_bg,
                     per_material_bgl: &self.inputs.routines.pbr.per_material,
-                    extra_bgs: None,
+                    extra_bgs: Some(self.inputs.routines.pbr.forward_extra_bgs()),
+                    extra_graph_bg: None,
                 },
                 culling_source: forward::CullingSource::Predicted,
                 samples: self.inputs.target.samples,
                 renderpass: self.primary_renderpass.clone(),
+                stencil_reference: 0,
             });
         }
     }
 
-    /// Render the PBR materials.
+    /// Render the PBR materials. Does nothing unless
+    /// [`BaseRenderGraphSettings::opaque`] is set.
     pub fn pbr_render_opaque_residual_triangles(&mut self) {
-        let routines = [&self.inputs.routines.pbr.opaque_routine, &self.inputs.routines.pbr.cutout_routine];
+        if !self.settings.opaque {
+            return;
+        }
+        let pbr = self.inputs.routines.pbr;
+        let routines = [&pbr.opaque_routine, &pbr.cutout_routine, &pbr.unlit_opaque_routine, &pbr.unlit_cutout_routine];
         for routine in routines {
             routine.add_forward_to_graph(ForwardRoutineArgs {
                 graph: self.graph,
                 label: "PBR Forward Pass 2",
-                camera: CameraSpecifier::Viewport,
+                camera: self.camera_specifier,
                 binding_data: forward::ForwardRoutineBindingData {
                     whole_frame_uniform_bg: self.forward_uniform_bg,
                     per_material_bgl: &self.inputs.routines.pbr.per_material,
-                    extra_bgs: None,
+                    extra_bgs: Some(self.inputs.routines.pbr.forward_extra_bgs()),
+                    extra_graph_bg: None,
                 },
                 culling_source: forward::CullingSource::Residual(self.cull),
                 samples: self.inputs.target.samples,
                 renderpass: self.primary_renderpass.clone(),
+                stencil_reference: 0,
             });
         }
     }
 
-    /// Render the PBR materials.
+    /// Render the motion vectors of the predicted triangles.
+    pub fn pbr_render_velocity_predicted_triangles(&mut self) {
+        self.pbr_render_velocity("PBR Velocity Pass 1", forward::CullingSource::Predicted);
+    }
+
+    /// Render the motion vectors of the residual triangles.
+    pub fn pbr_render_velocity_residual_triangles(&mut self) {
+        self.pbr_render_velocity("PBR Velocity Pass 2", forward::CullingSource::Residual(self.cull));
+    }
+
+    fn pbr_render_velocity(&mut self, label: &str, culling_source: forward::CullingSource) {
+        let Some(renderpass) = self.velocity_renderpass.as_ref().filter(|_| self.settings.opaque) else {
+            return;
+        };
+
+        let routines = [&self.inputs.routines.pbr.opaque_velocity, &self.inputs.routines.pbr.cutout_velocity];
+        for routine in routines {
+            routine.add_forward_to_graph(ForwardRoutineArgs {
+                graph: self.graph,
+                label,
+                camera: self.camera_specifier,
+                binding_data: forward::ForwardRoutineBindingData {
+                    whole_frame_uniform_bg: self.forward_uniform_bg,
+                    per_material_bgl: &self.inputs.routines.pbr.per_material,
+                    extra_bgs: None,
+                    extra_graph_bg: None,
+                },
+                culling_source,
+                samples: self.inputs.target.samples,
+                renderpass: renderpass.clone(),
+                stencil_reference: 0,
+            });
+        }
+    }
+
+    /// Render the PBR materials. Does nothing unless
+    /// [`BaseRenderGraphSettings::transparent`] is set.
     pub fn pbr_forward_rendering_transparent(&mut self) {
-        self.inputs.routines.pbr.blend_routine.add_forward_to_graph(ForwardRoutineArgs {
+        if !self.settings.transparent {
+            return;
+        }
+        let pbr = self.inputs.routines.pbr;
+        pbr.blend_routine.add_forward_to_graph(ForwardRoutineArgs {
             graph: self.graph,
             label: "PBR Forward Transparent",
-            camera: CameraSpecifier::Viewport,
+            camera: self.camera_specifier,
             binding_data: forward::ForwardRoutineBindingData {
                 whole_frame_uniform_bg: self.forward_uniform_bg,
-                per_material_bgl: &self.inputs.routines.pbr.per_material,
-                extra_bgs: None,
+                per_material_bgl: &pbr.per_material,
+                extra_bgs: Some(pbr.forward_extra_bgs()),
+                extra_graph_bg: pbr.supports_transmission().then_some(self.transmission_bg),
             },
             culling_source: forward::CullingSource::Residual(self.cull),
             samples: self.inputs.target.samples,
             renderpass: self.primary_renderpass.clone(),
+            stencil_reference: 0,
         });
     }
 
+    /// Copy the opaque scene so transmissive materials can show what is behind
+    /// them. Does nothing unless [`BaseRenderGraphSettings::transparent`] is
+    /// set. The scene is only copied if
+    /// [`PbrRoutine::has_transmission`](pbr::PbrRoutine::has_transmission).
+    pub fn transmission_background(&mut self) {
+        if !self.settings.transparent {
+            return;
+        }
+        self.inputs.routines.pbr.add_transmission_background_to_graph(
+            self.graph,
+            self.primary_renderpass.resolved_color(0),
+            self.render_resolution,
+            self.transmission_bg,
+        );
+    }
+
+    /// Draw the lines and points queued on the debug draw routine, if given.
+    pub fn debug_draw(&mut self) {
+        if let Some(debug_draw) = self.inputs.routines.debug_draw {
+            debug_draw.add_to_graph(
+                self.graph,
+                self.primary_renderpass.clone(),
+                self.forward_uniform_bg,
+                self.inputs.target.samples,
+            );
+        }
+    }
+
+    /// Build the hi-z pyramid from the predicted depth. Does nothing unless
+    /// [`BaseRenderGraphSettings::occlusion_culling`] is set and there is a
+    /// depth buffer.
     pub fn hi_z(&mut self) {
-        self.inputs.routines.pbr.hi_z.add_hi_z_to_graph(self.graph, self.depth, self.inputs.target.resolution);
+        let Some(depth) = self.depth.filter(|_| self.settings.occlusion_culling) else {
+            return;
+        };
+        self.inputs.routines.pbr.hi_z.add_hi_z_to_graph(self.graph, depth, self.render_resolution);
     }
 
     /// Tonemap onto the given render target.
@@ -473,8 +1038,15 @@ impl<'a, 'node> BaseRenderGraphIntermediateState<'a, 'node> {
         self.inputs.routines.tonemapping.add_to_graph(
             self.graph,
             self.primary_renderpass.resolved_color(0),
-            self.inputs.target.handle,
+            self.tonemapped,
             self.forward_uniform_bg,
         );
     }
+
+    /// Grade the tonemapped image onto the output.
+    pub fn color_grade(&mut self) {
+        if let Some(color_grade) = self.inputs.routines.color_grade {
+            color_grade.add_to_graph(self.graph, self.tonemapped, self.inputs.target.handle);
+        }
+    }
 }
@@ -18,3 +18,13 @@ pub fn add_depth_clear_to_graph(graph: &mut RenderGraph<'_>, depth: RenderTarget
 
     builder.build(|_| ())
 }
+
+/// Clears the targets of a renderpass, for when the passes which would
+/// otherwise be the first to use them are skipped.
+pub fn add_renderpass_clear_to_graph(graph: &mut RenderGraph<'_>, renderpass: RenderPassTargets) {
+    let mut builder = graph.add_node("Clear Renderpass");
+
+    let _rpass_handle = builder.add_renderpass(renderpass, NodeResourceUsage::Output);
+
+    builder.build(|_| ())
+}
@@ -0,0 +1,258 @@
+//! Clustered forward lighting.
+//!
+//! Splits the view frustum into a grid of froxels, exponentially distributed
+//! in depth, and bins every point light into the froxels its sphere of
+//! influence touches. The forward shader then only needs to iterate the
+//! lights in its own froxel instead of every light in the scene.
+//!
+//! Clustering is opt-in through
+//! [`BaseRenderGraphSettings::clustered_lighting`](crate::base::BaseRenderGraphSettings::clustered_lighting).
+//! When disabled, the forward shader falls back to looping over every point
+//! light, which is cheaper for small light counts.
+
+use std::borrow::Cow;
+
+use encase::{ShaderSize, ShaderType, UniformBuffer};
+use glam::{Mat4, UVec3, Vec2, Vec4};
+use rend3::{
+    graph::{RenderGraph, ViewportRect},
+    managers::CameraState,
+    types::DepthConvention,
+    util::{
+        bind_merge::{BindGroupBuilder, BindGroupLayoutBuilder},
+        math::div_round_up,
+    },
+    ShaderPreProcessor,
+};
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    BindGroupLayout, BindingType, Buffer, BufferBindingType, BufferDescriptor, BufferUsages, ComputePassDescriptor,
+    ComputePipeline, ComputePipelineDescriptor, Device, PipelineLayoutDescriptor, ShaderModuleDescriptor, ShaderStages,
+};
+
+/// Amount of clusters in the x, y, and z directions.
+pub const CLUSTER_DIMENSIONS: UVec3 = UVec3::new(16, 9, 24);
+/// Maximum amount of lights that can affect a single cluster. Lights past this
+/// limit are ignored for that cluster.
+///
+/// Must be kept in sync with `ClusterLightList` in `structures.wgsl`.
+pub const MAX_LIGHTS_PER_CLUSTER: u32 = 63;
+/// Default view space depth of the far edge of the second to last depth
+/// slice. The last slice extends to infinity.
+pub const DEFAULT_CLUSTER_FAR: f32 = 1000.0;
+
+const WORKGROUP_SIZE: u32 = 64;
+
+/// Size of a single cluster's light list: a count followed by the indices.
+const CLUSTER_LIST_SIZE: u64 = (MAX_LIGHTS_PER_CLUSTER as u64 + 1) * 4;
+
+#[derive(Debug, Copy, Clone, ShaderType)]
+struct ShaderClusterUniforms {
+    view: Mat4,
+    inv_proj: Mat4,
+    origin: Vec2,
+    resolution: Vec2,
+    near: f32,
+    far: f32,
+    dimensions: UVec3,
+    enabled: u32,
+}
+
+/// Routine which bins point lights into view space clusters.
+///
+/// The cluster buffers are persistent and are bound into the forward uniform
+/// bind group through [`LightClusteringRoutine::add_to_bg`].
+pub struct LightClusteringRoutine {
+    pipeline: ComputePipeline,
+    bgl: BindGroupLayout,
+    uniform_buffer: Buffer,
+    cluster_buffer: Buffer,
+    /// View space depth of the far edge of the second to last depth slice.
+    pub far: f32,
+}
+
+impl LightClusteringRoutine {
+    pub fn new(device: &Device, spp: &ShaderPreProcessor, depth_convention: DepthConvention) -> Self {
+        profiling::scope!("LightClusteringRoutine::new");
+
+        let bgl = BindGroupLayoutBuilder::new()
+            .append_buffer(
+                ShaderStages::COMPUTE,
+                BufferBindingType::Uniform,
+                false,
+                ShaderClusterUniforms::SHADER_SIZE.get(),
+            )
+            // Point lights
+            .append(
+                ShaderStages::COMPUTE,
+                BindingType::Buffer {
+                    ty: BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                None,
+            )
+            .append_buffer(ShaderStages::COMPUTE, BufferBindingType::Storage { read_only: false }, false, 4)
+            .build(device, Some("light clustering bgl"));
+
+        let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("light clustering pll"),
+            bind_group_layouts: &[&bgl],
+            push_constant_ranges: &[],
+        });
+
+        let module = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("light clustering sm"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Owned(
+                spp.render_shader(
+                    "rend3-routine/light_cluster.wgsl",
+                    &serde_json::json!({"forward_z": !depth_convention.is_reverse()}),
+                    None,
+                )
+                .unwrap(),
+            )),
+        });
+
+        let pipeline = device.create_compute_pipeline(&ComputePipelineDescriptor {
+            label: Some("light clustering pipeline"),
+            layout: Some(&layout),
+            module: &module,
+            entry_point: "main",
+        });
+
+        let uniform_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("light cluster uniforms"),
+            size: ShaderClusterUniforms::SHADER_SIZE.get(),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let cluster_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("light cluster lists"),
+            size: cluster_count() as u64 * CLUSTER_LIST_SIZE,
+            usage: BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+
+        Self { pipeline, bgl, uniform_buffer, cluster_buffer, far: DEFAULT_CLUSTER_FAR }
+    }
+
+    /// Add the cluster bindings to the given bind group layout builder.
+    pub fn add_to_bgl(bglb: &mut BindGroupLayoutBuilder) {
+        bglb.append(
+            ShaderStages::FRAGMENT,
+            BindingType::Buffer {
+                ty: BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: Some(ShaderClusterUniforms::SHADER_SIZE),
+            },
+            None,
+        )
+        .append(
+            ShaderStages::FRAGMENT,
+            BindingType::Buffer {
+                ty: BufferBindingType::Storage { read_only: true },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            None,
+        );
+    }
+
+    /// Add the cluster buffers to the given bind group builder.
+    pub fn add_to_bg<'a>(&'a self, bgb: &mut BindGroupBuilder<'a>) {
+        bgb.append_buffer(&self.uniform_buffer).append_buffer(&self.cluster_buffer);
+    }
+
+    /// Add the light binning pass to the graph. This must be added before any
+    /// forward pass which reads the clusters.
+    ///
+    /// If `enabled` is false, the forward shader is told to fall back to
+    /// iterating every light and no binning happens.
+    /// `camera` replaces the renderer's camera if given. `viewport` is the
+    /// viewport of the forward passes reading the clusters.
+    ///
+    /// The uniforms are written through the command encoder, so the routine
+    /// can be added several times to one graph, as long as each view's
+    /// forward passes come before the next binning pass.
+    pub fn add_to_graph<'node>(
+        &'node self,
+        graph: &mut RenderGraph<'node>,
+        camera: Option<CameraState>,
+        viewport: ViewportRect,
+        enabled: bool,
+    ) {
+        let mut builder = graph.add_node("Light Clustering");
+        builder.add_side_effect();
+
+        builder.build(move |mut ctx| {
+            let camera = camera.as_ref().unwrap_or(&ctx.data_core.viewport_camera_state);
+
+            // Clustering relies on a perspective projection to slice the frustum in depth.
+            let enabled = enabled && is_perspective(camera.proj());
+
+            let uniforms = compute_uniforms(camera, viewport, self.far, enabled);
+            let mut data = UniformBuffer::new(Vec::new());
+            data.write(&uniforms).unwrap();
+            let staging = ctx.renderer.device.create_buffer_init(&BufferInitDescriptor {
+                label: Some("light cluster uniforms staging"),
+                contents: &data.into_inner(),
+                usage: BufferUsages::COPY_SRC,
+            });
+
+            let encoder = ctx.encoder_or_pass.take_encoder();
+            encoder.copy_buffer_to_buffer(
+                &staging,
+                0,
+                &self.uniform_buffer,
+                0,
+                ShaderClusterUniforms::SHADER_SIZE.get(),
+            );
+
+            if !enabled {
+                return;
+            }
+
+            profiling::scope!("Light Clustering");
+
+            let mut bgb = BindGroupBuilder::new();
+            bgb.append_buffer(&self.uniform_buffer);
+            ctx.data_core.point_light_manager.add_to_bg(&mut bgb);
+            bgb.append_buffer(&self.cluster_buffer);
+            let bg = bgb.build(&ctx.renderer.device, Some("light clustering bg"), &self.bgl);
+
+            let mut cpass = encoder
+                .begin_compute_pass(&ComputePassDescriptor { label: Some("Light Clustering"), timestamp_writes: None });
+            cpass.set_pipeline(&self.pipeline);
+            cpass.set_bind_group(0, &bg, &[]);
+            cpass.dispatch_workgroups(div_round_up(cluster_count(), WORKGROUP_SIZE), 1, 1);
+        });
+    }
+}
+
+fn cluster_count() -> u32 {
+    CLUSTER_DIMENSIONS.x * CLUSTER_DIMENSIONS.y * CLUSTER_DIMENSIONS.z
+}
+
+fn is_perspective(proj: Mat4) -> bool {
+    // Perspective matrices move view depth into w.
+    proj.w_axis.w == 0.0
+}
+
+fn compute_uniforms(camera: &CameraState, viewport: ViewportRect, far: f32, enabled: bool) -> ShaderClusterUniforms {
+    let inv_proj = camera.proj().inverse();
+
+    let near_undiv = inv_proj * Vec4::new(0.0, 0.0, camera.depth_convention().near_depth(), 1.0);
+    let near = (near_undiv.z / near_undiv.w).abs().max(f32::EPSILON);
+
+    ShaderClusterUniforms {
+        view: camera.view(),
+        inv_proj,
+        origin: viewport.offset.as_vec2(),
+        resolution: viewport.size.as_vec2(),
+        near,
+        far: far.max(near * 2.0),
+        dimensions: CLUSTER_DIMENSIONS,
+        enabled: enabled as u32,
+    }
+}
@@ -0,0 +1,404 @@
+//! Color grading through a 3D lookup table.
+//!
+//! Runs after tonemapping and remaps every display referred color through an
+//! artist authored lookup table (LUT). LUTs can be loaded from the `.cube`
+//! format with [`LutData::from_cube`], or from the common "strip" layout, where
+//! the blue slices of the cube are laid side by side in a single image, with
+//! [`LutData::from_strip`].
+//!
+//! The graded image needs the tonemapped image as input, so when grading is
+//! active, the base rendergraph tonemaps into an intermediate target of the
+//! same format as the output instead of directly into the output.
+
+use std::borrow::Cow;
+
+use encase::{ShaderSize, ShaderType, UniformBuffer};
+use glam::{Vec3, Vec4};
+use rend3::{
+    graph::{NodeResourceUsage, RenderGraph, RenderPassTarget, RenderPassTargets, RenderTargetHandle},
+    util::bind_merge::{BindGroupBuilder, BindGroupLayoutBuilder},
+    Renderer, ShaderConfig, ShaderPreProcessor,
+};
+use thiserror::Error;
+use wgpu::{
+    util::{DeviceExt, TextureDataOrder},
+    AddressMode, BindGroupLayout, BindingType, Buffer, BufferBindingType, BufferDescriptor, BufferUsages,
+    ColorTargetState, ColorWrites, Extent3d, FilterMode, FragmentState, FrontFace, MultisampleState,
+    PipelineLayoutDescriptor, PolygonMode, PrimitiveState, PrimitiveTopology, RenderPipeline, RenderPipelineDescriptor,
+    Sampler, SamplerBindingType, SamplerDescriptor, ShaderModuleDescriptor, ShaderSource, ShaderStages, Texture,
+    TextureDescriptor, TextureDimension, TextureFormat, TextureSampleType, TextureUsages, TextureView,
+    TextureViewDescriptor, TextureViewDimension, VertexState,
+};
+
+#[derive(Debug, Error)]
+pub enum LutLoadError {
+    #[error("Line {line} of the .cube file is malformed: {reason}")]
+    MalformedLine { line: usize, reason: &'static str },
+    #[error("1D lookup tables are not supported")]
+    Unsupported1d,
+    #[error("Only the default domain of 0.0 to 1.0 is supported, found {min} to {max}")]
+    UnsupportedDomain { min: Vec3, max: Vec3 },
+    #[error("The .cube file never declared its size with LUT_3D_SIZE")]
+    MissingSize,
+    #[error("Lookup table of size {size} needs {expected} entries, found {found}")]
+    WrongEntryCount { size: u32, expected: usize, found: usize },
+    #[error("A lookup table strip of size {width}x{height} must be {height}² pixels wide")]
+    InvalidStripDimensions { width: u32, height: u32 },
+}
+
+/// Contents of a 3D color lookup table.
+#[derive(Debug, Clone)]
+pub struct LutData {
+    /// Number of entries along each edge of the cube.
+    pub size: u32,
+    /// RGBA8 entries in red-fastest, then green, then blue order.
+    pub data: Vec<[u8; 4]>,
+}
+
+impl LutData {
+    /// A lookup table which maps every color to itself.
+    pub fn identity(size: u32) -> Self {
+        let max = size.saturating_sub(1).max(1) as f32;
+        let data = (0..size)
+            .flat_map(|b| (0..size).flat_map(move |g| (0..size).map(move |r| (r, g, b))))
+            .map(|(r, g, b)| quantize(Vec3::new(r as f32, g as f32, b as f32) / max))
+            .collect();
+        Self { size, data }
+    }
+
+    /// Parse a lookup table from the contents of an Adobe/Resolve `.cube`
+    /// file. Entries are clamped to the 0.0 to 1.0 range.
+    pub fn from_cube(source: &str) -> Result<Self, LutLoadError> {
+        let mut size = None;
+        let mut data = Vec::new();
+
+        for (idx, line) in source.lines().enumerate() {
+            let line_number = idx + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut tokens = line.split_whitespace();
+            let first = tokens.next().unwrap();
+
+            if let Ok(r) = first.parse::<f32>() {
+                let color = parse_rest(r, tokens, line_number)?;
+                data.push(quantize(color));
+                continue;
+            }
+
+            match first {
+                "LUT_3D_SIZE" => {
+                    let value = tokens
+                        .next()
+                        .and_then(|t| t.parse::<u32>().ok())
+                        .filter(|&s| s >= 2)
+                        .ok_or(LutLoadError::MalformedLine { line: line_number, reason: "invalid LUT_3D_SIZE" })?;
+                    size = Some(value);
+                }
+                "LUT_1D_SIZE" => return Err(LutLoadError::Unsupported1d),
+                "DOMAIN_MIN" | "DOMAIN_MAX" => {
+                    let is_min = first == "DOMAIN_MIN";
+                    let x = tokens
+                        .next()
+                        .and_then(|t| t.parse::<f32>().ok())
+                        .ok_or(LutLoadError::MalformedLine { line: line_number, reason: "expected three numbers" })?;
+                    let value = parse_rest(x, tokens, line_number)?;
+                    let default = if is_min { Vec3::ZERO } else { Vec3::ONE };
+                    if value != default {
+                        let (min, max) = if is_min { (value, Vec3::ONE) } else { (Vec3::ZERO, value) };
+                        return Err(LutLoadError::UnsupportedDomain { min, max });
+                    }
+                }
+                // TITLE and any vendor specific keywords don't affect the table.
+                _ => {}
+            }
+        }
+
+        let size = size.ok_or(LutLoadError::MissingSize)?;
+        let expected = size.pow(3) as usize;
+        if data.len() != expected {
+            return Err(LutLoadError::WrongEntryCount { size, expected, found: data.len() });
+        }
+
+        Ok(Self { size, data })
+    }
+
+    /// Create a lookup table from an RGBA8 image in the strip layout: a
+    /// `size² x size` image where each `size x size` square is a slice of
+    /// constant blue, with red increasing to the right and green increasing
+    /// downwards.
+    pub fn from_strip(width: u32, height: u32, rgba: &[u8]) -> Result<Self, LutLoadError> {
+        let size = height;
+        if size < 2 || width != size * size {
+            return Err(LutLoadError::InvalidStripDimensions { width, height });
+        }
+        let expected = (width * height) as usize;
+        if rgba.len() != expected * 4 {
+            return Err(LutLoadError::WrongEntryCount { size, expected, found: rgba.len() / 4 });
+        }
+
+        let data = (0..size)
+            .flat_map(|b| (0..size).flat_map(move |g| (0..size).map(move |r| (r, g, b))))
+            .map(|(r, g, b)| {
+                let offset = ((g * width + b * size + r) * 4) as usize;
+                [rgba[offset], rgba[offset + 1], rgba[offset + 2], 255]
+            })
+            .collect();
+
+        Ok(Self { size, data })
+    }
+}
+
+fn parse_rest<'a>(first: f32, mut tokens: impl Iterator<Item = &'a str>, line: usize) -> Result<Vec3, LutLoadError> {
+    let mut next = || {
+        tokens
+            .next()
+            .and_then(|t| t.parse::<f32>().ok())
+            .ok_or(LutLoadError::MalformedLine { line, reason: "expected three numbers" })
+    };
+    Ok(Vec3::new(first, next()?, next()?))
+}
+
+fn quantize(color: Vec3) -> [u8; 4] {
+    let color = (color.clamp(Vec3::ZERO, Vec3::ONE) * 255.0).round();
+    [color.x as u8, color.y as u8, color.z as u8, 255]
+}
+
+#[derive(Debug, Copy, Clone, ShaderType)]
+struct ColorGradeUniforms {
+    strength: f32,
+}
+
+struct StoredLut {
+    _texture: Texture,
+    view: TextureView,
+}
+
+/// Post-tonemapping color grading routine.
+///
+/// See module for documentation.
+pub struct ColorGradeRoutine {
+    bgl: BindGroupLayout,
+    pipeline: RenderPipeline,
+    sampler: Sampler,
+    uniform_buffer: Buffer,
+    lut: Option<StoredLut>,
+    output_format: TextureFormat,
+    /// How strongly the lookup table is applied. 0.0 leaves the image
+    /// untouched and 1.0 fully applies the table.
+    pub strength: f32,
+}
+
+impl ColorGradeRoutine {
+    /// Create the routine. The output format must match the format used by the
+    /// tonemapping routine, as tonemapping renders into an intermediate
+    /// target of this format.
+    pub fn new(renderer: &Renderer, spp: &ShaderPreProcessor, output_format: TextureFormat) -> Self {
+        profiling::scope!("ColorGradeRoutine::new");
+
+        let bgl = BindGroupLayoutBuilder::new()
+            .append(
+                ShaderStages::FRAGMENT,
+                BindingType::Texture {
+                    sample_type: TextureSampleType::Float { filterable: true },
+                    view_dimension: TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                None,
+            )
+            .append(
+                ShaderStages::FRAGMENT,
+                BindingType::Texture {
+                    sample_type: TextureSampleType::Float { filterable: true },
+                    view_dimension: TextureViewDimension::D3,
+                    multisampled: false,
+                },
+                None,
+            )
+            .append(ShaderStages::FRAGMENT, BindingType::Sampler(SamplerBindingType::Filtering), None)
+            .append_buffer(
+                ShaderStages::FRAGMENT,
+                BufferBindingType::Uniform,
+                false,
+                ColorGradeUniforms::SHADER_SIZE.get(),
+            )
+            .build(&renderer.device, Some("color grade bgl"));
+
+        let module = renderer.device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("color grade"),
+            source: ShaderSource::Wgsl(Cow::Owned(
+                spp.render_shader("rend3-routine/color_grade.wgsl", &ShaderConfig::default(), None).unwrap(),
+            )),
+        });
+
+        let fs_entry_point = if output_format.is_srgb() { "fs_main_scene" } else { "fs_main_monitor" };
+
+        let pll = renderer.device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("color grade pass"),
+            bind_group_layouts: &[&bgl],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = renderer.device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("color grade pass"),
+            layout: Some(&pll),
+            vertex: VertexState { module: &module, entry_point: "vs_main", buffers: &[] },
+            primitive: PrimitiveState {
+                topology: PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: FrontFace::Cw,
+                cull_mode: None,
+                unclipped_depth: false,
+                polygon_mode: PolygonMode::Fill,
+                conservative: false,
+            },
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+            fragment: Some(FragmentState {
+                module: &module,
+                entry_point: fs_entry_point,
+                targets: &[Some(ColorTargetState {
+                    format: output_format,
+                    blend: None,
+                    write_mask: ColorWrites::all(),
+                })],
+            }),
+            multiview: None,
+        });
+
+        let sampler = renderer.device.create_sampler(&SamplerDescriptor {
+            label: Some("color grade sampler"),
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            address_mode_w: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let uniform_buffer = renderer.device.create_buffer(&BufferDescriptor {
+            label: Some("color grade uniforms"),
+            size: ColorGradeUniforms::SHADER_SIZE.get(),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Self { bgl, pipeline, sampler, uniform_buffer, lut: None, output_format, strength: 1.0 }
+    }
+
+    /// Set the lookup table to grade with. If `None`, grading is disabled
+    /// and the base rendergraph tonemaps directly into the output.
+    pub fn set_lut(&mut self, renderer: &Renderer, lut: Option<&LutData>) {
+        self.lut = lut.map(|lut| {
+            let texture = renderer.device.create_texture_with_data(
+                &renderer.queue,
+                &TextureDescriptor {
+                    label: Some("color grade lut"),
+                    size: Extent3d { width: lut.size, height: lut.size, depth_or_array_layers: lut.size },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: TextureDimension::D3,
+                    format: TextureFormat::Rgba8Unorm,
+                    usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+                    view_formats: &[],
+                },
+                TextureDataOrder::LayerMajor,
+                bytemuck::cast_slice(&lut.data),
+            );
+            let view = texture.create_view(&TextureViewDescriptor::default());
+            StoredLut { _texture: texture, view }
+        });
+    }
+
+    /// Returns true if a lookup table is set, so grading will happen.
+    pub fn is_active(&self) -> bool {
+        self.lut.is_some()
+    }
+
+    /// Format of the output, and of the intermediate target tonemapping
+    /// renders into.
+    pub fn output_format(&self) -> TextureFormat {
+        self.output_format
+    }
+
+    /// Add grading `src` into `dst` to the rendergraph. Does nothing if no
+    /// lookup table is set.
+    pub fn add_to_graph<'node>(
+        &'node self,
+        graph: &mut RenderGraph<'node>,
+        src: RenderTargetHandle,
+        dst: RenderTargetHandle,
+    ) {
+        let Some(lut) = &self.lut else {
+            return;
+        };
+
+        let mut builder = graph.add_node("Color Grading");
+
+        let input_handle = builder.add_render_target(src, NodeResourceUsage::Input);
+
+        let rpass_handle = builder.add_renderpass(
+            RenderPassTargets {
+                targets: vec![RenderPassTarget { color: dst, clear: Vec4::ZERO, resolve: None }],
+                depth_stencil: None,
+            },
+            NodeResourceUsage::InputOutput,
+        );
+
+        builder.build(move |mut ctx| {
+            let rpass = ctx.encoder_or_pass.take_rpass(rpass_handle);
+            let ldr_color = ctx.graph_data.get_render_target(input_handle);
+
+            profiling::scope!("color grading");
+
+            let mut data = UniformBuffer::new(Vec::new());
+            data.write(&ColorGradeUniforms { strength: self.strength }).unwrap();
+            ctx.renderer.queue.write_buffer(&self.uniform_buffer, 0, &data.into_inner());
+
+            let bg = ctx.temps.add(
+                BindGroupBuilder::new()
+                    .append_texture_view(ldr_color)
+                    .append_texture_view(&lut.view)
+                    .append_sampler(&self.sampler)
+                    .append_buffer(&self.uniform_buffer)
+                    .build(&ctx.renderer.device, Some("color grade bg"), &self.bgl),
+            );
+
+            rpass.set_pipeline(&self.pipeline);
+            rpass.set_bind_group(0, bg, &[]);
+            rpass.draw(0..3, 0..1);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LutData;
+
+    #[test]
+    fn cube_identity() {
+        let source =
+            "TITLE \"identity\"\n# comment\nLUT_3D_SIZE 2\n\n0 0 0\n1 0 0\n0 1 0\n1 1 0\n0 0 1\n1 0 1\n0 1 1\n1 1 1\n";
+        let lut = LutData::from_cube(source).unwrap();
+
+        assert_eq!(lut.size, 2);
+        assert_eq!(lut.data, LutData::identity(2).data);
+    }
+
+    #[test]
+    fn strip_matches_identity() {
+        let size = 4;
+        let width = size * size;
+        let mut rgba = vec![0; (width * size * 4) as usize];
+        for (r, g, b) in (0..size).flat_map(|b| (0..size).flat_map(move |g| (0..size).map(move |r| (r, g, b)))) {
+            let offset = ((g * width + b * size + r) * 4) as usize;
+            let value = |c: u32| (c as f32 / (size - 1) as f32 * 255.0).round() as u8;
+            rgba[offset..offset + 4].copy_from_slice(&[value(r), value(g), value(b), 255]);
+        }
+        let lut = LutData::from_strip(width, size, &rgba).unwrap();
+
+        assert_eq!(lut.data, LutData::identity(size).data);
+    }
+}
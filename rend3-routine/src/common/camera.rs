@@ -3,6 +3,9 @@
 pub enum CameraSpecifier {
     Viewport,
     Shadow(u32),
+    /// A camera replacing the renderer's, identified by the id of its
+    /// [`InputCamera`](crate::base::InputCamera).
+    Custom(u32),
 }
 
 impl CameraSpecifier {
@@ -22,11 +25,19 @@ impl CameraSpecifier {
         matches!(self, Self::Shadow(..))
     }
 
-    /// Returns a shader compatible index for the camera, using u32::MAX for the viewport camera.
+    /// Returns `true` if the camera specifier is [`Custom`].
+    ///
+    /// [`Custom`]: CameraSpecifier::Custom
+    #[must_use]
+    pub fn is_custom(&self) -> bool {
+        matches!(self, Self::Custom(..))
+    }
+
+    /// Returns a shader compatible index for the camera, using u32::MAX for the viewport and custom cameras.
     #[must_use]
     pub fn to_shader_index(&self) -> u32 {
         match *self {
-            Self::Viewport => u32::MAX,
+            Self::Viewport | Self::Custom(_) => u32::MAX,
             Self::Shadow(index) => {
                 assert_ne!(index, u32::MAX, "Shadow camera index cannot be 0xFFFF_FFFF");
                 index
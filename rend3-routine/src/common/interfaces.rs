@@ -7,10 +7,11 @@ use rend3::{
     util::bind_merge::BindGroupLayoutBuilder,
 };
 use wgpu::{
-    BindGroupLayout, BindingType, BufferBindingType, Device, ShaderStages, TextureSampleType, TextureViewDimension,
+    BindGroupLayout, BindingType, BufferBindingType, Device, ShaderStages, TextureFormat, TextureSampleType,
+    TextureViewDimension,
 };
 
-use crate::{common::samplers::Samplers, uniforms::FrameUniforms};
+use crate::{clustering::LightClusteringRoutine, common::samplers::Samplers, uniforms::FrameUniforms};
 
 /// Interfaces which are used throughout the whole frame.
 ///
@@ -22,10 +23,17 @@ pub struct WholeFrameInterfaces {
     pub depth_uniform_bgl: BindGroupLayout,
     /// Includes everything.
     pub forward_uniform_bgl: BindGroupLayout,
+    /// Format of the main depth buffer, which all pipelines drawing into it
+    /// must use. `None` if the base rendergraph renders without one, in which
+    /// case pipelines drawing the scene must have no depth stencil state.
+    pub depth_format: Option<TextureFormat>,
+    /// Format of the HDR color target, which all pipelines drawing into it
+    /// must use.
+    pub hdr_format: TextureFormat,
 }
 
 impl WholeFrameInterfaces {
-    pub fn new(device: &Device) -> Self {
+    pub fn new(device: &Device, depth_format: Option<TextureFormat>, hdr_format: TextureFormat) -> Self {
         profiling::scope!("ShaderInterfaces::new");
 
         let mut uniform_bglb = BindGroupLayoutBuilder::new();
@@ -58,9 +66,11 @@ impl WholeFrameInterfaces {
             None,
         );
 
+        LightClusteringRoutine::add_to_bgl(&mut uniform_bglb);
+
         let forward_uniform_bgl = uniform_bglb.build(device, Some("forward uniform bgl"));
 
-        Self { depth_uniform_bgl: shadow_uniform_bgl, forward_uniform_bgl }
+        Self { depth_uniform_bgl: shadow_uniform_bgl, forward_uniform_bgl, depth_format, hdr_format }
     }
 }
 
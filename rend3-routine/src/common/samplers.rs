@@ -1,29 +1,53 @@
-use rend3::util::bind_merge::{BindGroupBuilder, BindGroupLayoutBuilder};
+use rend3::{
+    util::bind_merge::{BindGroupBuilder, BindGroupLayoutBuilder},
+    Renderer,
+};
 use wgpu::{
-    AddressMode, BindingType, CompareFunction, Device, FilterMode, Sampler, SamplerBindingType, SamplerDescriptor,
-    ShaderStages,
+    AddressMode, BindingType, CompareFunction, Device, DownlevelFlags, FilterMode, Sampler, SamplerBindingType,
+    SamplerDescriptor, ShaderStages,
 };
 
 /// Container holding a variety of samplers.
 pub struct Samplers {
-    /// Aniso 16 sampler
+    /// Trilinear sampler, anisotropic if [`Samplers::anisotropy`] is above 1.
     pub linear: Sampler,
     /// Nearest neighbor sampler
     pub nearest: Sampler,
-    /// Bilinear greater-or-equal comparison sampler
+    /// Bilinear comparison sampler which passes for depths at least as close
+    /// as the reference, according to the depth convention.
     pub shadow: Sampler,
+    /// Maximum anisotropy of the linear sampler, after falling back to what
+    /// the device supports.
+    pub anisotropy: u16,
 }
 
 impl Samplers {
-    /// Create a new set of samplers with this device.
-    pub fn new(device: &Device) -> Self {
+    /// Create a new set of samplers for this renderer.
+    ///
+    /// `anisotropy` is the maximum anisotropy of the linear sampler, usually
+    /// 1, 2, 4, 8, or 16. It is clamped to 1..=16, and is 1 if the device
+    /// doesn't support anisotropic filtering.
+    pub fn new(renderer: &Renderer, anisotropy: u16) -> Self {
         profiling::scope!("Samplers::new");
 
-        let linear = create_sampler(device, FilterMode::Linear, None);
-        let nearest = create_sampler(device, FilterMode::Nearest, None);
-        let shadow = create_sampler(device, FilterMode::Linear, Some(CompareFunction::GreaterEqual));
+        let device = &renderer.device;
+        let anisotropy = supported_anisotropy(renderer, anisotropy);
+
+        let linear = create_sampler(device, FilterMode::Linear, None, anisotropy);
+        let nearest = create_sampler(device, FilterMode::Nearest, None, 1);
+        let shadow = create_sampler(device, FilterMode::Linear, Some(renderer.depth_convention.closer_equal()), 1);
+
+        Self { linear, nearest, shadow, anisotropy }
+    }
 
-        Self { linear, nearest, shadow }
+    /// Recreate the linear sampler with a different maximum anisotropy. See
+    /// [`Samplers::new`] for how the value is limited.
+    ///
+    /// Takes effect from the next frame, as bind groups using the samplers are
+    /// rebuilt every frame.
+    pub fn set_anisotropy(&mut self, renderer: &Renderer, anisotropy: u16) {
+        self.anisotropy = supported_anisotropy(renderer, anisotropy);
+        self.linear = create_sampler(&renderer.device, FilterMode::Linear, None, self.anisotropy);
     }
 
     /// Add the samplers to the given bind group layout builder.
@@ -39,7 +63,15 @@ impl Samplers {
     }
 }
 
-fn create_sampler(device: &Device, filter: FilterMode, compare: Option<CompareFunction>) -> Sampler {
+fn supported_anisotropy(renderer: &Renderer, anisotropy: u16) -> u16 {
+    if renderer.downlevel.flags.contains(DownlevelFlags::ANISOTROPIC_FILTERING) {
+        anisotropy.clamp(1, 16)
+    } else {
+        1
+    }
+}
+
+fn create_sampler(device: &Device, filter: FilterMode, compare: Option<CompareFunction>, anisotropy: u16) -> Sampler {
     device.create_sampler(&SamplerDescriptor {
         label: Some("linear"),
         address_mode_u: AddressMode::Repeat,
@@ -51,7 +83,7 @@ fn create_sampler(device: &Device, filter: FilterMode, compare: Option<CompareFu
         lod_min_clamp: 0.0,
         lod_max_clamp: 100.0,
         compare,
-        anisotropy_clamp: 1,
+        anisotropy_clamp: anisotropy,
         border_color: None,
     })
 }
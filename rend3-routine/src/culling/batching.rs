@@ -5,7 +5,7 @@ use ordered_float::OrderedFloat;
 use rend3::{
     graph::NodeExecutionContext,
     managers::{CameraState, TextureBindGroupIndex},
-    types::{GraphDataHandle, Material, RawObjectHandle, SortingOrder, SortingReason},
+    types::{Culling, GraphDataHandle, Material, RawObjectHandle, SortingOrder, SortingReason},
     util::{math::round_up, typedefs::FastHashMap},
 };
 
@@ -28,12 +28,14 @@ pub(super) struct JobSubRegion {
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub(super) struct ShaderJobKey {
     pub material_key: u64,
+    pub culling: Culling,
     pub bind_group_index: TextureBindGroupIndex,
 }
 
 #[derive(Debug, Clone, Copy, Eq)]
 pub(super) struct ShaderJobSortingKey {
     pub job_key: ShaderJobKey,
+    pub sort_bias: i32,
     pub distance: OrderedFloat<f32>,
     pub sorting_reason: SortingReason,
 }
@@ -61,14 +63,28 @@ impl Ord for ShaderJobSortingKey {
             Ordering::Equal => {}
             ord => return ord,
         }
+        // user provided bias overrides everything automatic
+        match self.sort_bias.cmp(&other.sort_bias) {
+            Ordering::Equal => {}
+            ord => return ord,
+        }
         // The above comparison means that both sides are equal
         if self.sorting_reason == SortingReason::Requirement {
             match self.distance.cmp(&other.distance) {
                 Ordering::Equal => {}
                 ord => return ord,
             }
+            match self.job_key.culling.cmp(&other.job_key.culling) {
+                Ordering::Equal => {}
+                ord => return ord,
+            }
             self.job_key.bind_group_index.cmp(&other.job_key.bind_group_index)
         } else {
+            // Grouping by culling keeps pipeline switches to a minimum
+            match self.job_key.culling.cmp(&other.job_key.culling) {
+                Ordering::Equal => {}
+                ord => return ord,
+            }
             match self.job_key.bind_group_index.cmp(&other.job_key.bind_group_index) {
                 Ordering::Equal => {}
                 ord => return ord,
@@ -97,6 +113,8 @@ pub(super) struct ShaderObjectCullingInformation {
     pub local_region_id: u32,
     pub previous_global_invocation: u32,
     pub atomic_capable: u32,
+    /// Index of the [`Culling`] variant of the material.
+    pub culling: u32,
 }
 
 /// Map containing the previous invocation of each object.
@@ -138,11 +156,17 @@ pub(super) fn batch_objects<M: Material>(
 
     let material_archetype = ctx.data_core.material_manager.archetype_view::<M>();
 
+    // A view matrix with a negative determinant mirrors the scene, so the faces to cull swap.
+    let mirrored = camera.view().determinant() < 0.0;
+    // Shadow passes still sort by the distance to the viewer.
+    let viewer_location =
+        if camera_specifier.is_shadow() { &ctx.data_core.viewport_camera_state } else { camera }.location();
+
     let mut sorted_objects = Vec::with_capacity(objects.len());
     {
         profiling::scope!("Sort Key Creation");
         for (handle, object) in objects {
-            // Frustum culling
+            // Frustum culling against the camera being rendered, which is the light's camera for shadow passes.
             if !camera.world_frustum().contains_sphere(object.inner.bounding_sphere) {
                 continue;
             }
@@ -151,16 +175,18 @@ pub(super) fn batch_objects<M: Material>(
             let bind_group_index = material.bind_group_index.map_gpu(|_| TextureBindGroupIndex::DUMMY).into_common();
 
             let material_key = material.inner.key();
+            let culling = material.inner.culling();
+            let culling = if mirrored { culling.mirrored() } else { culling };
             let sorting = material.inner.sorting();
 
-            let mut distance_sq =
-                ctx.data_core.viewport_camera_state.location().distance_squared(object.location.into());
+            let mut distance_sq = viewer_location.distance_squared(object.location.into());
             if sorting.order == SortingOrder::BackToFront {
                 distance_sq = -distance_sq;
             }
             sorted_objects.push((
                 ShaderJobSortingKey {
-                    job_key: ShaderJobKey { material_key, bind_group_index },
+                    job_key: ShaderJobKey { material_key, culling, bind_group_index },
+                    sort_bias: object.sort_bias,
                     distance: OrderedFloat(distance_sq),
                     sorting_reason: sorting.reason,
                 },
@@ -225,6 +251,7 @@ pub(super) fn batch_objects<M: Material>(
                 local_region_id: current_region_object_index,
                 previous_global_invocation: previous_invocation_map.get(&handle).copied().unwrap_or(u32::MAX),
                 atomic_capable: matches!(sorting_reason, SortingReason::Optimization) as u32,
+                culling: key.culling as u32,
             };
 
             current_invocation_map.insert(handle, current_invocation + current_base_invocation);
@@ -13,7 +13,7 @@ use rend3::{
     format_sso,
     graph::{DataHandle, DeclaredDependency, NodeExecutionContext, NodeResourceUsage, RenderGraph, RenderTargetHandle},
     managers::{CameraState, ShaderObject, TextureBindGroupIndex},
-    types::{GraphDataHandle, Material, MaterialArray, SampleCount, VERTEX_ATTRIBUTE_POSITION},
+    types::{Culling, GraphDataHandle, Material, MaterialArray, SampleCount, VERTEX_ATTRIBUTE_POSITION},
     util::{frustum::Frustum, math::IntegerExt, typedefs::FastHashMap},
     Renderer, ShaderPreProcessor, ShaderVertexBufferConfig,
 };
@@ -22,7 +22,7 @@ use wgpu::{
     BindGroupLayoutEntry, BindingResource, BindingType, Buffer, BufferBinding, BufferBindingType, BufferDescriptor,
     BufferUsages, CommandEncoder, ComputePassDescriptor, ComputePipeline, ComputePipelineDescriptor, Device,
     FilterMode, PipelineLayoutDescriptor, Queue, Sampler, SamplerBindingType, SamplerDescriptor,
-    ShaderModuleDescriptor, ShaderStages, TextureSampleType, TextureViewDimension,
+    ShaderModuleDescriptor, ShaderStages, TextureAspect, TextureSampleType, TextureViewDimension,
 };
 
 use crate::{
@@ -46,6 +46,8 @@ pub struct DrawCallSet {
 #[derive(Debug, Clone)]
 pub struct DrawCall {
     pub bind_group_index: TextureBindGroupIndex,
+    /// Culling of the material, selecting the pipeline variant to draw with.
+    pub culling: Culling,
     pub batch_index: u32,
 }
 
@@ -152,6 +154,7 @@ bitflags::bitflags! {
     struct PerCameraUniformFlags: u32 {
         const POSTIIVE_AREA_VISIBLE = 1 << 0;
         const MULTISAMPLED = 1 << 1;
+        const OCCLUSION_CULLING = 1 << 2;
     }
 }
 
@@ -267,6 +270,7 @@ impl GpuCuller {
                 "rend3-routine/cull.wgsl",
                 &serde_json::json! {{
                     "position_attribute_offset": position_offset,
+                    "forward_z": !renderer.depth_convention.is_reverse(),
                 }},
                 Some(&ShaderVertexBufferConfig::from_material::<M>()),
             )
@@ -431,6 +435,7 @@ impl GpuCuller {
         camera_specifier: CameraSpecifier,
         resolution: UVec2,
         samples: SampleCount,
+        occlusion_culling: bool,
     ) where
         M: Material,
     {
@@ -476,7 +481,7 @@ impl GpuCuller {
 
         let culling = match camera_specifier {
             CameraSpecifier::Shadow(_) => wgpu::Face::Front,
-            CameraSpecifier::Viewport => wgpu::Face::Back,
+            CameraSpecifier::Viewport | CameraSpecifier::Custom(_) => wgpu::Face::Back,
         };
 
         {
@@ -495,6 +500,7 @@ impl GpuCuller {
                         TriangleVisibility::from_winding_and_face(self.winding, culling).is_positive(),
                     );
                     flags.set(PerCameraUniformFlags::MULTISAMPLED, samples != SampleCount::One);
+                    flags.set(PerCameraUniformFlags::OCCLUSION_CULLING, occlusion_culling);
                     flags.bits()
                 },
                 object_count: max_object_count as u32,
@@ -634,7 +640,11 @@ impl GpuCuller {
                 current_material_key_range_start = range_end;
             }
 
-            draw_calls.push(DrawCall { bind_group_index: region.key.bind_group_index, batch_index: region.job_index });
+            draw_calls.push(DrawCall {
+                bind_group_index: region.key.bind_group_index,
+                culling: region.key.culling,
+                batch_index: region.job_index,
+            });
         }
 
         material_key_ranges.insert(current_material_key, current_material_key_range_start..draw_calls.len());
@@ -658,12 +668,17 @@ impl GpuCuller {
         DrawCallSet { culling_data_buffer, per_camera_uniform, draw_calls, material_key_ranges }
     }
 
+    /// `viewport_camera` replaces the renderer's camera for
+    /// [`CameraSpecifier::Viewport`] and [`CameraSpecifier::Custom`].
+    #[allow(clippy::too_many_arguments)]
     pub fn add_object_uniform_upload_to_graph<'node, M: Material>(
         &'node self,
         graph: &mut RenderGraph<'node>,
         camera_specifier: CameraSpecifier,
+        viewport_camera: Option<CameraState>,
         resolution: UVec2,
         samples: SampleCount,
+        occlusion_culling: bool,
         name: &str,
     ) {
         let mut node = graph.add_node(name);
@@ -672,32 +687,39 @@ impl GpuCuller {
         node.build(move |mut ctx| {
             let camera = match camera_specifier {
                 CameraSpecifier::Shadow(i) => &ctx.eval_output.shadows[i as usize].camera,
-                CameraSpecifier::Viewport => &ctx.data_core.viewport_camera_state,
+                CameraSpecifier::Viewport | CameraSpecifier::Custom(_) => {
+                    viewport_camera.as_ref().unwrap_or(&ctx.data_core.viewport_camera_state)
+                }
             };
 
-            self.object_uniform_upload::<M>(&mut ctx, camera, camera_specifier, resolution, samples);
+            self.object_uniform_upload::<M>(&mut ctx, camera, camera_specifier, resolution, samples, occlusion_culling);
         });
     }
 
+    /// `viewport_camera` replaces the renderer's camera for
+    /// [`CameraSpecifier::Viewport`] and [`CameraSpecifier::Custom`].
     pub fn add_culling_to_graph<'node, M: Material>(
         &'node self,
         graph: &mut RenderGraph<'node>,
         draw_calls_hdl: DataHandle<Arc<DrawCallSet>>,
         depth_handle: RenderTargetHandle,
         camera_specifier: CameraSpecifier,
+        viewport_camera: Option<CameraState>,
         name: &str,
     ) {
         let mut node = graph.add_node(name);
         let output = node.add_data(draw_calls_hdl, NodeResourceUsage::Output);
         let depth_handle = node.add_render_target(
-            depth_handle,
+            depth_handle.set_aspect(TextureAspect::DepthOnly),
             if camera_specifier.is_shadow() { NodeResourceUsage::Reference } else { NodeResourceUsage::Input },
         );
 
         node.build(move |mut ctx| {
             let camera = match camera_specifier {
                 CameraSpecifier::Shadow(i) => &ctx.eval_output.shadows[i as usize].camera,
-                CameraSpecifier::Viewport => &ctx.data_core.viewport_camera_state,
+                CameraSpecifier::Viewport | CameraSpecifier::Custom(_) => {
+                    viewport_camera.as_ref().unwrap_or(&ctx.data_core.viewport_camera_state)
+                }
             };
 
             let jobs = batch_objects::<M>(&mut ctx, &self.previous_invocation_map_handle, camera, camera_specifier);
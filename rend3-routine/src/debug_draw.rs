@@ -0,0 +1,233 @@
+//! Lines and points drawn over the scene, for debug visualization.
+//!
+//! Queue shapes on a [`DebugDrawRoutine`] each frame, then pass it to the base
+//! rendergraph as
+//! [`BaseRenderGraphRoutines::debug_draw`](crate::base::BaseRenderGraphRoutines::debug_draw).
+//! Everything queued is drawn after the forward passes, then cleared, so no
+//! meshes or objects need to be made for it.
+
+use std::{borrow::Cow, mem};
+
+use glam::{BVec3, Mat4, Vec3, Vec4};
+use parking_lot::Mutex;
+use rend3::{
+    graph::{DataHandle, NodeResourceUsage, RenderGraph, RenderPassTargets},
+    types::SampleCount,
+    Renderer, ShaderConfig, ShaderPreProcessor,
+};
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    vertex_attr_array, BindGroup, BlendState, BufferUsages, ColorTargetState, ColorWrites, CompareFunction,
+    DepthBiasState, DepthStencilState, FragmentState, MultisampleState, PipelineLayoutDescriptor, PrimitiveState,
+    RenderPipeline, RenderPipelineDescriptor, ShaderModuleDescriptor, ShaderSource, StencilState, VertexBufferLayout,
+    VertexState, VertexStepMode,
+};
+
+use crate::common::WholeFrameInterfaces;
+
+/// A line between two points, as the vertex shader reads it. Points are
+/// segments which start and end in the same place.
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+struct DebugSegment {
+    start: [f32; 3],
+    end: [f32; 3],
+    start_color: [f32; 4],
+    end_color: [f32; 4],
+    width: f32,
+}
+
+unsafe impl bytemuck::Pod for DebugSegment {}
+unsafe impl bytemuck::Zeroable for DebugSegment {}
+
+/// Draws antialiased lines and points queued during the frame.
+///
+/// Widths and sizes are in pixels of the render resolution, and colors are
+/// scene-referred linear, like the rest of the HDR image. Shapes are queued
+/// through `&self`, so they can be added from any thread.
+pub struct DebugDrawRoutine {
+    /// Hide lines and points behind opaque objects. When disabled, they are
+    /// drawn over everything. Defaults to true.
+    pub depth_test: bool,
+    depth_tested_s1: RenderPipeline,
+    depth_tested_s4: RenderPipeline,
+    overlay_s1: RenderPipeline,
+    overlay_s4: RenderPipeline,
+    segments: Mutex<Vec<DebugSegment>>,
+}
+
+impl DebugDrawRoutine {
+    /// Create the routine. `interfaces` must be those of the rendergraph it
+    /// draws in.
+    pub fn new(renderer: &Renderer, spp: &ShaderPreProcessor, interfaces: &WholeFrameInterfaces) -> Self {
+        profiling::scope!("DebugDrawRoutine::new");
+
+        let module = renderer.device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("debug draw"),
+            source: ShaderSource::Wgsl(Cow::Owned(
+                spp.render_shader("rend3-routine/debug_draw.wgsl", &ShaderConfig::default(), None).unwrap(),
+            )),
+        });
+
+        let pll = renderer.device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("debug draw pass"),
+            bind_group_layouts: &[&interfaces.forward_uniform_bgl],
+            push_constant_ranges: &[],
+        });
+
+        let attributes =
+            vertex_attr_array![0 => Float32x3, 1 => Float32x3, 2 => Float32x4, 3 => Float32x4, 4 => Float32];
+
+        let inner = |samples: SampleCount, depth_test: bool| {
+            renderer.device.create_render_pipeline(&RenderPipelineDescriptor {
+                label: Some("debug draw pass"),
+                layout: Some(&pll),
+                vertex: VertexState {
+                    module: &module,
+                    entry_point: "vs_main",
+                    buffers: &[VertexBufferLayout {
+                        array_stride: mem::size_of::<DebugSegment>() as u64,
+                        step_mode: VertexStepMode::Instance,
+                        attributes: &attributes,
+                    }],
+                },
+                primitive: PrimitiveState::default(),
+                depth_stencil: interfaces.depth_format.map(|format| DepthStencilState {
+                    format,
+                    depth_write_enabled: false,
+                    depth_compare: match depth_test {
+                        true => renderer.depth_convention.closer_equal(),
+                        false => CompareFunction::Always,
+                    },
+                    stencil: StencilState::default(),
+                    bias: DepthBiasState::default(),
+                }),
+                multisample: MultisampleState { count: samples as u32, ..Default::default() },
+                fragment: Some(FragmentState {
+                    module: &module,
+                    entry_point: "fs_main",
+                    targets: &[Some(ColorTargetState {
+                        format: interfaces.hdr_format,
+                        blend: Some(BlendState::ALPHA_BLENDING),
+                        write_mask: ColorWrites::all(),
+                    })],
+                }),
+                multiview: None,
+            })
+        };
+
+        Self {
+            depth_test: true,
+            depth_tested_s1: inner(SampleCount::One, true),
+            depth_tested_s4: inner(SampleCount::Four, true),
+            overlay_s1: inner(SampleCount::One, false),
+            overlay_s4: inner(SampleCount::Four, false),
+            segments: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Queue a line from `start` to `end` in world space, `width` pixels wide.
+    pub fn line(&self, start: Vec3, end: Vec3, color: Vec4, width: f32) {
+        self.line_gradient(start, end, color, color, width);
+    }
+
+    /// Queue a line which blends from `start_color` at `start` to `end_color`
+    /// at `end`.
+    pub fn line_gradient(&self, start: Vec3, end: Vec3, start_color: Vec4, end_color: Vec4, width: f32) {
+        self.segments.lock().push(DebugSegment {
+            start: start.to_array(),
+            end: end.to_array(),
+            start_color: start_color.to_array(),
+            end_color: end_color.to_array(),
+            width,
+        });
+    }
+
+    /// Queue a round point at `position` in world space, `size` pixels across.
+    pub fn point(&self, position: Vec3, color: Vec4, size: f32) {
+        self.line(position, position, color, size);
+    }
+
+    /// Queue lines joining each of `points` to the next.
+    pub fn path(&self, points: impl IntoIterator<Item = Vec3>, color: Vec4, width: f32) {
+        let mut points = points.into_iter();
+        let Some(mut previous) = points.next() else {
+            return;
+        };
+        for point in points {
+            self.line(previous, point, color, width);
+            previous = point;
+        }
+    }
+
+    /// Queue the edges of the axis aligned box from `min` to `max`.
+    pub fn aabb(&self, min: Vec3, max: Vec3, color: Vec4, width: f32) {
+        let corner = |i: usize| Vec3::select(BVec3::new(i & 1 != 0, i & 2 != 0, i & 4 != 0), max, min);
+        for i in 0..8 {
+            for axis in [1, 2, 4] {
+                if i & axis == 0 {
+                    self.line(corner(i), corner(i | axis), color, width);
+                }
+            }
+        }
+    }
+
+    /// Queue the X, Y and Z axes of `transform`, colored red, green and blue,
+    /// each as long as the transform's scale along it.
+    pub fn axes(&self, transform: Mat4, width: f32) {
+        let origin = transform.w_axis.truncate();
+        self.line(origin, origin + transform.x_axis.truncate(), Vec4::new(1.0, 0.0, 0.0, 1.0), width);
+        self.line(origin, origin + transform.y_axis.truncate(), Vec4::new(0.0, 1.0, 0.0, 1.0), width);
+        self.line(origin, origin + transform.z_axis.truncate(), Vec4::new(0.0, 0.0, 1.0, 1.0), width);
+    }
+
+    /// Drops everything queued so far without drawing it.
+    pub fn clear(&self) {
+        self.segments.lock().clear();
+    }
+
+    /// Add drawing everything queued so far into `renderpass` to the
+    /// rendergraph. The queue is cleared when the graph executes.
+    pub fn add_to_graph<'node>(
+        &'node self,
+        graph: &mut RenderGraph<'node>,
+        renderpass: RenderPassTargets,
+        forward_uniform_bg: DataHandle<BindGroup>,
+        samples: SampleCount,
+    ) {
+        let mut builder = graph.add_node("Debug Draw");
+
+        let rpass_handle = builder.add_renderpass(renderpass, NodeResourceUsage::InputOutput);
+
+        let forward_uniform_handle = builder.add_data(forward_uniform_bg, NodeResourceUsage::Input);
+
+        builder.build(move |mut ctx| {
+            let rpass = ctx.encoder_or_pass.take_rpass(rpass_handle);
+
+            let forward_uniform_bg = ctx.graph_data.get_data(ctx.temps, forward_uniform_handle).unwrap();
+
+            let segments = mem::take(&mut *self.segments.lock());
+            if segments.is_empty() {
+                return;
+            }
+
+            let buffer = ctx.temps.add(ctx.renderer.device.create_buffer_init(&BufferInitDescriptor {
+                label: Some("debug draw segments"),
+                contents: bytemuck::cast_slice(&segments),
+                usage: BufferUsages::VERTEX,
+            }));
+
+            let pipeline = match (self.depth_test, samples) {
+                (true, SampleCount::One) => &self.depth_tested_s1,
+                (true, SampleCount::Four) => &self.depth_tested_s4,
+                (false, SampleCount::One) => &self.overlay_s1,
+                (false, SampleCount::Four) => &self.overlay_s4,
+            };
+
+            rpass.set_pipeline(pipeline);
+            rpass.set_bind_group(0, forward_uniform_bg, &[]);
+            rpass.set_vertex_buffer(0, buffer.slice(..));
+            rpass.draw(0..6, 0..segments.len() as u32);
+        });
+    }
+}
@@ -8,16 +8,15 @@ use arrayvec::ArrayVec;
 use encase::ShaderSize;
 use rend3::{
     graph::{self, DataHandle, NodeResourceUsage, RenderGraph, RenderPassTargets},
-    types::{GraphDataHandle, Material, SampleCount},
+    types::{Culling, GraphDataHandle, Material, SampleCount},
     util::{bind_merge::BindGroupBuilder, typedefs::FastHashMap},
     ProfileData, Renderer, RendererDataCore, RendererProfile, ShaderPreProcessor,
 };
 use serde::Serialize;
 use wgpu::{
-    BindGroup, BindGroupLayout, ColorTargetState, ColorWrites, CompareFunction, DepthBiasState, DepthStencilState,
-    FragmentState, IndexFormat, MultisampleState, PipelineLayoutDescriptor, PolygonMode, PrimitiveState,
-    PrimitiveTopology, RenderPipeline, RenderPipelineDescriptor, ShaderModule, StencilState, TextureFormat,
-    VertexState,
+    BindGroup, BindGroupLayout, ColorTargetState, ColorWrites, DepthBiasState, DepthStencilState, FragmentState,
+    IndexFormat, MultisampleState, PipelineLayoutDescriptor, PolygonMode, PrimitiveState, PrimitiveTopology,
+    RenderPipeline, RenderPipelineDescriptor, ShaderModule, StencilState, TextureFormat, VertexState,
 };
 
 use crate::{
@@ -31,7 +30,7 @@ struct ForwardPreprocessingArguments {
     vertex_array_counts: u32,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum RoutineType {
     Depth,
     Forward,
@@ -59,6 +58,7 @@ impl DeclaredCullingOutput {
     }
 }
 
+#[derive(Debug, Clone, Copy)]
 pub enum CullingSource {
     /// We are rendering the first pass with the predicted triangles from last frame.
     ///
@@ -91,13 +91,23 @@ pub struct ForwardRoutineCreateArgs<'a, M> {
 
     pub interfaces: &'a WholeFrameInterfaces,
     pub per_material: &'a PerMaterialArchetypeInterface<M>,
-    pub material_key: u64,
+    /// Keys of the materials this routine draws, as returned by
+    /// [`Material::key`].
+    pub material_keys: &'a [u64],
 
     pub routine_type: RoutineType,
     pub shaders: ShaderModulePair<'a>,
 
     pub culling_buffer_map_handle: GraphDataHandle<CullingBufferMap>,
 
+    /// Format of the depth target this routine renders to. Use a format with a
+    /// stencil aspect, like [`TextureFormat::Depth24PlusStencil8`], to make use
+    /// of `stencil`. `None` for renderpasses without a depth target, which
+    /// disables depth testing and writing.
+    pub depth_format: Option<TextureFormat>,
+    /// Stencil test and operations used by the pipelines.
+    pub stencil: StencilState,
+
     pub extra_bgls: &'a [&'a BindGroupLayout],
     #[allow(clippy::type_complexity)]
     pub descriptor_callback: Option<&'a dyn Fn(&mut RenderPipelineDescriptor<'_>, &mut [Option<ColorTargetState>])>,
@@ -115,6 +125,9 @@ pub struct ForwardRoutineBindingData<'node, M> {
     pub per_material_bgl: &'node PerMaterialArchetypeInterface<M>,
     /// Extra bind groups to be added to the pipeline.
     pub extra_bgs: Option<&'node [BindGroup]>,
+    /// Bind group made by a previous node, bound right after `extra_bgs`. Use
+    /// this for bind groups which reference render targets of the graph.
+    pub extra_graph_bg: Option<DataHandle<BindGroup>>,
 }
 
 pub struct ForwardRoutineArgs<'a, 'node, M> {
@@ -129,13 +142,18 @@ pub struct ForwardRoutineArgs<'a, 'node, M> {
     pub culling_source: CullingSource,
     pub samples: SampleCount,
     pub renderpass: RenderPassTargets,
+    /// Reference value used by the stencil test.
+    pub stencil_reference: u32,
 }
 
 /// A set of pipelines for rendering a specific combination of a material.
+///
+/// There is a pipeline for each [`Culling`] mode and sample count, draw calls
+/// use the one matching the culling of their material.
 pub struct ForwardRoutine<M: Material> {
-    pipeline_s1: RenderPipeline,
-    pipeline_s4: RenderPipeline,
-    material_key: u64,
+    pipelines_s1: [RenderPipeline; 3],
+    pipelines_s4: [RenderPipeline; 3],
+    material_keys: Vec<u64>,
     culling_buffer_map_handle: GraphDataHandle<CullingBufferMap>,
     draw_call_set_cache_handle: GraphDataHandle<FastHashMap<CameraSpecifier, Arc<DrawCallSet>>>,
     _phantom: PhantomData<M>,
@@ -153,8 +171,8 @@ impl<M: Material> ForwardRoutine<M> {
     ///
     /// Blend state is passed through to the pipeline.
     ///
-    /// If use_prepass is true, depth tests/writes are set such that it is
-    /// assumed a full depth-prepass has happened before.
+    /// Forward pipelines test and write depth themselves, no depth prepass is
+    /// needed.
     #[allow(clippy::too_many_arguments)]
     pub fn new(args: ForwardRoutineCreateArgs<'_, M>) -> Self {
         profiling::scope!("PrimaryPasses::new");
@@ -179,9 +197,11 @@ impl<M: Material> ForwardRoutine<M> {
         });
 
         Self {
-            pipeline_s1: build_forward_pipeline_inner(&pll, &args, SampleCount::One),
-            pipeline_s4: build_forward_pipeline_inner(&pll, &args, SampleCount::Four),
-            material_key: args.material_key,
+            pipelines_s1: CULLING_MODES
+                .map(|culling| build_forward_pipeline_inner(&pll, &args, SampleCount::One, culling)),
+            pipelines_s4: CULLING_MODES
+                .map(|culling| build_forward_pipeline_inner(&pll, &args, SampleCount::Four, culling)),
+            material_keys: args.material_keys.to_vec(),
             draw_call_set_cache_handle: args.renderer.add_graph_data(FastHashMap::default()),
             culling_buffer_map_handle: args.culling_buffer_map_handle,
             _phantom: PhantomData,
@@ -199,6 +219,8 @@ impl<M: Material> ForwardRoutine<M> {
         let whole_frame_uniform_handle =
             builder.add_data(args.binding_data.whole_frame_uniform_bg, NodeResourceUsage::Input);
         let culling_output_handle = args.culling_source.add_inner_data(&mut builder);
+        let extra_graph_bg_handle =
+            builder.add_optional_data(args.binding_data.extra_graph_bg, NodeResourceUsage::Input);
 
         builder.build(move |mut ctx| {
             let rpass = ctx.encoder_or_pass.take_rpass(rpass_handle);
@@ -231,7 +253,7 @@ impl<M: Material> ForwardRoutine<M> {
                     draw_call_set
                 }
             };
-            let residual = culling_output_handle.is_residual() && args.camera.is_viewport();
+            let residual = culling_output_handle.is_residual() && !args.camera.is_shadow();
 
             let culling_buffer_storage = ctx.data_core.graph_storage.get(&self.culling_buffer_map_handle);
 
@@ -263,67 +285,83 @@ impl<M: Material> ForwardRoutine<M> {
                     .build(&ctx.renderer.device, Some("Per-Material BG"), &args.binding_data.per_material_bgl.bgl),
             );
 
-            let pipeline = match args.samples {
-                SampleCount::One => &self.pipeline_s1,
-                SampleCount::Four => &self.pipeline_s4,
+            let pipelines = match args.samples {
+                SampleCount::One => &self.pipelines_s1,
+                SampleCount::Four => &self.pipelines_s4,
             };
             rpass.set_index_buffer(
                 index_buffer.slice(culling_buffers.index_buffer.partition_slice(partition)),
                 IndexFormat::Uint32,
             );
-            rpass.set_pipeline(pipeline);
+            rpass.set_stencil_reference(args.stencil_reference);
             rpass.set_bind_group(0, whole_frame_uniform_bg, &[]);
-            if let Some(v) = args.binding_data.extra_bgs {
-                for (idx, bg) in v.iter().enumerate() {
-                    rpass.set_bind_group((idx + 3) as _, bg, &[])
-                }
+            let extra_bgs = args.binding_data.extra_bgs.unwrap_or_default();
+            for (idx, bg) in extra_bgs.iter().enumerate() {
+                rpass.set_bind_group((idx + 3) as _, bg, &[])
+            }
+            if let Some(handle) = extra_graph_bg_handle {
+                let bg = ctx.graph_data.get_data(ctx.temps, handle).unwrap();
+                rpass.set_bind_group((extra_bgs.len() + 3) as _, bg, &[]);
             }
             if let ProfileData::Gpu(ref bg) = ctx.eval_output.d2_texture.bg {
                 rpass.set_bind_group(2, bg, &[]);
             }
 
-            // If there are no draw calls for this material, just bail.
-            let Some(range) = draw_call_set.material_key_ranges.get(&self.material_key) else {
-                return;
-            };
-
-            for (range_relative_idx, call) in draw_call_set.draw_calls[range.clone()].iter().enumerate() {
-                // Help RA out
-                let call: &DrawCall = call;
-                // Add the base of the range to the index to get the actual index
-                let idx = range_relative_idx + range.start;
-
-                // If we're in cpu driven mode, we need to update the texture bind group.
-                if ctx.renderer.profile.is_cpu_driven() {
+            for material_key in &self.material_keys {
+                // Skip keys without any draw calls.
+                let Some(range) = draw_call_set.material_key_ranges.get(material_key) else {
+                    continue;
+                };
+
+                let mut current_culling = None;
+                for (range_relative_idx, call) in draw_call_set.draw_calls[range.clone()].iter().enumerate() {
+                    // Help RA out
+                    let call: &DrawCall = call;
+                    // Add the base of the range to the index to get the actual index
+                    let idx = range_relative_idx + range.start;
+
+                    // Calls are sorted by culling, so this rarely switches.
+                    if current_culling != Some(call.culling) {
+                        rpass.set_pipeline(&pipelines[call.culling as usize]);
+                        current_culling = Some(call.culling);
+                    }
+
+                    // If we're in cpu driven mode, we need to update the texture bind group.
+                    if ctx.renderer.profile.is_cpu_driven() {
+                        rpass.set_bind_group(
+                            2,
+                            ctx.data_core.material_manager.texture_bind_group(call.bind_group_index),
+                            &[],
+                        );
+                    }
                     rpass.set_bind_group(
-                        2,
-                        ctx.data_core.material_manager.texture_bind_group(call.bind_group_index),
-                        &[],
+                        1,
+                        per_material_bg,
+                        &[call.batch_index * culling::ShaderBatchData::SHADER_SIZE.get() as u32],
+                    );
+                    rpass.draw_indexed_indirect(
+                        draw_call_buffer,
+                        culling_buffers.draw_call_buffer.element_offset(partition, idx as u64),
                     );
                 }
-                rpass.set_bind_group(
-                    1,
-                    per_material_bg,
-                    &[call.batch_index * culling::ShaderBatchData::SHADER_SIZE.get() as u32],
-                );
-                rpass.draw_indexed_indirect(
-                    draw_call_buffer,
-                    culling_buffers.draw_call_buffer.element_offset(partition, idx as u64),
-                );
             }
         });
     }
 }
 
+/// Culling modes in the order of their discriminants, used to index pipelines.
+const CULLING_MODES: [Culling; 3] = [Culling::Back, Culling::Front, Culling::None];
+
 fn build_forward_pipeline_inner<M: Material>(
     pll: &wgpu::PipelineLayout,
     args: &ForwardRoutineCreateArgs<'_, M>,
     samples: SampleCount,
+    culling: Culling,
 ) -> RenderPipeline {
     let mut render_targets: ArrayVec<_, 1> = ArrayVec::new();
     if matches!(args.routine_type, RoutineType::Forward) {
         render_targets.push(Some(ColorTargetState {
-            format: TextureFormat::Rgba16Float,
+            format: args.interfaces.hdr_format,
             blend: None,
             write_mask: ColorWrites::all(),
         }));
@@ -336,7 +374,7 @@ fn build_forward_pipeline_inner<M: Material>(
             topology: PrimitiveTopology::TriangleList,
             strip_index_format: None,
             front_face: args.renderer.handedness.into(),
-            cull_mode: Some(match args.routine_type {
+            cull_mode: culling.to_face(match args.routine_type {
                 RoutineType::Depth => wgpu::Face::Front,
                 RoutineType::Forward => wgpu::Face::Back,
             }),
@@ -344,11 +382,11 @@ fn build_forward_pipeline_inner<M: Material>(
             polygon_mode: PolygonMode::Fill,
             conservative: false,
         },
-        depth_stencil: Some(DepthStencilState {
-            format: TextureFormat::Depth32Float,
+        depth_stencil: args.depth_format.map(|format| DepthStencilState {
+            format,
             depth_write_enabled: true,
-            depth_compare: CompareFunction::GreaterEqual,
-            stencil: StencilState::default(),
+            depth_compare: args.renderer.depth_convention.closer_equal(),
+            stencil: args.stencil.clone(),
             bias: match args.routine_type {
                 // TODO: figure out what to put here
                 RoutineType::Depth => DepthBiasState { constant: 0, slope_scale: 0.0, clamp: 0.0 },
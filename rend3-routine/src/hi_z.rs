@@ -6,14 +6,15 @@ use rend3::{
         DeclaredDependency, NodeExecutionContext, NodeResourceUsage, RenderGraph, RenderPassDepthTarget,
         RenderPassHandle, RenderPassTargets, RenderTargetHandle, ViewportRect,
     },
+    types::DepthConvention,
     Renderer, ShaderPreProcessor,
 };
 use wgpu::{
     BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor, BindGroupLayoutEntry,
     BindingResource, BindingType, CompareFunction, DepthBiasState, DepthStencilState, Extent3d, FragmentState,
     MultisampleState, PipelineLayoutDescriptor, PrimitiveState, RenderPipeline, RenderPipelineDescriptor,
-    ShaderModuleDescriptor, ShaderStages, StencilState, TextureDimension, TextureFormat, TextureSampleType,
-    TextureViewDimension, VertexState,
+    ShaderModuleDescriptor, ShaderStages, StencilState, TextureAspect, TextureDimension, TextureFormat,
+    TextureSampleType, TextureViewDimension, VertexState,
 };
 
 use crate::base::DepthTargets;
@@ -23,14 +24,24 @@ pub struct HiZRoutine {
     single_sampled_bgl: BindGroupLayout,
     downscale_pipeline: RenderPipeline,
     resolve_pipeline: RenderPipeline,
+    depth_convention: DepthConvention,
 }
 
 impl HiZRoutine {
-    pub fn new(renderer: &Renderer, spp: &ShaderPreProcessor) -> Self {
+    /// The pyramid is built in the main depth buffer, so `depth_format` must
+    /// match it.
+    pub fn new(renderer: &Renderer, spp: &ShaderPreProcessor, depth_format: TextureFormat) -> Self {
+        let depth_convention = renderer.depth_convention;
+        let forward_z = !depth_convention.is_reverse();
         let resolve_source = spp
-            .render_shader("rend3-routine/resolve_depth_min.wgsl", &serde_json::json!({"SAMPLES": 4}), None)
+            .render_shader(
+                "rend3-routine/resolve_depth_min.wgsl",
+                &serde_json::json!({"SAMPLES": 4, "forward_z": forward_z}),
+                None,
+            )
             .unwrap();
-        let downscale_source = spp.render_shader("rend3-routine/hi_z.wgsl", &(), None).unwrap();
+        let downscale_source =
+            spp.render_shader("rend3-routine/hi_z.wgsl", &serde_json::json!({"forward_z": forward_z}), None).unwrap();
 
         let resolve_sm = renderer.device.create_shader_module(ShaderModuleDescriptor {
             label: Some("HiZ Resolver"),
@@ -87,7 +98,7 @@ impl HiZRoutine {
             vertex: VertexState { module: &resolve_sm, entry_point: "vs_main", buffers: &[] },
             primitive: PrimitiveState::default(),
             depth_stencil: Some(DepthStencilState {
-                format: TextureFormat::Depth32Float,
+                format: depth_format,
                 depth_write_enabled: true,
                 depth_compare: CompareFunction::Always,
                 stencil: StencilState::default(),
@@ -104,7 +115,7 @@ impl HiZRoutine {
             vertex: VertexState { module: &downscale_sm, entry_point: "vs_main", buffers: &[] },
             primitive: PrimitiveState::default(),
             depth_stencil: Some(DepthStencilState {
-                format: TextureFormat::Depth32Float,
+                format: depth_format,
                 depth_write_enabled: true,
                 depth_compare: CompareFunction::Always,
                 stencil: StencilState::default(),
@@ -115,7 +126,7 @@ impl HiZRoutine {
             multiview: None,
         });
 
-        Self { single_sampled_bgl, downscale_pipeline, multisampled_bgl, resolve_pipeline }
+        Self { single_sampled_bgl, downscale_pipeline, multisampled_bgl, resolve_pipeline, depth_convention }
     }
 
     pub fn resolve<'pass>(
@@ -172,14 +183,16 @@ impl HiZRoutine {
         if let Some(multi_sample) = depth_targets.multi_sample {
             let mut node = graph.add_node("HiZ Resolve");
 
-            let source = node.add_render_target(multi_sample, NodeResourceUsage::Output);
+            // Depth-stencil formats can only be sampled through their depth aspect.
+            let source =
+                node.add_render_target(multi_sample.set_aspect(TextureAspect::DepthOnly), NodeResourceUsage::Output);
 
             let rpass_handle = node.add_renderpass(
                 RenderPassTargets {
                     targets: vec![],
                     depth_stencil: Some(RenderPassDepthTarget {
                         target: depth_targets.single_sample_mipped.set_mips(0..1),
-                        depth_clear: Some(0.0),
+                        depth_clear: Some(self.depth_convention.clear_depth()),
                         stencil_clear: None,
                     }),
                 },
@@ -209,6 +222,7 @@ impl HiZRoutine {
                 depth_targets
                     .single_sample_mipped
                     .set_mips(src_mip..src_mip + 1)
+                    .set_aspect(TextureAspect::DepthOnly)
                     .set_viewport(ViewportRect::from_size(UVec2::new(src_extent.width, src_extent.height))),
                 NodeResourceUsage::Input,
             );
@@ -218,7 +232,7 @@ impl HiZRoutine {
                     targets: vec![],
                     depth_stencil: Some(RenderPassDepthTarget {
                         target: dst_target,
-                        depth_clear: Some(0.0),
+                        depth_clear: Some(self.depth_convention.clear_depth()),
                         stencil_clear: None,
                     }),
                 },
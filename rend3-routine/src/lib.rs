@@ -9,8 +9,8 @@
 //!
 //! The starting point when using this crate is
 //! [`BaseRenderGraph`](base::BaseRenderGraph), which provides a
-//! fully-put-together rendergraph including the PBR impl, skybox renderer, and
-//! tonemapper.
+//! fully-put-together rendergraph including the PBR impl, skybox renderer,
+//! tonemapper, and color grading.
 //!
 //! As you reach for more customization, you can copy
 //! [`BaseRenderGraph::add_to_graph`](base::BaseRenderGraph::add_to_graph) into
@@ -18,16 +18,23 @@
 //! abstraction is designed to be easily replaced and extended without needing
 //! too much user side boilerplate.
 
+pub mod background;
 pub mod base;
 pub mod clear;
+pub mod clustering;
+pub mod color_grade;
 pub mod common;
 pub mod culling;
+pub mod debug_draw;
 pub mod forward;
 pub mod hi_z;
 pub mod pbr;
+pub mod reflection_probe;
 mod shaders;
 pub mod skinning;
 pub mod skybox;
+pub mod sun;
+pub mod thumbnail;
 pub mod tonemapping;
 pub mod uniforms;
 
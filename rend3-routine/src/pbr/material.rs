@@ -2,11 +2,16 @@
 
 use encase::ShaderType;
 use glam::{Mat3, Vec3, Vec4};
-use rend3::types::{
-    Material, RawTexture2DHandle, Sorting, Texture2DHandle, VertexAttributeId, VERTEX_ATTRIBUTE_COLOR_0,
-    VERTEX_ATTRIBUTE_NORMAL, VERTEX_ATTRIBUTE_POSITION, VERTEX_ATTRIBUTE_TANGENT,
-    VERTEX_ATTRIBUTE_TEXTURE_COORDINATES_0, VERTEX_ATTRIBUTE_TEXTURE_COORDINATES_1,
+use rend3::{
+    types::{
+        Culling, Material, MaterialHandle, RawTexture2DHandle, Sorting, Texture2DHandle, VertexAttributeId,
+        VERTEX_ATTRIBUTE_COLOR_0, VERTEX_ATTRIBUTE_NORMAL, VERTEX_ATTRIBUTE_PACKED_NORMAL,
+        VERTEX_ATTRIBUTE_PACKED_TANGENT, VERTEX_ATTRIBUTE_POSITION, VERTEX_ATTRIBUTE_TANGENT,
+        VERTEX_ATTRIBUTE_TEXTURE_COORDINATES_0, VERTEX_ATTRIBUTE_TEXTURE_COORDINATES_1,
+    },
+    Renderer,
 };
+use serde::{Deserialize, Serialize};
 
 bitflags::bitflags! {
     /// Flags which shaders use to determine properties of a material
@@ -27,10 +32,18 @@ bitflags::bitflags! {
         const CC_BW_SPLIT =         0b0001_0000_0000_0000;
         const UNLIT =               0b0010_0000_0000_0000;
         const NEAREST =             0b0100_0000_0000_0000;
+        const BLEND_PREMULTIPLY =   0b1000_0000_0000_0000;
+        const BLEND_ADDITIVE =    0b1_0000_0000_0000_0000;
+        const OPAQUE_ALPHA =     0b10_0000_0000_0000_0000;
+        const DOUBLE_SIDED =    0b100_0000_0000_0000_0000;
     }
 }
 
 /// How the albedo color should be determined.
+///
+/// Vertex colors are multiplied in as linear values. Setting `srgb` decodes
+/// them from sRGB in the shader first, for meshes whose colors were authored
+/// in sRGB, which otherwise look washed out.
 #[derive(Debug, Clone)]
 pub enum AlbedoComponent {
     /// No albedo color.
@@ -167,7 +180,7 @@ impl<T: Copy> MaterialComponent<T> {
 }
 
 /// The direction of the Y (i.e. green) value in the normal maps
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum NormalTextureYDirection {
     /// Right handed. X right, Y up. OpenGL convention.
     Up,
@@ -230,6 +243,36 @@ impl NormalTexture {
     }
 }
 
+/// How parallax occlusion mapping should be applied.
+#[derive(Debug, Default, Clone)]
+pub enum ParallaxTexture {
+    /// No parallax occlusion mapping.
+    #[default]
+    None,
+    /// Height stored in the R value, where 1.0 is the top of the surface and
+    /// 0.0 is the deepest point.
+    Height {
+        texture: Texture2DHandle,
+        /// Maximum depth of the surface, in UV units.
+        scale: f32,
+    },
+}
+impl ParallaxTexture {
+    pub fn to_texture(&self) -> Option<&Texture2DHandle> {
+        match *self {
+            Self::None => None,
+            Self::Height { ref texture, .. } => Some(texture),
+        }
+    }
+
+    pub fn to_scale(&self) -> f32 {
+        match *self {
+            Self::None => 0.0,
+            Self::Height { scale, .. } => scale,
+        }
+    }
+}
+
 /// How the Ambient Occlusion, Metalic, and Roughness values should be
 /// determined.
 #[derive(Debug, Clone)]
@@ -368,7 +411,7 @@ impl Default for ClearcoatTextures {
 }
 
 /// How textures should be sampled.
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum SampleType {
     Nearest,
     Linear,
@@ -395,7 +438,7 @@ impl From<Transparency> for TransparencyType {
         match t {
             Transparency::Opaque => Self::Opaque,
             Transparency::Cutout { .. } => Self::Cutout,
-            Transparency::Blend => Self::Blend,
+            Transparency::Blend { .. } => Self::Blend,
         }
     }
 }
@@ -414,6 +457,14 @@ impl TransparencyType {
             Self::Blend => Sorting::BLENDING,
         }
     }
+
+    /// Material key of [`PbrMaterial`]s with this transparency, which selects
+    /// the routines drawing them. Blended materials share one key whether or
+    /// not they are unlit, so they are all depth sorted together.
+    pub fn material_key(self, unlit: bool) -> u64 {
+        let unlit = unlit && self != Self::Blend;
+        self as u64 | ((unlit as u64) << 2)
+    }
 }
 
 #[allow(clippy::cmp_owned)] // This thinks making a temporary TransparencyType is the end of the world
@@ -430,6 +481,21 @@ impl PartialEq<TransparencyType> for Transparency {
     }
 }
 
+/// How a blended material is composited over what is behind it.
+///
+/// All modes are drawn in the same back-to-front sorted pass.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum BlendMode {
+    /// Color is weighted by alpha: `src * a + dst * (1 - a)`.
+    #[default]
+    StraightAlpha,
+    /// Color has already been multiplied by alpha, as in textures authored
+    /// for premultiplied compositing: `src + dst * (1 - a)`.
+    Premultiplied,
+    /// Color weighted by alpha is added to what is behind it: `src * a + dst`.
+    Additive,
+}
+
 /// How transparency should be handled in a material.
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum Transparency {
@@ -437,8 +503,8 @@ pub enum Transparency {
     Opaque,
     /// Pixels with alpha less than `cutout` is discorded.
     Cutout { cutout: f32 },
-    /// Alpha is blended.
-    Blend,
+    /// Alpha is blended using the given blend mode.
+    Blend { mode: BlendMode },
 }
 impl Default for Transparency {
     fn default() -> Self {
@@ -446,6 +512,54 @@ impl Default for Transparency {
     }
 }
 
+/// Which set of texture coordinates each texture of a material samples.
+///
+/// A value of 0 samples `TEXTURE_COORDINATES_0` through
+/// [`PbrMaterial::uv_transform0`]. Any other value samples
+/// `TEXTURE_COORDINATES_1` through [`PbrMaterial::uv_transform1`].
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct UvSets {
+    pub albedo: u8,
+    pub normal: u8,
+    pub roughness: u8,
+    pub metallic: u8,
+    pub reflectance: u8,
+    pub clearcoat: u8,
+    pub clearcoat_roughness: u8,
+    pub emissive: u8,
+    pub anisotropy: u8,
+    pub ambient_occlusion: u8,
+    pub parallax: u8,
+    pub transmission: u8,
+    pub sheen_color: u8,
+    pub sheen_roughness: u8,
+}
+
+impl UvSets {
+    /// Packs one bit per texture, in the order of the fields.
+    pub fn to_bits(&self) -> u32 {
+        [
+            self.albedo,
+            self.normal,
+            self.roughness,
+            self.metallic,
+            self.reflectance,
+            self.clearcoat,
+            self.clearcoat_roughness,
+            self.emissive,
+            self.anisotropy,
+            self.ambient_occlusion,
+            self.parallax,
+            self.transmission,
+            self.sheen_color,
+            self.sheen_roughness,
+        ]
+        .into_iter()
+        .enumerate()
+        .fold(0, |bits, (idx, set)| bits | ((set != 0) as u32) << idx)
+    }
+}
+
 // Consider:
 //
 // - Green screen value
@@ -456,6 +570,7 @@ pub struct PbrMaterial {
     pub albedo: AlbedoComponent,
     pub transparency: Transparency,
     pub normal: NormalTexture,
+    pub parallax: ParallaxTexture,
     pub aomr_textures: AoMRTextures,
     pub ao_factor: Option<f32>,
     pub metallic_factor: Option<f32>,
@@ -465,19 +580,88 @@ pub struct PbrMaterial {
     pub clearcoat_roughness_factor: Option<f32>,
     pub emissive: MaterialComponent<Vec3>,
     pub reflectance: MaterialComponent<f32>,
+    /// Index of refraction of the dielectric part of the surface, as in
+    /// `KHR_materials_ior`. If set, F0 is computed from it and `reflectance`
+    /// is ignored.
+    pub ior: Option<f32>,
+    /// Strength of anisotropic reflections, from 0 to 1. A texture holds the
+    /// direction of anisotropy in tangent space in RG, and a multiplier for
+    /// the strength in B, as in `KHR_materials_anisotropy`.
     pub anisotropy: MaterialComponent<f32>,
+    /// Counter-clockwise rotation of the direction of anisotropy from the
+    /// tangent, in radians.
+    pub anisotropy_rotation: f32,
+    /// Fraction of light passing through the surface, from 0 to 1, as in
+    /// `KHR_materials_transmission`. Read from the R channel of a texture.
+    ///
+    /// Transmissive materials are drawn along with blended materials, seeing
+    /// the opaque scene behind them through the surface without distortion.
+    /// Transmission through other transparent objects isn't visible.
+    ///
+    /// The CpuDriven profile ignores the texture and doesn't show the scene
+    /// behind, the surface only loses its diffuse color.
+    pub transmission: MaterialComponent<f32>,
+    /// Color of the sheen on top of the surface, such as the fuzz of cloth, as
+    /// in `KHR_materials_sheen`. Read from the RGB channels of a texture.
+    /// Black disables sheen. The CpuDriven profile ignores the texture.
+    pub sheen_color: MaterialComponent<Vec3>,
+    /// Perceptual roughness of the sheen. Read from the A channel of a texture.
+    /// The CpuDriven profile ignores the texture.
+    pub sheen_roughness: MaterialComponent<f32>,
+    /// Baked lighting in linear RGB, always sampled with the second uv set.
+    /// It is multiplied by the diffuse color and added to the final color,
+    /// unaffected by dynamic lights and ambient occlusion. Ignored by the
+    /// CpuDriven profile.
+    pub lightmap: Option<Texture2DHandle>,
     pub uv_transform0: Mat3,
     pub uv_transform1: Mat3,
-    // TODO: Make unlit a different shader entirely.
+    pub uv_sets: UvSets,
+    /// Output the albedo as is, ignoring lights, shadows and the
+    /// environment. Unlit opaque and cutout materials are drawn by dedicated
+    /// pipelines which skip lighting entirely, making them cheap for UI and
+    /// emissive content. Blended unlit materials are drawn by the regular
+    /// blend pipeline so they are sorted against lit ones.
     pub unlit: bool,
     pub sample_type: SampleType,
+    /// Which faces are culled. Culling front faces with a slightly inflated
+    /// copy of a mesh draws an inverted hull outline.
+    ///
+    /// [`Culling::None`] makes the material double sided without duplicating
+    /// the faces of the mesh like
+    /// [`MeshBuilder::with_double_sided`](rend3::types::MeshBuilder::with_double_sided)
+    /// does. Back faces are then lit with their normal flipped.
+    pub culling: Culling,
+}
+
+impl PbrMaterial {
+    /// Transparency type used to decide which routine draws this material.
+    pub fn transparency_type(&self) -> TransparencyType {
+        match self.transmission {
+            MaterialComponent::None => TransparencyType::from(self.transparency),
+            _ => TransparencyType::Blend,
+        }
+    }
+
+    /// Replaces the uv transforms of an existing material, without touching
+    /// anything else. Use this to scroll or rotate textures every frame.
+    #[track_caller]
+    pub fn set_uv_transforms(renderer: &Renderer, handle: &MaterialHandle, uv_transform0: Mat3, uv_transform1: Mat3) {
+        renderer.update_material_with(handle, move |material: &mut PbrMaterial| {
+            material.uv_transform0 = uv_transform0;
+            material.uv_transform1 = uv_transform1;
+        });
+    }
 }
 
 impl Material for PbrMaterial {
     type DataType = ShaderMaterial;
-    type TextureArrayType = [Option<RawTexture2DHandle>; 10];
+    type TextureArrayType = [Option<RawTexture2DHandle>; 15];
     type RequiredAttributeArrayType = [&'static VertexAttributeId; 1];
-    type SupportedAttributeArrayType = [&'static VertexAttributeId; 6];
+    type SupportedAttributeArrayType = [&'static VertexAttributeId; 8];
+
+    // The lightmap, transmission and sheen textures come last and are left out of the
+    // CpuDriven profile, which can't bind that many textures.
+    const CPU_TEXTURE_COUNT: u32 = 11;
 
     fn required_attributes() -> Self::RequiredAttributeArrayType {
         [&VERTEX_ATTRIBUTE_POSITION]
@@ -491,15 +675,21 @@ impl Material for PbrMaterial {
             &VERTEX_ATTRIBUTE_TEXTURE_COORDINATES_0,
             &VERTEX_ATTRIBUTE_TEXTURE_COORDINATES_1,
             &VERTEX_ATTRIBUTE_COLOR_0,
+            &VERTEX_ATTRIBUTE_PACKED_NORMAL,
+            &VERTEX_ATTRIBUTE_PACKED_TANGENT,
         ]
     }
 
     fn key(&self) -> u64 {
-        TransparencyType::from(self.transparency) as u64
+        self.transparency_type().material_key(self.unlit)
     }
 
     fn sorting(&self) -> Sorting {
-        TransparencyType::from(self.transparency).to_sorting()
+        self.transparency_type().to_sorting()
+    }
+
+    fn culling(&self) -> Culling {
+        self.culling
     }
 
     fn to_textures(&self) -> Self::TextureArrayType {
@@ -514,6 +704,11 @@ impl Material for PbrMaterial {
             self.emissive.to_texture(),
             self.anisotropy.to_texture(),
             self.aomr_textures.to_ao_texture(),
+            self.parallax.to_texture(),
+            self.lightmap.as_ref(),
+            self.transmission.to_texture(),
+            self.sheen_color.to_texture(),
+            self.sheen_roughness.to_texture(),
         ]
         .map(|opt| opt.map(|r| r.get_raw()))
     }
@@ -540,6 +735,15 @@ pub struct ShaderMaterial {
     alpha_cutout: f32,
 
     material_flags: u32,
+    parallax_scale: f32,
+    uv_sets: u32,
+    anisotropy_rotation: f32,
+    transmission: f32,
+
+    sheen_color: Vec3,
+    sheen_roughness: f32,
+
+    ior: f32,
 }
 
 unsafe impl bytemuck::Zeroable for ShaderMaterial {}
@@ -569,6 +773,16 @@ impl ShaderMaterial {
                 flags |= material.aomr_textures.to_flags();
                 flags |= material.clearcoat_textures.to_flags();
                 flags.set(MaterialFlags::UNLIT, material.unlit);
+                flags.set(MaterialFlags::DOUBLE_SIDED, material.culling == Culling::None);
+                flags |= match material.transparency {
+                    Transparency::Blend { mode: BlendMode::StraightAlpha } => MaterialFlags::BLEND_PREMULTIPLY,
+                    Transparency::Blend { mode: BlendMode::Premultiplied } => MaterialFlags::empty(),
+                    Transparency::Blend { mode: BlendMode::Additive } => {
+                        MaterialFlags::BLEND_PREMULTIPLY | MaterialFlags::BLEND_ADDITIVE
+                    }
+                    // Transmissive materials are drawn in the blend pass, but must still cover what is behind them.
+                    Transparency::Opaque | Transparency::Cutout { .. } => MaterialFlags::OPAQUE_ALPHA,
+                };
                 flags.set(
                     MaterialFlags::NEAREST,
                     match material.sample_type {
@@ -578,6 +792,20 @@ impl ShaderMaterial {
                 );
                 flags.bits()
             },
+            parallax_scale: material.parallax.to_scale(),
+            sheen_color: match material.sheen_color {
+                MaterialComponent::None => Vec3::ZERO,
+                ref sheen_color => sheen_color.to_value(Vec3::ONE),
+            },
+            sheen_roughness: material.sheen_roughness.to_value(1.0),
+            // Zero tells the shader to use the reflectance instead.
+            ior: material.ior.unwrap_or(0.0),
+            uv_sets: material.uv_sets.to_bits(),
+            anisotropy_rotation: material.anisotropy_rotation,
+            transmission: match material.transmission {
+                MaterialComponent::None => 0.0,
+                ref transmission => transmission.to_value(1.0),
+            },
         }
     }
 }
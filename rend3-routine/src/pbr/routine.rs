@@ -1,23 +1,64 @@
 use std::{borrow::Cow, sync::Arc};
 
+use encase::{ShaderSize, ShaderType, UniformBuffer};
+use glam::{Mat3, Quat, UVec2};
 use rend3::{
-    types::GraphDataHandle, Renderer, RendererDataCore, RendererProfile, ShaderPreProcessor, ShaderVertexBufferConfig,
+    graph::{DataHandle, NodeResourceUsage, RenderGraph, RenderTargetDescriptor, RenderTargetHandle},
+    types::{GraphDataHandle, SampleCount, Texture2DHandle, TextureCubeHandle},
+    util::bind_merge::{BindGroupBuilder, BindGroupLayoutBuilder},
+    Renderer, RendererDataCore, RendererProfile, ShaderPreProcessor, ShaderVertexBufferConfig,
+    INTERNAL_SHADOW_DEPTH_FORMAT,
 };
 use serde::Serialize;
-use wgpu::{BlendState, ShaderModuleDescriptor, ShaderSource};
+use wgpu::{
+    AddressMode, BindGroup, BindGroupLayout, BindingType, BlendState, BufferBindingType, BufferUsages, Device,
+    FilterMode, Sampler, SamplerBindingType, SamplerDescriptor, ShaderModuleDescriptor, ShaderSource, ShaderStages,
+    StencilState, TextureFormat, TextureSampleType, TextureUsages, TextureView, TextureViewDimension,
+};
 
 use crate::{
     common::{PerMaterialArchetypeInterface, WholeFrameInterfaces},
     culling::CullingBufferMap,
     forward::{ForwardRoutine, ForwardRoutineCreateArgs, RoutineType, ShaderModulePair},
     hi_z::HiZRoutine,
-    pbr::{PbrMaterial, TransparencyType},
+    pbr::{MaterialComponent, PbrMaterial, TransparencyType},
 };
 
 #[derive(Serialize)]
 struct BlendModeWrapper {
     profile: RendererProfile,
     discard: bool,
+    alpha_to_coverage: bool,
+    transmission: bool,
+    unlit: bool,
+}
+
+#[derive(Debug, Copy, Clone, ShaderType)]
+struct ShaderEnvironment {
+    inv_rotation: Mat3,
+    enabled: u32,
+}
+
+/// Set of textures used for image based lighting.
+#[derive(Debug, Clone)]
+pub struct PbrEnvironment {
+    /// Cosine convolved cubemap used for diffuse lighting.
+    pub irradiance: TextureCubeHandle,
+    /// Cubemap with each mip prefiltered for increasing roughness, used for
+    /// specular lighting.
+    pub prefiltered: TextureCubeHandle,
+    /// Split-sum BRDF lookup table, indexed by (n dot v, roughness).
+    pub brdf_lut: Texture2DHandle,
+    /// Rotation applied to the environment. Set this to the same value as
+    /// [`SkyboxRoutine::set_background_rotation`](crate::skybox::SkyboxRoutine::set_background_rotation)
+    /// to keep reflections lined up with the background.
+    pub rotation: Quat,
+}
+
+struct StoredEnvironment {
+    environment: Option<PbrEnvironment>,
+    bg: BindGroup,
+    dirty: bool,
 }
 
 /// Render routine that renders the using PBR materials
@@ -27,8 +68,37 @@ pub struct PbrRoutine {
     pub opaque_routine: ForwardRoutine<PbrMaterial>,
     pub cutout_routine: ForwardRoutine<PbrMaterial>,
     pub blend_routine: ForwardRoutine<PbrMaterial>,
+    /// Draws opaque materials with [`PbrMaterial::unlit`] set, without any
+    /// lighting or shadow sampling.
+    pub unlit_opaque_routine: ForwardRoutine<PbrMaterial>,
+    /// Draws cutout materials with [`PbrMaterial::unlit`] set. These never
+    /// use alpha to coverage.
+    pub unlit_cutout_routine: ForwardRoutine<PbrMaterial>,
+    /// Writes motion vectors of opaque objects to an `Rg16Float` target.
+    /// Must be rendered after the depth buffer has been filled.
+    pub opaque_velocity: ForwardRoutine<PbrMaterial>,
+    /// Writes motion vectors of cutout objects to an `Rg16Float` target.
+    /// Must be rendered after the depth buffer has been filled.
+    pub cutout_velocity: ForwardRoutine<PbrMaterial>,
+    /// The variant of `cutout_routine` not selected by
+    /// [`PbrRoutine::set_alpha_to_coverage`].
+    inactive_cutout_routine: ForwardRoutine<PbrMaterial>,
+    alpha_to_coverage: bool,
+    cutout_bias: f32,
     pub hi_z: HiZRoutine,
     pub per_material: PerMaterialArchetypeInterface<PbrMaterial>,
+    /// Layout of the image based lighting bind group, bound at index 3 of the
+    /// forward passes.
+    pub environment_bgl: BindGroupLayout,
+    /// Layout of the bind group holding the copy of the opaque scene, bound at
+    /// index 4 of the blend pass. Unused in the CpuDriven profile.
+    pub transmission_bgl: BindGroupLayout,
+    /// Format of the HDR target, which the transmission background copies.
+    hdr_format: TextureFormat,
+    environment_sampler: Sampler,
+    environment: StoredEnvironment,
+    supports_transmission: bool,
+    transmission: bool,
 }
 
 impl PbrRoutine {
@@ -41,17 +111,47 @@ impl PbrRoutine {
     ) -> Self {
         profiling::scope!("PbrRenderRoutine::new");
 
+        // The CpuDriven profile only has four bind groups, so there is no room for the
+        // copy of the opaque scene.
+        let supports_transmission = renderer.profile == RendererProfile::GpuDriven;
+
         // This ensures the BGLs for the material are created
         data_core.material_manager.ensure_archetype::<PbrMaterial>(&renderer.device, renderer.profile);
 
         let per_material = PerMaterialArchetypeInterface::<PbrMaterial>::new(&renderer.device);
 
+        let environment_bgl = create_environment_bgl(&renderer.device);
+        let environment_sampler = renderer.device.create_sampler(&SamplerDescriptor {
+            label: Some("pbr environment sampler"),
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            address_mode_w: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            mipmap_filter: FilterMode::Linear,
+            ..SamplerDescriptor::default()
+        });
+        let environment_bg = create_environment_bg(
+            &renderer.device,
+            &environment_bgl,
+            &environment_sampler,
+            [data_core.d2c_texture_manager.get_null_view(), data_core.d2c_texture_manager.get_null_view()],
+            data_core.d2_texture_manager.get_null_view(),
+            None,
+        );
+
         let pbr_depth_cutout = renderer.device.create_shader_module(ShaderModuleDescriptor {
             label: Some("pbr depth cutout sm"),
             source: ShaderSource::Wgsl(Cow::Owned(
                 spp.render_shader(
                     "rend3-routine/depth.wgsl",
-                    &BlendModeWrapper { profile: renderer.profile, discard: true },
+                    &BlendModeWrapper {
+                        profile: renderer.profile,
+                        discard: true,
+                        alpha_to_coverage: false,
+                        transmission: false,
+                        unlit: false,
+                    },
                     Some(&ShaderVertexBufferConfig::from_material::<PbrMaterial>()),
                 )
                 .unwrap(),
@@ -63,7 +163,13 @@ impl PbrRoutine {
             source: ShaderSource::Wgsl(Cow::Owned(
                 spp.render_shader(
                     "rend3-routine/depth.wgsl",
-                    &BlendModeWrapper { profile: renderer.profile, discard: false },
+                    &BlendModeWrapper {
+                        profile: renderer.profile,
+                        discard: false,
+                        alpha_to_coverage: false,
+                        transmission: false,
+                        unlit: false,
+                    },
                     Some(&ShaderVertexBufferConfig::from_material::<PbrMaterial>()),
                 )
                 .unwrap(),
@@ -75,7 +181,31 @@ impl PbrRoutine {
             source: ShaderSource::Wgsl(Cow::Owned(
                 spp.render_shader(
                     "rend3-routine/opaque.wgsl",
-                    &BlendModeWrapper { profile: renderer.profile, discard: true },
+                    &BlendModeWrapper {
+                        profile: renderer.profile,
+                        discard: true,
+                        alpha_to_coverage: false,
+                        transmission: false,
+                        unlit: false,
+                    },
+                    Some(&ShaderVertexBufferConfig::from_material::<PbrMaterial>()),
+                )
+                .unwrap(),
+            )),
+        });
+
+        let pbr_cutout_alpha_to_coverage = renderer.device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("pbr opaque cutout alpha to coverage sm"),
+            source: ShaderSource::Wgsl(Cow::Owned(
+                spp.render_shader(
+                    "rend3-routine/opaque.wgsl",
+                    &BlendModeWrapper {
+                        profile: renderer.profile,
+                        discard: true,
+                        alpha_to_coverage: true,
+                        transmission: false,
+                        unlit: false,
+                    },
                     Some(&ShaderVertexBufferConfig::from_material::<PbrMaterial>()),
                 )
                 .unwrap(),
@@ -87,22 +217,163 @@ impl PbrRoutine {
             source: ShaderSource::Wgsl(Cow::Owned(
                 spp.render_shader(
                     "rend3-routine/opaque.wgsl",
-                    &BlendModeWrapper { profile: renderer.profile, discard: false },
+                    &BlendModeWrapper {
+                        profile: renderer.profile,
+                        discard: false,
+                        alpha_to_coverage: false,
+                        transmission: false,
+                        unlit: false,
+                    },
+                    Some(&ShaderVertexBufferConfig::from_material::<PbrMaterial>()),
+                )
+                .unwrap(),
+            )),
+        });
+
+        let pbr_blend = renderer.device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("pbr blend sm"),
+            source: ShaderSource::Wgsl(Cow::Owned(
+                spp.render_shader(
+                    "rend3-routine/opaque.wgsl",
+                    &BlendModeWrapper {
+                        profile: renderer.profile,
+                        discard: false,
+                        alpha_to_coverage: false,
+                        transmission: supports_transmission,
+                        unlit: false,
+                    },
+                    Some(&ShaderVertexBufferConfig::from_material::<PbrMaterial>()),
+                )
+                .unwrap(),
+            )),
+        });
+
+        let pbr_unlit = renderer.device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("pbr unlit opaque sm"),
+            source: ShaderSource::Wgsl(Cow::Owned(
+                spp.render_shader(
+                    "rend3-routine/opaque.wgsl",
+                    &BlendModeWrapper {
+                        profile: renderer.profile,
+                        discard: false,
+                        alpha_to_coverage: false,
+                        transmission: false,
+                        unlit: true,
+                    },
+                    Some(&ShaderVertexBufferConfig::from_material::<PbrMaterial>()),
+                )
+                .unwrap(),
+            )),
+        });
+
+        let pbr_unlit_cutout = renderer.device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("pbr unlit opaque cutout sm"),
+            source: ShaderSource::Wgsl(Cow::Owned(
+                spp.render_shader(
+                    "rend3-routine/opaque.wgsl",
+                    &BlendModeWrapper {
+                        profile: renderer.profile,
+                        discard: true,
+                        alpha_to_coverage: false,
+                        transmission: false,
+                        unlit: true,
+                    },
+                    Some(&ShaderVertexBufferConfig::from_material::<PbrMaterial>()),
+                )
+                .unwrap(),
+            )),
+        });
+
+        let pbr_velocity_cutout = renderer.device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("pbr velocity cutout sm"),
+            source: ShaderSource::Wgsl(Cow::Owned(
+                spp.render_shader(
+                    "rend3-routine/velocity.wgsl",
+                    &BlendModeWrapper {
+                        profile: renderer.profile,
+                        discard: true,
+                        alpha_to_coverage: false,
+                        transmission: false,
+                        unlit: false,
+                    },
+                    Some(&ShaderVertexBufferConfig::from_material::<PbrMaterial>()),
+                )
+                .unwrap(),
+            )),
+        });
+
+        let pbr_velocity = renderer.device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("pbr velocity sm"),
+            source: ShaderSource::Wgsl(Cow::Owned(
+                spp.render_shader(
+                    "rend3-routine/velocity.wgsl",
+                    &BlendModeWrapper {
+                        profile: renderer.profile,
+                        discard: false,
+                        alpha_to_coverage: false,
+                        transmission: false,
+                        unlit: false,
+                    },
                     Some(&ShaderVertexBufferConfig::from_material::<PbrMaterial>()),
                 )
                 .unwrap(),
             )),
         });
 
-        let mut inner = |routine_type, module, transparency| {
+        let transmission_bgl = BindGroupLayoutBuilder::new()
+            .append(
+                ShaderStages::FRAGMENT,
+                BindingType::Texture {
+                    sample_type: TextureSampleType::Float { filterable: true },
+                    view_dimension: TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                None,
+            )
+            .build(&renderer.device, Some("pbr transmission bgl"));
+
+        let forward_extra_bgls = [&environment_bgl];
+        let blend_extra_bgls: &[&BindGroupLayout] = match supports_transmission {
+            true => &[&environment_bgl, &transmission_bgl],
+            false => &forward_extra_bgls,
+        };
+        let mut inner = |routine_type,
+                         module,
+                         transparency: TransparencyType,
+                         velocity: bool,
+                         alpha_to_coverage: bool,
+                         unlit: bool| {
+            let extra_bgls: &[&BindGroupLayout] = match routine_type {
+                RoutineType::Forward if transparency == TransparencyType::Blend => blend_extra_bgls,
+                RoutineType::Forward if !velocity => &forward_extra_bgls,
+                _ => &[],
+            };
+            let name = match (velocity, alpha_to_coverage, unlit) {
+                (true, ..) => format!("pbr velocity {transparency:?}"),
+                (false, true, _) => format!("pbr {routine_type:?} {transparency:?} alpha to coverage"),
+                (false, false, true) => format!("pbr {routine_type:?} {transparency:?} unlit"),
+                (false, false, false) => format!("pbr {routine_type:?} {transparency:?}"),
+            };
+            // Depth and motion vectors don't depend on lighting, so those routines draw lit and unlit materials alike.
+            let both_keys = [transparency.material_key(false), transparency.material_key(true)];
+            let material_keys = match (routine_type, velocity) {
+                (RoutineType::Depth, _) | (_, true) => &both_keys[..],
+                (RoutineType::Forward, false) => std::slice::from_ref(&both_keys[unlit as usize]),
+            };
+            // Depth routines render shadows into the shadow atlas, whatever depth format the
+            // main view uses.
+            let depth_format = match routine_type {
+                RoutineType::Depth => Some(INTERNAL_SHADOW_DEPTH_FORMAT),
+                RoutineType::Forward => interfaces.depth_format,
+            };
             ForwardRoutine::new(ForwardRoutineCreateArgs {
-                name: &format!("pbr {routine_type:?} {transparency:?}"),
+                name: &name,
                 renderer,
                 data_core,
                 spp,
                 interfaces,
                 per_material: &per_material,
-                material_key: transparency as u64,
+                material_keys,
                 routine_type,
                 shaders: ShaderModulePair {
                     vs_entry: "vs_main",
@@ -110,11 +381,27 @@ impl PbrRoutine {
                     fs_entry: "fs_main",
                     fs_module: module,
                 },
-                extra_bgls: &[],
+                depth_format,
+                stencil: StencilState::default(),
+                extra_bgls,
                 descriptor_callback: Some(&|desc, targets| {
+                    if velocity {
+                        // Velocity is drawn against the already filled depth buffer.
+                        if let Some(depth_stencil) = &mut desc.depth_stencil {
+                            depth_stencil.depth_write_enabled = false;
+                        }
+                        targets[0].as_mut().unwrap().format = TextureFormat::Rg16Float;
+                    }
+                    if alpha_to_coverage {
+                        // Alpha to coverage is only valid with multiple samples, the shader
+                        // falls back to discarding otherwise.
+                        desc.multisample.alpha_to_coverage_enabled = desc.multisample.count > 1;
+                    }
                     if transparency == TransparencyType::Blend {
-                        desc.depth_stencil.as_mut().unwrap().depth_write_enabled = false;
-                        targets[0].as_mut().unwrap().blend = Some(BlendState::ALPHA_BLENDING)
+                        if let Some(depth_stencil) = &mut desc.depth_stencil {
+                            depth_stencil.depth_write_enabled = false;
+                        }
+                        targets[0].as_mut().unwrap().blend = Some(BlendState::PREMULTIPLIED_ALPHA_BLENDING)
                     }
                 }),
                 culling_buffer_map_handle: culling_buffer_map_handle.clone(),
@@ -122,13 +409,285 @@ impl PbrRoutine {
         };
 
         Self {
-            opaque_depth: inner(RoutineType::Depth, &pbr_depth, TransparencyType::Opaque),
-            cutout_depth: inner(RoutineType::Depth, &pbr_depth_cutout, TransparencyType::Cutout),
-            opaque_routine: inner(RoutineType::Forward, &pbr_forward, TransparencyType::Opaque),
-            cutout_routine: inner(RoutineType::Forward, &pbr_cutout, TransparencyType::Cutout),
-            blend_routine: inner(RoutineType::Forward, &pbr_forward, TransparencyType::Blend),
-            hi_z: HiZRoutine::new(renderer, spp),
+            opaque_depth: inner(RoutineType::Depth, &pbr_depth, TransparencyType::Opaque, false, false, false),
+            cutout_depth: inner(RoutineType::Depth, &pbr_depth_cutout, TransparencyType::Cutout, false, false, false),
+            opaque_routine: inner(RoutineType::Forward, &pbr_forward, TransparencyType::Opaque, false, false, false),
+            cutout_routine: inner(RoutineType::Forward, &pbr_cutout, TransparencyType::Cutout, false, false, false),
+            blend_routine: inner(RoutineType::Forward, &pbr_blend, TransparencyType::Blend, false, false, false),
+            unlit_opaque_routine: inner(RoutineType::Forward, &pbr_unlit, TransparencyType::Opaque, false, false, true),
+            unlit_cutout_routine: inner(
+                RoutineType::Forward,
+                &pbr_unlit_cutout,
+                TransparencyType::Cutout,
+                false,
+                false,
+                true,
+            ),
+            opaque_velocity: inner(RoutineType::Forward, &pbr_velocity, TransparencyType::Opaque, true, false, false),
+            cutout_velocity: inner(
+                RoutineType::Forward,
+                &pbr_velocity_cutout,
+                TransparencyType::Cutout,
+                true,
+                false,
+                false,
+            ),
+            inactive_cutout_routine: inner(
+                RoutineType::Forward,
+                &pbr_cutout_alpha_to_coverage,
+                TransparencyType::Cutout,
+                false,
+                true,
+                false,
+            ),
+            alpha_to_coverage: false,
+            cutout_bias: 0.0,
+            // The pyramid is never built without a depth buffer, so the format doesn't matter then.
+            hi_z: HiZRoutine::new(renderer, spp, interfaces.depth_format.unwrap_or(INTERNAL_SHADOW_DEPTH_FORMAT)),
             per_material,
+            environment_bgl,
+            transmission_bgl,
+            hdr_format: interfaces.hdr_format,
+            environment_sampler,
+            environment: StoredEnvironment { environment: None, bg: environment_bg, dirty: false },
+            supports_transmission,
+            transmission: false,
+        }
+    }
+
+    /// Set the image based lighting environment used for ambient and specular
+    /// lighting. Passing `None` falls back to the flat ambient color.
+    ///
+    /// Changes take effect after the next call to [`PbrRoutine::evaluate`].
+    pub fn set_environment(&mut self, environment: Option<PbrEnvironment>) {
+        self.environment.environment = environment;
+        self.environment.dirty = true;
+    }
+
+    /// Returns the current image based lighting environment.
+    pub fn environment(&self) -> Option<&PbrEnvironment> {
+        self.environment.environment.as_ref()
+    }
+
+    /// Use alpha to coverage for cutout materials, so their edges are
+    /// antialiased by MSAA instead of being cut sharply. Has no effect without
+    /// multisampling. Disabled by default.
+    ///
+    /// This selects which pipelines `cutout_routine` holds, so takes effect the
+    /// next time the graph is built.
+    pub fn set_alpha_to_coverage(&mut self, enabled: bool) {
+        if self.alpha_to_coverage != enabled {
+            std::mem::swap(&mut self.cutout_routine, &mut self.inactive_cutout_routine);
+            self.alpha_to_coverage = enabled;
         }
     }
+
+    /// Returns if cutout materials use alpha to coverage.
+    pub fn alpha_to_coverage(&self) -> bool {
+        self.alpha_to_coverage
+    }
+
+    /// Add `bias` to the alpha cutout of every cutout material, in both the
+    /// forward and shadow passes. Ramping this over time makes cheap dissolve
+    /// transitions. Defaults to 0.0.
+    ///
+    /// Takes effect the next time the graph is built.
+    pub fn set_cutout_bias(&mut self, bias: f32) {
+        self.cutout_bias = bias;
+    }
+
+    /// Returns the bias added to the alpha cutout of cutout materials.
+    pub fn cutout_bias(&self) -> f32 {
+        self.cutout_bias
+    }
+
+    /// Returns if any material had transmission when the routine was last
+    /// evaluated. The opaque scene is only copied for the blend pass if so.
+    /// Always false if the renderer doesn't
+    /// [support transmission](PbrRoutine::supports_transmission).
+    pub fn has_transmission(&self) -> bool {
+        self.transmission
+    }
+
+    /// Returns if the blend pass can show the scene behind transmissive
+    /// materials. Only the GpuDriven profile has a bind group to spare for it.
+    pub fn supports_transmission(&self) -> bool {
+        self.supports_transmission
+    }
+
+    /// Evaluate any changes that have happened to the pbr routine. Call this
+    /// every frame, after [`Renderer::evaluate_instructions`].
+    pub fn evaluate(&mut self, renderer: &Renderer) {
+        let data_core = renderer.data_core.lock();
+
+        self.transmission = self.supports_transmission
+            && data_core
+            .material_manager
+            .archetype_view::<PbrMaterial>()
+            .materials()
+            .any(|material| !matches!(material.inner.transmission, MaterialComponent::None));
+
+        if !self.environment.dirty {
+            return;
+        }
+
+        profiling::scope!("Update PBR Environment");
+
+        let d2_texture_manager = &data_core.d2_texture_manager;
+        let d2c_texture_manager = &data_core.d2c_texture_manager;
+
+        self.environment.bg = match self.environment.environment {
+            Some(ref env) => create_environment_bg(
+                &renderer.device,
+                &self.environment_bgl,
+                &self.environment_sampler,
+                [
+                    d2c_texture_manager.get_view(env.irradiance.get_raw()),
+                    d2c_texture_manager.get_view(env.prefiltered.get_raw()),
+                ],
+                d2_texture_manager.get_view(env.brdf_lut.get_raw()),
+                Some(env.rotation),
+            ),
+            None => create_environment_bg(
+                &renderer.device,
+                &self.environment_bgl,
+                &self.environment_sampler,
+                [d2c_texture_manager.get_null_view(), d2c_texture_manager.get_null_view()],
+                d2_texture_manager.get_null_view(),
+                None,
+            ),
+        };
+        self.environment.dirty = false;
+    }
+
+    /// Extra bind groups which must be passed to the forward (non-depth)
+    /// routines of this material.
+    pub fn forward_extra_bgs(&self) -> &[BindGroup] {
+        std::slice::from_ref(&self.environment.bg)
+    }
+
+    /// Copies `color` into a new texture and makes the bind group for the
+    /// blend pass from it, so transmissive materials can sample the scene
+    /// behind them. `color` must be single sampled and have `COPY_SRC` usage.
+    ///
+    /// If no material has transmission, nothing is copied and the bind group
+    /// holds a blank texture instead. Without
+    /// [support for transmission](PbrRoutine::supports_transmission) this
+    /// adds nothing and the blend pass takes no such bind group.
+    ///
+    /// The bind group must be passed as the
+    /// [`extra_graph_bg`](crate::forward::ForwardRoutineBindingData::extra_graph_bg)
+    /// of `blend_routine`.
+    pub fn add_transmission_background_to_graph<'node>(
+        &'node self,
+        graph: &mut RenderGraph<'node>,
+        color: RenderTargetHandle,
+        resolution: UVec2,
+        transmission_bg: DataHandle<BindGroup>,
+    ) {
+        if !self.supports_transmission {
+            return;
+        }
+        if !self.transmission {
+            let mut builder = graph.add_node("Transmission Bind Group");
+            let output_handle = builder.add_data(transmission_bg, NodeResourceUsage::Output);
+
+            builder.build(move |ctx| {
+                let bg = BindGroupBuilder::new()
+                    .append_texture_view(ctx.data_core.d2_texture_manager.get_null_view())
+                    .build(&ctx.renderer.device, Some("pbr transmission bg"), &self.transmission_bgl);
+                ctx.graph_data.set_data(output_handle, Some(bg));
+            });
+            return;
+        }
+
+        let background = graph.add_render_target(RenderTargetDescriptor {
+            label: Some("transmission background".into()),
+            resolution,
+            depth: 1,
+            mip_levels: Some(1),
+            samples: SampleCount::One,
+            format: self.hdr_format,
+            usage: TextureUsages::COPY_DST | TextureUsages::TEXTURE_BINDING,
+        });
+        graph.add_copy(color, background);
+
+        let mut builder = graph.add_node("Transmission Bind Group");
+        let background_handle = builder.add_render_target(background, NodeResourceUsage::Input);
+        let output_handle = builder.add_data(transmission_bg, NodeResourceUsage::Output);
+        builder.add_dependencies_to_render_targets(transmission_bg, [background]);
+
+        builder.build(move |ctx| {
+            let view = ctx.graph_data.get_render_target(background_handle);
+            let bg = BindGroupBuilder::new().append_texture_view(view).build(
+                &ctx.renderer.device,
+                Some("pbr transmission bg"),
+                &self.transmission_bgl,
+            );
+            ctx.graph_data.set_data(output_handle, Some(bg));
+        });
+    }
+}
+
+fn create_environment_bgl(device: &Device) -> BindGroupLayout {
+    let cube = BindingType::Texture {
+        sample_type: TextureSampleType::Float { filterable: true },
+        view_dimension: TextureViewDimension::Cube,
+        multisampled: false,
+    };
+    BindGroupLayoutBuilder::new()
+        .append(
+            ShaderStages::FRAGMENT,
+            BindingType::Buffer {
+                ty: BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: Some(ShaderEnvironment::SHADER_SIZE),
+            },
+            None,
+        )
+        .append(ShaderStages::FRAGMENT, BindingType::Sampler(SamplerBindingType::Filtering), None)
+        .append(ShaderStages::FRAGMENT, cube, None)
+        .append(ShaderStages::FRAGMENT, cube, None)
+        .append(
+            ShaderStages::FRAGMENT,
+            BindingType::Texture {
+                sample_type: TextureSampleType::Float { filterable: true },
+                view_dimension: TextureViewDimension::D2,
+                multisampled: false,
+            },
+            None,
+        )
+        .build(device, Some("pbr environment bgl"))
+}
+
+fn create_environment_bg(
+    device: &Device,
+    bgl: &BindGroupLayout,
+    sampler: &Sampler,
+    [irradiance, prefiltered]: [&TextureView; 2],
+    brdf_lut: &TextureView,
+    rotation: Option<Quat>,
+) -> BindGroup {
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("pbr environment uniforms"),
+        size: ShaderEnvironment::SHADER_SIZE.get(),
+        usage: BufferUsages::UNIFORM,
+        mapped_at_creation: true,
+    });
+    let mut mapping = buffer.slice(..).get_mapped_range_mut();
+    let uniforms = ShaderEnvironment {
+        inv_rotation: Mat3::from_quat(rotation.unwrap_or_default().inverse()),
+        enabled: rotation.is_some() as u32,
+    };
+    UniformBuffer::new(&mut *mapping).write(&uniforms).unwrap();
+    drop(mapping);
+    buffer.unmap();
+
+    BindGroupBuilder::new()
+        .append_buffer(&buffer)
+        .append_sampler(sampler)
+        .append_texture_view(irradiance)
+        .append_texture_view(prefiltered)
+        .append_texture_view(brdf_lut)
+        .build(device, Some("pbr environment bg"), bgl)
 }
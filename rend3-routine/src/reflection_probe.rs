@@ -0,0 +1,273 @@
+//! Captures of the scene into cubemaps, for reflections.
+//!
+//! A [`ReflectionProbe`] renders the scene through the base rendergraph six
+//! times, once per cubemap face, from its position. The resulting cubemap can
+//! be used as the [`PbrEnvironment`](crate::pbr::PbrEnvironment) of the PBR
+//! routine. As capturing is as expensive as rendering six frames, probes are
+//! usually only updated every few frames.
+
+use std::sync::Arc;
+
+use glam::{Mat3, Mat4, UVec2, Vec3};
+use rend3::{
+    graph::{InstructionEvaluationOutput, RenderGraph, ViewportRect},
+    managers::TextureCreationError,
+    types::{Camera, CameraProjection, Handedness, MipmapCount, MipmapSource, SampleCount, Texture, TextureCubeHandle},
+    Renderer, ShaderPreProcessor,
+};
+use wgpu::{
+    CommandEncoderDescriptor, Extent3d, ImageCopyTexture, Origin3d, TextureAspect, TextureDescriptor, TextureDimension,
+    TextureFormat, TextureUsages,
+};
+
+use crate::{
+    base::{
+        BaseRenderGraph, BaseRenderGraphInputs, BaseRenderGraphRoutines, BaseRenderGraphSettings, InputCamera,
+        OutputRenderTarget,
+    },
+    common::WholeFrameInterfaces,
+    pbr::PbrRoutine,
+    skybox::SkyboxRoutine,
+    tonemapping::{OutputTransfer, TonemappingRoutine},
+};
+
+/// Right, up and forward directions of each cubemap face, in the order and
+/// orientation wgpu samples cubemaps with.
+const FACE_BASES: [[Vec3; 3]; 6] = [
+    [Vec3::NEG_Z, Vec3::Y, Vec3::X],
+    [Vec3::Z, Vec3::Y, Vec3::NEG_X],
+    [Vec3::X, Vec3::NEG_Z, Vec3::Y],
+    [Vec3::X, Vec3::Z, Vec3::NEG_Y],
+    [Vec3::X, Vec3::Y, Vec3::Z],
+    [Vec3::NEG_X, Vec3::Y, Vec3::NEG_Z],
+];
+
+/// Camera looking through the given face of a cubemap centered on `position`.
+///
+/// Cubemap faces are laid out for a left handed world, so the view is
+/// mirrored in a right handed one.
+pub fn cube_face_camera(face: u32, position: Vec3, near: f32, handedness: Handedness) -> Camera {
+    let [right, up, forward] = FACE_BASES[face as usize];
+    // View space looks down +Z when left handed and -Z when right handed.
+    let forward = match handedness {
+        Handedness::Left => forward,
+        Handedness::Right => -forward,
+    };
+    let rotation = Mat3::from_cols(right, up, forward).transpose();
+    let view = Mat4::from_mat3(rotation) * Mat4::from_translation(-position);
+
+    Camera { projection: CameraProjection::Perspective { vfov: 90.0, near }, view }
+}
+
+/// Captures the scene around a point into a cubemap.
+///
+/// The capture is HDR and isn't filtered, so using it for both the irradiance
+/// and the prefiltered cubemap gives cheap, approximate lighting, like
+/// [`SkyboxRoutine::capture_to_cube`] does for the skybox.
+pub struct ReflectionProbe {
+    /// Point the scene is captured around.
+    pub position: Vec3,
+    /// Distance of the near plane of the capture cameras. Objects closer to
+    /// the probe than this are clipped, which is useful to hide the object
+    /// showing the reflection.
+    pub near: f32,
+    /// Number of frames between captures made by [`Self::update`]. 1 captures
+    /// every frame.
+    pub update_interval: u32,
+    /// Settings used for the captures. Set these to match the main view, as
+    /// the defaults have no ambient light and a transparent background.
+    pub settings: BaseRenderGraphSettings,
+    first_camera_id: u32,
+    resolution: u32,
+    handle: TextureCubeHandle,
+    face: wgpu::Texture,
+    tonemapping: TonemappingRoutine,
+    frames_until_update: u32,
+}
+
+impl ReflectionProbe {
+    /// Create a probe capturing cubemaps with faces of `resolution` pixels
+    /// square. `interfaces` must be those of the [`BaseRenderGraph`] the
+    /// probe captures with.
+    ///
+    /// The faces are rendered with the [`InputCamera`] ids
+    /// `first_camera_id..first_camera_id + 6`, which must not be used by other
+    /// probes or views.
+    pub fn new(
+        renderer: &Arc<Renderer>,
+        spp: &ShaderPreProcessor,
+        interfaces: &WholeFrameInterfaces,
+        position: Vec3,
+        resolution: u32,
+        first_camera_id: u32,
+    ) -> Result<Self, TextureCreationError> {
+        let handle = renderer.add_texture_cube(Texture {
+            label: Some("reflection probe".into()),
+            data: vec![0; resolution as usize * resolution as usize * 6 * 8],
+            format: TextureFormat::Rgba16Float,
+            size: UVec2::splat(resolution),
+            mip_count: MipmapCount::ONE,
+            mip_source: MipmapSource::Uploaded,
+        })?;
+
+        // Textures of the texture manager can't be rendered to, so each face is rendered here and copied.
+        let face = renderer.device.create_texture(&TextureDescriptor {
+            label: Some("reflection probe face"),
+            size: Extent3d { width: resolution, height: resolution, depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::Rgba16Float,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+
+        let tonemapping = TonemappingRoutine::new(
+            renderer,
+            spp,
+            interfaces,
+            TextureFormat::Rgba16Float,
+            OutputTransfer::PassthroughHdr,
+        );
+
+        Ok(Self {
+            position,
+            near: 0.1,
+            update_interval: 1,
+            settings: BaseRenderGraphSettings::default(),
+            first_camera_id,
+            resolution,
+            handle,
+            face,
+            tonemapping,
+            frames_until_update: 0,
+        })
+    }
+
+    /// Cubemap the scene is captured into.
+    pub fn handle(&self) -> &TextureCubeHandle {
+        &self.handle
+    }
+
+    /// Size of each face of the cubemap in pixels.
+    pub fn resolution(&self) -> u32 {
+        self.resolution
+    }
+
+    /// Counts a frame, capturing the scene if [`Self::update_interval`] frames
+    /// have passed since the last capture. The first call always captures.
+    /// Returns whether the scene was captured.
+    ///
+    /// See [`Self::capture`] for the requirements.
+    pub fn update(
+        &mut self,
+        renderer: &Arc<Renderer>,
+        base: &BaseRenderGraph,
+        pbr: &PbrRoutine,
+        skybox: Option<&SkyboxRoutine>,
+        eval_output: &mut InstructionEvaluationOutput,
+    ) -> bool {
+        if self.frames_until_update > 0 {
+            self.frames_until_update -= 1;
+            return false;
+        }
+
+        self.capture(renderer, base, pbr, skybox, eval_output);
+        self.frames_until_update = self.update_interval.saturating_sub(1);
+        true
+    }
+
+    /// Captures the scene into the cubemap, executing one graph per face.
+    ///
+    /// Call this after [`Renderer::evaluate_instructions`] has added the
+    /// cubemap, with its output. Shadows follow the renderer's camera.
+    pub fn capture(
+        &self,
+        renderer: &Arc<Renderer>,
+        base: &BaseRenderGraph,
+        pbr: &PbrRoutine,
+        skybox: Option<&SkyboxRoutine>,
+        eval_output: &mut InstructionEvaluationOutput,
+    ) {
+        profiling::scope!("Reflection Probe Capture");
+
+        let size = UVec2::splat(self.resolution);
+        for face in 0..6 {
+            let mut graph = RenderGraph::new();
+            let handle = graph.add_imported_render_target(&self.face, 0..1, 0..1, ViewportRect::from_size(size));
+            base.add_to_graph(
+                &mut graph,
+                BaseRenderGraphInputs {
+                    eval_output,
+                    routines: BaseRenderGraphRoutines {
+                        pbr,
+                        skybox,
+                        tonemapping: &self.tonemapping,
+                        color_grade: None,
+                        debug_draw: None,
+                    },
+                    target: OutputRenderTarget { handle, resolution: size, samples: SampleCount::One },
+                    depth: None,
+                    camera: Some(InputCamera {
+                        camera: cube_face_camera(face, self.position, self.near, renderer.handedness),
+                        id: self.first_camera_id + face,
+                    }),
+                },
+                self.settings.clone(),
+            );
+            graph.execute(renderer, eval_output);
+
+            let data_core = renderer.data_core.lock();
+            let target = &data_core.d2c_texture_manager.get_internal(self.handle.get_raw()).texture;
+            let mut encoder = renderer
+                .device
+                .create_command_encoder(&CommandEncoderDescriptor { label: Some("reflection probe copy") });
+            encoder.copy_texture_to_texture(
+                self.face.as_image_copy(),
+                ImageCopyTexture {
+                    texture: target,
+                    mip_level: 0,
+                    origin: Origin3d { x: 0, y: 0, z: face },
+                    aspect: TextureAspect::All,
+                },
+                Extent3d { width: self.resolution, height: self.resolution, depth_or_array_layers: 1 },
+            );
+            renderer.queue.submit(Some(encoder.finish()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{Vec3, Vec4Swizzles};
+    use rend3::types::Handedness;
+
+    use super::cube_face_camera;
+
+    #[test]
+    fn faces_look_along_their_axis() {
+        let position = Vec3::new(1.0, 2.0, 3.0);
+        let forwards = [Vec3::X, Vec3::NEG_X, Vec3::Y, Vec3::NEG_Y, Vec3::Z, Vec3::NEG_Z];
+        for (face, forward) in forwards.into_iter().enumerate() {
+            for handedness in [Handedness::Left, Handedness::Right] {
+                let view = cube_face_camera(face as u32, position, 0.1, handedness).view;
+                let depth = match handedness {
+                    Handedness::Left => 1.0,
+                    Handedness::Right => -1.0,
+                };
+                let point = view * (position + forward).extend(1.0);
+                assert!(point.xyz().abs_diff_eq(Vec3::new(0.0, 0.0, depth), 1e-5), "{face} {handedness:?} {point}");
+            }
+        }
+    }
+
+    #[test]
+    fn right_handed_faces_are_mirrored() {
+        for face in 0..6 {
+            let left = cube_face_camera(face, Vec3::ZERO, 0.1, Handedness::Left).view;
+            let right = cube_face_camera(face, Vec3::ZERO, 0.1, Handedness::Right).view;
+            assert!(left.determinant() > 0.0);
+            assert!(right.determinant() < 0.0);
+        }
+    }
+}
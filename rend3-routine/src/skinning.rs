@@ -1,11 +1,12 @@
-use std::{borrow::Cow, mem};
+use std::borrow::Cow;
 
 use encase::{ShaderSize, ShaderType};
 use glam::Mat4;
 use rend3::{
     graph::{NodeExecutionContext, RenderGraph},
     types::{
-        VERTEX_ATTRIBUTE_JOINT_INDICES, VERTEX_ATTRIBUTE_JOINT_WEIGHTS, VERTEX_ATTRIBUTE_NORMAL,
+        SkinningMethod, VERTEX_ATTRIBUTE_JOINT_INDICES, VERTEX_ATTRIBUTE_JOINT_INDICES_1,
+        VERTEX_ATTRIBUTE_JOINT_WEIGHTS, VERTEX_ATTRIBUTE_JOINT_WEIGHTS_1, VERTEX_ATTRIBUTE_NORMAL,
         VERTEX_ATTRIBUTE_POSITION, VERTEX_ATTRIBUTE_TANGENT,
     },
     util::{
@@ -32,6 +33,10 @@ pub struct GpuSkinningInput {
     joint_indices_offset: u32,
     /// Byte offset into vertex buffer of joint weights of mesh.
     joint_weight_offset: u32,
+    /// Byte offset into vertex buffer of the second set of joint indices of mesh.
+    joint_indices_1_offset: u32,
+    /// Byte offset into vertex buffer of the second set of joint weights of mesh.
+    joint_weight_1_offset: u32,
     /// Byte offset into vertex buffer of position attribute of skinned mesh.
     updated_position_offset: u32,
     /// Byte offset into vertex buffer of normal attribute of skinned mesh.
@@ -43,12 +48,14 @@ pub struct GpuSkinningInput {
     joint_matrix_base_offset: u32,
     /// Count of vertices in this mesh.
     vertex_count: u32,
+    /// 0 for linear blend skinning, 1 for dual quaternion skinning.
+    dual_quaternion: u32,
 }
 
-/// The two buffers uploaded to the GPU during pre-skinning.
+/// The buffer uploaded to the GPU during pre-skinning. The joint matrices
+/// are kept up to date by the skeleton manager.
 pub struct PreSkinningBuffers {
     gpu_skinning_inputs: Buffer,
-    joint_matrices: Buffer,
 }
 
 fn build_gpu_skinning_input_buffers(ctx: &NodeExecutionContext) -> PreSkinningBuffers {
@@ -63,79 +70,56 @@ fn build_gpu_skinning_input_buffers(ctx: &NodeExecutionContext) -> PreSkinningBu
         mapped_at_creation: true,
     });
 
-    let joint_matrices = ctx.renderer.device.create_buffer(&BufferDescriptor {
-        label: Some("joint matrices"),
-        size: (ctx.data_core.skeleton_manager.global_joint_count() * mem::size_of::<Mat4>()) as u64,
-        usage: BufferUsages::STORAGE,
-        mapped_at_creation: true,
-    });
-
     let mut skinning_input_range = gpu_skinning_inputs.slice(..).get_mapped_range_mut();
     let mut skinning_input_data = encase::DynamicStorageBuffer::new(&mut *skinning_input_range);
-    let mut joint_matrices_data = joint_matrices.slice(..).get_mapped_range_mut();
-
-    // Skeletons have a variable number of joints, so we need to keep track of
-    // the global index here.
-    let mut joint_matrix_idx = 0;
 
-    // Iterate over the skeletons, fill the buffers
+    // Iterate over the skeletons, fill the buffer
     for skeleton in ctx.data_core.skeleton_manager.skeletons() {
-        // SAFETY: We are always accessing elements in bounds and all accesses are
-        // aligned
-        unsafe {
-            let mut input = GpuSkinningInput {
-                base_position_offset: u32::MAX,
-                base_normal_offset: u32::MAX,
-                base_tangent_offset: u32::MAX,
-                joint_indices_offset: u32::MAX,
-                joint_weight_offset: u32::MAX,
-                updated_position_offset: u32::MAX,
-                updated_normal_offset: u32::MAX,
-                updated_tangent_offset: u32::MAX,
-                joint_matrix_base_offset: joint_matrix_idx,
-                vertex_count: skeleton.vertex_count,
-            };
-
-            for (attribute, range) in &skeleton.source_attribute_ranges {
-                match attribute {
-                    a if *a == *VERTEX_ATTRIBUTE_POSITION => input.base_position_offset = range.start as u32,
-                    a if *a == *VERTEX_ATTRIBUTE_NORMAL => input.base_normal_offset = range.start as u32,
-                    a if *a == *VERTEX_ATTRIBUTE_TANGENT => input.base_tangent_offset = range.start as u32,
-                    a if *a == *VERTEX_ATTRIBUTE_JOINT_INDICES => input.joint_indices_offset = range.start as u32,
-                    a if *a == *VERTEX_ATTRIBUTE_JOINT_WEIGHTS => input.joint_weight_offset = range.start as u32,
-                    a => unreachable!("Unknown skinning input attribute {a:?}"),
-                }
+        let mut input = GpuSkinningInput {
+            base_position_offset: u32::MAX,
+            base_normal_offset: u32::MAX,
+            base_tangent_offset: u32::MAX,
+            joint_indices_offset: u32::MAX,
+            joint_weight_offset: u32::MAX,
+            joint_indices_1_offset: u32::MAX,
+            joint_weight_1_offset: u32::MAX,
+            updated_position_offset: u32::MAX,
+            updated_normal_offset: u32::MAX,
+            updated_tangent_offset: u32::MAX,
+            joint_matrix_base_offset: skeleton.joint_range.start,
+            vertex_count: skeleton.vertex_count,
+            dual_quaternion: (skeleton.skinning_method == SkinningMethod::DualQuaternion) as u32,
+        };
+
+        for (attribute, range) in &skeleton.source_attribute_ranges {
+            match attribute {
+                a if *a == *VERTEX_ATTRIBUTE_POSITION => input.base_position_offset = range.start as u32,
+                a if *a == *VERTEX_ATTRIBUTE_NORMAL => input.base_normal_offset = range.start as u32,
+                a if *a == *VERTEX_ATTRIBUTE_TANGENT => input.base_tangent_offset = range.start as u32,
+                a if *a == *VERTEX_ATTRIBUTE_JOINT_INDICES => input.joint_indices_offset = range.start as u32,
+                a if *a == *VERTEX_ATTRIBUTE_JOINT_WEIGHTS => input.joint_weight_offset = range.start as u32,
+                a if *a == *VERTEX_ATTRIBUTE_JOINT_INDICES_1 => input.joint_indices_1_offset = range.start as u32,
+                a if *a == *VERTEX_ATTRIBUTE_JOINT_WEIGHTS_1 => input.joint_weight_1_offset = range.start as u32,
+                a => unreachable!("Unknown skinning input attribute {a:?}"),
             }
+        }
 
-            for (attribute, range) in &skeleton.overridden_attribute_ranges {
-                match attribute {
-                    a if *a == *VERTEX_ATTRIBUTE_POSITION => input.updated_position_offset = range.start as u32,
-                    a if *a == *VERTEX_ATTRIBUTE_NORMAL => input.updated_normal_offset = range.start as u32,
-                    a if *a == *VERTEX_ATTRIBUTE_TANGENT => input.updated_tangent_offset = range.start as u32,
-                    a => unreachable!("Unknown skinning output attribute {a:?}"),
-                }
-            }
-
-            skinning_input_data.write(&input).unwrap();
-
-            let joint_matrices_ptr = joint_matrices_data.as_mut_ptr() as *mut [[f32; 4]; 4];
-            for joint_matrix in &skeleton.joint_matrices {
-                // Here, the access can't be OOB either: The joint_matrix_idx
-                // will get incremented once for every joint matrix, and the
-                // length of the buffer is exactly the sum of all joint matrix
-                // vector lengths.
-                joint_matrices_ptr.add(joint_matrix_idx as usize).write_unaligned(joint_matrix.to_cols_array_2d());
-                joint_matrix_idx += 1;
+        for (attribute, range) in &skeleton.overridden_attribute_ranges {
+            match attribute {
+                a if *a == *VERTEX_ATTRIBUTE_POSITION => input.updated_position_offset = range.start as u32,
+                a if *a == *VERTEX_ATTRIBUTE_NORMAL => input.updated_normal_offset = range.start as u32,
+                a if *a == *VERTEX_ATTRIBUTE_TANGENT => input.updated_tangent_offset = range.start as u32,
+                a => unreachable!("Unknown skinning output attribute {a:?}"),
             }
         }
+
+        skinning_input_data.write(&input).unwrap();
     }
 
     drop(skinning_input_range);
-    drop(joint_matrices_data);
     gpu_skinning_inputs.unmap();
-    joint_matrices.unmap();
 
-    PreSkinningBuffers { gpu_skinning_inputs, joint_matrices }
+    PreSkinningBuffers { gpu_skinning_inputs }
 }
 
 /// Holds the necessary wgpu data structures for the GPU skinning compute pass
@@ -182,7 +166,7 @@ impl GpuSkinner {
         let bg = BindGroupBuilder::new()
             .append_buffer(&ctx.eval_output.mesh_buffer)
             .append_buffer_with_size(&buffers.gpu_skinning_inputs, GpuSkinningInput::SHADER_SIZE.get())
-            .append_buffer(&buffers.joint_matrices)
+            .append_buffer(ctx.data_core.skeleton_manager.joint_buffer())
             .build(&ctx.renderer.device, Some("GPU skinning inputs"), &self.bgl);
 
         let mut cpass =
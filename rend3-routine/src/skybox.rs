@@ -2,6 +2,8 @@
 
 use std::borrow::Cow;
 
+use encase::{ShaderSize, ShaderType, UniformBuffer};
+use glam::{Mat3, Quat};
 use rend3::{
     graph::{DataHandle, NodeResourceUsage, RenderGraph, RenderPassTargets},
     types::{SampleCount, TextureCubeHandle},
@@ -9,14 +11,43 @@ use rend3::{
     Renderer, ShaderConfig, ShaderPreProcessor,
 };
 use wgpu::{
-    BindGroup, BindGroupLayout, BindingType, ColorTargetState, ColorWrites, CompareFunction, DepthBiasState,
-    DepthStencilState, Face, FragmentState, FrontFace, MultisampleState, PipelineLayoutDescriptor, PolygonMode,
-    PrimitiveState, PrimitiveTopology, RenderPipeline, RenderPipelineDescriptor, ShaderModuleDescriptor, ShaderSource,
-    ShaderStages, StencilState, TextureFormat, TextureSampleType, TextureViewDimension, VertexState,
+    BindGroup, BindGroupLayout, BindingType, Buffer, BufferBindingType, BufferDescriptor, BufferUsages,
+    ColorTargetState, ColorWrites, CommandEncoderDescriptor, DepthBiasState, DepthStencilState, Extent3d, Face,
+    FilterMode, FragmentState, FrontFace, ImageCopyTexture, LoadOp, MultisampleState, Operations, Origin3d,
+    PipelineLayoutDescriptor, PolygonMode, PrimitiveState, PrimitiveTopology, RenderPassColorAttachment,
+    RenderPassDescriptor, RenderPipeline, RenderPipelineDescriptor, Sampler, SamplerBindingType, SamplerDescriptor,
+    ShaderModuleDescriptor, ShaderSource, ShaderStages, StencilState, StoreOp, TextureAspect, TextureDescriptor,
+    TextureDimension, TextureFormat, TextureSampleType, TextureUsages, TextureViewDescriptor, TextureViewDimension,
+    VertexState,
 };
 
 use crate::common::WholeFrameInterfaces;
 
+#[derive(Debug, Copy, Clone, ShaderType)]
+struct SkyboxUniforms {
+    inv_rotation: Mat3,
+    intensity: f32,
+    mip_bias: f32,
+}
+
+/// How the skybox samples the background texture.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SkyboxSampling {
+    /// Maximum anisotropy of the sampler, from 1 to 16. Anisotropic
+    /// filtering keeps the sky crisp where the texture is viewed at a steep
+    /// angle, like the horizon of a panorama. Defaults to 1, which disables it.
+    pub anisotropy: u16,
+    /// Added to the mip level the background is sampled from. Negative values
+    /// sharpen, positive ones blur. Defaults to 0.0.
+    pub mip_bias: f32,
+}
+
+impl Default for SkyboxSampling {
+    fn default() -> Self {
+        Self { anisotropy: 1, mip_bias: 0.0 }
+    }
+}
+
 struct StoredSkybox {
     bg: Option<BindGroup>,
     handle: Option<TextureCubeHandle>,
@@ -28,7 +59,14 @@ struct StoredSkybox {
 pub struct SkyboxRoutine {
     pipelines: SkyboxPipelines,
     bgl: BindGroupLayout,
+    uniform_buffer: Buffer,
     current_skybox: StoredSkybox,
+    capture_bg: BindGroup,
+    /// Rebuilt by [`Self::evaluate`] when `None`.
+    sampler: Option<Sampler>,
+    sampling: SkyboxSampling,
+    rotation: Quat,
+    intensity: f32,
 }
 
 impl SkyboxRoutine {
@@ -44,11 +82,43 @@ impl SkyboxRoutine {
                 },
                 None,
             )
+            .append_buffer(ShaderStages::FRAGMENT, BufferBindingType::Uniform, false, SkyboxUniforms::SHADER_SIZE.get())
+            .append(ShaderStages::FRAGMENT, BindingType::Sampler(SamplerBindingType::Filtering), None)
             .build(&renderer.device, Some("skybox bgl"));
 
+        let uniform_buffer = renderer.device.create_buffer(&BufferDescriptor {
+            label: Some("skybox uniforms"),
+            size: SkyboxUniforms::SHADER_SIZE.get(),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
         let pipelines = SkyboxPipelines::new(renderer, spp, interfaces, &bgl);
 
-        Self { current_skybox: StoredSkybox { bg: None, handle: None }, bgl, pipelines }
+        let capture_sampler = renderer.device.create_sampler(&SamplerDescriptor {
+            label: Some("skybox capture sampler"),
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            mipmap_filter: FilterMode::Linear,
+            ..Default::default()
+        });
+        let capture_bg = BindGroupBuilder::new().append_sampler(&capture_sampler).build(
+            &renderer.device,
+            Some("skybox capture"),
+            &pipelines.capture_bgl,
+        );
+
+        Self {
+            current_skybox: StoredSkybox { bg: None, handle: None },
+            capture_bg,
+            sampler: None,
+            sampling: SkyboxSampling::default(),
+            bgl,
+            uniform_buffer,
+            pipelines,
+            rotation: Quat::IDENTITY,
+            intensity: 1.0,
+        }
     }
 
     /// Set the current background texture. Bad things will happen if this isn't
@@ -58,6 +128,35 @@ impl SkyboxRoutine {
         self.current_skybox.bg = None;
     }
 
+    /// Set the rotation of the background texture, relative to world space.
+    ///
+    /// This does not affect image based lighting, so set
+    /// [`PbrEnvironment::rotation`](crate::pbr::PbrEnvironment::rotation) to
+    /// the same value to keep reflections lined up with the background.
+    pub fn set_background_rotation(&mut self, rotation: Quat) {
+        self.rotation = rotation;
+    }
+
+    /// Set the multiplier applied to the background texture. Defaults to 1.0.
+    pub fn set_background_intensity(&mut self, intensity: f32) {
+        self.intensity = intensity;
+    }
+
+    /// Set how the background texture is sampled. Takes effect after the next
+    /// call to [`Self::evaluate`].
+    pub fn set_background_sampling(&mut self, sampling: SkyboxSampling) {
+        if self.sampling.anisotropy != sampling.anisotropy {
+            self.sampler = None;
+            self.current_skybox.bg = None;
+        }
+        self.sampling = sampling;
+    }
+
+    /// Returns how the background texture is sampled.
+    pub fn background_sampling(&self) -> SkyboxSampling {
+        self.sampling
+    }
+
     /// Evaluate any changes that have happened to the skybox routine.
     pub fn evaluate(&mut self, renderer: &Renderer) {
         let data_core = renderer.data_core.lock();
@@ -65,10 +164,23 @@ impl SkyboxRoutine {
 
         profiling::scope!("Update Skybox");
 
+        let mut data = UniformBuffer::new(Vec::new());
+        data.write(&SkyboxUniforms {
+            inv_rotation: Mat3::from_quat(self.rotation.inverse()),
+            intensity: self.intensity,
+            mip_bias: self.sampling.mip_bias,
+        })
+        .unwrap();
+        renderer.queue.write_buffer(&self.uniform_buffer, 0, &data.into_inner());
+
+        let sampler = self.sampler.get_or_insert_with(|| create_sampler(renderer, self.sampling));
+
         if let Some(ref handle) = self.current_skybox.handle {
             if self.current_skybox.bg.is_none() {
                 let bg = BindGroupBuilder::new()
                     .append_texture_view(d2c_texture_manager.get_view(handle.get_raw()))
+                    .append_buffer(&self.uniform_buffer)
+                    .append_sampler(sampler)
                     .build(&renderer.device, Some("skybox"), &self.bgl);
 
                 self.current_skybox.bg = Some(bg)
@@ -76,6 +188,75 @@ impl SkyboxRoutine {
         }
     }
 
+    /// Renders the skybox into the first mip of every face of `target`, for
+    /// example to use as the environment of image based lighting without an
+    /// authored cubemap. Call again whenever the skybox changes.
+    ///
+    /// The capture is in world space, with the background rotation applied,
+    /// so the [`PbrEnvironment`](crate::pbr::PbrEnvironment) using it should
+    /// not be rotated. It isn't filtered, so using it for both the irradiance
+    /// and the prefiltered cubemap gives cheap, approximate lighting.
+    ///
+    /// `target` must be an `Rgba16Float` cube texture which has already been
+    /// added by [`Renderer::evaluate_instructions`]. Call this after
+    /// [`Self::evaluate`]. Does nothing if there is no background texture.
+    pub fn capture_to_cube(&self, renderer: &Renderer, target: &TextureCubeHandle) {
+        profiling::scope!("Skybox Capture");
+
+        let Some(ref bg) = self.current_skybox.bg else {
+            return;
+        };
+
+        let data_core = renderer.data_core.lock();
+        let target = &data_core.d2c_texture_manager.get_internal(target.get_raw()).texture;
+        assert_eq!(target.format(), TextureFormat::Rgba16Float, "Skybox captures must be Rgba16Float");
+
+        // Textures of the texture manager can't be rendered to, so we render into an intermediate and copy.
+        let size = Extent3d { width: target.width(), height: target.height(), depth_or_array_layers: 6 };
+        let faces = renderer.device.create_texture(&TextureDescriptor {
+            label: Some("skybox capture"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::Rgba16Float,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+
+        let mut encoder =
+            renderer.device.create_command_encoder(&CommandEncoderDescriptor { label: Some("skybox capture") });
+        for face in 0..6 {
+            let view = faces.create_view(&TextureViewDescriptor {
+                dimension: Some(TextureViewDimension::D2),
+                base_array_layer: face,
+                array_layer_count: Some(1),
+                ..Default::default()
+            });
+            let mut rpass = encoder.begin_render_pass(&RenderPassDescriptor {
+                label: Some("skybox capture face"),
+                color_attachments: &[Some(RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    ops: Operations { load: LoadOp::Clear(wgpu::Color::BLACK), store: StoreOp::Store },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            rpass.set_pipeline(&self.pipelines.capture);
+            rpass.set_bind_group(0, &self.capture_bg, &[]);
+            rpass.set_bind_group(1, bg, &[]);
+            rpass.draw(0..3, face..face + 1);
+        }
+        encoder.copy_texture_to_texture(
+            faces.as_image_copy(),
+            ImageCopyTexture { texture: target, mip_level: 0, origin: Origin3d::ZERO, aspect: TextureAspect::All },
+            size,
+        );
+        renderer.queue.submit(Some(encoder.finish()));
+    }
+
     /// Add rendering the skybox to the given rendergraph.
     pub fn add_to_graph<'node>(
         &'node self,
@@ -110,10 +291,26 @@ impl SkyboxRoutine {
     }
 }
 
+fn create_sampler(renderer: &Renderer, sampling: SkyboxSampling) -> Sampler {
+    renderer.device.create_sampler(&SamplerDescriptor {
+        label: Some("skybox sampler"),
+        mag_filter: FilterMode::Linear,
+        min_filter: FilterMode::Linear,
+        mipmap_filter: FilterMode::Linear,
+        anisotropy_clamp: sampling.anisotropy.clamp(1, 16),
+        ..Default::default()
+    })
+}
+
 /// Container for all needed skybox pipelines
 pub struct SkyboxPipelines {
     pub pipeline_s1: RenderPipeline,
     pub pipeline_s4: RenderPipeline,
+    /// Renders cubemap faces for [`SkyboxRoutine::capture_to_cube`].
+    pub capture: RenderPipeline,
+    /// Holds the sampler of the capture pipeline, in place of the frame
+    /// uniforms.
+    pub capture_bgl: BindGroupLayout,
 }
 impl SkyboxPipelines {
     pub fn new(
@@ -126,7 +323,12 @@ impl SkyboxPipelines {
         let skybox_sm = renderer.device.create_shader_module(ShaderModuleDescriptor {
             label: Some("skybox vert"),
             source: ShaderSource::Wgsl(Cow::Owned(
-                spp.render_shader("rend3-routine/skybox.wgsl", &ShaderConfig::default(), None).unwrap(),
+                spp.render_shader(
+                    "rend3-routine/skybox.wgsl",
+                    &ShaderConfig { forward_z: !renderer.depth_convention.is_reverse(), ..Default::default() },
+                    None,
+                )
+                .unwrap(),
             )),
         });
 
@@ -150,10 +352,10 @@ impl SkyboxPipelines {
                     polygon_mode: PolygonMode::Fill,
                     conservative: false,
                 },
-                depth_stencil: Some(DepthStencilState {
-                    format: TextureFormat::Depth32Float,
+                depth_stencil: interfaces.depth_format.map(|format| DepthStencilState {
+                    format,
                     depth_write_enabled: true,
-                    depth_compare: CompareFunction::GreaterEqual,
+                    depth_compare: renderer.depth_convention.closer_equal(),
                     stencil: StencilState::default(),
                     bias: DepthBiasState::default(),
                 }),
@@ -162,7 +364,7 @@ impl SkyboxPipelines {
                     module: &skybox_sm,
                     entry_point: "fs_main",
                     targets: &[Some(ColorTargetState {
-                        format: TextureFormat::Rgba16Float,
+                        format: interfaces.hdr_format,
                         blend: None,
                         write_mask: ColorWrites::all(),
                     })],
@@ -171,6 +373,35 @@ impl SkyboxPipelines {
             })
         };
 
-        Self { pipeline_s1: inner(SampleCount::One), pipeline_s4: inner(SampleCount::Four) }
+        let capture_bgl = BindGroupLayoutBuilder::new()
+            .append(ShaderStages::FRAGMENT, BindingType::Sampler(SamplerBindingType::Filtering), None)
+            .build(&renderer.device, Some("skybox capture bgl"));
+
+        let capture_pll = renderer.device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("skybox capture"),
+            bind_group_layouts: &[&capture_bgl, bgl],
+            push_constant_ranges: &[],
+        });
+
+        let capture = renderer.device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("skybox capture"),
+            layout: Some(&capture_pll),
+            vertex: VertexState { module: &skybox_sm, entry_point: "vs_capture", buffers: &[] },
+            primitive: PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+            fragment: Some(FragmentState {
+                module: &skybox_sm,
+                entry_point: "fs_capture",
+                targets: &[Some(ColorTargetState {
+                    format: TextureFormat::Rgba16Float,
+                    blend: None,
+                    write_mask: ColorWrites::all(),
+                })],
+            }),
+            multiview: None,
+        });
+
+        Self { pipeline_s1: inner(SampleCount::One), pipeline_s4: inner(SampleCount::Four), capture, capture_bgl }
     }
 }
@@ -0,0 +1,154 @@
+//! Places the sun for a given time of day, for day-night cycles.
+//!
+//! [`TimeOfDay::sun_light`] makes a [`DirectionalLightChange`] to apply to the
+//! sun every frame with
+//! [`Renderer::update_directional_light`](rend3::Renderer::update_directional_light),
+//! and [`TimeOfDay::sky_color`] gives a matching ambient color.
+
+use std::f32::consts::{PI, TAU};
+
+use glam::{Vec3, Vec4};
+use rend3::types::DirectionalLightChange;
+
+/// Optical depth of a clear atmosphere looking straight up, per color
+/// channel. Blue scatters the most, reddening the sun near the horizon.
+const ZENITH_OPTICAL_DEPTH: Vec3 = Vec3::new(0.14, 0.2, 0.34);
+
+/// Directions in world space the sun is placed relative to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Compass {
+    pub up: Vec3,
+    pub north: Vec3,
+    pub east: Vec3,
+}
+
+impl Default for Compass {
+    /// Y up, north towards -Z and east towards +X.
+    fn default() -> Self {
+        Self { up: Vec3::Y, north: Vec3::NEG_Z, east: Vec3::X }
+    }
+}
+
+/// Place and time on the planet which decide where the sun is.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimeOfDay {
+    /// Latitude in degrees, positive towards the north pole.
+    pub latitude: f32,
+    /// Day of the year, where 0.0 is the start of January 1st.
+    pub day_of_year: f32,
+    /// Local solar time in hours, where 12.0 is when the sun is highest.
+    pub hour: f32,
+}
+
+impl TimeOfDay {
+    /// Unit vector pointing from the ground towards the sun.
+    pub fn sun_direction(&self, compass: &Compass) -> Vec3 {
+        // Approximation of the declination, from the tilt of the earth.
+        let declination = -23.44f32.to_radians() * (TAU / 365.0 * (self.day_of_year + 10.0)).cos();
+        let hour_angle = (self.hour - 12.0) * (PI / 12.0);
+        let latitude = self.latitude.to_radians();
+
+        let east = -declination.cos() * hour_angle.sin();
+        let north = latitude.cos() * declination.sin() - latitude.sin() * declination.cos() * hour_angle.cos();
+        let up = latitude.sin() * declination.sin() + latitude.cos() * declination.cos() * hour_angle.cos();
+
+        (compass.east * east + compass.north * north + compass.up * up).normalize()
+    }
+
+    /// Sine of the angle of the sun above the horizon.
+    fn sun_elevation_sin(&self) -> f32 {
+        self.sun_direction(&Compass::default()).y
+    }
+
+    /// Sunlight after passing through the atmosphere, as a color normalized
+    /// to a maximum of 1.0 and an intensity relative to the sun at zenith.
+    /// The intensity is zero once the sun has set.
+    pub fn sun_color(&self) -> (Vec3, f32) {
+        let elevation_sin = self.sun_elevation_sin();
+        if elevation_sin <= 0.0 {
+            return (Vec3::ONE, 0.0);
+        }
+
+        // Relative airmass, from Kasten and Young 1989, which stays finite at the horizon.
+        let zenith = elevation_sin.acos().to_degrees();
+        let airmass = 1.0 / (elevation_sin + 0.50572 * (96.07995 - zenith).powf(-1.6364));
+
+        // Relative to the transmittance at zenith, which has an airmass of 1.
+        let relative = (-ZENITH_OPTICAL_DEPTH * (airmass - 1.0)).to_array().map(f32::exp);
+        let relative = Vec3::from_array(relative);
+        let max = relative.max_element();
+
+        (relative / max, max)
+    }
+
+    /// Directional light change placing the sun, with `intensity` being the
+    /// intensity with the sun at zenith.
+    pub fn sun_light(&self, compass: &Compass, intensity: f32) -> DirectionalLightChange {
+        let (color, relative_intensity) = self.sun_color();
+        DirectionalLightChange {
+            color: Some(color),
+            intensity: Some(intensity * relative_intensity),
+            direction: Some(-self.sun_direction(compass)),
+            ..Default::default()
+        }
+    }
+
+    /// Rough color of the sky, fading from blue during the day through a warm
+    /// twilight to dark blue at night. Meant for
+    /// [`BaseRenderGraphSettings::ambient_color`](crate::base::BaseRenderGraphSettings::ambient_color).
+    pub fn sky_color(&self) -> Vec4 {
+        let elevation_sin = self.sun_elevation_sin();
+
+        let night = Vec3::new(0.002, 0.003, 0.008);
+        let day = Vec3::new(0.25, 0.35, 0.5);
+        let twilight = Vec3::new(0.3, 0.15, 0.08);
+
+        let daylight = smoothstep(-0.1, 0.3, elevation_sin);
+        // Peaks with the sun on the horizon.
+        let twilight_amount = (1.0 - (elevation_sin / 0.15).abs()).max(0.0);
+
+        (night.lerp(day, daylight) + twilight * twilight_amount).extend(1.0)
+    }
+}
+
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec3;
+
+    use super::{Compass, TimeOfDay};
+
+    #[test]
+    fn equinox_noon_at_equator_is_overhead() {
+        // The declination is zero close to day 80.
+        let time = TimeOfDay { latitude: 0.0, day_of_year: 81.25, hour: 12.0 };
+        let direction = time.sun_direction(&Compass::default());
+        assert!(direction.abs_diff_eq(Vec3::Y, 0.01), "{direction}");
+
+        let (color, intensity) = time.sun_color();
+        assert!(color.abs_diff_eq(Vec3::ONE, 0.01), "{color}");
+        assert!((intensity - 1.0).abs() < 0.01, "{intensity}");
+    }
+
+    #[test]
+    fn sun_rises_in_the_east() {
+        let time = TimeOfDay { latitude: 45.0, day_of_year: 81.25, hour: 6.5 };
+        let direction = time.sun_direction(&Compass::default());
+        assert!(direction.x > 0.9, "{direction}");
+        assert!(direction.y > 0.0, "{direction}");
+
+        let (color, _) = time.sun_color();
+        assert!(color.x > color.z, "{color}");
+    }
+
+    #[test]
+    fn no_sun_at_night() {
+        let time = TimeOfDay { latitude: 45.0, day_of_year: 0.0, hour: 0.0 };
+        assert!(time.sun_direction(&Compass::default()).y < 0.0);
+        assert_eq!(time.sun_color().1, 0.0);
+    }
+}
@@ -0,0 +1,181 @@
+//! Offscreen rendering of single objects, for preview icons.
+//!
+//! Objects in a [`Renderer`] are all drawn together, so to draw an object on
+//! its own, [`ThumbnailRenderer`] owns a separate renderer sharing the device
+//! of the main one. Meshes and materials for thumbnails must be added to
+//! [`ThumbnailRenderer::renderer`].
+
+use std::sync::Arc;
+
+use glam::{UVec2, Vec4};
+use rend3::{
+    graph::{RenderGraph, ViewportRect},
+    types::{Camera, Handedness, Object, SampleCount},
+    InstanceAdapterDevice, Renderer, RendererInitializationError, ShaderPreProcessor,
+};
+use thiserror::Error;
+use wgpu::{
+    BufferAsyncError, BufferDescriptor, BufferUsages, CommandEncoderDescriptor, Extent3d, ImageCopyBuffer,
+    ImageDataLayout, Maintain, MapMode, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages,
+    COPY_BYTES_PER_ROW_ALIGNMENT,
+};
+
+use crate::{
+    background::Background,
+    base::{
+        BaseRenderGraph, BaseRenderGraphInputs, BaseRenderGraphRoutines, BaseRenderGraphSettings, OutputRenderTarget,
+    },
+    pbr::PbrRoutine,
+    tonemapping::{OutputTransfer, TonemappingRoutine},
+};
+
+#[derive(Debug, Error)]
+pub enum ThumbnailError {
+    #[error("Failed to map the thumbnail readback buffer")]
+    Map(#[source] BufferAsyncError),
+    #[error("The thumbnail readback was dropped before completing")]
+    Disconnected,
+}
+
+/// Renders single objects into small images.
+pub struct ThumbnailRenderer {
+    /// Renderer which only ever holds the object being rendered. Add the
+    /// meshes and materials of thumbnails to this.
+    pub renderer: Arc<Renderer>,
+    /// Settings used for every thumbnail. Defaults to a neutral grey
+    /// background lit by a white ambient light.
+    pub settings: BaseRenderGraphSettings,
+    base_rendergraph: BaseRenderGraph,
+    pbr: PbrRoutine,
+    tonemapping: TonemappingRoutine,
+}
+
+impl ThumbnailRenderer {
+    /// Create a thumbnail renderer on the device of the given IAD. Passing a
+    /// clone of the IAD used by the main renderer shares the device.
+    ///
+    /// `spp` must have the [`builtin_shaders`](crate::builtin_shaders) added.
+    pub fn new(
+        iad: InstanceAdapterDevice,
+        handedness: Handedness,
+        spp: &ShaderPreProcessor,
+    ) -> Result<Self, RendererInitializationError> {
+        let renderer = Renderer::new(iad, handedness, None)?;
+
+        let base_rendergraph = BaseRenderGraph::new(&renderer, spp);
+        let pbr = PbrRoutine::new(
+            &renderer,
+            &mut renderer.data_core.lock(),
+            spp,
+            &base_rendergraph.interfaces,
+            &base_rendergraph.gpu_culler.culling_buffer_map_handle,
+        );
+        let tonemapping = TonemappingRoutine::new(
+            &renderer,
+            spp,
+            &base_rendergraph.interfaces,
+            TextureFormat::Rgba8UnormSrgb,
+            OutputTransfer::Srgb,
+        );
+
+        Ok(Self {
+            renderer,
+            settings: BaseRenderGraphSettings {
+                ambient_color: Vec4::ONE,
+                background: Background::SolidColor(Vec4::new(0.2, 0.2, 0.2, 1.0)),
+                ..Default::default()
+            },
+            base_rendergraph,
+            pbr,
+            tonemapping,
+        })
+    }
+
+    /// Render `object` as seen from `camera` into an image of the given size.
+    ///
+    /// Returns tightly packed sRGB RGBA8 rows, top row first. The object is
+    /// removed again afterwards.
+    ///
+    /// On native this blocks the calling thread until the GPU has finished
+    /// the thumbnail, as it waits on a device poll before the future returns.
+    /// On the web the future resolves once the browser has read it back.
+    pub async fn render(&mut self, object: Object, camera: Camera, size: UVec2) -> Result<Vec<u8>, ThumbnailError> {
+        profiling::scope!("ThumbnailRenderer::render");
+
+        let renderer = &self.renderer;
+
+        let _object = renderer.add_object(object);
+        renderer.set_camera_data(camera);
+        renderer.set_aspect_ratio(size.x as f32 / size.y as f32);
+
+        let texture = renderer.device.create_texture(&TextureDescriptor {
+            label: Some("thumbnail"),
+            size: Extent3d { width: size.x, height: size.y, depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::Rgba8UnormSrgb,
+            usage: TextureUsages::COPY_SRC | TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+
+        renderer.swap_instruction_buffers();
+        let mut eval_output = renderer.evaluate_instructions();
+        self.pbr.evaluate(renderer);
+
+        let mut graph = RenderGraph::new();
+        let handle = graph.add_imported_render_target(&texture, 0..1, 0..1, ViewportRect::from_size(size));
+        self.base_rendergraph.add_to_graph(
+            &mut graph,
+            BaseRenderGraphInputs {
+                eval_output: &eval_output,
+                routines: BaseRenderGraphRoutines {
+                    pbr: &self.pbr,
+                    skybox: None,
+                    tonemapping: &self.tonemapping,
+                    color_grade: None,
+                    debug_draw: None,
+                },
+                target: OutputRenderTarget { handle, resolution: size, samples: SampleCount::One },
+                depth: None,
+                camera: None,
+            },
+            self.settings.clone(),
+        );
+        graph.execute(renderer, &mut eval_output);
+
+        // Rows of a texture to buffer copy must be aligned, so we copy padded rows and strip them after.
+        let row_bytes = size.x * 4;
+        let padded_row_bytes = wgpu::util::align_to(row_bytes, COPY_BYTES_PER_ROW_ALIGNMENT);
+        let buffer = renderer.device.create_buffer(&BufferDescriptor {
+            label: Some("thumbnail readback"),
+            size: (padded_row_bytes * size.y) as u64,
+            usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder =
+            renderer.device.create_command_encoder(&CommandEncoderDescriptor { label: Some("thumbnail readback") });
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            ImageCopyBuffer {
+                buffer: &buffer,
+                layout: ImageDataLayout { offset: 0, bytes_per_row: Some(padded_row_bytes), rows_per_image: None },
+            },
+            Extent3d { width: size.x, height: size.y, depth_or_array_layers: 1 },
+        );
+        let submit_index = renderer.queue.submit(Some(encoder.finish()));
+
+        let (sender, receiver) = flume::bounded(1);
+        buffer.slice(..).map_async(MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        renderer.device.poll(Maintain::WaitForSubmissionIndex(submit_index));
+        receiver.recv_async().await.map_err(|_| ThumbnailError::Disconnected)?.map_err(ThumbnailError::Map)?;
+
+        let mapping = buffer.slice(..).get_mapped_range();
+        let pixels = mapping.chunks_exact(padded_row_bytes as usize).flat_map(|row| &row[..row_bytes as usize]);
+
+        Ok(pixels.copied().collect())
+    }
+}
@@ -4,10 +4,10 @@
 //! auto-exposure yet. Once we have auto-exposure, we can do proper tonemapping,
 //! and will offer a variety of tonemapping operators.
 //!
-//! When creating the tonemapping, ensure you use the correct format for the
-//! output. Each TonemappingRoutine instance only has a single pipeline, so if
-//! you need to render to two different formats potentially, use two different
-//! routines.
+//! When creating the tonemapping, ensure you use the correct format and
+//! [`OutputTransfer`] for the output. Each TonemappingRoutine instance only has
+//! a single pipeline, so if you need to render to two different formats
+//! potentially, use two different routines.
 
 use std::borrow::Cow;
 
@@ -26,12 +26,42 @@ use wgpu::{
 
 use crate::common::WholeFrameInterfaces;
 
+/// Transfer function the tonemapper encodes its output with.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum OutputTransfer {
+    /// Output is sRGB encoded, ready to be shown on a monitor. If the output
+    /// format is an sRGB format, the hardware does the encoding, otherwise the
+    /// shader does.
+    #[default]
+    Srgb,
+    /// Output is left linear and clamped to 0-1, for compositing in another
+    /// pipeline which does its own encoding. The output format must not be an
+    /// sRGB format.
+    Linear,
+    /// Output is left linear and unclamped, for HDR outputs or further HDR
+    /// processing. The output format should be a float format and must not be
+    /// an sRGB format.
+    PassthroughHdr,
+}
+
+impl OutputTransfer {
+    fn fragment_entry_point(self, output_format: TextureFormat) -> &'static str {
+        match self {
+            Self::Srgb if output_format.is_srgb() => "fs_main_scene",
+            Self::Srgb => "fs_main_monitor",
+            Self::Linear => "fs_main_linear",
+            Self::PassthroughHdr => "fs_main_scene",
+        }
+    }
+}
+
 fn create_pipeline(
     device: &Device,
     spp: &ShaderPreProcessor,
     interfaces: &WholeFrameInterfaces,
     bgl: &BindGroupLayout,
     output_format: TextureFormat,
+    transfer: OutputTransfer,
 ) -> RenderPipeline {
     profiling::scope!("TonemappingPass::new");
     let module = device.create_shader_module(ShaderModuleDescriptor {
@@ -41,7 +71,7 @@ fn create_pipeline(
         )),
     });
 
-    let fs_entry_point = if output_format.is_srgb() { "fs_main_scene" } else { "fs_main_monitor" };
+    let fs_entry_point = transfer.fragment_entry_point(output_format);
 
     let pll = device.create_pipeline_layout(&PipelineLayoutDescriptor {
         label: Some("tonemapping pass"),
@@ -82,12 +112,25 @@ pub struct TonemappingRoutine {
 }
 
 impl TonemappingRoutine {
+    /// Create the routine, writing to `output_format` encoded with `transfer`.
+    ///
+    /// # Panics
+    ///
+    /// If `transfer` is [`OutputTransfer::Linear`] or
+    /// [`OutputTransfer::PassthroughHdr`] and `output_format` is an sRGB
+    /// format, as the hardware would encode the output a second time.
     pub fn new(
         renderer: &Renderer,
         spp: &ShaderPreProcessor,
         interfaces: &WholeFrameInterfaces,
         output_format: TextureFormat,
+        transfer: OutputTransfer,
     ) -> Self {
+        assert!(
+            transfer == OutputTransfer::Srgb || !output_format.is_srgb(),
+            "Output transfer {transfer:?} cannot be used with sRGB format {output_format:?}"
+        );
+
         let bgl = BindGroupLayoutBuilder::new()
             .append(
                 ShaderStages::FRAGMENT,
@@ -100,7 +143,7 @@ impl TonemappingRoutine {
             )
             .build(&renderer.device, Some("bind bgl"));
 
-        let pipeline = create_pipeline(&renderer.device, spp, interfaces, &bgl, output_format);
+        let pipeline = create_pipeline(&renderer.device, spp, interfaces, &bgl, output_format, transfer);
 
         Self { bgl, pipeline }
     }
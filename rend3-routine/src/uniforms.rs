@@ -10,13 +10,17 @@ use rend3::{
 };
 use wgpu::{BindGroup, BufferUsages};
 
-use crate::common::{Samplers, WholeFrameInterfaces};
+use crate::{
+    clustering::LightClusteringRoutine,
+    common::{Samplers, WholeFrameInterfaces},
+};
 
 /// Set of uniforms that are useful for the whole frame.
 #[derive(Debug, Copy, Clone, ShaderType)]
 pub struct FrameUniforms {
     pub view: Mat4,
     pub view_proj: Mat4,
+    pub previous_view_proj: Mat4,
     pub origin_view_proj: Mat4,
     pub inv_view: Mat4,
     pub inv_view_proj: Mat4,
@@ -24,6 +28,7 @@ pub struct FrameUniforms {
     pub frustum: Frustum,
     pub ambient: Vec4,
     pub resolution: UVec2,
+    pub cutout_bias: f32,
 }
 impl FrameUniforms {
     /// Use the given camera to generate these uniforms.
@@ -37,6 +42,7 @@ impl FrameUniforms {
         Self {
             view,
             view_proj,
+            previous_view_proj: camera.previous_view_proj(),
             origin_view_proj,
             inv_view: view.inverse(),
             inv_view_proj: view_proj.inverse(),
@@ -44,6 +50,7 @@ impl FrameUniforms {
             frustum: Frustum::from_matrix(camera.proj()),
             ambient: info.ambient,
             resolution: info.resolution,
+            cutout_bias: info.cutout_bias,
         }
     }
 }
@@ -52,10 +59,16 @@ impl FrameUniforms {
 pub struct UniformInformation<'node> {
     /// Struct containing the default set of samplers.
     pub samplers: &'node Samplers,
+    /// Light clusters read by the forward pass.
+    pub light_clustering: &'node LightClusteringRoutine,
     /// Ambient light color.
     pub ambient: Vec4,
     /// Resolution of the viewport.
     pub resolution: UVec2,
+    /// Added to the alpha cutout of every cutout material.
+    pub cutout_bias: f32,
+    /// Replaces the renderer's camera if given.
+    pub camera: Option<CameraState>,
 }
 
 pub struct UniformBindingHandles<'node> {
@@ -91,7 +104,8 @@ pub fn add_to_graph<'node>(
 
         info.samplers.add_to_bg(&mut bgb);
 
-        let uniforms = FrameUniforms::new(&ctx.data_core.viewport_camera_state, &info);
+        let camera = info.camera.as_ref().unwrap_or(&ctx.data_core.viewport_camera_state);
+        let uniforms = FrameUniforms::new(camera, &info);
         let uniform_buffer = ctx.renderer.device.create_buffer(&wgpu::BufferDescriptor {
             label: Some("Frame Uniforms"),
             size: FrameUniforms::SHADER_SIZE.get(),
@@ -112,6 +126,7 @@ pub fn add_to_graph<'node>(
             bgb.build(&ctx.renderer.device, Some("shadow uniform bg"), &binding_handles.interfaces.depth_uniform_bgl);
 
         bgb.append_texture_view(shadow_target);
+        info.light_clustering.add_to_bg(&mut bgb);
 
         let forward_uniform_bg = bgb.build(
             &ctx.renderer.device,
@@ -1,6 +1,6 @@
 use std::sync::Arc;
 
-use glam::{Mat4, Vec3, Vec4};
+use glam::{DVec3, Mat4, Vec3, Vec4};
 use rend3::types::{DirectionalLightHandle, MaterialHandle, MeshBuilder, ObjectHandle};
 use rend3_routine::pbr::PbrMaterial;
 use wgpu::Device;
@@ -32,6 +32,9 @@ impl TestRunner {
             distance: 5.0,
             intensity: 1.0,
             direction,
+            casts_shadows: true,
+            snap_to_texels: true,
+            angular_size: 0.0,
         })
     }
 
@@ -70,6 +73,9 @@ impl TestRunner {
             mesh_kind: rend3::types::ObjectMeshKind::Static(self.add_mesh(mesh).unwrap()),
             material,
             transform,
+            world_offset: DVec3::ZERO,
+            sort_bias: 0,
+            debug_tint: None,
         })
     }
 
@@ -126,6 +132,9 @@ impl TestRunner {
             mesh_kind: rend3::types::ObjectMeshKind::Static(self.add_mesh(mesh).unwrap()),
             material,
             transform,
+            world_offset: DVec3::ZERO,
+            sort_bias: 0,
+            debug_tint: None,
         })
     }
 }
@@ -9,7 +9,11 @@ use rend3::{
     types::{Handedness, SampleCount},
     Renderer,
 };
-use rend3_routine::{base::BaseRenderGraph, pbr::PbrRoutine, tonemapping::TonemappingRoutine};
+use rend3_routine::{
+    base::BaseRenderGraph,
+    pbr::PbrRoutine,
+    tonemapping::{OutputTransfer, TonemappingRoutine},
+};
 use wgpu::{
     Extent3d, ImageCopyBuffer, ImageDataLayout, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages,
 };
@@ -49,6 +53,7 @@ impl Default for FrameRenderSettings {
 pub struct TestRunnerBuilder {
     handness: Option<Handedness>,
     iad: Option<rend3::InstanceAdapterDevice>,
+    without_depth: bool,
 }
 
 impl TestRunnerBuilder {
@@ -66,6 +71,13 @@ impl TestRunnerBuilder {
         self
     }
 
+    /// Render with a base rendergraph made by
+    /// [`BaseRenderGraph::new_without_depth`].
+    pub fn without_depth(mut self) -> Self {
+        self.without_depth = true;
+        self
+    }
+
     pub async fn build(self) -> Result<TestRunner> {
         let _ = env_logger::try_init();
 
@@ -81,7 +93,11 @@ impl TestRunnerBuilder {
         let mut spp = rend3::ShaderPreProcessor::new();
         rend3_routine::builtin_shaders(&mut spp);
 
-        let base_rendergraph = BaseRenderGraph::new(&renderer, &spp);
+        let base_rendergraph = match self.without_depth {
+            true => BaseRenderGraph::new_without_depth(&renderer, &spp, TextureFormat::Rgba16Float)
+                .context("Base rendergraph creation failed")?,
+            false => BaseRenderGraph::new(&renderer, &spp),
+        };
 
         let pbr = PbrRoutine::new(
             &renderer,
@@ -90,8 +106,13 @@ impl TestRunnerBuilder {
             &base_rendergraph.interfaces,
             &base_rendergraph.gpu_culler.culling_buffer_map_handle,
         );
-        let tonemapping =
-            TonemappingRoutine::new(&renderer, &spp, &base_rendergraph.interfaces, TextureFormat::Rgba8UnormSrgb);
+        let tonemapping = TonemappingRoutine::new(
+            &renderer,
+            &spp,
+            &base_rendergraph.interfaces,
+            TextureFormat::Rgba8UnormSrgb,
+            OutputTransfer::Srgb,
+        );
 
         Ok(TestRunner { renderer, pbr, tonemapping, base_rendergraph, capture_guard })
     }
@@ -150,16 +171,21 @@ impl TestRunner {
                     pbr: &self.pbr,
                     skybox: None,
                     tonemapping: &self.tonemapping,
+                    color_grade: None,
+                    debug_draw: None,
                 },
                 target: rend3_routine::base::OutputRenderTarget {
                     handle: frame_handle,
                     resolution: UVec2::splat(settings.size),
                     samples: settings.samples,
                 },
+                depth: None,
+                camera: None,
             },
             rend3_routine::base::BaseRenderGraphSettings {
                 ambient_color: glam::Vec4::ZERO,
-                clear_color: glam::Vec4::ZERO,
+                background: rend3_routine::background::Background::Transparent,
+                ..Default::default()
             },
         );
 
@@ -224,6 +250,33 @@ pub async fn download_image(
     image::RgbaImage::from_raw(size.x, size.y, mapping.to_vec()).context("Failed to create image from mapping")
 }
 
+/// Copies the contents of a buffer with `COPY_SRC` usage back to the cpu.
+pub async fn download_buffer(renderer: &Renderer, source: &wgpu::Buffer) -> anyhow::Result<Vec<u8>> {
+    let buffer = renderer.device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Test readback buffer"),
+        size: source.size(),
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+
+    let mut encoder = renderer
+        .device
+        .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("Test readback encoder") });
+    encoder.copy_buffer_to_buffer(source, 0, &buffer, 0, source.size());
+
+    let submit_index = renderer.queue.submit(Some(encoder.finish()));
+
+    let (sender, receiver) = flume::bounded(1);
+    buffer.slice(..).map_async(wgpu::MapMode::Read, move |_| sender.send(()).unwrap());
+    renderer.device.poll(wgpu::Maintain::WaitForSubmissionIndex(submit_index));
+
+    receiver.recv_async().await.context("Failed to recieve message from map_async")?;
+
+    let mapping = buffer.slice(..).get_mapped_range();
+
+    Ok(mapping.to_vec())
+}
+
 pub fn compare_image_to_path(
     test_rgba: &image::RgbaImage,
     path: &Path,
@@ -0,0 +1,118 @@
+use anyhow::Context;
+use glam::{UVec2, UVec3};
+use rend3::graph::{ComputeBindGroup, ComputeDispatch, RenderGraph, ViewportRect};
+use rend3_test::{download_buffer, download_image, no_gpu_return, test_attr, TestRunner};
+use wgpu::{
+    BindGroupDescriptor, BindGroupEntry, BufferDescriptor, BufferUsages, ComputePipelineDescriptor, Extent3d,
+    ImageDataLayout, SamplerDescriptor, ShaderModuleDescriptor, ShaderSource, TextureDescriptor, TextureDimension,
+    TextureFormat, TextureUsages,
+};
+
+const FILL_SHADER: &str = "
+@group(0) @binding(0)
+var<storage, read_write> values: array<u32>;
+
+@compute @workgroup_size(4)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    values[id.x] = id.x + 42u;
+}
+";
+
+/// Ensure a compute node added with add_compute_node is dispatched.
+#[test_attr]
+pub async fn compute_node() -> anyhow::Result<()> {
+    let iad = no_gpu_return!(rend3::create_iad(None, None, None, None).await)
+        .context("InstanceAdapterDevice creation failed")?;
+
+    let Ok(runner) = TestRunner::builder().iad(iad).build().await else {
+        return Ok(());
+    };
+
+    let module = runner.device.create_shader_module(ShaderModuleDescriptor {
+        label: Some("fill shader"),
+        source: ShaderSource::Wgsl(FILL_SHADER.into()),
+    });
+    let pipeline = runner.device.create_compute_pipeline(&ComputePipelineDescriptor {
+        label: Some("fill pipeline"),
+        layout: None,
+        module: &module,
+        entry_point: "main",
+    });
+    let buffer = runner.device.create_buffer(&BufferDescriptor {
+        label: Some("fill buffer"),
+        size: 8 * 4,
+        usage: BufferUsages::STORAGE | BufferUsages::COPY_SRC,
+        mapped_at_creation: false,
+    });
+    let bind_group = runner.device.create_bind_group(&BindGroupDescriptor {
+        label: Some("fill bind group"),
+        layout: &pipeline.get_bind_group_layout(0),
+        entries: &[BindGroupEntry { binding: 0, resource: buffer.as_entire_binding() }],
+    });
+
+    runner.swap_instruction_buffers();
+    let mut eval_output = runner.evaluate_instructions();
+
+    let mut graph = RenderGraph::new();
+    graph.add_compute_node(
+        "fill",
+        &pipeline,
+        &[ComputeBindGroup::Static(&bind_group)],
+        ComputeDispatch::Direct(UVec3::new(2, 1, 1)),
+    );
+    graph.execute(&runner.renderer, &mut eval_output);
+
+    let data = download_buffer(&runner.renderer, &buffer).await?;
+    let values: Vec<u32> = data.chunks_exact(4).map(|c| u32::from_le_bytes(c.try_into().unwrap())).collect();
+    assert_eq!(values, (42..50).collect::<Vec<u32>>());
+
+    Ok(())
+}
+
+/// Ensure add_blit scales a texture over the whole destination.
+#[test_attr]
+pub async fn blit() -> anyhow::Result<()> {
+    let iad = no_gpu_return!(rend3::create_iad(None, None, None, None).await)
+        .context("InstanceAdapterDevice creation failed")?;
+
+    let Ok(runner) = TestRunner::builder().iad(iad).build().await else {
+        return Ok(());
+    };
+
+    let texture = |size: u32, usage| {
+        runner.device.create_texture(&TextureDescriptor {
+            label: None,
+            size: Extent3d { width: size, height: size, depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::Rgba8Unorm,
+            usage,
+            view_formats: &[],
+        })
+    };
+    let src = texture(4, TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST);
+    let dst = texture(64, TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC);
+    let color = [255, 128, 0, 255];
+    runner.queue.write_texture(
+        src.as_image_copy(),
+        &color.repeat(16),
+        ImageDataLayout { offset: 0, bytes_per_row: Some(4 * 4), rows_per_image: None },
+        src.size(),
+    );
+    let sampler = runner.device.create_sampler(&SamplerDescriptor::default());
+
+    runner.swap_instruction_buffers();
+    let mut eval_output = runner.evaluate_instructions();
+
+    let mut graph = RenderGraph::new();
+    let src_handle = graph.add_imported_render_target(&src, 0..1, 0..1, ViewportRect::from_size(UVec2::splat(4)));
+    let dst_handle = graph.add_imported_render_target(&dst, 0..1, 0..1, ViewportRect::from_size(UVec2::splat(64)));
+    graph.add_blit(src_handle, dst_handle, &sampler);
+    graph.execute(&runner.renderer, &mut eval_output);
+
+    let image = download_image(&runner.renderer, dst, UVec2::splat(64)).await?;
+    assert!(image.pixels().all(|pixel| pixel.0 == color));
+
+    Ok(())
+}
@@ -1,5 +1,5 @@
 use anyhow::Context;
-use glam::{Mat4, Vec3, Vec4};
+use glam::{DVec3, Mat4, Vec3, Vec4};
 use rend3::types::{Camera, Handedness, MeshBuilder, Object, ObjectMeshKind, SampleCount};
 use rend3_test::{no_gpu_return, test_attr, FrameRenderSettings, TestRunner, Threshold};
 
@@ -22,8 +22,14 @@ pub async fn triangle() -> anyhow::Result<()> {
 
     let mesh_hdl = runner.add_mesh(mesh).unwrap();
     let material_hdl = runner.add_unlit_material(Vec4::new(0.25, 0.5, 0.75, 1.0));
-    let object =
-        Object { mesh_kind: ObjectMeshKind::Static(mesh_hdl), material: material_hdl, transform: Mat4::IDENTITY };
+    let object = Object {
+        mesh_kind: ObjectMeshKind::Static(mesh_hdl),
+        material: material_hdl,
+        transform: Mat4::IDENTITY,
+        world_offset: DVec3::ZERO,
+        sort_bias: 0,
+        debug_tint: None,
+    };
     let _object_hdl = runner.add_object(object);
 
     runner.set_camera_data(Camera {
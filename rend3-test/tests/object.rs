@@ -1,9 +1,10 @@
 use anyhow::Context;
-use glam::{Mat4, Quat, Vec3, Vec4};
+use glam::{DVec3, Mat4, Quat, Vec3, Vec4};
 use rend3::{
-    types::{Camera, Handedness, ObjectChange},
+    types::{Camera, Culling, Handedness, MeshBuilder, Object, ObjectChange, ObjectMeshKind, PointLight},
     util::freelist::FreelistDerivedBuffer,
 };
+use rend3_routine::pbr::{AlbedoComponent, PbrMaterial};
 use rend3_test::{no_gpu_return, test_attr, FrameRenderSettings, TestRunner, Threshold};
 
 /// Ensure that duplicate_object doesn't retain the object for an extra frame.
@@ -107,3 +108,311 @@ pub async fn multi_frame_add() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+/// Ensure that clear_world removes objects added before it, but not those added after it,
+/// and that dropping a cleared handle afterwards is harmless.
+#[test_attr]
+pub async fn clear_world() -> anyhow::Result<()> {
+    let iad = no_gpu_return!(rend3::create_iad(None, None, None, None).await)
+        .context("InstanceAdapterDevice creation failed")?;
+
+    let Ok(runner) = TestRunner::builder().iad(iad.clone()).handedness(Handedness::Left).build().await else {
+        return Ok(());
+    };
+
+    runner.set_camera_data(Camera {
+        projection: rend3::types::CameraProjection::Raw(Mat4::IDENTITY),
+        view: Mat4::IDENTITY,
+    });
+
+    let material = runner.add_unlit_material(Vec4::ONE);
+    let object1 = runner.plane(
+        material.clone(),
+        Mat4::from_scale_rotation_translation(Vec3::new(-0.25, 0.25, 0.25), Quat::IDENTITY, Vec3::new(-0.5, 0.0, 0.0)),
+    );
+
+    runner
+        .render_and_compare(
+            FrameRenderSettings::new(),
+            "tests/results/object/duplicate-object-retain-left.png",
+            Threshold::Mean(0.0),
+        )
+        .await?;
+
+    runner.clear_world();
+    let _object2 = runner.plane(
+        material,
+        Mat4::from_scale_rotation_translation(Vec3::new(-0.25, 0.25, 0.25), Quat::IDENTITY, Vec3::new(0.5, 0.0, 0.0)),
+    );
+    runner.set_object_transform(&object1, Mat4::IDENTITY);
+
+    runner
+        .render_and_compare(
+            FrameRenderSettings::new(),
+            "tests/results/object/duplicate-object-retain-right.png",
+            Threshold::Mean(0.0),
+        )
+        .await?;
+
+    drop(object1);
+
+    runner
+        .render_and_compare(
+            FrameRenderSettings::new(),
+            "tests/results/object/duplicate-object-retain-right.png",
+            Threshold::Mean(0.0),
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// Ensure that objects can be found by their id for as long as they are alive, and that
+/// reusing an id moves it to the new object.
+#[test_attr]
+pub async fn object_by_id() -> anyhow::Result<()> {
+    let iad = no_gpu_return!(rend3::create_iad(None, None, None, None).await)
+        .context("InstanceAdapterDevice creation failed")?;
+
+    let Ok(runner) = TestRunner::builder().iad(iad.clone()).handedness(Handedness::Left).build().await else {
+        return Ok(());
+    };
+
+    let material = runner.add_unlit_material(Vec4::ONE);
+    let mesh = MeshBuilder::new(vec![Vec3::ZERO, Vec3::X, Vec3::Y], Handedness::Left).build()?;
+    let mesh = runner.add_mesh(mesh).unwrap();
+    let object = || Object {
+        mesh_kind: ObjectMeshKind::Static(mesh.clone()),
+        material: material.clone(),
+        transform: Mat4::IDENTITY,
+        world_offset: DVec3::ZERO,
+        sort_bias: 0,
+        debug_tint: None,
+    };
+
+    let object1 = runner.add_object_with_id(7, object());
+    assert_eq!(runner.object_by_id(7), Some(object1.clone()));
+    assert_eq!(runner.object_by_id(8), None);
+
+    let object2 = runner.add_object_with_id(7, object());
+    assert_eq!(runner.object_by_id(7), Some(object2.clone()));
+
+    drop(object2);
+    assert_eq!(runner.object_by_id(7), None);
+
+    Ok(())
+}
+
+/// Ensure that children keep their place when attached, follow their parent including
+/// through a grandparent, and stay put once detached.
+#[test_attr]
+pub async fn object_parenting() -> anyhow::Result<()> {
+    let iad = no_gpu_return!(rend3::create_iad(None, None, None, None).await)
+        .context("InstanceAdapterDevice creation failed")?;
+
+    let Ok(runner) = TestRunner::builder().iad(iad.clone()).handedness(Handedness::Left).build().await else {
+        return Ok(());
+    };
+
+    let material = runner.add_unlit_material(Vec4::ONE);
+    let mesh = MeshBuilder::new(vec![Vec3::ZERO, Vec3::X, Vec3::Y], Handedness::Left).build()?;
+    let mesh = runner.add_mesh(mesh).unwrap();
+    let object = |transform| Object {
+        mesh_kind: ObjectMeshKind::Static(mesh.clone()),
+        material: material.clone(),
+        transform,
+        world_offset: DVec3::ZERO,
+        sort_bias: 0,
+        debug_tint: None,
+    };
+    let center = |handle| {
+        let (min, max) = runner.object_bounds(handle).unwrap();
+        (min + max) / 2.0
+    };
+    let evaluate = || {
+        runner.swap_instruction_buffers();
+        runner.evaluate_instructions()
+    };
+
+    let grandparent = runner.add_object(object(Mat4::IDENTITY));
+    let parent = runner.add_object(object(Mat4::from_translation(Vec3::X)));
+    let child = runner.add_object(object(Mat4::from_translation(Vec3::new(1.0, 1.0, 0.0))));
+    runner.set_object_parent(&parent, Some(&grandparent));
+    runner.set_object_parent(&child, Some(&parent));
+    evaluate();
+
+    // Attaching keeps the world transform.
+    let child_start = center(&child);
+    assert!(child_start.abs_diff_eq(center(&grandparent) + Vec3::new(1.0, 1.0, 0.0), 1e-5));
+
+    // Moving the grandparent moves the whole hierarchy.
+    runner.set_object_transform(&grandparent, Mat4::from_translation(Vec3::Z));
+    evaluate();
+    assert!(center(&child).abs_diff_eq(child_start + Vec3::Z, 1e-5));
+
+    // Transforms of children are relative to their parent.
+    runner.set_object_transform(&child, Mat4::from_translation(Vec3::new(1.0, 0.0, 0.0)));
+    evaluate();
+    assert!(center(&child).abs_diff_eq(child_start + Vec3::new(1.0, -1.0, 1.0), 1e-5));
+
+    // Parenting to a descendant is ignored.
+    runner.set_object_parent(&grandparent, Some(&child));
+    runner.set_object_parent(&child, None);
+    runner.set_object_transform(&grandparent, Mat4::IDENTITY);
+    evaluate();
+    assert!(center(&child).abs_diff_eq(child_start + Vec3::new(1.0, -1.0, 1.0), 1e-5));
+    assert!(center(&parent).abs_diff_eq(center(&grandparent) + Vec3::X, 1e-5));
+
+    Ok(())
+}
+
+/// Ensure that objects far from the world origin keep their precision relative to the
+/// render origin, and that moving the render origin doesn't move objects in the world.
+#[test_attr]
+pub async fn render_origin() -> anyhow::Result<()> {
+    let iad = no_gpu_return!(rend3::create_iad(None, None, None, None).await)
+        .context("InstanceAdapterDevice creation failed")?;
+
+    let Ok(runner) = TestRunner::builder().iad(iad.clone()).handedness(Handedness::Left).build().await else {
+        return Ok(());
+    };
+
+    let material = runner.add_unlit_material(Vec4::ONE);
+    let mesh = MeshBuilder::new(vec![Vec3::ZERO, Vec3::X, Vec3::Y], Handedness::Left).build()?;
+    let mesh = runner.add_mesh(mesh).unwrap();
+    let far = DVec3::new(1.0e9, 0.0, -1.0e9);
+    let object = runner.add_object(Object {
+        mesh_kind: ObjectMeshKind::Static(mesh),
+        material,
+        transform: Mat4::from_translation(Vec3::new(0.25, 0.0, 0.0)),
+        world_offset: far,
+        sort_bias: 0,
+        debug_tint: None,
+    });
+    let center = || {
+        let (min, max) = runner.object_bounds(&object).unwrap();
+        (min + max) / 2.0
+    };
+    let evaluate = || {
+        runner.swap_instruction_buffers();
+        runner.evaluate_instructions()
+    };
+
+    runner.set_render_origin(far);
+    evaluate();
+    let start = center();
+    assert!(start.abs_diff_eq(Vec3::new(0.75, 0.5, 0.0), 1e-5), "{start}");
+
+    // Moving the render origin moves everything on the gpu the other way.
+    runner.set_render_origin(far + DVec3::new(0.0, 1.0, 0.0));
+    evaluate();
+    assert!(center().abs_diff_eq(start - Vec3::Y, 1e-5));
+
+    // Moving the object by its world offset keeps the same precision.
+    runner.set_object_world_offset(&object, far + DVec3::new(0.0, 0.0, 2.0));
+    evaluate();
+    assert!(center().abs_diff_eq(start + Vec3::new(0.0, -1.0, 2.0), 1e-5));
+
+    Ok(())
+}
+
+/// Ensure that object_handles lists exactly the objects which are alive.
+#[test_attr]
+pub async fn object_handles() -> anyhow::Result<()> {
+    let iad = no_gpu_return!(rend3::create_iad(None, None, None, None).await)
+        .context("InstanceAdapterDevice creation failed")?;
+
+    let Ok(runner) = TestRunner::builder().iad(iad.clone()).handedness(Handedness::Left).build().await else {
+        return Ok(());
+    };
+
+    let material = runner.add_unlit_material(Vec4::ONE);
+    let object1 = runner.plane(material.clone(), Mat4::IDENTITY);
+    let object2 = runner.plane(material, Mat4::IDENTITY);
+
+    runner.swap_instruction_buffers();
+    runner.evaluate_instructions();
+    assert_eq!(runner.object_handles(), vec![object1.get_raw(), object2.get_raw()]);
+
+    drop(object1);
+    runner.swap_instruction_buffers();
+    runner.evaluate_instructions();
+    assert_eq!(runner.object_handles(), vec![object2.get_raw()]);
+
+    Ok(())
+}
+
+/// Ensure unchanged objects and lights aren't uploaded again.
+#[test_attr]
+pub async fn upload_skipped_when_unchanged() -> anyhow::Result<()> {
+    let iad = no_gpu_return!(rend3::create_iad(None, None, None, None).await)
+        .context("InstanceAdapterDevice creation failed")?;
+
+    let Ok(runner) = TestRunner::builder().iad(iad.clone()).handedness(Handedness::Left).build().await else {
+        return Ok(());
+    };
+
+    let material = runner.add_unlit_material(Vec4::ONE);
+    let object = runner.plane(material, Mat4::IDENTITY);
+    let _light = runner.renderer.add_point_light(PointLight {
+        position: Vec3::ZERO,
+        color: Vec3::ONE,
+        radius: 1.0,
+        intensity: 1.0,
+    });
+
+    let evaluate = || {
+        runner.swap_instruction_buffers();
+        let output = runner.evaluate_instructions();
+        (output.objects_uploaded, output.point_lights_uploaded)
+    };
+
+    assert_eq!(evaluate(), (1, true));
+    assert_eq!(evaluate(), (0, false));
+
+    // Moving an object writes it again the frame after, to catch up its previous transform.
+    runner.set_object_transform(&object, Mat4::from_translation(Vec3::X));
+    assert_eq!(evaluate(), (1, false));
+    assert_eq!(evaluate(), (1, false));
+    assert_eq!(evaluate(), (0, false));
+
+    Ok(())
+}
+
+/// Materials choose which faces are culled, both in the GPU culler and in the
+/// pipeline they are drawn with.
+#[test_attr]
+pub async fn material_culling() -> anyhow::Result<()> {
+    let iad = no_gpu_return!(rend3::create_iad(None, None, None, None).await)
+        .context("InstanceAdapterDevice creation failed")?;
+
+    let Ok(runner) = TestRunner::builder().iad(iad.clone()).handedness(Handedness::Left).build().await else {
+        return Ok(());
+    };
+
+    runner.set_camera_data(Camera {
+        projection: rend3::types::CameraProjection::Raw(Mat4::IDENTITY),
+        view: Mat4::IDENTITY,
+    });
+
+    let mut visible = Vec::new();
+    for culling in [Culling::Back, Culling::Front, Culling::None] {
+        let material = runner.add_material(PbrMaterial {
+            albedo: AlbedoComponent::Value(Vec4::ONE),
+            unlit: true,
+            culling,
+            ..Default::default()
+        });
+        let _object = runner.plane(material, Mat4::from_scale(Vec3::splat(0.5)));
+
+        let image = runner.render_frame(FrameRenderSettings::new()).await?;
+        let center = image.get_pixel(image.width() / 2, image.height() / 2);
+        visible.push(center[3] != 0);
+    }
+
+    // The plane shows exactly one of its faces to the camera.
+    assert_ne!(visible[0], visible[1], "{visible:?}");
+    assert!(visible[2], "{visible:?}");
+
+    Ok(())
+}
@@ -1,4 +1,7 @@
+mod graph;
 mod msaa;
 mod object;
 mod shadow;
 mod simple;
+mod texture;
+mod thumbnail;
@@ -1,7 +1,7 @@
 use std::f32::consts::FRAC_PI_2;
 
 use anyhow::Context;
-use glam::{Mat4, Quat, Vec3, Vec3A, Vec4};
+use glam::{Mat4, Quat, Vec2, Vec3, Vec4};
 use rend3::types::{Camera, Handedness};
 use rend3_test::{no_gpu_return, test_attr, FrameRenderSettings, TestRunner, Threshold};
 
@@ -21,7 +21,7 @@ pub async fn shadows() -> anyhow::Result<()> {
     let _plane = runner.plane(material1, Mat4::from_rotation_x(-FRAC_PI_2));
 
     runner.set_camera_data(Camera {
-        projection: rend3::types::CameraProjection::Orthographic { size: Vec3A::new(2.5, 2.5, 5.0) },
+        projection: rend3::types::CameraProjection::Orthographic { size: Vec2::splat(2.5), near: -2.5, far: 2.5 },
         view: Mat4::look_at_lh(Vec3::new(0.0, 1.0, -1.0), Vec3::ZERO, Vec3::Y),
     });
 
@@ -1,5 +1,5 @@
 use anyhow::Context;
-use glam::{Mat4, Vec3, Vec4};
+use glam::{DVec3, Mat4, Vec3, Vec4};
 use rend3::types::{Camera, Handedness, MeshBuilder, Object, ObjectMeshKind};
 use rend3_test::{no_gpu_return, test_attr, FrameRenderSettings, TestRunner, Threshold};
 use wgpu::FrontFace;
@@ -59,8 +59,14 @@ pub async fn triangle() -> anyhow::Result<()> {
 
         let mesh_hdl = runner.add_mesh(mesh).unwrap();
         let material_hdl = runner.add_unlit_material(Vec4::new(0.25, 0.5, 0.75, 1.0));
-        let object =
-            Object { mesh_kind: ObjectMeshKind::Static(mesh_hdl), material: material_hdl, transform: Mat4::IDENTITY };
+        let object = Object {
+            mesh_kind: ObjectMeshKind::Static(mesh_hdl),
+            material: material_hdl,
+            transform: Mat4::IDENTITY,
+            world_offset: DVec3::ZERO,
+            sort_bias: 0,
+            debug_tint: None,
+        };
         let _object_hdl = runner.add_object(object);
 
         runner.set_camera_data(Camera {
@@ -84,6 +90,48 @@ pub async fn triangle() -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Ensure a base rendergraph without a depth buffer still draws objects.
+#[test_attr]
+pub async fn triangle_without_depth() -> anyhow::Result<()> {
+    let iad = no_gpu_return!(rend3::create_iad(None, None, None, None).await)
+        .context("InstanceAdapterDevice creation failed")?;
+
+    let Ok(runner) = TestRunner::builder().iad(iad).without_depth().build().await else {
+        return Ok(());
+    };
+
+    let mesh = MeshBuilder::new(
+        vec![Vec3::new(0.5, -0.5, 0.0), Vec3::new(-0.5, -0.5, 0.0), Vec3::new(0.0, 0.5, 0.0)],
+        Handedness::Left,
+    )
+    .build()
+    .context("Failed to create mesh")?;
+
+    let mesh_hdl = runner.add_mesh(mesh).unwrap();
+    let material_hdl = runner.add_unlit_material(Vec4::new(0.25, 0.5, 0.75, 1.0));
+    let object = Object {
+        mesh_kind: ObjectMeshKind::Static(mesh_hdl),
+        material: material_hdl,
+        transform: Mat4::IDENTITY,
+        world_offset: DVec3::ZERO,
+        sort_bias: 0,
+        debug_tint: None,
+    };
+    let _object_hdl = runner.add_object(object);
+
+    runner.set_camera_data(Camera {
+        projection: rend3::types::CameraProjection::Raw(Mat4::IDENTITY),
+        view: Mat4::IDENTITY,
+    });
+
+    runner
+        .render_and_compare(FrameRenderSettings::new(), "tests/results/simple/triangle.png", Threshold::Mean(0.0))
+        .await
+        .context("Image Comparison Failed")?;
+
+    Ok(())
+}
+
 #[test_attr]
 pub async fn coordinate_space() -> anyhow::Result<()> {
     let tests = [
@@ -120,8 +168,14 @@ pub async fn coordinate_space() -> anyhow::Result<()> {
 
         let mesh_hdl = runner.add_mesh(mesh).unwrap();
         let material_hdl = runner.add_unlit_material(color.extend(1.0));
-        let object =
-            Object { mesh_kind: ObjectMeshKind::Static(mesh_hdl), material: material_hdl, transform: Mat4::IDENTITY };
+        let object = Object {
+            mesh_kind: ObjectMeshKind::Static(mesh_hdl),
+            material: material_hdl,
+            transform: Mat4::IDENTITY,
+            world_offset: DVec3::ZERO,
+            sort_bias: 0,
+            debug_tint: None,
+        };
         runner.add_object(object)
     });
 
@@ -140,3 +194,62 @@ pub async fn coordinate_space() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+/// Ensure invalid pipelines are reported as errors instead of reaching the uncaptured error handler.
+#[test_attr]
+pub async fn pipeline_validation_error() -> anyhow::Result<()> {
+    let iad = no_gpu_return!(rend3::create_iad(None, None, None, None).await)
+        .context("InstanceAdapterDevice creation failed")?;
+
+    let Ok(runner) = TestRunner::builder().iad(iad).build().await else {
+        return Ok(());
+    };
+
+    let module = runner.device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("empty compute"),
+        source: wgpu::ShaderSource::Wgsl("@compute @workgroup_size(1) fn main() {}".into()),
+    });
+
+    let valid = runner.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+        label: Some("valid"),
+        layout: None,
+        module: &module,
+        entry_point: "main",
+    });
+    assert!(valid.is_ok());
+
+    let invalid = runner.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+        label: Some("missing entry point"),
+        layout: None,
+        module: &module,
+        entry_point: "missing",
+    });
+    assert_eq!(invalid.err().and_then(|e| e.label).as_deref(), Some("missing entry point"));
+
+    Ok(())
+}
+
+/// Ensure retained meshes can be read back, and other meshes aren't kept.
+#[test_attr]
+pub async fn mesh_readback() -> anyhow::Result<()> {
+    let iad = no_gpu_return!(rend3::create_iad(None, None, None, None).await)
+        .context("InstanceAdapterDevice creation failed")?;
+
+    let Ok(runner) = TestRunner::builder().iad(iad).build().await else {
+        return Ok(());
+    };
+
+    let triangle = || {
+        MeshBuilder::new(vec![Vec3::ZERO, Vec3::X, Vec3::Y], Handedness::Left).build().context("Failed to build mesh")
+    };
+
+    let retained = runner.add_mesh_retained(triangle()?).unwrap();
+    let mesh = runner.read_mesh(&retained).context("Retained mesh wasn't kept")?;
+    assert_eq!(mesh.vertex_count, 3);
+    assert_eq!(mesh.indices, [0, 1, 2]);
+
+    let not_retained = runner.add_mesh(triangle()?).unwrap();
+    assert!(runner.read_mesh(&not_retained).is_none());
+
+    Ok(())
+}
@@ -0,0 +1,116 @@
+use std::num::NonZeroU32;
+
+use anyhow::Context;
+use glam::UVec2;
+use rend3::{
+    graph::RenderGraph,
+    managers::TextureCreationError,
+    types::{MipmapCount, MipmapFilter, MipmapGeneration, MipmapSource, Texture, TextureFormat},
+};
+use rend3_test::{download_buffer, no_gpu_return, test_attr, TestRunner};
+use wgpu::{
+    BufferDescriptor, BufferUsages, Extent3d, ImageCopyBuffer, ImageCopyTexture, ImageDataLayout, Origin3d,
+    TextureDescriptor, TextureDimension, TextureUsages,
+};
+
+/// Ensure mipmaps generated with a kernel filter keep a flat color, and that
+/// normals are renormalized in every generated level.
+#[test_attr]
+pub async fn generated_mipmaps() -> anyhow::Result<()> {
+    let iad = no_gpu_return!(rend3::create_iad(None, None, None, None).await)
+        .context("InstanceAdapterDevice creation failed")?;
+
+    let Ok(runner) = TestRunner::builder().iad(iad).build().await else {
+        return Ok(());
+    };
+
+    let size = 128;
+    let tests = [
+        (MipmapFilter::Triangle, false, [255, 128, 0, 255], [255, 128, 0, 255]),
+        (MipmapFilter::Kaiser, false, [255, 128, 0, 255], [255, 128, 0, 255]),
+        // (1, 1, 1) normalized is (0.577, 0.577, 0.577), encoded as 0.789.
+        (MipmapFilter::Kaiser, true, [255, 255, 255, 255], [201, 201, 201, 255]),
+    ];
+
+    for (filter, renormalize_normals, texel, expected) in tests {
+        let generation = MipmapGeneration { filter, renormalize_normals };
+        let handle = runner.add_texture_2d(Texture {
+            label: None,
+            data: texel.repeat((size * size) as usize),
+            format: TextureFormat::Rgba8Unorm,
+            size: UVec2::splat(size),
+            mip_count: MipmapCount::Specific(NonZeroU32::new(2).unwrap()),
+            mip_source: MipmapSource::GeneratedWith(generation),
+        })?;
+
+        runner.swap_instruction_buffers();
+        let mut eval_output = runner.evaluate_instructions();
+        RenderGraph::new().execute(&runner.renderer, &mut eval_output);
+
+        let mip_size = size / 2;
+        let buffer = runner.device.create_buffer(&BufferDescriptor {
+            label: Some("mip buffer"),
+            size: (mip_size * mip_size * 4) as u64,
+            usage: BufferUsages::COPY_DST | BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let mut encoder = runner.device.create_command_encoder(&Default::default());
+        encoder.copy_texture_to_buffer(
+            ImageCopyTexture {
+                texture: &runner.data_core.lock().d2_texture_manager.get_internal(handle.get_raw()).texture,
+                mip_level: 1,
+                origin: Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            ImageCopyBuffer {
+                buffer: &buffer,
+                layout: ImageDataLayout { offset: 0, bytes_per_row: Some(mip_size * 4), rows_per_image: None },
+            },
+            Extent3d { width: mip_size, height: mip_size, depth_or_array_layers: 1 },
+        );
+        runner.queue.submit(Some(encoder.finish()));
+
+        let data = download_buffer(&runner.renderer, &buffer).await?;
+        for texel in data.chunks_exact(4) {
+            let close = texel.iter().zip(expected).all(|(&actual, expected)| actual.abs_diff(expected) <= 1);
+            assert!(close, "{generation:?}: expected {expected:?}, got {texel:?}");
+        }
+    }
+
+    Ok(())
+}
+
+/// Ensure only bindable 2D textures of a filterable float format can be imported.
+#[test_attr]
+pub async fn import_texture() -> anyhow::Result<()> {
+    let iad = no_gpu_return!(rend3::create_iad(None, None, None, None).await)
+        .context("InstanceAdapterDevice creation failed")?;
+
+    let Ok(runner) = TestRunner::builder().iad(iad).build().await else {
+        return Ok(());
+    };
+
+    let texture = |format, usage| {
+        runner.device.create_texture(&TextureDescriptor {
+            label: None,
+            size: Extent3d { width: 4, height: 4, depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format,
+            usage,
+            view_formats: &[],
+        })
+    };
+
+    let imported = runner.import_texture_2d(texture(TextureFormat::Rgba8UnormSrgb, TextureUsages::TEXTURE_BINDING));
+    assert!(imported.is_ok());
+
+    let not_float = runner.import_texture_2d(texture(TextureFormat::Rgba8Uint, TextureUsages::TEXTURE_BINDING));
+    assert!(matches!(not_float, Err(TextureCreationError::TextureFormatNotFloat { .. })));
+
+    let not_bindable = runner.import_texture_2d(texture(TextureFormat::Rgba8Unorm, TextureUsages::COPY_DST));
+    assert!(matches!(not_bindable, Err(TextureCreationError::ImportedTextureNotBindable { .. })));
+
+    Ok(())
+}
@@ -0,0 +1,61 @@
+use anyhow::Context;
+use glam::{DVec3, Mat4, UVec2, Vec3, Vec4};
+use rend3::{
+    types::{Camera, CameraProjection, Handedness, MeshBuilder, Object, ObjectMeshKind},
+    ShaderPreProcessor,
+};
+use rend3_routine::{
+    pbr::{AlbedoComponent, PbrMaterial},
+    thumbnail::ThumbnailRenderer,
+};
+use rend3_test::{no_gpu_return, test_attr};
+
+#[test_attr]
+pub async fn single_object() -> anyhow::Result<()> {
+    let iad = no_gpu_return!(rend3::create_iad(None, None, None, None).await)
+        .context("InstanceAdapterDevice creation failed")?;
+
+    let mut spp = ShaderPreProcessor::new();
+    rend3_routine::builtin_shaders(&mut spp);
+
+    let Ok(mut thumbnails) = ThumbnailRenderer::new(iad, Handedness::Left, &spp) else {
+        return Ok(());
+    };
+
+    let mesh = MeshBuilder::new(
+        vec![Vec3::new(0.5, -0.5, 0.0), Vec3::new(-0.5, -0.5, 0.0), Vec3::new(0.0, 0.5, 0.0)],
+        Handedness::Left,
+    )
+    .build()
+    .context("Failed to create mesh")?;
+    let mesh_hdl = thumbnails.renderer.add_mesh(mesh).unwrap();
+    let material_hdl = thumbnails.renderer.add_material(PbrMaterial {
+        albedo: AlbedoComponent::Value(Vec4::new(0.25, 0.5, 0.75, 1.0)),
+        unlit: true,
+        ..Default::default()
+    });
+    let object = Object {
+        mesh_kind: ObjectMeshKind::Static(mesh_hdl),
+        material: material_hdl,
+        transform: Mat4::IDENTITY,
+        world_offset: DVec3::ZERO,
+        sort_bias: 0,
+        debug_tint: None,
+    };
+    let camera = Camera { projection: CameraProjection::Raw(Mat4::IDENTITY), view: Mat4::IDENTITY };
+
+    let size = UVec2::splat(64);
+    let pixels = thumbnails.render(object, camera, size).await.context("Thumbnail rendering failed")?;
+    assert_eq!(pixels.len(), 64 * 64 * 4);
+
+    let pixel = |x: usize, y: usize| &pixels[(y * 64 + x) * 4..][..4];
+
+    // The corner shows the grey background, the center the object.
+    let corner = pixel(0, 0);
+    assert!(corner[0] == corner[1] && corner[1] == corner[2], "{corner:?}");
+    let center = pixel(32, 32);
+    assert!(center[0] < center[1] && center[1] < center[2], "{center:?}");
+    assert_eq!(center[3], 255);
+
+    Ok(())
+}
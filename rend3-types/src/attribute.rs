@@ -4,7 +4,7 @@ use std::{
     sync::atomic::{AtomicUsize, Ordering},
 };
 
-use bytemuck::Pod;
+use bytemuck::{Pod, Zeroable};
 use once_cell::sync::OnceCell;
 
 #[derive(Debug, Copy, Clone)]
@@ -122,14 +122,122 @@ impl VertexFormat for [u8; 4] {
     };
 }
 
+/// A unit vector packed into 32 bits with an octahedral encoding, for storing
+/// normals and tangents in a third of the space of a [`glam::Vec3`].
+///
+/// Shaders see it as a normalized `vec3<f32>`.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+#[repr(transparent)]
+pub struct OctNormal(pub u32);
+
+// SAFETY: repr(transparent) over a u32, so has no padding and any bit pattern is valid.
+unsafe impl Zeroable for OctNormal {}
+unsafe impl Pod for OctNormal {}
+
+impl OctNormal {
+    /// Encode a vector. It does not need to be normalized. Zero vectors
+    /// decode as +Z.
+    pub fn encode(vector: glam::Vec3) -> Self {
+        let sign_not_zero = |v: f32| if v >= 0.0 { 1.0 } else { -1.0 };
+
+        let l1_norm = vector.x.abs() + vector.y.abs() + vector.z.abs();
+        if l1_norm == 0.0 {
+            return Self(0);
+        }
+        let projected = vector / l1_norm;
+        let (x, y) = if projected.z >= 0.0 {
+            (projected.x, projected.y)
+        } else {
+            // Fold the lower hemisphere over the diagonals.
+            (
+                (1.0 - projected.y.abs()) * sign_not_zero(projected.x),
+                (1.0 - projected.x.abs()) * sign_not_zero(projected.y),
+            )
+        };
+
+        let snorm = |v: f32| (v.clamp(-1.0, 1.0) * i16::MAX as f32).round() as i16 as u16 as u32;
+        Self(snorm(x) | (snorm(y) << 16))
+    }
+
+    /// Decode to a normalized vector. This matches what shaders see.
+    pub fn decode(self) -> glam::Vec3 {
+        let unsnorm = |v: u32| (v as u16 as i16 as f32 / i16::MAX as f32).max(-1.0);
+        let (x, y) = (unsnorm(self.0), unsnorm(self.0 >> 16));
+
+        let mut vector = glam::Vec3::new(x, y, 1.0 - x.abs() - y.abs());
+        let t = (-vector.z).max(0.0);
+        vector.x += if vector.x >= 0.0 { -t } else { t };
+        vector.y += if vector.y >= 0.0 { -t } else { t };
+        vector.normalize()
+    }
+}
+
+impl VertexFormat for OctNormal {
+    const METADATA: VertexFormatMetadata =
+        VertexFormatMetadata { size: 4, shader_extract_fn: "extract_attribute_oct_normal", shader_type: "vec3<f32>" };
+}
+
 pub static VERTEX_ATTRIBUTE_POSITION: VertexAttribute<glam::Vec3> = VertexAttribute::new("position", None);
 pub static VERTEX_ATTRIBUTE_NORMAL: VertexAttribute<glam::Vec3> = VertexAttribute::new("normal", None);
 pub static VERTEX_ATTRIBUTE_TANGENT: VertexAttribute<glam::Vec3> = VertexAttribute::new("tangent", None);
+/// Packed alternative to [`VERTEX_ATTRIBUTE_NORMAL`]. A mesh should have at
+/// most one of the two. Packed normals are not updated by skinning.
+pub static VERTEX_ATTRIBUTE_PACKED_NORMAL: VertexAttribute<OctNormal> = VertexAttribute::new("packed_normal", None);
+/// Packed alternative to [`VERTEX_ATTRIBUTE_TANGENT`]. A mesh should have at
+/// most one of the two. Packed tangents are not updated by skinning.
+pub static VERTEX_ATTRIBUTE_PACKED_TANGENT: VertexAttribute<OctNormal> = VertexAttribute::new("packed_tangent", None);
 pub static VERTEX_ATTRIBUTE_TEXTURE_COORDINATES_0: VertexAttribute<glam::Vec2> =
     VertexAttribute::new("texture_coords_0", None);
 pub static VERTEX_ATTRIBUTE_TEXTURE_COORDINATES_1: VertexAttribute<glam::Vec2> =
     VertexAttribute::new("texture_coords_1", None);
+/// Vertex colors, as unorm8. Colors are linear unless the material says they
+/// are sRGB encoded, see the `srgb` flag of rend3-routine's `AlbedoComponent`.
 pub static VERTEX_ATTRIBUTE_COLOR_0: VertexAttribute<[u8; 4]> = VertexAttribute::new("color_0", Some("vec4<f32>(1.0)"));
 pub static VERTEX_ATTRIBUTE_COLOR_1: VertexAttribute<[u8; 4]> = VertexAttribute::new("color_1", Some("vec4<f32>(1.0)"));
 pub static VERTEX_ATTRIBUTE_JOINT_INDICES: VertexAttribute<[u16; 4]> = VertexAttribute::new("joint_indices", None);
 pub static VERTEX_ATTRIBUTE_JOINT_WEIGHTS: VertexAttribute<glam::Vec4> = VertexAttribute::new("joint_weights", None);
+/// Joint indices of the 5th to 8th joint influencing each vertex.
+pub static VERTEX_ATTRIBUTE_JOINT_INDICES_1: VertexAttribute<[u16; 4]> = VertexAttribute::new("joint_indices_1", None);
+/// Joint weights of the 5th to 8th joint influencing each vertex.
+pub static VERTEX_ATTRIBUTE_JOINT_WEIGHTS_1: VertexAttribute<glam::Vec4> =
+    VertexAttribute::new("joint_weights_1", None);
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec3;
+
+    use super::OctNormal;
+
+    #[test]
+    fn oct_normal_round_trip() {
+        let normals = [
+            // Axis aligned
+            Vec3::X,
+            Vec3::Y,
+            Vec3::Z,
+            -Vec3::X,
+            -Vec3::Y,
+            -Vec3::Z,
+            // Diagonal
+            Vec3::new(1.0, 1.0, 1.0),
+            Vec3::new(-1.0, 1.0, 1.0),
+            Vec3::new(1.0, -1.0, 0.0),
+            // Negative z, which is folded over the diagonals
+            Vec3::new(1.0, 1.0, -1.0),
+            Vec3::new(-1.0, -1.0, -1.0),
+            Vec3::new(0.3, -0.2, -0.9),
+            Vec3::new(-0.7, 0.1, -0.1),
+        ];
+
+        for normal in normals {
+            let normal = normal.normalize();
+            let decoded = OctNormal::encode(normal).decode();
+            assert!(decoded.abs_diff_eq(normal, 1e-4), "{normal} decoded as {decoded}");
+        }
+    }
+
+    #[test]
+    fn oct_normal_zero_decodes_as_z() {
+        assert_eq!(OctNormal::encode(Vec3::ZERO).decode(), Vec3::Z);
+    }
+}
@@ -14,13 +14,13 @@ use std::{
     num::NonZeroU32,
     ops::Deref,
     slice,
-    sync::Arc,
+    sync::{Arc, Weak},
 };
 
 use bytemuck::Zeroable;
 /// Reexport of the glam version rend3 is using.
 pub use glam;
-use glam::{Mat4, UVec2, Vec2, Vec3, Vec3A, Vec4};
+use glam::{DVec3, Mat4, UVec2, Vec2, Vec3, Vec4};
 use thiserror::Error;
 
 mod attribute;
@@ -142,6 +142,12 @@ impl<T> ResourceHandle<T> {
     pub fn get_raw(&self) -> RawResourceHandle<T> {
         self.raw
     }
+
+    /// Creates a handle which refers to the same resource without keeping it
+    /// alive.
+    pub fn downgrade(&self) -> WeakResourceHandle<T> {
+        WeakResourceHandle { refcount: Arc::downgrade(&self.refcount), raw: self.raw, _phantom: PhantomData }
+    }
 }
 
 impl<T> Deref for ResourceHandle<T> {
@@ -152,6 +158,47 @@ impl<T> Deref for ResourceHandle<T> {
     }
 }
 
+/// Resource handle which doesn't keep the resource alive. Created with
+/// [`ResourceHandle::downgrade`].
+pub struct WeakResourceHandle<T> {
+    #[cfg(not(target_arch = "wasm32"))]
+    refcount: Weak<dyn Fn(RawResourceHandle<T>) + Send + Sync>,
+    #[cfg(target_arch = "wasm32")]
+    refcount: Weak<dyn Fn(RawResourceHandle<T>)>,
+    raw: RawResourceHandle<T>,
+    _phantom: PhantomData<T>,
+}
+
+impl<T> Debug for WeakResourceHandle<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WeakResourceHandle")
+            .field("refcount", &self.refcount.strong_count())
+            .field("idx", &self.raw.idx)
+            .finish()
+    }
+}
+
+impl<T> Clone for WeakResourceHandle<T> {
+    fn clone(&self) -> Self {
+        Self { refcount: self.refcount.clone(), raw: self.raw, _phantom: self._phantom }
+    }
+}
+
+impl<T> WeakResourceHandle<T> {
+    /// Gets an owning handle to the resource, if any owning handles are still
+    /// alive.
+    pub fn upgrade(&self) -> Option<ResourceHandle<T>> {
+        Some(ResourceHandle { refcount: self.refcount.upgrade()?, raw: self.raw, _phantom: PhantomData })
+    }
+
+    /// Gets the equivalent raw handle for this handle.
+    ///
+    /// Part of rend3's internal interface for accessing internal resrouces
+    pub fn get_raw(&self) -> RawResourceHandle<T> {
+        self.raw
+    }
+}
+
 /// Tag type for differentiating Texture2Ds on the type level.
 #[doc(hidden)]
 pub struct Texture2DTag;
@@ -175,6 +222,8 @@ pub type TextureCubeHandle = ResourceHandle<TextureCubeTag>;
 pub type MaterialHandle = ResourceHandle<MaterialTag>;
 /// Refcounted handle to an Object
 pub type ObjectHandle = ResourceHandle<Object>;
+/// Weak handle to an Object
+pub type WeakObjectHandle = WeakResourceHandle<Object>;
 /// Refcounted handle to a DirectionalLight
 pub type DirectionalLightHandle = ResourceHandle<DirectionalLight>;
 /// Refcounted handle to a PointLight
@@ -275,6 +324,11 @@ pub enum MeshValidationError {
     ExceededMaxIndexCount { count: usize },
     #[error("Mesh has {count} indices which is not a multiple of three. Meshes are always composed of triangles")]
     IndexCountNotMultipleOfThree { count: usize },
+    #[error(
+        "Mesh has no indices and {count} vertices which is not a multiple of three, so the vertices can't be used as \
+         a triangle list"
+    )]
+    VertexCountNotMultipleOfThree { count: usize },
     #[error(
         "Index at position {index} has the value {value} which is out of bounds for vertex buffers of {max} length"
     )]
@@ -344,6 +398,7 @@ pub struct MeshBuilder {
     handedness: Handedness,
     flip_winding_order: bool,
     double_sided: bool,
+    packed_normals: bool,
 }
 impl MeshBuilder {
     /// Create a new [`MeshBuilder`] with a given set of positions.
@@ -358,8 +413,18 @@ impl MeshBuilder {
         }
     }
 
+    /// Add the values of a vertex attribute to the given mesh. Values given
+    /// earlier for the same attribute, including the positions, are replaced.
+    /// Replacing the positions changes the vertex count.
     pub fn with_attribute<T: VertexFormat>(mut self, attribute: &'static VertexAttribute<T>, values: Vec<T>) -> Self {
-        self.vertex_attributes.push(StoredVertexAttributeData::new(attribute, values));
+        if attribute.id() == VERTEX_ATTRIBUTE_POSITION.id() {
+            self.vertex_count = values.len();
+        }
+        let data = StoredVertexAttributeData::new(attribute, values);
+        match self.vertex_attributes.iter_mut().find(|existing| existing.id == data.id) {
+            Some(existing) => *existing = data,
+            None => self.vertex_attributes.push(data),
+        }
         self
     }
 
@@ -381,6 +446,16 @@ impl MeshBuilder {
         self.with_attribute(&VERTEX_ATTRIBUTE_TANGENT, tangents)
     }
 
+    /// Store the normals and tangents packed as [`OctNormal`]s once they are
+    /// built, using 4 bytes per vertex for each instead of 12.
+    ///
+    /// Packed normals and tangents are not updated by skinning, so this should
+    /// not be used for skinned meshes.
+    pub fn with_packed_normals(mut self) -> Self {
+        self.packed_normals = true;
+        self
+    }
+
     /// Add the first set of texture coordinates to the given mesh.
     ///
     /// # Panic
@@ -401,6 +476,9 @@ impl MeshBuilder {
 
     /// Add vertex colors to the given mesh.
     ///
+    /// Colors are treated as linear, unless the material they are drawn with
+    /// marks vertex colors as sRGB encoded.
+    ///
     /// # Panic
     ///
     /// Will panic if the length is different from the position buffer length.
@@ -426,6 +504,26 @@ impl MeshBuilder {
         self.with_attribute(&VERTEX_ATTRIBUTE_JOINT_WEIGHTS, joint_weights)
     }
 
+    /// Add a second set of vertex joint indices to the given mesh, for
+    /// vertices influenced by up to 8 joints.
+    ///
+    /// # Panic
+    ///
+    /// Will panic if the length is different from the position buffer length.
+    pub fn with_vertex_joint_indices_1(self, joint_indices: Vec<[u16; 4]>) -> Self {
+        self.with_attribute(&VERTEX_ATTRIBUTE_JOINT_INDICES_1, joint_indices)
+    }
+
+    /// Add a second set of vertex joint weights to the given mesh, for
+    /// vertices influenced by up to 8 joints.
+    ///
+    /// # Panic
+    ///
+    /// Will panic if the length is different from the position buffer length.
+    pub fn with_vertex_joint_weights_1(self, joint_weights: Vec<Vec4>) -> Self {
+        self.with_attribute(&VERTEX_ATTRIBUTE_JOINT_WEIGHTS_1, joint_weights)
+    }
+
     /// Add indices to the given mesh.
     ///
     /// # Panic
@@ -447,6 +545,9 @@ impl MeshBuilder {
     /// Mark this mesh as needing to be double sided. This will duplicate all
     /// faces with the opposite winding order. This acts as if backface culling
     /// was disabled.
+    ///
+    /// Materials which support [`Culling::None`] make meshes double sided
+    /// without doubling their index memory.
     pub fn with_double_sided(mut self) -> Self {
         self.double_sided = true;
         self
@@ -462,7 +563,8 @@ impl MeshBuilder {
     /// - The count of vertices is less than [`MAX_VERTEX_COUNT`].
     /// - All indexes are in bounds for the given vertex arrays.
     /// - There is a non-zero count of indices.
-    /// - There is a multiple-of-three count of indices.
+    /// - There is a multiple-of-three count of indices, or of vertices if no
+    ///   indices were given.
     pub unsafe fn without_validation(mut self) -> Self {
         self.without_validation = true;
         self
@@ -475,12 +577,21 @@ impl MeshBuilder {
     ///
     /// All others will be filled with defaults.
     pub fn build(self) -> Result<Mesh, MeshValidationError> {
+        if !self.without_validation && self.indices.is_none() && self.vertex_count % 3 != 0 {
+            return Err(MeshValidationError::VertexCountNotMultipleOfThree { count: self.vertex_count });
+        }
+
         let mut mesh = Mesh {
             attributes: self.vertex_attributes,
             vertex_count: self.vertex_count,
             indices: self.indices.unwrap_or_else(|| (0..self.vertex_count as u32).collect()),
         };
 
+        // Validate before double siding, so bad index counts are reported as given.
+        if !self.without_validation {
+            mesh.validate()?;
+        }
+
         if self.double_sided {
             mesh.double_side();
         }
@@ -488,10 +599,6 @@ impl MeshBuilder {
         let has_normals = mesh.find_attribute_index(&VERTEX_ATTRIBUTE_NORMAL).is_some();
         let has_tangents = mesh.find_attribute_index(&VERTEX_ATTRIBUTE_TANGENT).is_some();
 
-        if !self.without_validation {
-            mesh.validate()?;
-        }
-
         // We need to flip winding order first, so the normals will be facing the right
         // direction.
         if self.flip_winding_order {
@@ -508,6 +615,10 @@ impl MeshBuilder {
             unsafe { mesh.calculate_tangents(true) };
         }
 
+        if self.packed_normals {
+            mesh.pack_normals();
+        }
+
         Ok(mesh)
     }
 }
@@ -597,6 +708,23 @@ impl Mesh {
         )
     }
 
+    /// Replaces the normals and tangents of the mesh with
+    /// [`VERTEX_ATTRIBUTE_PACKED_NORMAL`] and
+    /// [`VERTEX_ATTRIBUTE_PACKED_TANGENT`], if it has them.
+    pub fn pack_normals(&mut self) {
+        for (source, packed) in [
+            (&VERTEX_ATTRIBUTE_NORMAL, &VERTEX_ATTRIBUTE_PACKED_NORMAL),
+            (&VERTEX_ATTRIBUTE_TANGENT, &VERTEX_ATTRIBUTE_PACKED_TANGENT),
+        ] {
+            let Some(index) = self.find_attribute_index(source.id()) else {
+                continue;
+            };
+            let vectors = self.attributes[index].typed_data(source).unwrap();
+            let encoded = vectors.iter().copied().map(OctNormal::encode).collect();
+            self.attributes[index] = StoredVertexAttributeData::new(packed, encoded);
+        }
+    }
+
     /// Calculate normals for the given mesh, assuming smooth shading and
     /// per-vertex normals.
     ///
@@ -837,6 +965,8 @@ impl Mesh {
     }
 
     /// Converts the mesh from single sided to double sided.
+    ///
+    /// Trailing indices which don't make up a whole triangle are dropped.
     pub fn double_side(&mut self) {
         let starting_len = self.indices.len();
         // This floors, so the following unsafe is in-bounds.
@@ -865,7 +995,8 @@ impl Mesh {
                 ptr.add(prim * 6 + 5).write(i1);
             }
 
-            self.indices.set_len(starting_len * 2);
+            // Only the whole triangles were written, which may be fewer than the starting indices.
+            self.indices.set_len(primative_count * 6);
         }
     }
 
@@ -908,8 +1039,38 @@ pub enum MipmapSource {
     /// The user will provide all of the mipmaps in the data texture. Upload all
     /// mip levels.
     Uploaded,
-    /// rend3 will generate the mipmaps for you. Upload only mip level 0.
+    /// rend3 will generate the mipmaps for you with a box filter. Upload only
+    /// mip level 0.
     Generated,
+    /// rend3 will generate the mipmaps for you with the given settings. Upload
+    /// only mip level 0.
+    GeneratedWith(MipmapGeneration),
+}
+
+/// Filter used to downsample each mip level from the one above it.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum MipmapFilter {
+    /// Averages each 2x2 block of texels. Cheapest, but aliases, which shows
+    /// up as shimmering on detailed textures.
+    #[default]
+    Box,
+    /// Tent filter two texels wide. Slightly blurrier than box, with less
+    /// aliasing.
+    Triangle,
+    /// Kaiser windowed sinc. Keeps the most detail with the least aliasing,
+    /// at the cost of slight ringing around sharp edges.
+    Kaiser,
+}
+
+/// Settings for generating mipmaps.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct MipmapGeneration {
+    pub filter: MipmapFilter,
+    /// Treat the RGB channels as a unit normal, encoded to 0..1 like in
+    /// tangent space normal maps, and renormalize it in every mip level.
+    /// Without this, filtered normals shorten, darkening lighting in the
+    /// distance.
+    pub renormalize_normals: bool,
 }
 
 /// A bitmap image used as a data source for a texture.
@@ -965,6 +1126,44 @@ pub enum SortingOrder {
     BackToFront,
 }
 
+/// Which faces of objects using a material are culled.
+///
+/// Faces are culled relative to the front face given by the [`Handedness`] of
+/// the renderer. Shadow passes cull the opposite faces, so `Back` draws the
+/// back faces into shadow maps.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Culling {
+    /// Cull back faces. This is the default.
+    #[default]
+    Back,
+    /// Cull front faces, such as for inverted hull outlines.
+    Front,
+    /// Cull no faces.
+    None,
+}
+
+impl Culling {
+    /// Face to cull given the face culled by the pass for [`Culling::Back`].
+    pub fn to_face(self, back: wgt::Face) -> Option<wgt::Face> {
+        match (self, back) {
+            (Self::Back, face) => Some(face),
+            (Self::Front, wgt::Face::Back) => Some(wgt::Face::Front),
+            (Self::Front, wgt::Face::Front) => Some(wgt::Face::Back),
+            (Self::None, _) => None,
+        }
+    }
+
+    /// Culling keeping the same faces when seen through a mirrored view, which
+    /// reverses the winding of every triangle.
+    pub fn mirrored(self) -> Self {
+        match self {
+            Self::Back => Self::Front,
+            Self::Front => Self::Back,
+            Self::None => Self::None,
+        }
+    }
+}
+
 /// Trait that abstracts over all possible arrays of optional raw texture handles.
 ///
 /// The IntoIterator stuff in this trait is because rust-analyzer gets totally
@@ -1026,7 +1225,8 @@ impl<const C: usize, T> MaterialArray<T> for [T; C] {
 ///     enabled, do `(texture_bitflag >> N) & 0x1 == 1`.
 /// - One Texture2D binding per texture, provided in the order given. If given a
 ///   `None`, will bind a null texture (1x1 texture with a (0, 0, 0, 255)
-///   pixel).
+///   pixel). Only the first [`Material::CPU_TEXTURE_COUNT`] textures are
+///   bound.
 ///
 /// ### GpuDriven Profile
 /// - A material array indexed by the material index. Each material has:
@@ -1041,6 +1241,11 @@ pub trait Material: WasmNotSend + WasmNotSync + 'static {
     type RequiredAttributeArrayType: MaterialArray<&'static VertexAttributeId>;
     type SupportedAttributeArrayType: MaterialArray<&'static VertexAttributeId>;
 
+    /// How many of the textures from [`Material::to_textures`] are bound in
+    /// the CpuDriven profile, where every texture takes a binding. Textures
+    /// past this count are only visible to GpuDriven shaders.
+    const CPU_TEXTURE_COUNT: u32 = <Self::TextureArrayType as MaterialArray<Option<RawTexture2DHandle>>>::COUNT;
+
     fn required_attributes() -> Self::RequiredAttributeArrayType;
     fn supported_attributes() -> Self::SupportedAttributeArrayType;
 
@@ -1050,6 +1255,11 @@ pub trait Material: WasmNotSend + WasmNotSync + 'static {
     /// How objects with this material should be sorted.
     fn sorting(&self) -> Sorting;
 
+    /// Which faces of objects with this material are culled.
+    fn culling(&self) -> Culling {
+        Culling::Back
+    }
+
     /// The array of textures that should be bound. Rend3 supports up to 32.
     fn to_textures(&self) -> Self::TextureArrayType;
 
@@ -1070,6 +1280,20 @@ changeable_struct! {
         pub mesh_kind: ObjectMeshKind,
         pub material: MaterialHandle,
         pub transform: Mat4,
+        /// Double precision translation added to `transform`. Objects far
+        /// from the world origin should keep their position here and
+        /// `transform` small, so they don't jitter once the render origin
+        /// is moved near them. Usually zero.
+        pub world_offset: DVec3,
+        /// Biases the draw order of this object. Objects with a higher bias
+        /// are drawn after objects with a lower bias, before distance to the
+        /// camera is taken into account, so transparent objects can be forced
+        /// over or under others. Only objects with the same material key are
+        /// ordered against each other. Usually 0.
+        pub sort_bias: i32,
+        /// Color the shaded object is multiplied by, to highlight it for
+        /// debugging or selection without changing its material.
+        pub debug_tint: Option<Vec4>,
     }
 }
 
@@ -1085,8 +1309,13 @@ pub struct Camera {
 #[derive(Debug, Copy, Clone)]
 pub enum CameraProjection {
     Orthographic {
-        /// Size assumes the location is at the center of the camera area.
-        size: Vec3A,
+        /// Width and height of the area seen, centered on the camera.
+        size: Vec2,
+        /// Distance of the near plane along the view direction. This may be
+        /// negative to see things behind the camera.
+        near: f32,
+        /// Distance of the far plane along the view direction.
+        far: f32,
     },
     Perspective {
         /// Vertical field of view in degrees.
@@ -1105,6 +1334,10 @@ impl Default for CameraProjection {
 
 changeable_struct! {
     /// Describes how directional lights (sun lights) and their shadows should be processed.
+    ///
+    /// Any number of directional lights can be added. Each shadow casting light
+    /// is given its own region of a shared shadow atlas, and the forward shader
+    /// sums the contributions of every light.
     pub struct DirectionalLight <- DirectionalLightChange {
         /// Color of the light.
         pub color: Vec3,
@@ -1116,6 +1349,35 @@ changeable_struct! {
         pub direction: Vec3,
         /// Distance from the camera that shadows should be calculated.
         pub distance: f32,
+        /// If false, no shadow map is allocated or rendered for this light and
+        /// it lights everything unoccluded. Useful for cheap fill lights.
+        pub casts_shadows: bool,
+        /// Move the shadow map with the camera in whole texel steps, which
+        /// stops shadow edges from shimmering as the camera moves. The
+        /// covered area then lags up to a texel behind the camera.
+        pub snap_to_texels: bool,
+        /// Angular diameter of the light in radians. If non-zero, shadows
+        /// are sharp where they touch their caster and soften with distance
+        /// from it, like the sun's at about 0.0093. This takes many more
+        /// shadow samples than the fixed size filter used at zero.
+        pub angular_size: f32,
+    }
+}
+
+impl Default for DirectionalLight {
+    /// A white light shining straight down, casting 2048x2048 shadows up to
+    /// 100 units from the camera.
+    fn default() -> Self {
+        Self {
+            color: Vec3::ONE,
+            resolution: 2048,
+            intensity: 1.0,
+            direction: Vec3::NEG_Y,
+            distance: 100.0,
+            casts_shadows: true,
+            snap_to_texels: true,
+            angular_size: 0.0,
+        }
     }
 }
 
@@ -1128,10 +1390,19 @@ changeable_struct! {
         /// The color of the light.
         pub color: Vec3,
 
-        /// The radius of the light.
+        /// The distance at which the light's contribution fades to zero.
+        ///
+        /// The light falls off with the inverse square of the distance, and
+        /// is smoothly windowed to reach zero at the radius. Surfaces outside
+        /// the radius are not lit at all, so larger radii are more expensive.
         pub radius: f32,
 
-        /// Constant multiplier for the light.
+        /// The luminous intensity of the light, in candela.
+        ///
+        /// A surface facing the light from `d` meters away is lit with an
+        /// illuminance of `intensity / d²`, before the radius window is
+        /// applied. At one meter, this matches a
+        /// [`DirectionalLight`] of the same intensity.
         pub intensity: f32,
     }
 }
@@ -1202,6 +1473,75 @@ impl Default for Handedness {
     }
 }
 
+/// Describes how view space depth is mapped onto the depth buffer. Affects the
+/// projection matrices, depth clear values, and depth comparisons of every
+/// pipeline.
+///
+/// Reverse depth maps the near plane to 1.0 and infinity to 0.0. Combined with
+/// a floating point depth buffer, this spreads precision evenly over the whole
+/// view distance and allows for an infinite far plane. This is the default and
+/// what all built in routines are tuned for.
+///
+/// Forward depth maps the near plane to 0.0 and the far plane to 1.0. Precision
+/// drops off sharply in the distance, but it matches what most external tools
+/// and hand written projection matrices expect.
+///
+/// [`CameraProjection::Raw`] matrices are used as-is, so they must match the
+/// convention in use.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum DepthConvention {
+    #[default]
+    Reverse,
+    Forward,
+}
+
+impl DepthConvention {
+    /// Returns `true` if this is [`DepthConvention::Reverse`].
+    pub fn is_reverse(self) -> bool {
+        self == Self::Reverse
+    }
+
+    /// Depth of the near plane.
+    pub fn near_depth(self) -> f32 {
+        match self {
+            Self::Reverse => 1.0,
+            Self::Forward => 0.0,
+        }
+    }
+
+    /// Depth the depth buffer is cleared to. This is the farthest depth
+    /// possible.
+    pub fn clear_depth(self) -> f32 {
+        match self {
+            Self::Reverse => 0.0,
+            Self::Forward => 1.0,
+        }
+    }
+
+    /// Comparison which passes when the incoming depth is at least as close to
+    /// the camera as the stored depth.
+    pub fn closer_equal(self) -> wgt::CompareFunction {
+        match self {
+            Self::Reverse => wgt::CompareFunction::GreaterEqual,
+            Self::Forward => wgt::CompareFunction::LessEqual,
+        }
+    }
+}
+
+/// How the joint matrices of a [`Skeleton`] are blended together for each
+/// vertex.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum SkinningMethod {
+    /// Blend the joint matrices linearly. Fast and supports any affine joint
+    /// matrix, but volume collapses around joints with a lot of twist.
+    #[default]
+    LinearBlend,
+    /// Blend the joints as dual quaternions. Preserves volume around twisting
+    /// joints. Scale is taken out of each joint matrix and blended linearly
+    /// on its own, so shear is not supported.
+    DualQuaternion,
+}
+
 /// A Skeleton stores the necessary data to do vertex skinning for an [Object].
 #[derive(Debug, Clone)]
 pub struct Skeleton {
@@ -1214,6 +1554,8 @@ pub struct Skeleton {
     /// a Skeleton with the joint transform matrices instead.
     pub joint_matrices: Vec<Mat4>,
     pub mesh: MeshHandle,
+    /// How the joint matrices are blended.
+    pub skinning_method: SkinningMethod,
 }
 
 impl Skeleton {
@@ -1225,7 +1567,7 @@ impl Skeleton {
         inverse_bind_transforms: &[Mat4],
     ) -> Skeleton {
         let joint_matrices = Self::compute_joint_matrices(joint_global_transforms, inverse_bind_transforms);
-        Skeleton { joint_matrices, mesh }
+        Skeleton { joint_matrices, mesh, skinning_method: SkinningMethod::LinearBlend }
     }
 
     /// Given a list of joint global positions and another one with inverse bind
@@ -1264,3 +1606,39 @@ macro_rules! trait_supertrait_alias {
         impl<T: $($supertraits)*> $name for T {}
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec3;
+
+    use super::{Handedness, MeshBuilder, VERTEX_ATTRIBUTE_NORMAL, VERTEX_ATTRIBUTE_POSITION};
+
+    #[test]
+    fn repeated_attribute_replaces_earlier_values() {
+        let positions = vec![Vec3::ZERO, Vec3::X, Vec3::Y];
+        let mesh = MeshBuilder::new(positions, Handedness::Left)
+            .with_vertex_normals(vec![Vec3::X; 3])
+            .with_vertex_normals(vec![Vec3::Z; 3])
+            .build()
+            .unwrap();
+
+        let normal_attributes = mesh.attributes.iter().filter(|a| a.id() == VERTEX_ATTRIBUTE_NORMAL.id()).count();
+        assert_eq!(normal_attributes, 1);
+        let normals = mesh.attributes.iter().find_map(|a| a.typed_data(&VERTEX_ATTRIBUTE_NORMAL)).unwrap();
+        assert_eq!(normals, [Vec3::Z; 3]);
+    }
+
+    #[test]
+    fn replacing_positions_updates_vertex_count() {
+        let mesh = MeshBuilder::new(vec![Vec3::ZERO, Vec3::X, Vec3::Y], Handedness::Left)
+            .with_attribute(
+                &VERTEX_ATTRIBUTE_POSITION,
+                vec![Vec3::ZERO, Vec3::X, Vec3::Y, Vec3::Z, Vec3::ONE, -Vec3::X],
+            )
+            .build()
+            .unwrap();
+
+        assert_eq!(mesh.vertex_count, 6);
+        assert_eq!(mesh.indices, [0, 1, 2, 3, 4, 5]);
+    }
+}
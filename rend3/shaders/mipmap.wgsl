@@ -26,7 +26,116 @@ var r_color: texture_2d<f32>;
 @group(1) @binding(0)
 var r_sampler: sampler;
 
+const FILTER_TRIANGLE: u32 = 1u;
+const FILTER_KAISER: u32 = 2u;
+
+const PI: f32 = 3.14159265358979;
+// Same parameters as nvidia-texture-tools' Kaiser filter.
+const KAISER_WIDTH: f32 = 3.0;
+const KAISER_ALPHA: f32 = 4.0;
+
+// Zeroth order modified Bessel function of the first kind.
+fn bessel0(x: f32) -> f32 {
+    let quarter_x2 = x * x * 0.25;
+    var sum = 1.0;
+    var term = 1.0;
+    for (var k = 1; k < 16; k++) {
+        term *= quarter_x2 / f32(k * k);
+        sum += term;
+    }
+    return sum;
+}
+
+fn sinc(x: f32) -> f32 {
+    if (abs(x) < 0.0001) {
+        return 1.0;
+    }
+    let pi_x = PI * x;
+    return sin(pi_x) / pi_x;
+}
+
+// Distance from the center of the filter to its edge, in destination texels.
+fn filter_radius(kind: u32) -> f32 {
+    if (kind == FILTER_KAISER) {
+        return KAISER_WIDTH;
+    }
+    return 1.0;
+}
+
+// Weight of a texel `x` destination texels from the center of the filter.
+fn filter_weight(kind: u32, x: f32) -> f32 {
+    if (kind == FILTER_KAISER) {
+        let t = x / KAISER_WIDTH;
+        if (abs(t) >= 1.0) {
+            return 0.0;
+        }
+        return sinc(x) * bessel0(KAISER_ALPHA * sqrt(1.0 - t * t)) / bessel0(KAISER_ALPHA);
+    }
+    return max(1.0 - abs(x), 0.0);
+}
+
+// Convolves the source level with a separable filter centered on the destination texel.
+fn kernel_filter(position: vec2<f32>, kind: u32) -> vec4<f32> {
+    let src_size = vec2<i32>(textureDimensions(r_color));
+    let dst_size = max(src_size / 2, vec2<i32>(1));
+    let scale = vec2<f32>(src_size) / vec2<f32>(dst_size);
+
+    let center = position * scale;
+    let radius = filter_radius(kind) * scale;
+    let start_texel = vec2<i32>(floor(center - radius));
+    let end_texel = vec2<i32>(ceil(center + radius));
+
+    var sum = vec4<f32>(0.0);
+    var total_weight = 0.0;
+    for (var y = start_texel.y; y <= end_texel.y; y++) {
+        let weight_y = filter_weight(kind, (f32(y) + 0.5 - center.y) / scale.y);
+        if (weight_y == 0.0) {
+            continue;
+        }
+        for (var x = start_texel.x; x <= end_texel.x; x++) {
+            let weight = weight_y * filter_weight(kind, (f32(x) + 0.5 - center.x) / scale.x);
+            let coords = clamp(vec2<i32>(x, y), vec2<i32>(0), src_size - 1);
+            sum += textureLoad(r_color, coords, 0) * weight;
+            total_weight += weight;
+        }
+    }
+    return sum / total_weight;
+}
+
+fn renormalize(color: vec4<f32>) -> vec4<f32> {
+    let normal = color.rgb * 2.0 - 1.0;
+    if (dot(normal, normal) == 0.0) {
+        return color;
+    }
+    return vec4<f32>(normalize(normal) * 0.5 + 0.5, color.a);
+}
+
 @fragment
-fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+fn fs_box(in: VertexOutput) -> @location(0) vec4<f32> {
     return textureSample(r_color, r_sampler, in.tex_coords);
 }
+
+@fragment
+fn fs_box_normal(in: VertexOutput) -> @location(0) vec4<f32> {
+    return renormalize(textureSample(r_color, r_sampler, in.tex_coords));
+}
+
+@fragment
+fn fs_triangle(in: VertexOutput) -> @location(0) vec4<f32> {
+    return kernel_filter(in.position.xy, FILTER_TRIANGLE);
+}
+
+@fragment
+fn fs_triangle_normal(in: VertexOutput) -> @location(0) vec4<f32> {
+    return renormalize(kernel_filter(in.position.xy, FILTER_TRIANGLE));
+}
+
+@fragment
+fn fs_kaiser(in: VertexOutput) -> @location(0) vec4<f32> {
+    return kernel_filter(in.position.xy, FILTER_KAISER);
+}
+
+@fragment
+fn fs_kaiser_normal(in: VertexOutput) -> @location(0) vec4<f32> {
+    return renormalize(kernel_filter(in.position.xy, FILTER_KAISER));
+}
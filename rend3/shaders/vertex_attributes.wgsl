@@ -79,6 +79,17 @@ fn extract_attribute_vec4_u16(byte_base_offset: u32, vertex_index: u32) -> vec4<
     );
 }
 
+fn extract_attribute_oct_normal(byte_base_offset: u32, vertex_index: u32) -> vec3<f32> {
+    let first_element_idx = byte_base_offset / 4u + vertex_index;
+    let encoded = unpack2x16snorm(vertex_buffer[first_element_idx]);
+    // Unfold the lower hemisphere from the diagonals.
+    var vector = vec3<f32>(encoded, 1.0 - abs(encoded.x) - abs(encoded.y));
+    let t = saturate(-vector.z);
+    vector.x += select(t, -t, vector.x >= 0.0);
+    vector.y += select(t, -t, vector.y >= 0.0);
+    return normalize(vector);
+}
+
 fn extract_attribute_vec4_u8_unorm(byte_base_offset: u32, vertex_index: u32) -> vec4<f32> {
     let first_element_idx = byte_base_offset / 4u + vertex_index;
     return unpack4x8unorm(vertex_buffer[first_element_idx]);
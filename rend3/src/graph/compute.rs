@@ -0,0 +1,88 @@
+use glam::UVec3;
+use wgpu::{BindGroup, Buffer, BufferAddress, ComputePassDescriptor, ComputePipeline};
+
+use crate::{
+    graph::{DataHandle, DeclaredDependency, NodeResourceUsage, RenderGraph},
+    util::typedefs::SsoString,
+};
+
+/// A bind group used by a compute node made with
+/// [`RenderGraph::add_compute_node`].
+#[derive(Debug, Clone, Copy)]
+pub enum ComputeBindGroup<'node> {
+    /// A bind group that lives outside of the graph.
+    Static(&'node BindGroup),
+    /// A bind group produced by a previous node. Use
+    /// [`RenderGraphNodeBuilder::add_dependencies_to_render_targets`](crate::graph::RenderGraphNodeBuilder::add_dependencies_to_render_targets)
+    /// on the handle if the bind group references graph render targets.
+    Data(DataHandle<BindGroup>),
+}
+
+/// How many workgroups a compute node dispatches.
+#[derive(Debug, Clone, Copy)]
+pub enum ComputeDispatch<'node> {
+    /// Dispatch a fixed amount of workgroups in each dimension.
+    Direct(UVec3),
+    /// Read the workgroup counts from a buffer at the given offset.
+    Indirect { buffer: &'node Buffer, offset: BufferAddress },
+}
+
+enum DeclaredBindGroup<'node> {
+    Static(&'node BindGroup),
+    Data(DeclaredDependency<DataHandle<BindGroup>>),
+}
+
+impl<'node> RenderGraph<'node> {
+    /// Adds a node which dispatches a compute shader.
+    ///
+    /// Bind groups are bound in order starting at group 0. As the graph can't
+    /// know what the shader writes to, the node is treated as having side
+    /// effects and will never be culled.
+    pub fn add_compute_node<S>(
+        &mut self,
+        label: S,
+        pipeline: &'node ComputePipeline,
+        bind_groups: &[ComputeBindGroup<'node>],
+        dispatch: ComputeDispatch<'node>,
+    ) where
+        SsoString: From<S>,
+    {
+        let label = SsoString::from(label);
+        let mut builder = self.add_node::<SsoString>(label.clone());
+        builder.add_side_effect();
+
+        let bind_groups: Vec<_> = bind_groups
+            .iter()
+            .map(|&bg| match bg {
+                ComputeBindGroup::Static(bg) => DeclaredBindGroup::Static(bg),
+                ComputeBindGroup::Data(handle) => {
+                    DeclaredBindGroup::Data(builder.add_data(handle, NodeResourceUsage::Input))
+                }
+            })
+            .collect();
+
+        builder.build(move |mut ctx| {
+            let encoder = ctx.encoder_or_pass.take_encoder();
+
+            profiling::scope!("compute node");
+
+            let mut cpass =
+                encoder.begin_compute_pass(&ComputePassDescriptor { label: Some(&label), timestamp_writes: None });
+            cpass.set_pipeline(pipeline);
+            for (idx, bg) in bind_groups.into_iter().enumerate() {
+                let bg = match bg {
+                    DeclaredBindGroup::Static(bg) => bg,
+                    DeclaredBindGroup::Data(handle) => ctx
+                        .graph_data
+                        .get_data(ctx.temps, handle)
+                        .expect("Compute node bind group data handle was never set"),
+                };
+                cpass.set_bind_group(idx as u32, bg, &[]);
+            }
+            match dispatch {
+                ComputeDispatch::Direct(count) => cpass.dispatch_workgroups(count.x, count.y, count.z),
+                ComputeDispatch::Indirect { buffer, offset } => cpass.dispatch_workgroups_indirect(buffer, offset),
+            }
+        });
+    }
+}
@@ -0,0 +1,245 @@
+use glam::Vec4;
+use wgpu::{Extent3d, Sampler, TextureFormat};
+
+use crate::{
+    graph::{
+        GraphSubResource, NodeResourceUsage, RenderGraph, RenderPassTarget, RenderPassTargets, RenderTargetHandle,
+    },
+    util::bind_merge::BindGroupBuilder,
+};
+
+impl<'node> RenderGraph<'node> {
+    /// Adds a node which copies the contents of `src` into `dst` using
+    /// `copy_texture_to_texture`.
+    ///
+    /// The first mip level of each handle is used. The copied region starts at
+    /// the offset of each handle's viewport and is the size of the source
+    /// viewport, covering every layer of the source.
+    ///
+    /// # Format Compatibility
+    ///
+    /// - Both textures must have the same format, ignoring the sRGB-ness of
+    ///   the format, so `Rgba8Unorm` can be copied to `Rgba8UnormSrgb`.
+    /// - Both textures must have the same sample count.
+    /// - `src` must have `COPY_SRC` usage and `dst` must have `COPY_DST` usage.
+    /// - Depth and stencil formats must copy whole subresources, so both
+    ///   viewports must cover the entire mip level and be at the origin.
+    ///
+    /// # Panics
+    ///
+    /// When the node runs, if the formats or sample counts differ or the
+    /// source region doesn't fit in the destination.
+    pub fn add_copy(&mut self, src: RenderTargetHandle, dst: RenderTargetHandle) {
+        let mut builder = self.add_node("Copy Texture");
+
+        let src_handle = builder.add_render_target(src, NodeResourceUsage::Input);
+        let dst_handle = builder.add_render_target(dst, NodeResourceUsage::Output);
+
+        builder.build(move |mut ctx| {
+            let src = ctx.graph_data.get_render_target_texture(src_handle);
+            let dst = ctx.graph_data.get_render_target_texture(dst_handle);
+
+            let src_format = src.texture.format();
+            let dst_format = dst.texture.format();
+            assert_eq!(
+                src_format.remove_srgb_suffix(),
+                dst_format.remove_srgb_suffix(),
+                "Cannot copy between textures of incompatible formats {:?} and {:?}",
+                src_format,
+                dst_format
+            );
+            assert_eq!(
+                src.texture.sample_count(),
+                dst.texture.sample_count(),
+                "Cannot copy between textures with different sample counts"
+            );
+
+            let layers = src.layers.end - src.layers.start;
+            assert!(
+                src.viewport.size.cmple(dst.viewport.size).all() && layers <= dst.layers.end - dst.layers.start,
+                "Copy source region {:?} with {} layers does not fit in destination region {:?} with {} layers",
+                src.viewport.size,
+                layers,
+                dst.viewport.size,
+                dst.layers.end - dst.layers.start
+            );
+
+            let encoder = ctx.encoder_or_pass.take_encoder();
+
+            profiling::scope!("copy texture");
+
+            encoder.copy_texture_to_texture(
+                src.as_image_copy(),
+                dst.as_image_copy(),
+                Extent3d { width: src.viewport.size.x, height: src.viewport.size.y, depth_or_array_layers: layers },
+            );
+        });
+    }
+
+    /// Adds a node which draws `src` over all of `dst` using a full-screen
+    /// triangle, filtering with `sampler`.
+    ///
+    /// Unlike [`RenderGraph::add_copy`], the formats and sizes of the textures
+    /// don't need to match, so this can be used to resize or convert between
+    /// formats. `src` must be a single layer of a filterable float texture, and
+    /// is sampled in its entirety regardless of its viewport. `dst` is rendered
+    /// to within its viewport and must be single sampled.
+    pub fn add_blit(&mut self, src: RenderTargetHandle, dst: RenderTargetHandle, sampler: &'node Sampler) {
+        let mut builder = self.add_node("Blit Texture");
+
+        let src_handle = builder.add_render_target(src, NodeResourceUsage::Input);
+        let dst_handle = builder.add_render_target(dst, NodeResourceUsage::Reference);
+
+        let rpass_handle = builder.add_renderpass(
+            RenderPassTargets {
+                targets: vec![RenderPassTarget { color: dst, clear: Vec4::ZERO, resolve: None }],
+                depth_stencil: None,
+            },
+            NodeResourceUsage::InputOutput,
+        );
+
+        builder.build(move |mut ctx| {
+            let rpass = ctx.encoder_or_pass.take_rpass(rpass_handle);
+            let src_view = ctx.graph_data.get_render_target(src_handle);
+            let dst = ctx.graph_data.get_render_target_texture(dst_handle);
+
+            profiling::scope!("blit texture");
+
+            let blitter = &ctx.renderer.blitter;
+            let device = &ctx.renderer.device;
+
+            let pipeline = ctx.temps.add(blitter.pipeline(device, dst.texture.format()));
+            let texture_bg = ctx.temps.add(BindGroupBuilder::new().append_texture_view(src_view).build(
+                device,
+                Some("blit texture bg"),
+                &blitter.texture_bgl,
+            ));
+            let sampler_bg = ctx.temps.add(BindGroupBuilder::new().append_sampler(sampler).build(
+                device,
+                Some("blit sampler bg"),
+                &blitter.sampler_bgl,
+            ));
+
+            rpass.set_pipeline(pipeline);
+            rpass.set_bind_group(0, texture_bg, &[]);
+            rpass.set_bind_group(1, sampler_bg, &[]);
+            rpass.draw(0..3, 0..1);
+        });
+    }
+
+    /// Adds a node which resolves the multisampled `src` into `dst`.
+    ///
+    /// This is an otherwise empty renderpass with `dst` as the resolve
+    /// attachment of `src`, so the whole of both textures is used regardless
+    /// of their viewports.
+    ///
+    /// # Constraints
+    ///
+    /// - `src` must have more than one sample and `dst` must have one.
+    /// - Both textures must have the exact same format, including sRGB-ness,
+    ///   and the format must support resolving.
+    /// - Both textures must be the same size.
+    /// - Both textures must have `RENDER_ATTACHMENT` usage.
+    ///
+    /// # Panics
+    ///
+    /// If the sample counts, formats, or sizes are wrong.
+    pub fn add_resolve(&mut self, src: RenderTargetHandle, dst: RenderTargetHandle) {
+        let (src_samples, src_format, src_size) = self.texture_properties(src);
+        let (dst_samples, dst_format, dst_size) = self.texture_properties(dst);
+        assert!(src_samples > 1, "Cannot resolve from a texture with a single sample");
+        assert_eq!(dst_samples, 1, "Cannot resolve into a multisampled texture");
+        assert_eq!(src_format, dst_format, "Cannot resolve between textures of different formats");
+        assert_eq!(src_size, dst_size, "Cannot resolve between textures of different sizes");
+
+        let mut builder = self.add_node("Resolve Texture");
+
+        builder.add_render_target(src, NodeResourceUsage::Input);
+        builder.add_render_target(dst, NodeResourceUsage::Output);
+
+        // Usages are declared above, this only gives the graph the attachments.
+        let _rpass_handle = builder.add_renderpass(
+            RenderPassTargets {
+                targets: vec![RenderPassTarget { color: src, clear: Vec4::ZERO, resolve: Some(dst) }],
+                depth_stencil: None,
+            },
+            NodeResourceUsage::Reference,
+        );
+
+        // The renderpass does the resolve, there is nothing to record.
+        builder.build(|_| {});
+    }
+
+    /// Sample count, format and size of the texture behind `handle`.
+    fn texture_properties(&self, handle: RenderTargetHandle) -> (u32, TextureFormat, Extent3d) {
+        match handle.resource {
+            GraphSubResource::Texture(region) => {
+                let desc = &self.targets[region.idx];
+                let size =
+                    Extent3d { width: desc.resolution.x, height: desc.resolution.y, depth_or_array_layers: desc.depth };
+                (desc.samples as u32, desc.format, size)
+            }
+            GraphSubResource::ImportedTexture(region) => {
+                let texture = self.imported_targets[region.idx].as_texture_ref();
+                (texture.sample_count(), texture.format(), texture.size())
+            }
+            GraphSubResource::External | GraphSubResource::Data(_) => unreachable!(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::UVec2;
+    use rend3_types::{SampleCount, TextureFormat, TextureUsages};
+
+    use crate::graph::{RenderGraph, RenderTargetDescriptor, RenderTargetHandle};
+
+    fn target(graph: &mut RenderGraph<'_>, samples: SampleCount, format: TextureFormat) -> RenderTargetHandle {
+        graph.add_render_target(RenderTargetDescriptor {
+            label: None,
+            resolution: UVec2::splat(16),
+            depth: 1,
+            samples,
+            mip_levels: Some(1),
+            format,
+            usage: TextureUsages::RENDER_ATTACHMENT,
+        })
+    }
+
+    #[test]
+    fn resolve() {
+        let mut graph = RenderGraph::new();
+        let src = target(&mut graph, SampleCount::Four, TextureFormat::Rgba16Float);
+        let dst = target(&mut graph, SampleCount::One, TextureFormat::Rgba16Float);
+        graph.add_resolve(src, dst);
+        assert_eq!(graph.nodes.len(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot resolve from a texture with a single sample")]
+    fn resolve_single_sampled_source() {
+        let mut graph = RenderGraph::new();
+        let src = target(&mut graph, SampleCount::One, TextureFormat::Rgba16Float);
+        let dst = target(&mut graph, SampleCount::One, TextureFormat::Rgba16Float);
+        graph.add_resolve(src, dst);
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot resolve into a multisampled texture")]
+    fn resolve_multisampled_destination() {
+        let mut graph = RenderGraph::new();
+        let src = target(&mut graph, SampleCount::Four, TextureFormat::Rgba16Float);
+        let dst = target(&mut graph, SampleCount::Four, TextureFormat::Rgba16Float);
+        graph.add_resolve(src, dst);
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot resolve between textures of different formats")]
+    fn resolve_different_formats() {
+        let mut graph = RenderGraph::new();
+        let src = target(&mut graph, SampleCount::Four, TextureFormat::Rgba16Float);
+        let dst = target(&mut graph, SampleCount::One, TextureFormat::Rgba8Unorm);
+        graph.add_resolve(src, dst);
+    }
+}
@@ -11,7 +11,7 @@ use glam::UVec2;
 use wgpu::{
     Buffer, CommandBuffer, CommandEncoder, CommandEncoderDescriptor, LoadOp, Operations, RenderPass,
     RenderPassColorAttachment, RenderPassDepthStencilAttachment, RenderPassDescriptor, StoreOp, SurfaceTexture,
-    Texture, TextureView, TextureViewDescriptor,
+    Texture, TextureAspect, TextureView, TextureViewDescriptor,
 };
 
 use super::ViewportRect;
@@ -34,6 +34,12 @@ pub struct InstructionEvaluationOutput {
     pub shadow_target_size: UVec2,
     pub shadows: Vec<ShadowDesc>,
     pub mesh_buffer: Arc<Buffer>,
+    /// Number of objects written to the object buffers this frame. Only
+    /// objects which changed since the last frame are written.
+    pub objects_uploaded: usize,
+    /// Whether the point light buffer was written this frame. It is only
+    /// written when a point light was added, changed, or removed.
+    pub point_lights_uploaded: bool,
 }
 
 pub trait AsTextureReference {
@@ -75,6 +81,9 @@ struct ResourceSpan {
 pub struct RenderGraph<'node> {
     pub(super) targets: Vec<RenderTargetDescriptor>,
     pub(super) imported_targets: Vec<&'node dyn AsTextureReference>,
+    /// Imported targets whose existing contents are loaded on first use,
+    /// instead of being cleared.
+    pub(super) preserved_imported_targets: FastHashSet<usize>,
     pub(super) data: Vec<DataContents>,
     pub(super) nodes: Vec<RenderGraphNode<'node>>,
 }
@@ -83,6 +92,7 @@ impl<'node> RenderGraph<'node> {
         Self {
             targets: Vec::with_capacity(32),
             imported_targets: Vec::with_capacity(32),
+            preserved_imported_targets: FastHashSet::default(),
             data: Vec::with_capacity(32),
             nodes: Vec::with_capacity(64),
         }
@@ -112,6 +122,7 @@ impl<'node> RenderGraph<'node> {
                 mip_start: 0,
                 mip_end: desc.to_core().mip_count(),
                 viewport: ViewportRect { offset: UVec2::ZERO, size: desc.resolution },
+                aspect: TextureAspect::All,
             }),
         };
         self.targets.push(desc);
@@ -135,10 +146,56 @@ impl<'node> RenderGraph<'node> {
                 mip_start: mips.start,
                 mip_end: mips.end,
                 viewport,
+                aspect: TextureAspect::All,
             }),
         }
     }
 
+    /// Imports an external texture whose contents are kept. Renderpasses load
+    /// it on first use instead of clearing it, so several graphs can render
+    /// into different viewports of the same texture.
+    pub fn add_preserved_imported_render_target(
+        &mut self,
+        texture: &'node dyn AsTextureReference,
+        layers: Range<u32>,
+        mips: Range<u8>,
+        viewport: ViewportRect,
+    ) -> RenderTargetHandle {
+        let handle = self.add_imported_render_target(texture, layers, mips, viewport);
+        self.preserved_imported_targets.insert(self.imported_targets.len() - 1);
+        handle
+    }
+
+    /// Imports an external depth texture, so the scene is depth tested against
+    /// what is already in it. Renderpasses load its contents on first use
+    /// instead of clearing it, then write to it as usual.
+    ///
+    /// The texture must have a format with a depth aspect and
+    /// `RENDER_ATTACHMENT` usage, and match the sample count of the passes it
+    /// is used in. Routines may require more, such as the base rendergraph of
+    /// rend3-routine. All mips of the first layer are imported.
+    pub fn add_imported_depth_target(
+        &mut self,
+        texture: &'node dyn AsTextureReference,
+        viewport: ViewportRect,
+    ) -> RenderTargetHandle {
+        let mip_count = texture.as_texture_ref().mip_level_count() as u8;
+        self.add_preserved_imported_render_target(texture, 0..1, 0..mip_count, viewport)
+    }
+
+    /// Declares a slot of graph-scoped data of type `T`, which starts out
+    /// unset.
+    ///
+    /// The returned handle is the only way to refer to the data; pass it to
+    /// every node that produces or consumes it. As the handle carries `T`,
+    /// reading it back as a different type is a compile error:
+    ///
+    /// ```compile_fail
+    /// # fn f(graph: &mut rend3::graph::RenderGraph<'_>) {
+    /// let handle = graph.add_data::<u32>();
+    /// let _: rend3::graph::DataHandle<f32> = handle;
+    /// # }
+    /// ```
     pub fn add_data<T: 'static>(&mut self) -> DataHandle<T> {
         let idx = self.data.len();
         self.data.push(DataContents::new::<T>());
@@ -330,6 +387,7 @@ impl<'node> RenderGraph<'node> {
                             array_layer_count: Some(region.layer_end - region.layer_start),
                             base_mip_level: region.mip_start as u32,
                             mip_level_count: Some((region.mip_end - region.mip_start) as u32),
+                            aspect: region.aspect,
                             ..TextureViewDescriptor::default()
                         });
                         vacant.insert(view);
@@ -343,6 +401,7 @@ impl<'node> RenderGraph<'node> {
                                 array_layer_count: Some(region.layer_end - region.layer_start),
                                 base_mip_level: region.mip_start as u32,
                                 mip_level_count: Some((region.mip_end - region.mip_start) as u32),
+                                aspect: region.aspect,
                                 ..TextureViewDescriptor::default()
                             });
                         vacant.insert(view);
@@ -414,6 +473,7 @@ impl<'node> RenderGraph<'node> {
                         &resource_spans,
                         &active_views,
                         &imported_views,
+                        &self.preserved_imported_targets,
                     ));
                 }
                 next_rpass_idx += 1;
@@ -423,6 +483,8 @@ impl<'node> RenderGraph<'node> {
                 let store = RenderGraphDataStore {
                     texture_mapping: &active_views,
                     external_texture_mapping: &imported_views,
+                    textures: &active_textures,
+                    external_textures: &self.imported_targets,
                     data: &self.data,
                 };
 
@@ -447,6 +509,8 @@ impl<'node> RenderGraph<'node> {
                             0.0,
                             1.0,
                         );
+                        // Keep everything, including fullscreen passes, within the viewport.
+                        rpass.set_scissor_rect(viewport.offset.x, viewport.offset.y, viewport.size.x, viewport.size.y);
 
                         RenderGraphEncoderOrPassInner::RenderPass(rpass)
                     }
@@ -509,6 +573,9 @@ impl<'node> RenderGraph<'node> {
 
         renderer.queue.submit(eval_output.cmd_bufs.drain(..));
 
+        // Run the callbacks of buffer mappings finished since the last frame.
+        renderer.poll();
+
         data_core.profiler.try_lock().unwrap().end_frame().unwrap();
 
         // This variable seems superfluous, but solves borrow checker issues with the borrow of data_core.
@@ -527,6 +594,7 @@ impl<'node> RenderGraph<'node> {
         resource_spans: &'rpass FastHashMap<GraphResource, ResourceSpan>,
         active_views: &'rpass FastHashMap<TextureRegion, TextureView>,
         active_imported_views: &'rpass FastHashMap<TextureRegion, TextureView>,
+        preserved_imported_targets: &FastHashSet<usize>,
     ) -> RenderPass<'rpass> {
         let color_attachments: Vec<_> = desc
             .targets
@@ -535,8 +603,12 @@ impl<'node> RenderGraph<'node> {
                 let view_span = resource_spans[&target.color.resource.to_resource()];
 
                 let first_usage = view_span.first_usage.expect("internal rendergraph error: renderpass attachment counts as a usage, but no first usage registered on texture");
+                let preserved = matches!(
+                    target.color.resource,
+                    GraphSubResource::ImportedTexture(region) if preserved_imported_targets.contains(&region.idx)
+                );
 
-                let load = if first_usage == node_idx {
+                let load = if first_usage == node_idx && !preserved {
                     let clear_f64 = target.clear.as_dvec4();
                     LoadOp::Clear(wgpu::Color {
                         r: clear_f64.x,
@@ -576,11 +648,16 @@ impl<'node> RenderGraph<'node> {
             let view_span = resource_spans[&resource.to_resource()];
 
             let first_usage = view_span.first_usage.expect("internal rendergraph error: renderpass attachment counts as a usage, but no first usage registered on texture");
+            // Preserved imports are never cleared, their contents are the starting point.
+            let first_usage = match resource {
+                GraphSubResource::ImportedTexture(region) if preserved_imported_targets.contains(&region.idx) => None,
+                _ => Some(first_usage),
+            };
 
             let store = if view_span.last_reference == Some(pass_end_idx) { StoreOp::Discard } else { StoreOp::Store };
 
             let depth_ops = ds_target.depth_clear.map(|clear| {
-                let load = if first_usage == node_idx {
+                let load = if first_usage == Some(node_idx) {
                     LoadOp::Clear(clear)
                 } else {
                     LoadOp::Load
@@ -590,7 +667,7 @@ impl<'node> RenderGraph<'node> {
             });
 
             let stencil_load = ds_target.stencil_clear.map(|clear| {
-                let load = if first_usage == node_idx {
+                let load = if first_usage == Some(node_idx) {
                     LoadOp::Clear(clear)
                 } else {
                     LoadOp::Load
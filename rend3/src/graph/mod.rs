@@ -47,10 +47,12 @@ use std::ops::Range;
 
 use glam::{UVec2, Vec4};
 use rend3_types::{SampleCount, TextureFormat, TextureUsages};
-use wgpu::{Extent3d, TextureDimension, TextureView};
+use wgpu::{Extent3d, TextureAspect, TextureDimension, TextureView};
 
 use crate::util::typedefs::SsoString;
 
+mod compute;
+mod copy;
 mod encpass;
 #[allow(clippy::module_inception)] // lmao
 mod graph;
@@ -59,6 +61,7 @@ mod store;
 mod temp;
 mod texture_store;
 
+pub use compute::*;
 pub use encpass::*;
 pub use graph::*;
 pub use node::*;
@@ -144,6 +147,7 @@ pub(super) struct TextureRegion {
     mip_start: u8,
     mip_end: u8,
     viewport: ViewportRect,
+    aspect: TextureAspect,
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
@@ -229,6 +233,21 @@ impl RenderTargetHandle {
         self
     }
 
+    /// Sets the aspect of the texture the view will cover.
+    ///
+    /// Combined depth-stencil targets must be narrowed to
+    /// [`TextureAspect::DepthOnly`] or [`TextureAspect::StencilOnly`] before
+    /// they can be bound for sampling.
+    pub fn set_aspect(mut self, aspect: TextureAspect) -> Self {
+        match &mut self.resource {
+            GraphSubResource::ImportedTexture(region) | GraphSubResource::Texture(region) => {
+                region.aspect = aspect;
+            }
+            _ => unreachable!(),
+        }
+        self
+    }
+
     pub fn set_viewport(mut self, viewport: ViewportRect) -> Self {
         match &mut self.resource {
             GraphSubResource::ImportedTexture(region) | GraphSubResource::Texture(region) => {
@@ -238,6 +257,11 @@ impl RenderTargetHandle {
         }
         self
     }
+
+    /// Viewport renderpasses using this target render to.
+    pub fn viewport(&self) -> ViewportRect {
+        self.to_region().viewport
+    }
 }
 
 /// Targets that make up a renderpass.
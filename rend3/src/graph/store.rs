@@ -1,10 +1,11 @@
-use std::{cell::RefCell, marker::PhantomData};
+use std::{cell::RefCell, marker::PhantomData, ops::Range, sync::Arc};
 
-use wgpu::TextureView;
+use wgpu::{ImageCopyTexture, Origin3d, Texture, TextureAspect, TextureView};
 
 use crate::{
     graph::{
-        DataContents, DeclaredDependency, GraphSubResource, RenderTargetHandle, RpassTemporaryPool, TextureRegion,
+        AsTextureReference, DataContents, DeclaredDependency, GraphSubResource, RenderTargetHandle, RpassTemporaryPool,
+        TextureRegion, ViewportRect,
     },
     util::typedefs::FastHashMap,
 };
@@ -35,12 +36,38 @@ impl<T> PartialEq for DataHandle<T> {
     }
 }
 
+/// The texture behind a render target, along with the region of it the
+/// handle refers to.
+#[derive(Debug, Clone)]
+pub struct RenderTargetTexture<'a> {
+    pub texture: &'a Texture,
+    pub layers: Range<u32>,
+    pub mips: Range<u32>,
+    pub viewport: ViewportRect,
+    pub aspect: TextureAspect,
+}
+
+impl<'a> RenderTargetTexture<'a> {
+    /// Describes the first layer and mip of the region for use in texture
+    /// copies, with the origin at the start of the viewport.
+    pub fn as_image_copy(&self) -> ImageCopyTexture<'a> {
+        ImageCopyTexture {
+            texture: self.texture,
+            mip_level: self.mips.start,
+            origin: Origin3d { x: self.viewport.offset.x, y: self.viewport.offset.y, z: self.layers.start },
+            aspect: self.aspect,
+        }
+    }
+}
+
 /// Provides read-only access to the renderer and access to graph resources.
 ///
 /// This is how you turn [DeclaredDependency] into actual wgpu resources.
 pub struct RenderGraphDataStore<'a> {
     pub(super) texture_mapping: &'a FastHashMap<TextureRegion, TextureView>,
     pub(super) external_texture_mapping: &'a FastHashMap<TextureRegion, TextureView>,
+    pub(super) textures: &'a FastHashMap<usize, Arc<Texture>>,
+    pub(super) external_textures: &'a [&'a dyn AsTextureReference],
     pub(super) data: &'a [DataContents], // Any is RefCell<Option<T>> where T is the stored data
 }
 
@@ -61,6 +88,29 @@ impl<'a> RenderGraphDataStore<'a> {
         }
     }
 
+    /// Get the texture behind a rendertarget, for operations that need the
+    /// whole texture instead of a view, like copies.
+    pub fn get_render_target_texture(&self, dep: DeclaredDependency<RenderTargetHandle>) -> RenderTargetTexture<'a> {
+        let (texture, region) = match dep.handle.resource {
+            GraphSubResource::Texture(region) => (
+                &**self.textures.get(&region.idx).expect("internal rendergraph error: failed to get named texture"),
+                region,
+            ),
+            GraphSubResource::ImportedTexture(region) => (self.external_textures[region.idx].as_texture_ref(), region),
+            r => {
+                panic!("internal rendergraph error: tried to get a {:?} as a render target", r)
+            }
+        };
+
+        RenderTargetTexture {
+            texture,
+            layers: region.layer_start..region.layer_end,
+            mips: region.mip_start as u32..region.mip_end as u32,
+            viewport: region.viewport,
+            aspect: region.aspect,
+        }
+    }
+
     /// Set the custom data behind a data handle.
     ///
     /// # Panics
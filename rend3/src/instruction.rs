@@ -1,6 +1,6 @@
 use std::{mem, panic::Location};
 
-use glam::Mat4;
+use glam::{DVec3, Mat4, Vec4};
 use parking_lot::Mutex;
 use rend3_types::{
     trait_supertrait_alias, ObjectChange, PointLight, PointLightChange, RawDirectionalLightHandle,
@@ -11,7 +11,8 @@ use wgpu::{CommandBuffer, Device};
 
 use crate::{
     managers::{GraphStorage, InternalSkeleton, InternalTexture, MaterialManager, TextureManager},
-    types::{Camera, DirectionalLight, DirectionalLightChange, Object, RawObjectHandle},
+    types::{Camera, DirectionalLight, DirectionalLightChange, Object, ObjectHandle, RawObjectHandle},
+    util::texture_staging::StagingAllocation,
     RendererProfile,
 };
 
@@ -35,16 +36,24 @@ pub enum InstructionKind {
     AddTexture2D {
         handle: RawTexture2DHandle,
         internal_texture: InternalTexture,
-        cmd_buf: Option<CommandBuffer>,
+        cmd_buf: CommandBuffer,
+        // Dropped once the upload is evaluated, letting the staging memory be reused.
+        _staging: StagingAllocation,
     },
     AddTexture2DFromTexture {
         handle: RawTexture2DHandle,
         texture: TextureFromTexture,
     },
+    ImportTexture2D {
+        handle: RawTexture2DHandle,
+        internal_texture: InternalTexture,
+    },
     AddTextureCube {
         handle: RawTextureCubeHandle,
         internal_texture: InternalTexture,
-        cmd_buf: Option<CommandBuffer>,
+        cmd_buf: CommandBuffer,
+        // Dropped once the upload is evaluated, letting the staging memory be reused.
+        _staging: StagingAllocation,
     },
     AddMaterial {
         handle: RawMaterialHandle,
@@ -108,6 +117,19 @@ pub enum InstructionKind {
         handle: RawObjectHandle,
         transform: Mat4,
     },
+    SetObjectWorldOffset {
+        handle: RawObjectHandle,
+        world_offset: DVec3,
+    },
+    SetObjectDebugTint {
+        handle: RawObjectHandle,
+        tint: Option<Vec4>,
+    },
+    SetObjectParent {
+        handle: RawObjectHandle,
+        // Strong, as the parent is kept alive by its children.
+        parent: Option<ObjectHandle>,
+    },
     SetSkeletonJointDeltas {
         handle: RawSkeletonHandle,
         joint_matrices: Vec<Mat4>,
@@ -118,11 +140,21 @@ pub enum InstructionKind {
     SetCameraData {
         data: Camera,
     },
+    SetRenderOrigin {
+        origin: DVec3,
+    },
+    ReserveObjects {
+        additional: usize,
+    },
+    ReserveJoints {
+        additional: usize,
+    },
     DuplicateObject {
         src_handle: RawObjectHandle,
         dst_handle: RawObjectHandle,
         change: ObjectChange,
     },
+    ClearWorld,
 }
 
 pub struct InstructionStreamPair {
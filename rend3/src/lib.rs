@@ -44,6 +44,7 @@
 //! - `rend3-framework`: Vastly simplifies correct handling of the window and
 //!   surface across platforms.
 //! - `rend3-gltf`: Modular gltf file and scene loader.
+//! - `rend3-obj`: obj and mtl model loader.
 //!
 //! ### Integration
 //!
@@ -129,6 +130,7 @@ pub mod types {
 /// Utilities and isolated bits of functionality that need a home.
 pub mod util {
     pub mod bind_merge;
+    pub mod blit;
     pub mod buffer;
     pub mod error_scope;
     pub mod frustum;
@@ -145,6 +147,7 @@ pub mod util {
     pub mod output;
     pub mod scatter_copy;
     pub mod sync;
+    pub mod texture_staging;
     pub mod typedefs;
     pub mod upload;
 }
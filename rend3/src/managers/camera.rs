@@ -1,5 +1,5 @@
 use glam::{Mat4, Vec3};
-use rend3_types::Handedness;
+use rend3_types::{DepthConvention, Handedness};
 
 use crate::{
     types::{Camera, CameraProjection},
@@ -10,26 +10,43 @@ use crate::{
 #[derive(Debug, Clone)]
 pub struct CameraState {
     handedness: Handedness,
+    depth_convention: DepthConvention,
     orig_view: Mat4,
     proj: Mat4,
     inv_view: Mat4,
     world_frustum: Frustum,
     data: Camera,
     aspect_ratio: f32,
+    previous_view_proj: Mat4,
 }
 impl CameraState {
     /// Builds a new camera, using the given aspect ratio. If no aspect ratio is
     /// given it is assumed that no aspect ratio scaling should be done.
-    pub fn new(data: Camera, handedness: Handedness, aspect_ratio: Option<f32>) -> Self {
+    pub fn new(
+        data: Camera,
+        handedness: Handedness,
+        depth_convention: DepthConvention,
+        aspect_ratio: Option<f32>,
+    ) -> Self {
         profiling::scope!("CameraState::new");
 
         let aspect_ratio = aspect_ratio.unwrap_or(1.0);
-        let proj = compute_projection_matrix(data, handedness, aspect_ratio);
+        let proj = compute_projection_matrix(data, handedness, depth_convention, aspect_ratio);
         let orig_view = compute_origin_matrix(data);
 
-        let frustum = Frustum::from_matrix(proj * data.view);
-
-        Self { handedness, orig_view, proj, inv_view: data.view.inverse(), world_frustum: frustum, data, aspect_ratio }
+        let frustum = Frustum::from_matrix_with_convention(proj * data.view, depth_convention);
+
+        Self {
+            handedness,
+            depth_convention,
+            orig_view,
+            proj,
+            inv_view: data.view.inverse(),
+            world_frustum: frustum,
+            data,
+            aspect_ratio,
+            previous_view_proj: proj * data.view,
+        }
     }
 
     /// Sets the camera data, rebuilding the using the given aspect ratio. If no
@@ -44,10 +61,10 @@ impl CameraState {
     }
 
     pub fn set_aspect_data(&mut self, data: Camera, aspect_ratio: f32) {
-        self.proj = compute_projection_matrix(data, self.handedness, aspect_ratio);
+        self.proj = compute_projection_matrix(data, self.handedness, self.depth_convention, aspect_ratio);
         self.orig_view = compute_origin_matrix(data);
         self.inv_view = data.view.inverse();
-        self.world_frustum = Frustum::from_matrix(self.proj * data.view);
+        self.world_frustum = Frustum::from_matrix_with_convention(self.proj * data.view, self.depth_convention);
         self.data = data;
         self.aspect_ratio = aspect_ratio;
     }
@@ -60,6 +77,10 @@ impl CameraState {
         self.handedness
     }
 
+    pub fn depth_convention(&self) -> DepthConvention {
+        self.depth_convention
+    }
+
     pub fn view(&self) -> Mat4 {
         self.data.view
     }
@@ -68,6 +89,17 @@ impl CameraState {
         self.proj * self.data.view
     }
 
+    /// The view projection matrix the previous frame was rendered with.
+    pub fn previous_view_proj(&self) -> Mat4 {
+        self.previous_view_proj
+    }
+
+    /// Records the current view projection matrix as the previous frame's.
+    /// Called once per frame, before any camera changes are applied.
+    pub(crate) fn begin_frame(&mut self) {
+        self.previous_view_proj = self.view_proj();
+    }
+
     pub fn origin_view_proj(&self) -> Mat4 {
         self.proj * self.orig_view
     }
@@ -85,23 +117,40 @@ impl CameraState {
     }
 }
 
-fn compute_projection_matrix(data: Camera, handedness: Handedness, aspect_ratio: f32) -> Mat4 {
+fn compute_projection_matrix(
+    data: Camera,
+    handedness: Handedness,
+    depth_convention: DepthConvention,
+    aspect_ratio: f32,
+) -> Mat4 {
     match data.projection {
-        CameraProjection::Orthographic { size } => {
+        CameraProjection::Orthographic { size, near, far } => {
             let half = size * 0.5;
+            // Orthographic matrices map near to 0 and far to 1, so swapping them reverses depth.
+            let (near, far) = match depth_convention {
+                DepthConvention::Reverse => (far, near),
+                DepthConvention::Forward => (near, far),
+            };
             if handedness == Handedness::Left {
-                Mat4::orthographic_lh(-half.x, half.x, -half.y, half.y, half.z, -half.z)
+                Mat4::orthographic_lh(-half.x, half.x, -half.y, half.y, near, far)
             } else {
-                Mat4::orthographic_rh(-half.x, half.x, -half.y, half.y, half.z, -half.z)
+                Mat4::orthographic_rh(-half.x, half.x, -half.y, half.y, near, far)
             }
         }
-        CameraProjection::Perspective { vfov, near } => {
-            if handedness == Handedness::Left {
+        CameraProjection::Perspective { vfov, near } => match (handedness, depth_convention) {
+            (Handedness::Left, DepthConvention::Reverse) => {
                 Mat4::perspective_infinite_reverse_lh(vfov.to_radians(), aspect_ratio, near)
-            } else {
+            }
+            (Handedness::Right, DepthConvention::Reverse) => {
                 Mat4::perspective_infinite_reverse_rh(vfov.to_radians(), aspect_ratio, near)
             }
-        }
+            (Handedness::Left, DepthConvention::Forward) => {
+                Mat4::perspective_infinite_lh(vfov.to_radians(), aspect_ratio, near)
+            }
+            (Handedness::Right, DepthConvention::Forward) => {
+                Mat4::perspective_infinite_rh(vfov.to_radians(), aspect_ratio, near)
+            }
+        },
         CameraProjection::Raw(proj) => proj,
     }
 }
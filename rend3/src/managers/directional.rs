@@ -50,6 +50,13 @@ struct ShaderDirectionalLight {
     pub atlas_offset: Vec2,
     /// [0, 1] size of the shadow map in the atlas.
     pub atlas_size: Vec2,
+    /// 1 if the light has a shadow map, 0 if not.
+    pub casts_shadows: u32,
+    /// Width of the penumbra in [0, 1] atlas units per world unit between
+    /// the caster and receiver. 0 uses a fixed size filter.
+    pub penumbra_scale: f32,
+    /// World units towards the light per unit of shadow map depth.
+    pub depth_to_distance: f32,
 }
 
 #[derive(Debug, Clone)]
@@ -59,12 +66,15 @@ pub struct ShadowDesc {
 }
 
 /// Manages directional lights and their associated shadow maps.
+///
+/// The shadow maps of all lights are packed into a single atlas texture.
 pub struct DirectionalLightManager {
     data: Vec<Option<InternalDirectionalLight>>,
     data_buffer: WrappedPotBuffer<ShaderDirectionalLightBuffer>,
 
     texture_size: UVec2,
     texture_view: TextureView,
+    max_dimension: u32,
 }
 impl DirectionalLightManager {
     pub fn new(device: &Device) -> Self {
@@ -78,10 +88,12 @@ impl DirectionalLightManager {
             data_buffer: WrappedPotBuffer::new(device, BufferUsages::STORAGE, "shadow data buffer"),
             texture_size,
             texture_view,
+            max_dimension: device.limits().max_texture_dimension_2d,
         }
     }
 
-    pub fn add(&mut self, handle: RawDirectionalLightHandle, light: DirectionalLight) {
+    pub fn add(&mut self, handle: RawDirectionalLightHandle, mut light: DirectionalLight) {
+        validate_resolution(handle, &mut light, self.max_dimension);
         if handle.idx >= self.data.len() {
             self.data.resize_with(handle.idx + 1, || None);
         }
@@ -89,23 +101,48 @@ impl DirectionalLightManager {
     }
 
     pub fn update(&mut self, handle: RawDirectionalLightHandle, change: DirectionalLightChange) {
-        self.data[handle.idx].as_mut().unwrap().inner.update_from_changes(change);
+        // The light may have been removed by a world clear while the user still holds the handle.
+        if let Some(light) = self.data[handle.idx].as_mut() {
+            light.inner.update_from_changes(change);
+            validate_resolution(handle, &mut light.inner, self.max_dimension);
+        }
     }
 
     pub fn remove(&mut self, handle: RawDirectionalLightHandle) {
-        self.data[handle.idx].take().unwrap();
+        self.data[handle.idx].take();
+    }
+
+    /// Removes every light. Outstanding handles stay allocated, and are
+    /// ignored until they are dropped.
+    pub fn clear(&mut self) {
+        self.data.iter_mut().for_each(|light| *light = None);
     }
 
     pub fn evaluate(&mut self, renderer: &Renderer, user_camera: &CameraState) -> (UVec2, Vec<ShadowDesc>) {
         profiling::scope!("DirectionalLightManager::evaluate");
 
-        let shadow_maps: Vec<_> = self
+        let mut shadow_maps: Vec<_> = self
             .data
             .iter()
             .enumerate()
-            .filter_map(|(idx, light)| Some((RawDirectionalLightHandle::new(idx), light.as_ref()?.inner.resolution)))
+            .filter_map(|(idx, light)| {
+                let light = &light.as_ref()?.inner;
+                light.casts_shadows.then_some((RawDirectionalLightHandle::new(idx), light.resolution))
+            })
             .collect();
-        let shadow_atlas = shadow_alloc::allocate_shadow_atlas(shadow_maps, renderer.limits.max_texture_dimension_2d);
+
+        // Too many large shadow maps may not fit in the largest texture, so we shrink them all until they do.
+        let mut halvings = 0;
+        let shadow_atlas = loop {
+            let atlas = shadow_alloc::allocate_shadow_atlas(shadow_maps.clone(), self.max_dimension);
+            let fits =
+                atlas.as_ref().map_or(true, |atlas| atlas.texture_dimensions.max_element() <= self.max_dimension);
+            if fits || shadow_maps.iter().all(|&(_, resolution)| resolution == 1) {
+                break atlas;
+            }
+            shadow_maps.iter_mut().for_each(|(_, resolution)| *resolution = (*resolution / 2).max(1));
+            halvings += 1;
+        };
 
         let new_shadow_map_size = match shadow_atlas {
             Some(ref m) => m.texture_dimensions.max(MINIMUM_SHADOW_MAP_SIZE),
@@ -114,14 +151,19 @@ impl DirectionalLightManager {
         let new_shadow_map_size_f32 = new_shadow_map_size.as_vec2();
 
         if new_shadow_map_size != self.texture_size {
+            if halvings != 0 {
+                log::warn!(
+                    "Shadow maps don't fit into a {0}x{0} atlas, their resolution was divided by {1}",
+                    self.max_dimension,
+                    1 << halvings
+                );
+            }
             self.texture_size = new_shadow_map_size;
             self.texture_view = create_shadow_texture(&renderer.device, self.texture_size);
         }
 
-        let coordinates = match shadow_atlas {
-            Some(m) => m.maps,
-            None => return (new_shadow_map_size, Vec::new()),
-        };
+        // Lights without shadows still need to be uploaded, so we don't bail out without an atlas.
+        let coordinates = shadow_atlas.map(|m| m.maps).unwrap_or_default();
 
         let shadow_data: Vec<_> = coordinates
             .into_iter()
@@ -146,8 +188,30 @@ impl DirectionalLightManager {
                         inv_resolution: 1.0 / new_shadow_map_size_f32,
                         atlas_offset: desc.map.offset.as_vec2() / new_shadow_map_size_f32,
                         atlas_size: desc.map.size as f32 / new_shadow_map_size_f32,
+                        casts_shadows: true as u32,
+                        penumbra_scale: light.angular_size.tan() * desc.map.size as f32
+                            / new_shadow_map_size_f32.x
+                            / light.distance,
+                        // The orthographic projection spans `distance` in depth, and the near plane faces the light.
+                        depth_to_distance: match renderer.depth_convention.is_reverse() {
+                            true => light.distance,
+                            false => -light.distance,
+                        },
                     }
                 })
+                .chain(self.data.iter().flatten().filter(|light| !light.inner.casts_shadows).map(|light| {
+                    ShaderDirectionalLight {
+                        view_proj: Mat4::ZERO,
+                        color: light.inner.color * light.inner.intensity,
+                        direction: light.inner.direction,
+                        inv_resolution: Vec2::ZERO,
+                        atlas_offset: Vec2::ZERO,
+                        atlas_size: Vec2::ZERO,
+                        casts_shadows: false as u32,
+                        penumbra_scale: 0.0,
+                        depth_to_distance: 0.0,
+                    }
+                }))
                 .collect(),
         };
 
@@ -173,6 +237,22 @@ impl DirectionalLightManager {
     }
 }
 
+/// Makes the shadow resolution of `light` one the atlas can hold, warning if
+/// it had to change.
+fn validate_resolution(handle: RawDirectionalLightHandle, light: &mut DirectionalLight, max_dimension: u32) {
+    let resolution = shadow_alloc::valid_shadow_resolution(light.resolution, max_dimension);
+    if resolution != light.resolution {
+        log::warn!(
+            "Shadow resolution {} of directional light {:?} must be a power of two no larger than {}, using {}",
+            light.resolution,
+            handle,
+            max_dimension,
+            resolution
+        );
+        light.resolution = resolution;
+    }
+}
+
 fn create_shadow_texture(device: &Device, size: UVec2) -> TextureView {
     profiling::scope!("shadow texture creation");
 
@@ -56,6 +56,15 @@ pub struct ShadowMap {
     pub handle: RawDirectionalLightHandle,
 }
 
+/// Closest shadow map resolution the atlas can hold: a power of two no larger
+/// than `max_dimension`.
+pub(super) fn valid_shadow_resolution(resolution: u16, max_dimension: u32) -> u16 {
+    let max_dimension = max_dimension.clamp(1, u16::MAX as u32);
+    let max_resolution = 1 << (u32::BITS - 1 - max_dimension.leading_zeros());
+
+    resolution.clamp(1, max_resolution as u16).next_power_of_two()
+}
+
 pub(super) fn allocate_shadow_atlas(
     mut maps: Vec<(RawDirectionalLightHandle, u16)>,
     max_dimension: u32,
@@ -141,7 +150,17 @@ mod tests {
     use rend3_types::RawDirectionalLightHandle as RDLH;
 
     use super::ShadowNode;
-    use crate::managers::directional::shadow_alloc::{allocate_shadow_atlas, ShadowMap};
+    use crate::managers::directional::shadow_alloc::{allocate_shadow_atlas, valid_shadow_resolution, ShadowMap};
+
+    #[test]
+    fn shadow_resolution_validation() {
+        assert_eq!(valid_shadow_resolution(1024, 8192), 1024);
+        assert_eq!(valid_shadow_resolution(1000, 8192), 1024);
+        assert_eq!(valid_shadow_resolution(0, 8192), 1);
+        assert_eq!(valid_shadow_resolution(16384, 8192), 8192);
+        assert_eq!(valid_shadow_resolution(u16::MAX, 10000), 8192);
+        assert_eq!(valid_shadow_resolution(u16::MAX, u32::MAX), 32768);
+    }
 
     #[test]
     fn chunk_subdivision_single() {
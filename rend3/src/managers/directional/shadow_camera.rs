@@ -1,4 +1,4 @@
-use glam::{Mat4, Vec3, Vec3A};
+use glam::{Mat4, Vec2, Vec3};
 use rend3_types::{Camera, CameraProjection, Handedness};
 
 use crate::managers::{CameraState, InternalDirectionalLight};
@@ -16,18 +16,28 @@ pub(super) fn shadow_camera(l: &InternalDirectionalLight, user_camera: &CameraSt
     let origin_view = look_at(Vec3::ZERO, l.inner.direction, Vec3::Y);
     let camera_origin_view = origin_view.transform_point3(camera_location);
 
-    let offset = camera_origin_view.truncate() % shadow_texel_size;
-    let shadow_location = camera_origin_view - Vec3::from((offset, 0.0));
+    let shadow_location = if l.inner.snap_to_texels {
+        let offset = camera_origin_view.truncate() % shadow_texel_size;
+        camera_origin_view - Vec3::from((offset, 0.0))
+    } else {
+        camera_origin_view
+    };
 
     let inv_origin_view = origin_view.inverse();
     let new_shadow_location = inv_origin_view.transform_point3(shadow_location);
 
     CameraState::new(
         Camera {
-            projection: CameraProjection::Orthographic { size: Vec3A::splat(l.inner.distance) },
+            // Centered on the camera, so casters behind it still cast shadows onto what it sees.
+            projection: CameraProjection::Orthographic {
+                size: Vec2::splat(l.inner.distance),
+                near: -l.inner.distance * 0.5,
+                far: l.inner.distance * 0.5,
+            },
             view: look_at(new_shadow_location, new_shadow_location + l.inner.direction, Vec3::Y),
         },
         user_camera.handedness(),
+        user_camera.depth_convention(),
         None,
     )
 }
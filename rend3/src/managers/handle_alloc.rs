@@ -8,9 +8,9 @@ use std::{
 };
 
 use parking_lot::Mutex;
-use rend3_types::{RawResourceHandle, ResourceHandle};
+use rend3_types::{ObjectHandle, RawObjectHandle, RawResourceHandle, ResourceHandle, WeakObjectHandle};
 
-use crate::{instruction::DeletableRawResourceHandle, Renderer};
+use crate::{instruction::DeletableRawResourceHandle, util::typedefs::FastHashMap, Renderer};
 
 pub(crate) struct HandleAllocator<T>
 where
@@ -27,6 +27,8 @@ where
     /// We do not need to do this for everything though, only for Object handles, as these
     /// are the root handle which the renderer accesses everything.
     delay_list: Option<Mutex<Vec<usize>>>,
+    /// Handles are only allocated below this index, if set.
+    limit: Option<usize>,
     _phantom: PhantomData<T>,
 }
 
@@ -39,20 +41,44 @@ where
             max_allocated: AtomicUsize::new(0),
             freelist: Mutex::new(Vec::new()),
             delay_list: delay_handle_reclaimation.then(|| Mutex::new(Vec::new())),
+            limit: None,
             _phantom: PhantomData,
         }
     }
 
+    pub fn with_limit(mut self, limit: Option<usize>) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    pub fn limit(&self) -> Option<usize> {
+        self.limit
+    }
+
     pub fn allocate(&self, renderer: &Arc<Renderer>) -> ResourceHandle<T> {
+        self.try_allocate(renderer).expect("Allocated a handle past the handle limit")
+    }
+
+    /// Allocates a handle, or returns `None` if every handle below the limit
+    /// is in use.
+    pub fn try_allocate(&self, renderer: &Arc<Renderer>) -> Option<ResourceHandle<T>> {
         let maybe_idx = self.freelist.lock().pop();
-        let idx = maybe_idx.unwrap_or_else(|| self.max_allocated.fetch_add(1, Ordering::Relaxed));
+        let idx = match maybe_idx {
+            Some(idx) => idx,
+            None => {
+                let limit = self.limit.unwrap_or(usize::MAX);
+                self.max_allocated
+                    .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |max| (max < limit).then_some(max + 1))
+                    .ok()?
+            }
+        };
 
         let renderer = Arc::clone(renderer);
         let destroy_fn = move |handle: RawResourceHandle<T>| {
             renderer.instructions.push(handle.into_delete_instruction_kind(), *Location::caller())
         };
 
-        ResourceHandle::new(destroy_fn, idx)
+        Some(ResourceHandle::new(destroy_fn, idx))
     }
 
     pub fn deallocate(&self, handle: RawResourceHandle<T>) {
@@ -75,3 +101,39 @@ where
         }
     }
 }
+
+/// Stable ids given to objects by the user, as handle indices are reused.
+///
+/// Entries don't keep objects alive and are removed when the object is
+/// deleted.
+#[derive(Default)]
+pub(crate) struct ObjectIdMap {
+    inner: Mutex<ObjectIdMapInner>,
+}
+
+#[derive(Default)]
+struct ObjectIdMapInner {
+    by_id: FastHashMap<u64, WeakObjectHandle>,
+    by_handle: FastHashMap<RawObjectHandle, u64>,
+}
+
+impl ObjectIdMap {
+    pub fn insert(&self, id: u64, handle: &ObjectHandle) {
+        let mut inner = self.inner.lock();
+        if let Some(previous) = inner.by_id.insert(id, handle.downgrade()) {
+            inner.by_handle.remove(&previous.get_raw());
+        }
+        inner.by_handle.insert(handle.get_raw(), id);
+    }
+
+    pub fn get(&self, id: u64) -> Option<ObjectHandle> {
+        self.inner.lock().by_id.get(&id)?.upgrade()
+    }
+
+    pub fn remove(&self, handle: RawObjectHandle) {
+        let mut inner = self.inner.lock();
+        if let Some(id) = inner.by_handle.remove(&handle) {
+            inner.by_id.remove(&id);
+        }
+    }
+}
@@ -73,6 +73,11 @@ impl<'a, M: Material> MaterialArchetypeView<'a, M> {
     pub fn material(&self, handle: RawMaterialHandle) -> &'a InternalMaterial<M> {
         self.data_vec[handle.idx].as_ref().unwrap()
     }
+
+    /// Iterates every live material of this archetype.
+    pub fn materials(&self) -> impl Iterator<Item = &'a InternalMaterial<M>> {
+        self.data_vec.iter().flatten()
+    }
 }
 
 /// Key which determine's an object's archetype.
@@ -140,7 +145,7 @@ impl MaterialManager {
             let textures = material.to_textures();
 
             let texture_bg_index =
-                self.texture_deduplicator.get_or_insert(device, texture_manager_2d, textures.as_ref());
+                self.texture_deduplicator.get_or_insert(device, texture_manager_2d, cpu_textures::<M>(&textures));
 
             ProfileData::Cpu(texture_bg_index)
         } else {
@@ -179,8 +184,11 @@ impl MaterialManager {
         if let ProfileData::Cpu(ref mut index) = internal.bind_group_index {
             // Create the new bind group first. If the bind group didn't change, this will prevent
             // the bind group from dying on the call to remove.
-            let bind_group_index =
-                self.texture_deduplicator.get_or_insert(device, texture_manager_2d, material.to_textures().as_ref());
+            let bind_group_index = self.texture_deduplicator.get_or_insert(
+                device,
+                texture_manager_2d,
+                cpu_textures::<M>(&material.to_textures()),
+            );
             self.texture_deduplicator.remove(*index);
             *index = bind_group_index;
         }
@@ -188,6 +196,39 @@ impl MaterialManager {
         internal.inner = material;
     }
 
+    /// Modifies a material in place. The material's data is re-uploaded, but
+    /// its bind group is only rebuilt if `modify` changed its textures.
+    pub fn update_with<M: Material>(
+        &mut self,
+        device: &Device,
+        texture_manager_2d: &TextureManager<crate::types::Texture2DTag>,
+        handle: RawMaterialHandle,
+        modify: impl FnOnce(&mut M),
+    ) {
+        let type_id = self.handle_to_typeid[&handle];
+
+        assert_eq!(type_id, TypeId::of::<M>());
+
+        let archetype = self.archetypes.get_mut(&type_id).unwrap();
+
+        let data_vec = archetype.data_vec.downcast_slice_mut::<Option<InternalMaterial<M>>>().unwrap();
+        let internal = data_vec[handle.idx].as_mut().unwrap();
+
+        let old_textures = internal.inner.to_textures();
+        modify(&mut internal.inner);
+
+        if let ProfileData::Cpu(ref mut index) = internal.bind_group_index {
+            let textures = internal.inner.to_textures();
+            if textures.as_ref() != old_textures.as_ref() {
+                let bind_group_index =
+                    self.texture_deduplicator.get_or_insert(device, texture_manager_2d, cpu_textures::<M>(&textures));
+                self.texture_deduplicator.remove(*index);
+                *index = bind_group_index;
+            }
+        }
+        archetype.buffer.use_index(handle.idx);
+    }
+
     pub fn remove(&mut self, handle: RawMaterialHandle) {
         let type_id = self.handle_to_typeid.remove(&handle).unwrap();
 
@@ -260,8 +301,7 @@ impl MaterialManager {
     }
 
     pub fn get_bind_group_layout_cpu<M: Material>(&self) -> &BindGroupLayout {
-        self.texture_deduplicator
-            .get_bgl(<M::TextureArrayType as MaterialArray<Option<RawTexture2DHandle>>>::COUNT as usize)
+        self.texture_deduplicator.get_bgl(M::CPU_TEXTURE_COUNT as usize)
     }
 
     pub fn get_attributes(
@@ -277,6 +317,11 @@ impl MaterialManager {
     }
 }
 
+/// The textures of a material which are bound in the CpuDriven profile.
+fn cpu_textures<M: Material>(textures: &M::TextureArrayType) -> &[Option<RawTexture2DHandle>] {
+    &textures.as_ref()[..M::CPU_TEXTURE_COUNT as usize]
+}
+
 fn remove_data<M: Material>(
     data_vec: &mut WasmVecAny,
     handle: RawMaterialHandle,
@@ -303,7 +348,7 @@ fn apply_buffer_cpu<M: Material>(
             data: material.to_data(),
             texture_enable: {
                 let mut bits = 0x0;
-                for t in material.to_textures().as_ref().iter().rev() {
+                for t in cpu_textures::<M>(&material.to_textures()).iter().rev() {
                     // Shift must happen first, if it happens second, the last bit will also be shifted
                     bits <<= 1;
                     bits |= t.is_some() as u32;
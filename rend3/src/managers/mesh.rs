@@ -6,7 +6,10 @@ use std::{
 
 use parking_lot::{Mutex, MutexGuard};
 use range_alloc::RangeAllocator;
-use rend3_types::{RawMeshHandle, VertexAttributeId, VERTEX_ATTRIBUTE_JOINT_INDICES, VERTEX_ATTRIBUTE_POSITION};
+use rend3_types::{
+    RawMeshHandle, VertexAttributeId, VERTEX_ATTRIBUTE_JOINT_INDICES, VERTEX_ATTRIBUTE_JOINT_INDICES_1,
+    VERTEX_ATTRIBUTE_POSITION,
+};
 use thiserror::Error;
 use wgpu::{
     Buffer, BufferAddress, BufferDescriptor, BufferUsages, CommandBuffer, CommandEncoder, CommandEncoderDescriptor,
@@ -38,6 +41,9 @@ pub struct InternalMesh {
     pub required_joint_count: Option<u16>,
     /// The bounding sphere of this mesh. Used for culling.
     pub bounding_sphere: BoundingSphere,
+    /// Cpu copy of the mesh, if it was added with
+    /// [`Renderer::add_mesh_retained`](crate::Renderer::add_mesh_retained).
+    pub cpu_mesh: Option<Arc<Mesh>>,
 }
 
 impl InternalMesh {
@@ -49,6 +55,7 @@ impl InternalMesh {
             index_range: 0..0,
             required_joint_count: None,
             bounding_sphere: BoundingSphere::from_mesh(&[]),
+            cpu_mesh: None,
         }
     }
 
@@ -72,6 +79,13 @@ pub enum MeshCreationError {
         #[source]
         inner: wgpu::Error,
     },
+    /// The error may have been caused by another thread using the device
+    /// at the same time, see [`ValidationErrorScope`](crate::util::error_scope::ValidationErrorScope).
+    #[error("Uploading the mesh failed validation")]
+    ValidationFailed {
+        #[source]
+        inner: wgpu::Error,
+    },
 }
 
 /// Contains all the state for the mesh buffer.
@@ -120,7 +134,7 @@ impl MeshManager {
         }
     }
 
-    pub fn add(&self, device: &Device, mesh: Mesh) -> Result<InternalMesh, MeshCreationError> {
+    pub fn add(&self, device: &Device, mesh: &Mesh) -> Result<InternalMesh, MeshCreationError> {
         profiling::scope!("MeshManager::add");
 
         let vertex_count = mesh.vertex_count;
@@ -133,10 +147,13 @@ impl MeshManager {
         // This value is used later when setting joints, to make sure all indices are
         // in-bounds with the specified amount of joints.
         let mut required_joint_count = None;
-        let joint_indices_attribute =
-            mesh.attributes.iter().find_map(|attribute| attribute.typed_data(&VERTEX_ATTRIBUTE_JOINT_INDICES));
-        if let Some(joint_indices) = joint_indices_attribute {
-            required_joint_count = Some(joint_indices.iter().flatten().max().map_or(0, |v| v + 1));
+        for joint_indices_attribute in [&VERTEX_ATTRIBUTE_JOINT_INDICES, &VERTEX_ATTRIBUTE_JOINT_INDICES_1] {
+            let joint_indices =
+                mesh.attributes.iter().find_map(|attribute| attribute.typed_data(joint_indices_attribute));
+            if let Some(joint_indices) = joint_indices {
+                let count = joint_indices.iter().flatten().max().map_or(0, |v| v + 1);
+                required_joint_count = Some(required_joint_count.map_or(count, |c: u16| c.max(count)));
+            }
         }
 
         let mut vertex_attribute_ranges = Vec::with_capacity(mesh.attributes.len());
@@ -180,6 +197,7 @@ impl MeshManager {
             index_range,
             required_joint_count,
             bounding_sphere,
+            cpu_mesh: None,
         })
     }
 
@@ -196,7 +214,11 @@ impl MeshManager {
 
     pub fn remove(&self, object_id: RawMeshHandle) {
         let mesh = self.data.lock()[object_id.idx].take().unwrap();
+        self.free(mesh);
+    }
 
+    /// Frees the ranges of a mesh which was never filled in to a handle.
+    pub fn free(&self, mesh: InternalMesh) {
         let mut buffer_state = self.buffer_state.lock();
         for (_id, range) in mesh.vertex_attribute_ranges {
             if range.is_empty() {
@@ -257,6 +279,11 @@ impl MeshManager {
         buffer_state.allocator.free_range(range);
     }
 
+    /// Cpu copy of the mesh, if it was retained when added.
+    pub fn cpu_mesh(&self, handle: RawMeshHandle) -> Option<Arc<Mesh>> {
+        self.data.lock().get(handle.idx)?.as_ref()?.cpu_mesh.clone()
+    }
+
     pub fn lock_internal_data(&self) -> LockedInternalMeshDataArray<'_> {
         LockedInternalMeshDataArray(self.data.lock())
     }
@@ -2,10 +2,10 @@ use std::{any::TypeId, ops::Range};
 
 use bytemuck::Zeroable;
 use encase::ShaderType;
-use glam::{Mat4, Vec3A};
+use glam::{DVec3, Mat4, Vec3A, Vec4};
 use rend3_types::{
-    Material, MaterialArray, MaterialHandle, ObjectChange, ObjectMeshKind, RawObjectHandle, VertexAttributeId,
-    WasmVecAny,
+    Material, MaterialArray, MaterialHandle, ObjectChange, ObjectHandle, ObjectMeshKind, RawObjectHandle,
+    VertexAttributeId, WasmVecAny,
 };
 use wgpu::{Buffer, CommandEncoder, Device};
 
@@ -14,8 +14,11 @@ use crate::{
     managers::{InternalMesh, MaterialManager, MeshManager},
     types::Object,
     util::{
-        freelist::FreelistDerivedBuffer, frustum::BoundingSphere, iter::ExactSizerIterator, scatter_copy::ScatterCopy,
-        typedefs::FastHashMap,
+        freelist::FreelistDerivedBuffer,
+        frustum::BoundingSphere,
+        iter::ExactSizerIterator,
+        scatter_copy::ScatterCopy,
+        typedefs::{FastHashMap, FastHashSet},
     },
 };
 
@@ -24,6 +27,8 @@ use crate::{
 pub struct ShaderObject<M: Material> {
     /// Model -> World matrix
     pub transform: Mat4,
+    /// Model -> World matrix of the previous frame, used for motion vectors.
+    pub previous_transform: Mat4,
     /// Bounding sphere in world space.
     pub bounding_sphere: BoundingSphere,
     pub first_index: u32,
@@ -33,18 +38,22 @@ pub struct ShaderObject<M: Material> {
         <M::SupportedAttributeArrayType as MaterialArray<&'static VertexAttributeId>>::U32Array,
     // 1 if enabled, 0 if disabled
     pub enabled: u32,
+    /// Multiplies the shaded color, one if there is no tint.
+    pub debug_tint: Vec4,
 }
 
 impl<M: Material> Default for ShaderObject<M> {
     fn default() -> Self {
         Self {
             transform: Default::default(),
+            previous_transform: Default::default(),
             bounding_sphere: Default::default(),
             first_index: Default::default(),
             index_count: Default::default(),
             material_index: Default::default(),
             vertex_attribute_start_offsets: Zeroable::zeroed(),
             enabled: Default::default(),
+            debug_tint: Default::default(),
         }
     }
 }
@@ -64,10 +73,17 @@ pub struct InternalObject<M: Material> {
     pub mesh_kind: ObjectMeshKind,
     pub material_handle: MaterialHandle,
 
-    /// World space
+    /// Transform as given by the user, relative to `world_offset`.
+    pub transform: Mat4,
+    pub world_offset: DVec3,
+    /// World space, relative to the render origin.
     pub location: Vec3A,
     pub mesh_bounding_sphere: BoundingSphere,
+    pub sort_bias: i32,
+    pub debug_tint: Option<Vec4>,
     pub inner: ShaderObject<M>,
+    /// If the transform has been changed this frame.
+    moved: bool,
 }
 
 // Manual impl so that M: !Clone
@@ -77,8 +93,13 @@ impl<M: Material> Clone for InternalObject<M> {
             mesh_kind: self.mesh_kind.clone(),
             mesh_bounding_sphere: self.mesh_bounding_sphere,
             material_handle: self.material_handle.clone(),
+            transform: self.transform,
+            world_offset: self.world_offset,
             location: self.location,
+            sort_bias: self.sort_bias,
+            debug_tint: self.debug_tint,
             inner: self.inner,
+            moved: self.moved,
         }
     }
 }
@@ -88,22 +109,62 @@ struct ObjectArchetype {
     data_vec: WasmVecAny,
     object_count: usize,
     buffer: FreelistDerivedBuffer,
-    set_object_transform: fn(&mut WasmVecAny, &mut FreelistDerivedBuffer, usize, Mat4),
+    /// Objects whose transform changed this frame.
+    moved_this_frame: Vec<usize>,
+    /// Objects whose transform changed last frame, so need their previous
+    /// transform caught up.
+    moved_last_frame: Vec<usize>,
+    set_object_transform: fn(&mut ObjectArchetype, usize, Mat4, DVec3),
+    set_object_world_offset: fn(&mut ObjectArchetype, usize, DVec3, DVec3),
+    set_render_origin: fn(&mut ObjectArchetype, DVec3, DVec3),
+    set_object_debug_tint: fn(&mut ObjectArchetype, usize, Option<Vec4>),
+    transform: fn(&WasmVecAny, usize) -> Option<Mat4>,
     duplicate_object: fn(&WasmVecAny, usize, ObjectChange) -> Object,
+    bounding_sphere: fn(&WasmVecAny, usize) -> Option<BoundingSphere>,
     remove: fn(&mut ObjectArchetype, usize),
-    evaluate: fn(&mut ObjectArchetype, &Device, &mut CommandEncoder, &ScatterCopy, &[RawObjectHandle]),
+    evaluate: fn(&mut ObjectArchetype, &Device, &mut CommandEncoder, &ScatterCopy, &[RawObjectHandle]) -> usize,
+}
+
+/// Link from an object to the object it is parented to.
+struct ObjectParent {
+    /// Keeps the parent alive for as long as the child is attached to it.
+    parent: ObjectHandle,
+    /// Transform relative to the parent.
+    local_transform: Mat4,
 }
 
 /// Manages objects. That's it. ¯\\\_(ツ)\_/¯
 pub struct ObjectManager {
     archetype: FastHashMap<TypeId, ObjectArchetype>,
     handle_to_typeid: FastHashMap<RawObjectHandle, TypeId>,
+    parents: FastHashMap<RawObjectHandle, ObjectParent>,
+    /// Objects whose world or local transform changed this frame, so their
+    /// children need to be moved along with them.
+    changed_this_frame: FastHashSet<RawObjectHandle>,
+    /// Objects removed by [`ObjectManager::clear`] this frame. As with handle
+    /// deletion, they are disabled now and deleted next frame.
+    cleared_this_frame: Vec<RawObjectHandle>,
+    cleared_last_frame: Vec<RawObjectHandle>,
+    /// Point in world space everything on the gpu is relative to.
+    render_origin: DVec3,
+    /// Number of objects the buffers of every archetype have room for, as
+    /// requested by [`ObjectManager::reserve`].
+    reserved_objects: usize,
 }
 impl ObjectManager {
     pub fn new() -> Self {
         profiling::scope!("ObjectManager::new");
 
-        Self { archetype: FastHashMap::default(), handle_to_typeid: FastHashMap::default() }
+        Self {
+            archetype: FastHashMap::default(),
+            handle_to_typeid: FastHashMap::default(),
+            parents: FastHashMap::default(),
+            changed_this_frame: FastHashSet::default(),
+            cleared_this_frame: Vec::new(),
+            cleared_last_frame: Vec::new(),
+            render_origin: DVec3::ZERO,
+            reserved_objects: 0,
+        }
     }
 
     fn ensure_archetype<M: Material>(&mut self, device: &Device) -> &mut ObjectArchetype {
@@ -111,9 +172,20 @@ impl ObjectManager {
         self.archetype.entry(type_id).or_insert_with(|| ObjectArchetype {
             data_vec: WasmVecAny::new::<Option<InternalObject<M>>>(),
             object_count: 0,
-            buffer: FreelistDerivedBuffer::new::<ShaderObject<M>>(device),
+            buffer: {
+                let mut buffer = FreelistDerivedBuffer::new::<ShaderObject<M>>(device);
+                buffer.reserve(self.reserved_objects);
+                buffer
+            },
+            moved_this_frame: Vec::new(),
+            moved_last_frame: Vec::new(),
             set_object_transform: set_object_transform::<M>,
+            set_object_world_offset: set_object_world_offset::<M>,
+            set_render_origin: set_render_origin::<M>,
+            set_object_debug_tint: set_object_debug_tint::<M>,
+            transform: transform::<M>,
             duplicate_object: duplicate_object::<M>,
+            bounding_sphere: bounding_sphere::<M>,
             remove: remove::<M>,
             evaluate: evaluate::<M>,
         })
@@ -148,31 +220,231 @@ impl ObjectManager {
     }
 
     pub fn set_object_transform(&mut self, handle: RawObjectHandle, transform: Mat4) {
-        let type_id = self.handle_to_typeid[&handle];
+        // The object may have been removed by a world clear while the user still holds the handle.
+        let Some(&type_id) = self.handle_to_typeid.get(&handle) else {
+            return;
+        };
+
+        // Parented objects are placed relative to their parent when the hierarchy is resolved.
+        if let Some(parent) = self.parents.get_mut(&handle) {
+            parent.local_transform = transform;
+            self.changed_this_frame.insert(handle);
+            return;
+        }
+
+        let archetype = self.archetype.get_mut(&type_id).unwrap();
+
+        (archetype.set_object_transform)(archetype, handle.idx, transform, self.render_origin);
+        if !self.parents.is_empty() {
+            self.changed_this_frame.insert(handle);
+        }
+    }
+
+    pub fn set_object_world_offset(&mut self, handle: RawObjectHandle, world_offset: DVec3) {
+        let Some(&type_id) = self.handle_to_typeid.get(&handle) else {
+            return;
+        };
+
+        // Parented objects follow their parent, wherever it is.
+        if self.parents.contains_key(&handle) {
+            return;
+        }
 
         let archetype = self.archetype.get_mut(&type_id).unwrap();
 
-        (archetype.set_object_transform)(&mut archetype.data_vec, &mut archetype.buffer, handle.idx, transform);
+        (archetype.set_object_world_offset)(archetype, handle.idx, world_offset, self.render_origin);
+        if !self.parents.is_empty() {
+            self.changed_this_frame.insert(handle);
+        }
+    }
+
+    /// Moves the point everything on the gpu is relative to. Object world
+    /// offsets are subtracted from it in double precision, so objects near
+    /// the render origin keep their precision wherever they are in the world.
+    pub fn set_render_origin(&mut self, render_origin: DVec3) {
+        if render_origin == self.render_origin {
+            return;
+        }
+
+        for archetype in self.archetype.values_mut() {
+            (archetype.set_render_origin)(archetype, self.render_origin, render_origin);
+        }
+        self.render_origin = render_origin;
+    }
+
+    pub fn render_origin(&self) -> DVec3 {
+        self.render_origin
+    }
+
+    /// Makes room in the object buffers for at least `additional` more
+    /// objects than there are now, like [`Vec::reserve`]. The buffers are
+    /// grown once when next evaluated, instead of doubling repeatedly as
+    /// objects are added over several frames. Archetypes created later are
+    /// made with this room too.
+    pub fn reserve(&mut self, additional: usize) {
+        self.reserved_objects = self.reserved_objects.max(self.handle_to_typeid.len() + additional);
+        for archetype in self.archetype.values_mut() {
+            archetype.buffer.reserve(self.reserved_objects);
+        }
+    }
+
+    pub fn set_object_debug_tint(&mut self, handle: RawObjectHandle, tint: Option<Vec4>) {
+        // The object may have been removed by a world clear while the user still holds the handle.
+        let Some(&type_id) = self.handle_to_typeid.get(&handle) else {
+            return;
+        };
+
+        let archetype = self.archetype.get_mut(&type_id).unwrap();
+
+        (archetype.set_object_debug_tint)(archetype, handle.idx, tint);
+    }
+
+    /// Attaches the object to a parent, or detaches it with `None`.
+    ///
+    /// Either way the object keeps its world transform, so it doesn't jump.
+    pub fn set_object_parent(&mut self, handle: RawObjectHandle, parent: Option<ObjectHandle>) {
+        let Some(&type_id) = self.handle_to_typeid.get(&handle) else {
+            return;
+        };
+
+        // World transforms need to be up to date for the object to keep its place.
+        self.resolve_hierarchy();
+
+        let Some(parent) = parent else {
+            self.parents.remove(&handle);
+            return;
+        };
+
+        let Some(&parent_type_id) = self.handle_to_typeid.get(&parent.get_raw()) else {
+            return;
+        };
+
+        // Walk up from the new parent, making sure we don't create a cycle.
+        let mut ancestor = Some(parent.get_raw());
+        while let Some(current) = ancestor {
+            if current == handle {
+                log::error!("Cannot parent object {handle:?} to its own descendant {:?}", parent.get_raw());
+                return;
+            }
+            ancestor = self.parents.get(&current).map(|p| p.parent.get_raw());
+        }
+
+        let archetype = &self.archetype[&type_id];
+        let transform = (archetype.transform)(&archetype.data_vec, handle.idx).unwrap();
+        let parent_archetype = &self.archetype[&parent_type_id];
+        let parent_transform = (parent_archetype.transform)(&parent_archetype.data_vec, parent.get_raw().idx).unwrap();
+
+        let local_transform = parent_transform.inverse() * transform;
+        self.parents.insert(handle, ObjectParent { parent, local_transform });
+    }
+
+    /// Number of parents between the object and the root of its hierarchy.
+    fn depth(&self, mut handle: RawObjectHandle) -> usize {
+        let mut depth = 0;
+        while let Some(parent) = self.parents.get(&handle) {
+            handle = parent.parent.get_raw();
+            depth += 1;
+        }
+        depth
+    }
+
+    /// Moves every parented object whose own or parent's transform changed
+    /// this frame. Parents are resolved before their children, so changes
+    /// propagate down the whole hierarchy.
+    fn resolve_hierarchy(&mut self) {
+        if self.changed_this_frame.is_empty() {
+            return;
+        }
+
+        let mut order: Vec<_> = self.parents.keys().map(|&handle| (self.depth(handle), handle)).collect();
+        order.sort_unstable_by_key(|&(depth, _)| depth);
+
+        for (_, handle) in order {
+            let node = &self.parents[&handle];
+            let parent = node.parent.get_raw();
+            if !self.changed_this_frame.contains(&handle) && !self.changed_this_frame.contains(&parent) {
+                continue;
+            }
+
+            let parent_archetype = &self.archetype[&self.handle_to_typeid[&parent]];
+            let Some(parent_transform) = (parent_archetype.transform)(&parent_archetype.data_vec, parent.idx) else {
+                continue;
+            };
+            let transform = parent_transform * node.local_transform;
+
+            // The parent transform is relative to the render origin, so the child's is as well.
+            let archetype = self.archetype.get_mut(&self.handle_to_typeid[&handle]).unwrap();
+            (archetype.set_object_world_offset)(archetype, handle.idx, self.render_origin, self.render_origin);
+            (archetype.set_object_transform)(archetype, handle.idx, transform, self.render_origin);
+            self.changed_this_frame.insert(handle);
+        }
+
+        self.changed_this_frame.clear();
+    }
+
+    /// Handles of every object which is currently alive, in no particular order.
+    pub fn handles(&self) -> impl ExactSizeIterator<Item = RawObjectHandle> + '_ {
+        self.handle_to_typeid.keys().copied()
+    }
+
+    /// World space bounding sphere of the object, as used for culling.
+    ///
+    /// Returns `None` if the object hasn't been added yet.
+    pub fn bounding_sphere(&self, handle: RawObjectHandle) -> Option<BoundingSphere> {
+        let type_id = self.handle_to_typeid.get(&handle)?;
+
+        let archetype = &self.archetype[type_id];
+
+        (archetype.bounding_sphere)(&archetype.data_vec, handle.idx)
     }
 
     pub fn remove(&mut self, handle: RawObjectHandle) {
-        let type_id = self.handle_to_typeid[&handle];
+        // Objects removed by a world clear have already been disabled.
+        let Some(type_id) = self.handle_to_typeid.remove(&handle) else {
+            return;
+        };
+        self.parents.remove(&handle);
 
         let archetype = self.archetype.get_mut(&type_id).unwrap();
 
         (archetype.remove)(archetype, handle.idx);
     }
 
+    /// Removes every object. Outstanding handles stay allocated so their
+    /// indices can't be reused, and are ignored until they are dropped.
+    pub fn clear(&mut self) {
+        self.parents.clear();
+        self.changed_this_frame.clear();
+        for (handle, type_id) in self.handle_to_typeid.drain() {
+            let archetype = self.archetype.get_mut(&type_id).unwrap();
+
+            (archetype.remove)(archetype, handle.idx);
+            self.cleared_this_frame.push(handle);
+        }
+    }
+
     pub fn evaluate(
         &mut self,
         device: &Device,
         encoder: &mut CommandEncoder,
         scatter: &ScatterCopy,
         deferred_removals: &[RawObjectHandle],
-    ) {
-        for archetype in self.archetype.values_mut() {
-            (archetype.evaluate)(archetype, device, encoder, scatter, deferred_removals);
-        }
+    ) -> usize {
+        self.resolve_hierarchy();
+
+        // Objects cleared last frame are deleted alongside the handles deleted last frame.
+        let mut cleared = std::mem::replace(&mut self.cleared_last_frame, std::mem::take(&mut self.cleared_this_frame));
+        let removals = if cleared.is_empty() {
+            deferred_removals
+        } else {
+            cleared.extend_from_slice(deferred_removals);
+            &cleared
+        };
+
+        self.archetype
+            .values_mut()
+            .map(|archetype| (archetype.evaluate)(archetype, device, encoder, scatter, removals))
+            .sum()
     }
 
     pub fn buffer<M: Material>(&self) -> Option<&Buffer> {
@@ -208,7 +480,10 @@ impl ObjectManager {
         skeleton_manager: &SkeletonManager,
         material_manager: &mut MaterialManager,
     ) {
-        let type_id = self.handle_to_typeid[&src_handle];
+        // If the source was removed by a world clear, there is nothing to duplicate.
+        let Some(&type_id) = self.handle_to_typeid.get(&src_handle) else {
+            return;
+        };
 
         let archetype = self.archetype.get_mut(&type_id).unwrap();
 
@@ -266,23 +541,31 @@ pub(super) fn object_add_callback<M: Material>(_material: &M, args: ObjectAddCal
 
     // Transform the bounding sphere from model to world space.
     let mesh_bounding_sphere = args.internal_mesh.bounding_sphere;
-    let bounding_sphere = mesh_bounding_sphere.apply_transform(args.object.transform);
+    let transform = relative_transform(args.object.transform, args.object.world_offset, args.manager.render_origin);
+    let bounding_sphere = mesh_bounding_sphere.apply_transform(transform);
     let index_range = args.internal_mesh.index_range.clone();
 
     let internal_object = InternalObject::<M> {
         location: bounding_sphere.center.into(),
         mesh_bounding_sphere,
+        sort_bias: args.object.sort_bias,
+        debug_tint: args.object.debug_tint,
+        transform: args.object.transform,
+        world_offset: args.object.world_offset,
         inner: ShaderObject {
             material_index: args.object.material.idx as u32,
-            transform: args.object.transform,
+            transform,
+            previous_transform: transform,
             bounding_sphere,
             first_index: (index_range.start / 4) as u32,
             index_count: ((index_range.end - index_range.start) / 4) as u32,
             vertex_attribute_start_offsets,
             enabled: true as u32,
+            debug_tint: args.object.debug_tint.unwrap_or(Vec4::ONE),
         },
         material_handle: args.object.material,
         mesh_kind: args.object.mesh_kind,
+        moved: false,
     };
 
     let type_id = TypeId::of::<M>();
@@ -299,20 +582,101 @@ pub(super) fn object_add_callback<M: Material>(_material: &M, args: ObjectAddCal
     archetype.buffer.use_index(args.handle.idx);
 }
 
+/// Transform relative to the render origin of an object with the given
+/// transform and world offset. The offset is applied in double precision, so
+/// only the distance to the render origin is rounded to f32.
+fn relative_transform(transform: Mat4, world_offset: DVec3, render_origin: DVec3) -> Mat4 {
+    if world_offset == render_origin {
+        return transform;
+    }
+
+    Mat4::from_translation((world_offset - render_origin).as_vec3()) * transform
+}
+
 fn set_object_transform<M: Material>(
-    data: &mut WasmVecAny,
-    buffer: &mut FreelistDerivedBuffer,
+    archetype: &mut ObjectArchetype,
     idx: usize,
     transform: Mat4,
+    render_origin: DVec3,
+) {
+    let data_vec = archetype.data_vec.downcast_slice_mut::<Option<InternalObject<M>>>().unwrap();
+
+    let object = data_vec[idx].as_mut().unwrap();
+    object.transform = transform;
+    place_object(object, idx, &mut archetype.moved_this_frame, render_origin);
+
+    archetype.buffer.use_index(idx);
+}
+
+fn set_object_world_offset<M: Material>(
+    archetype: &mut ObjectArchetype,
+    idx: usize,
+    world_offset: DVec3,
+    render_origin: DVec3,
 ) {
-    let data_vec = data.downcast_slice_mut::<Option<InternalObject<M>>>().unwrap();
+    let data_vec = archetype.data_vec.downcast_slice_mut::<Option<InternalObject<M>>>().unwrap();
 
     let object = data_vec[idx].as_mut().unwrap();
+    object.world_offset = world_offset;
+    place_object(object, idx, &mut archetype.moved_this_frame, render_origin);
+
+    archetype.buffer.use_index(idx);
+}
+
+/// Updates the gpu side transform of an object after its transform or world
+/// offset changed.
+fn place_object<M: Material>(
+    object: &mut InternalObject<M>,
+    idx: usize,
+    moved_this_frame: &mut Vec<usize>,
+    render_origin: DVec3,
+) {
+    // The first change in a frame records where the object was rendered last frame.
+    if !object.moved {
+        object.moved = true;
+        object.inner.previous_transform = object.inner.transform;
+        moved_this_frame.push(idx);
+    }
+    let transform = relative_transform(object.transform, object.world_offset, render_origin);
     object.inner.transform = transform;
     object.inner.bounding_sphere = object.mesh_bounding_sphere.apply_transform(transform);
     object.location = transform.transform_point3a(Vec3A::ZERO);
+}
+
+fn set_render_origin<M: Material>(archetype: &mut ObjectArchetype, old_origin: DVec3, new_origin: DVec3) {
+    let data_vec = archetype.data_vec.downcast_slice_mut::<Option<InternalObject<M>>>().unwrap();
+
+    // Previous transforms move along with the origin, so moving it doesn't show up as motion.
+    let shift = Mat4::from_translation((old_origin - new_origin).as_vec3());
+    for (idx, object) in data_vec.iter_mut().enumerate() {
+        let Some(object) = object else {
+            continue;
+        };
+
+        let transform = relative_transform(object.transform, object.world_offset, new_origin);
+        object.inner.transform = transform;
+        object.inner.previous_transform = shift * object.inner.previous_transform;
+        object.inner.bounding_sphere = object.mesh_bounding_sphere.apply_transform(transform);
+        object.location = transform.transform_point3a(Vec3A::ZERO);
+
+        archetype.buffer.use_index(idx);
+    }
+}
+
+fn set_object_debug_tint<M: Material>(archetype: &mut ObjectArchetype, idx: usize, tint: Option<Vec4>) {
+    let data_vec = archetype.data_vec.downcast_slice_mut::<Option<InternalObject<M>>>().unwrap();
+
+    let object = data_vec[idx].as_mut().unwrap();
+    object.debug_tint = tint;
+    object.inner.debug_tint = tint.unwrap_or(Vec4::ONE);
 
-    buffer.use_index(idx);
+    archetype.buffer.use_index(idx);
+}
+
+fn transform<M: Material>(data: &WasmVecAny, idx: usize) -> Option<Mat4> {
+    let data_vec = data.downcast_slice::<Option<InternalObject<M>>>().unwrap();
+
+    Some(data_vec.get(idx)?.as_ref()?.inner.transform)
 }
 
 fn duplicate_object<M: Material>(data: &WasmVecAny, idx: usize, change: ObjectChange) -> Object {
@@ -323,10 +687,19 @@ fn duplicate_object<M: Material>(data: &WasmVecAny, idx: usize, change: ObjectCh
     Object {
         mesh_kind: change.mesh_kind.unwrap_or_else(|| src_obj.mesh_kind.clone()),
         material: change.material.unwrap_or_else(|| src_obj.material_handle.clone()),
-        transform: change.transform.unwrap_or(src_obj.inner.transform),
+        transform: change.transform.unwrap_or(src_obj.transform),
+        world_offset: change.world_offset.unwrap_or(src_obj.world_offset),
+        sort_bias: change.sort_bias.unwrap_or(src_obj.sort_bias),
+        debug_tint: change.debug_tint.unwrap_or(src_obj.debug_tint),
     }
 }
 
+fn bounding_sphere<M: Material>(data: &WasmVecAny, idx: usize) -> Option<BoundingSphere> {
+    let data_vec = data.downcast_slice::<Option<InternalObject<M>>>().unwrap();
+
+    Some(data_vec.get(idx)?.as_ref()?.inner.bounding_sphere)
+}
+
 fn remove<M: Material>(archetype: &mut ObjectArchetype, idx: usize) {
     let data_vec = archetype.data_vec.downcast_slice_mut::<Option<InternalObject<M>>>().unwrap();
 
@@ -347,18 +720,35 @@ fn evaluate<M: Material>(
     encoder: &mut CommandEncoder,
     scatter: &ScatterCopy,
     deferred_removals: &[RawObjectHandle],
-) {
+) -> usize {
     let data_vec = archetype.data_vec.downcast_slice_mut::<Option<InternalObject<M>>>().unwrap();
 
     for removal in deferred_removals {
         // Only one archetype will have each handle,
         // so if we have it, we can be sure it's ours.
-        let removed_obj = data_vec[removal.idx].take();
+        let removed_obj = data_vec.get_mut(removal.idx).and_then(Option::take);
 
         if removed_obj.is_some() {
             archetype.object_count -= 1;
         }
     }
 
+    // Objects which moved last frame but not this frame are now at rest, so their
+    // previous transform needs to catch up to their current one.
+    for idx in archetype.moved_last_frame.drain(..) {
+        if let Some(object) = data_vec.get_mut(idx).and_then(Option::as_mut) {
+            if !object.moved {
+                object.inner.previous_transform = object.inner.transform;
+                archetype.buffer.use_index(idx);
+            }
+        }
+    }
+    for &idx in &archetype.moved_this_frame {
+        if let Some(object) = data_vec.get_mut(idx).and_then(Option::as_mut) {
+            object.moved = false;
+        }
+    }
+    std::mem::swap(&mut archetype.moved_this_frame, &mut archetype.moved_last_frame);
+
     archetype.buffer.apply(device, encoder, scatter, |idx| data_vec[idx].as_ref().map(|o| o.inner).unwrap_or_default())
 }
@@ -29,6 +29,8 @@ struct ShaderPointLight {
 pub struct PointLightManager {
     data: Vec<Option<PointLight>>,
     data_buffer: WrappedPotBuffer<ShaderPointLightBuffer>,
+    /// Lights changed since the buffer was last written.
+    dirty: bool,
 }
 
 impl PointLightManager {
@@ -36,6 +38,7 @@ impl PointLightManager {
         Self {
             data: Vec::new(),
             data_buffer: WrappedPotBuffer::new(device, BufferUsages::STORAGE, "point light buffer"),
+            dirty: true,
         }
     }
 
@@ -45,17 +48,36 @@ impl PointLightManager {
         }
 
         self.data[handle.idx] = Some(light);
+        self.dirty = true;
     }
 
     pub fn update(&mut self, handle: RawPointLightHandle, change: PointLightChange) {
-        self.data[handle.idx].as_mut().unwrap().update_from_changes(change);
+        // The light may have been removed by a world clear while the user still holds the handle.
+        if let Some(light) = self.data[handle.idx].as_mut() {
+            light.update_from_changes(change);
+            self.dirty = true;
+        }
     }
 
     pub fn remove(&mut self, handle: RawPointLightHandle) {
-        self.data[handle.idx].take().unwrap();
+        self.dirty |= self.data[handle.idx].take().is_some();
+    }
+
+    /// Removes every light. Outstanding handles stay allocated, and are
+    /// ignored until they are dropped.
+    pub fn clear(&mut self) {
+        self.data.iter_mut().for_each(|light| *light = None);
+        self.dirty = true;
     }
 
-    pub fn evaluate(&mut self, renderer: &Renderer) {
+    /// Uploads the lights if any changed since the last call, returning
+    /// whether an upload happened.
+    pub fn evaluate(&mut self, renderer: &Renderer) -> bool {
+        if !self.dirty {
+            return false;
+        }
+        self.dirty = false;
+
         let buffer = ShaderPointLightBuffer {
             count: ArrayLength,
             array: self
@@ -71,6 +93,8 @@ impl PointLightManager {
         };
 
         self.data_buffer.write_to_buffer(&renderer.device, &renderer.queue, &buffer);
+
+        true
     }
 
     pub fn add_to_bgl(bglb: &mut BindGroupLayoutBuilder) {
@@ -1,19 +1,26 @@
-use std::ops::Range;
+use std::{mem, ops::Range};
 
 use arrayvec::ArrayVec;
-use glam::Mat4;
+use glam::{Mat4, Vec4};
+use range_alloc::RangeAllocator;
 use rend3_types::{
-    MeshHandle, RawSkeletonHandle, Skeleton, VertexAttributeId, VERTEX_ATTRIBUTE_JOINT_INDICES,
-    VERTEX_ATTRIBUTE_JOINT_WEIGHTS, VERTEX_ATTRIBUTE_NORMAL, VERTEX_ATTRIBUTE_POSITION, VERTEX_ATTRIBUTE_TANGENT,
+    MeshHandle, RawSkeletonHandle, Skeleton, SkinningMethod, VertexAttributeId, VERTEX_ATTRIBUTE_JOINT_INDICES,
+    VERTEX_ATTRIBUTE_JOINT_INDICES_1, VERTEX_ATTRIBUTE_JOINT_WEIGHTS, VERTEX_ATTRIBUTE_JOINT_WEIGHTS_1,
+    VERTEX_ATTRIBUTE_NORMAL, VERTEX_ATTRIBUTE_POSITION, VERTEX_ATTRIBUTE_TANGENT,
 };
 use thiserror::Error;
-use wgpu::Device;
+use wgpu::{Buffer, BufferDescriptor, BufferUsages, Device};
 
 use crate::{
     managers::{MeshCreationError, MeshManager},
     util::iter::ExactSizerIterator,
+    Renderer,
 };
 
+/// Number of joint matrices the joint buffer has room for before it first
+/// needs to grow.
+const STARTING_JOINT_CAPACITY: u32 = 256;
+
 /// Internal representation of a Skeleton
 #[derive(Debug)]
 pub struct InternalSkeleton {
@@ -22,9 +29,15 @@ pub struct InternalSkeleton {
     /// The list of per-joint transformation matrices that will be applied to
     /// vertices.
     pub joint_matrices: Vec<Mat4>,
-    /// There are 5 different ranges we need to store here:
-    /// Position, Normals, Tangent, Joint Index, Joint Weight
-    pub source_attribute_ranges: ArrayVec<(VertexAttributeId, Range<u64>), 5>,
+    /// Range of this skeleton's matrices in the packed joint matrix buffer.
+    /// Assigned when the skeleton is added to the manager.
+    pub joint_range: Range<u32>,
+    /// How the joint matrices are blended.
+    pub skinning_method: SkinningMethod,
+    /// There are up to 7 different ranges we need to store here:
+    /// Position, Normals, Tangent, and one or two sets of Joint Index and
+    /// Joint Weight
+    pub source_attribute_ranges: ArrayVec<(VertexAttributeId, Range<u64>), 7>,
     /// There are three attributes that we can possibly override here:
     /// Position, Normals, and Tangent
     pub overridden_attribute_ranges: ArrayVec<(VertexAttributeId, Range<u64>), 3>,
@@ -47,21 +60,78 @@ pub enum SkeletonCreationError {
     NotEnoughJoints { mesh_joint_count: u16, joint_matrix_count: usize },
 }
 
+#[derive(Debug, Error)]
+pub enum SkeletonUpdateError {
+    #[error(
+        "Not enough joints to update this skeleton. The mesh has {mesh_joint_count} joints, \
+         but only {joint_matrix_count} joint matrices were provided."
+    )]
+    NotEnoughJoints { mesh_joint_count: usize, joint_matrix_count: usize },
+}
+
+/// Packs a joint matrix the way the skinning shader reads it.
+///
+/// For dual quaternion skinning, the first column is the rotation
+/// quaternion, the second the dual part holding the translation, and the
+/// third the scale.
+fn pack_joint_matrix(joint_matrix: &Mat4, skinning_method: SkinningMethod) -> Mat4 {
+    match skinning_method {
+        SkinningMethod::LinearBlend => *joint_matrix,
+        SkinningMethod::DualQuaternion => {
+            let (scale, rotation, translation) = joint_matrix.to_scale_rotation_translation();
+            let rotation_xyz = rotation.xyz();
+            // 0.5 * (translation, 0) * rotation
+            let dual = 0.5
+                * (rotation.w * translation + translation.cross(rotation_xyz)).extend(-translation.dot(rotation_xyz));
+            Mat4::from_cols(Vec4::from(rotation), dual, scale.extend(0.0), Vec4::ZERO)
+        }
+    }
+}
+
+fn create_joint_buffer(device: &Device, capacity: u32) -> Buffer {
+    device.create_buffer(&BufferDescriptor {
+        label: Some("joint matrices"),
+        size: capacity as u64 * mem::size_of::<Mat4>() as u64,
+        usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
 /// Manages skeletons.
 ///
 /// Skeletons only contain the relevant data for vertex skinning. No bone
 /// hierarchy is stored.
+///
+/// The joint matrices of every skeleton are packed into a single buffer,
+/// each skeleton owning a fixed range of it. Only skeletons whose matrices
+/// changed are uploaded each frame.
 pub struct SkeletonManager {
     data: Vec<Option<InternalSkeleton>>,
     skeleton_count: usize,
     /// The number of joints of all the skeletons in this manager
     global_joint_count: usize,
+
+    joint_buffer: Buffer,
+    /// Capacity of `joint_buffer`, in joints.
+    joint_buffer_capacity: u32,
+    joint_allocator: RangeAllocator<u32>,
+    /// Indices of skeletons whose joint matrices need uploading.
+    stale: Vec<usize>,
 }
 impl SkeletonManager {
-    pub fn new() -> Self {
+    pub fn new(device: &Device) -> Self {
         profiling::scope!("SkeletonManager::new");
 
-        Self { data: Vec::new(), skeleton_count: 0, global_joint_count: 0 }
+        Self {
+            data: Vec::new(),
+            skeleton_count: 0,
+            global_joint_count: 0,
+
+            joint_buffer: create_joint_buffer(device, STARTING_JOINT_CAPACITY),
+            joint_buffer_capacity: STARTING_JOINT_CAPACITY,
+            joint_allocator: RangeAllocator::new(0..STARTING_JOINT_CAPACITY),
+            stale: Vec::new(),
+        }
     }
 
     pub fn validate_skeleton(
@@ -90,10 +160,25 @@ impl SkeletonManager {
 
         let overridden_attributes = [&VERTEX_ATTRIBUTE_POSITION, &VERTEX_ATTRIBUTE_NORMAL, &VERTEX_ATTRIBUTE_TANGENT];
 
-        let mut source_attribute_ranges: ArrayVec<_, 5> = ArrayVec::new();
+        let mut source_attribute_ranges: ArrayVec<_, 7> = ArrayVec::new();
         source_attribute_ranges.push((*VERTEX_ATTRIBUTE_JOINT_WEIGHTS.id(), joint_weight_range));
         source_attribute_ranges.push((*VERTEX_ATTRIBUTE_JOINT_INDICES.id(), joint_indices_range));
 
+        // The second set of joints, for vertices with more than 4 influences, is optional but must be complete.
+        match (
+            internal_mesh.get_attribute(&VERTEX_ATTRIBUTE_JOINT_WEIGHTS_1),
+            internal_mesh.get_attribute(&VERTEX_ATTRIBUTE_JOINT_INDICES_1),
+        ) {
+            (Some(joint_weight_range), Some(joint_indices_range)) => {
+                source_attribute_ranges.push((*VERTEX_ATTRIBUTE_JOINT_WEIGHTS_1.id(), joint_weight_range));
+                source_attribute_ranges.push((*VERTEX_ATTRIBUTE_JOINT_INDICES_1.id(), joint_indices_range));
+            }
+            (None, Some(_)) => return Err(SkeletonCreationError::MissingAttributesJointWeights),
+            (Some(_), None) => return Err(SkeletonCreationError::MissingAttributesJointIndices),
+            (None, None) => {}
+        }
+        let joint_attribute_count = source_attribute_ranges.len();
+
         let mut overridden_attribute_ranges: ArrayVec<_, 3> = ArrayVec::new();
         for attribute in overridden_attributes {
             let original_range = match internal_mesh.get_attribute(attribute) {
@@ -106,8 +191,8 @@ impl SkeletonManager {
         // We split this for loop into two parts so that because we need &mut on the mesh manager
         // the original loop needs & on the mesh manager to call get_attribute.
         //
-        // We skip the first two as those are always the joint* attributes.
-        for (attribute_id, original_range) in &source_attribute_ranges[2..] {
+        // We skip the joint* attributes, which always come first.
+        for (attribute_id, original_range) in &source_attribute_ranges[joint_attribute_count..] {
             let skeleton_range = mesh_manager.allocate_range(device, original_range.end - original_range.start)?;
             overridden_attribute_ranges.push((*attribute_id, skeleton_range));
         }
@@ -118,6 +203,8 @@ impl SkeletonManager {
 
         Ok(InternalSkeleton {
             joint_matrices,
+            joint_range: 0..0,
+            skinning_method: skeleton.skinning_method,
             mesh_handle: skeleton.mesh,
             source_attribute_ranges,
             overridden_attribute_ranges,
@@ -125,20 +212,55 @@ impl SkeletonManager {
         })
     }
 
-    pub fn add(&mut self, handle: RawSkeletonHandle, internal: InternalSkeleton) {
+    pub fn add(&mut self, handle: RawSkeletonHandle, mut internal: InternalSkeleton) {
         self.global_joint_count += internal.joint_matrices.len();
+        internal.joint_range = self.allocate_joints(internal.joint_matrices.len() as u32);
 
         if handle.idx >= self.data.len() {
             self.data.resize_with(handle.idx + 1, || None);
         }
         self.data[handle.idx] = Some(internal);
+        self.stale.push(handle.idx);
 
         self.skeleton_count += 1;
     }
 
+    fn allocate_joints(&mut self, count: u32) -> Range<u32> {
+        if count == 0 {
+            return 0..0;
+        }
+
+        match self.joint_allocator.allocate_range(count) {
+            Ok(range) => range,
+            Err(..) => {
+                // The buffer itself is replaced when next evaluated.
+                let current = self.joint_allocator.initial_range().end;
+                self.joint_allocator.grow_to((current + count).next_power_of_two());
+                self.joint_allocator.allocate_range(count).expect(
+                    "Second allocation range should always succeed, as there should always be enough space in the tail of the buffer",
+                )
+            }
+        }
+    }
+
+    /// Makes room in the joint buffer for at least `additional` more joints
+    /// than there are now. The buffer is grown once when next evaluated,
+    /// instead of doubling repeatedly as skeletons are added.
+    pub fn reserve_joints(&mut self, additional: usize) {
+        let desired = u32::try_from(self.global_joint_count + additional)
+            .expect("Reserving more than 2^32 joints")
+            .next_power_of_two();
+        if desired > self.joint_allocator.initial_range().end {
+            self.joint_allocator.grow_to(desired);
+        }
+    }
+
     pub fn remove(&mut self, mesh_manager: &MeshManager, handle: RawSkeletonHandle) {
         let skeleton = self.data[handle.idx].take().unwrap();
         self.global_joint_count -= skeleton.joint_matrices.len();
+        if !skeleton.joint_range.is_empty() {
+            self.joint_allocator.free_range(skeleton.joint_range);
+        }
 
         // Free the owned regions of the mesh data buffer
         for (_, range) in skeleton.overridden_attribute_ranges {
@@ -148,18 +270,78 @@ impl SkeletonManager {
         self.skeleton_count -= 1;
     }
 
-    pub fn set_joint_matrices(&mut self, handle: RawSkeletonHandle, mut joint_matrices: Vec<Mat4>) {
+    /// Replaces the joint matrices of a skeleton. Leaves the skeleton
+    /// untouched if there are fewer matrices than the mesh has joints.
+    pub fn set_joint_matrices(
+        &mut self,
+        handle: RawSkeletonHandle,
+        mut joint_matrices: Vec<Mat4>,
+    ) -> Result<(), SkeletonUpdateError> {
         let skeleton = self.data[handle.idx].as_mut().unwrap();
-        assert!(
-            skeleton.joint_matrices.len() <= joint_matrices.len(),
-            "Not enough joints to update this skeleton. The mesh has {} joints, \
-            but only {} joint matrices were provided.",
-            skeleton.joint_matrices.len(),
-            joint_matrices.len(),
-        );
+        if skeleton.joint_matrices.len() > joint_matrices.len() {
+            return Err(SkeletonUpdateError::NotEnoughJoints {
+                mesh_joint_count: skeleton.joint_matrices.len(),
+                joint_matrix_count: joint_matrices.len(),
+            });
+        }
         // Truncate to avoid storing any extra joint matrices
         joint_matrices.truncate(skeleton.joint_matrices.len());
         skeleton.joint_matrices = joint_matrices;
+        self.stale.push(handle.idx);
+        Ok(())
+    }
+
+    /// Uploads the joint matrices of every skeleton changed since the last
+    /// call, reallocating the joint buffer if it ran out of room.
+    pub fn evaluate(&mut self, renderer: &Renderer) {
+        profiling::scope!("SkeletonManager::evaluate");
+
+        let capacity = self.joint_allocator.initial_range().end;
+        if capacity != self.joint_buffer_capacity {
+            self.joint_buffer = create_joint_buffer(&renderer.device, capacity);
+            self.joint_buffer_capacity = capacity;
+
+            // The new buffer starts out empty, so every skeleton needs uploading.
+            self.stale.clear();
+            self.stale.extend((0..self.data.len()).filter(|&idx| self.data[idx].is_some()));
+        }
+
+        // A skeleton may be updated many times in a frame, it only needs uploading once.
+        self.stale.sort_unstable();
+        self.stale.dedup();
+
+        let mut packed = Vec::new();
+        for idx in self.stale.drain(..) {
+            // The skeleton may have been removed since it was changed.
+            let Some(skeleton) = &self.data[idx] else {
+                continue;
+            };
+            if skeleton.joint_range.is_empty() {
+                continue;
+            }
+
+            packed.clear();
+            packed.extend(
+                skeleton.joint_matrices.iter().map(|matrix| pack_joint_matrix(matrix, skeleton.skinning_method)),
+            );
+            renderer.queue.write_buffer(
+                &self.joint_buffer,
+                skeleton.joint_range.start as u64 * mem::size_of::<Mat4>() as u64,
+                bytemuck::cast_slice(&packed),
+            );
+        }
+    }
+
+    /// The buffer holding the packed joint matrices of every skeleton, indexed
+    /// by [`InternalSkeleton::joint_range`].
+    pub fn joint_buffer(&self) -> &Buffer {
+        &self.joint_buffer
+    }
+
+    /// Number of joint matrices of the skeleton, which is the number of joints
+    /// its mesh uses.
+    pub fn joint_count(&self, handle: RawSkeletonHandle) -> usize {
+        self.internal_data(handle).joint_matrices.len()
     }
 
     pub fn internal_data(&self, handle: RawSkeletonHandle) -> &InternalSkeleton {
@@ -176,8 +358,30 @@ impl SkeletonManager {
     }
 }
 
-impl Default for SkeletonManager {
-    fn default() -> Self {
-        Self::new()
+#[cfg(test)]
+mod tests {
+    use glam::{Mat4, Quat, Vec3};
+    use rend3_types::SkinningMethod;
+
+    use super::pack_joint_matrix;
+
+    #[test]
+    fn dual_quaternion_matches_matrix() {
+        let matrix = Mat4::from_scale_rotation_translation(
+            Vec3::new(2.0, 2.0, 2.0),
+            Quat::from_rotation_y(1.0) * Quat::from_rotation_x(0.5),
+            Vec3::new(1.0, -2.0, 3.0),
+        );
+        let packed = pack_joint_matrix(&matrix, SkinningMethod::DualQuaternion);
+        let (real, dual, scale) = (packed.x_axis, packed.y_axis, packed.z_axis.truncate());
+
+        // Mirrors the dual quaternion path of skinning.wgsl.
+        let point = Vec3::new(0.5, 1.0, -1.5);
+        let rotation = Quat::from_vec4(real);
+        let translation =
+            2.0 * (real.w * dual.truncate() - dual.w * real.truncate() + real.truncate().cross(dual.truncate()));
+        let skinned = rotation * (point * scale) + translation;
+
+        assert!(skinned.abs_diff_eq(matrix.transform_point3(point), 1e-5));
     }
 }
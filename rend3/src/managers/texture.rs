@@ -1,17 +1,22 @@
 use std::{marker::PhantomData, num::NonZeroU32, sync::Arc};
 
-use rend3_types::{MipmapCount, MipmapSource, RawResourceHandle, TextureFormat, TextureFromTexture, TextureUsages};
+use rend3_types::{
+    MipmapCount, MipmapGeneration, MipmapSource, RawResourceHandle, TextureFormat, TextureFromTexture, TextureUsages,
+};
 use thiserror::Error;
 use wgpu::{
-    util::DeviceExt, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor,
-    BindGroupLayoutEntry, BindingResource, BindingType, CommandBuffer, CommandEncoder, CommandEncoderDescriptor,
-    Device, Extent3d, Features, ImageCopyTexture, ImageDataLayout, Origin3d, ShaderStages, Texture, TextureAspect,
-    TextureDescriptor, TextureDimension, TextureSampleType, TextureView, TextureViewDescriptor, TextureViewDimension,
+    BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor, BindGroupLayoutEntry,
+    BindingResource, BindingType, CommandBuffer, CommandEncoder, CommandEncoderDescriptor, Device, Extent3d, Features,
+    ImageCopyTexture, Origin3d, ShaderStages, Texture, TextureAspect, TextureDescriptor, TextureDimension,
+    TextureSampleType, TextureView, TextureViewDescriptor, TextureViewDimension,
 };
 
 use crate::{
     profile::ProfileData,
-    util::{error_scope::AllocationErrorScope, math::round_up, mipmap::MipmapGenerationError},
+    util::{
+        error_scope::AllocationErrorScope, math::round_up, mipmap::MipmapGenerationError,
+        texture_staging::StagingAllocation,
+    },
     Renderer, RendererProfile,
 };
 
@@ -45,6 +50,16 @@ pub enum TextureCreationError {
     TextureFormatNotFloat { format: TextureFormat, sample_type: Option<TextureSampleType> },
     #[error("Mipmap creation failed")]
     MipmapCreationFailed(#[from] MipmapGenerationError),
+    #[error("All {max} slots of the bindless texture array are in use")]
+    TextureLimitExceeded { max: u32 },
+    /// The error may have been caused by another thread using the device
+    /// at the same time, see [`ValidationErrorScope`](crate::util::error_scope::ValidationErrorScope).
+    #[error("Uploading the texture failed validation")]
+    ValidationFailed(#[source] wgpu::Error),
+    #[error("Imported textures must be single sampled 2D textures with one layer. Got a {dimension:?} texture with {layers} layers and {samples} samples.")]
+    ImportedTextureNot2D { dimension: TextureDimension, layers: u32, samples: u32 },
+    #[error("Imported textures must have the TEXTURE_BINDING usage. Got {usage:?}.")]
+    ImportedTextureNotBindable { usage: TextureUsages },
 }
 
 unsafe impl Send for TextureCreationError {}
@@ -55,6 +70,24 @@ const TEXTURE_PREALLOCATION: usize = 1024;
 /// What we divide the texture limit by to get the count supplied in the BGL.
 const BGL_DIVISOR: u32 = 4;
 
+/// Sampled textures the PBR shaders bind next to the bindless array in the
+/// fragment stage: the shadow atlas, the three image based lighting textures,
+/// and the transmission background.
+pub const RESERVED_SAMPLED_TEXTURES: u32 = 5;
+
+/// Size of the bindless texture arrays used when none is requested, given
+/// the device's `max_sampled_textures_per_shader_stage`.
+pub fn default_max_bindless_textures(texture_limit: u32) -> u32 {
+    (texture_limit / BGL_DIVISOR).min(max_bindless_textures(texture_limit))
+}
+
+/// Largest bindless texture arrays that can be requested, given the device's
+/// `max_sampled_textures_per_shader_stage`. The [`RESERVED_SAMPLED_TEXTURES`]
+/// come out of the same limit.
+pub fn max_bindless_textures(texture_limit: u32) -> u32 {
+    texture_limit.saturating_sub(RESERVED_SAMPLED_TEXTURES).min(MAX_TEXTURE_COUNT)
+}
+
 /// Manages textures and associated bindless bind groups
 pub struct TextureManager<T> {
     layout: ProfileData<(), Arc<BindGroupLayout>>,
@@ -63,6 +96,8 @@ pub struct TextureManager<T> {
 
     null_view: TextureView,
 
+    max_textures: ProfileData<(), u32>,
+
     data: Vec<Option<InternalTexture>>,
 
     dimension: TextureViewDimension,
@@ -70,25 +105,28 @@ pub struct TextureManager<T> {
     _phantom: PhantomData<T>,
 }
 impl<T: 'static> TextureManager<T> {
-    pub fn new(device: &Device, profile: RendererProfile, texture_limit: u32, dimension: TextureViewDimension) -> Self {
+    /// Create the manager. When GpuDriven, textures are bound in an array of
+    /// `max_textures` textures.
+    pub fn new(device: &Device, profile: RendererProfile, max_textures: u32, dimension: TextureViewDimension) -> Self {
         profiling::scope!("TextureManager::new");
 
         let null_view = create_null_tex_view(device, dimension);
 
-        let max_textures = (texture_limit / BGL_DIVISOR).min(MAX_TEXTURE_COUNT);
-
         let mut data = Vec::with_capacity(TEXTURE_PREALLOCATION);
         data.resize_with(TEXTURE_PREALLOCATION, || None);
 
         let layout = profile.into_data(|| (), || create_bind_group_layout(device, max_textures, dimension));
-        let group =
-            profile.into_data(|| (), || create_bind_group(device, layout.as_gpu(), &null_view, &data, dimension));
+        let group = profile.into_data(
+            || (),
+            || create_bind_group(device, layout.as_gpu(), &null_view, &data, max_textures, dimension),
+        );
 
         Self {
             layout,
             group,
             group_dirty: profile.into_data(|| (), || false),
             null_view,
+            max_textures: profile.into_data(|| (), || max_textures),
             data,
             dimension,
             _phantom: PhantomData,
@@ -99,7 +137,7 @@ impl<T: 'static> TextureManager<T> {
         renderer: &Renderer,
         texture: crate::types::Texture,
         cube: bool,
-    ) -> Result<(Option<CommandBuffer>, InternalTexture), TextureCreationError> {
+    ) -> Result<(CommandBuffer, StagingAllocation, InternalTexture), TextureCreationError> {
         validate_texture_format(texture.format, renderer.features)?;
 
         let (block_x, block_y) = texture.format.block_dimensions();
@@ -131,56 +169,33 @@ impl<T: 'static> TextureManager<T> {
             view_formats: &[],
         };
 
-        let (buffer, tex) = match texture.mip_source {
-            MipmapSource::Uploaded => {
-                let scope = AllocationErrorScope::new(&renderer.device);
-                let texture = renderer.device.create_texture_with_data(
-                    &renderer.queue,
-                    &desc,
-                    wgpu::util::TextureDataOrder::LayerMajor,
-                    &texture.data,
-                );
-                scope.end().map_err(TextureCreationError::TextureAllocationFailed)?;
-                (None, texture)
-            }
-            MipmapSource::Generated => {
-                assert!(!cube, "Cannot generate mipmaps from cubemaps currently");
-
-                let desc = TextureDescriptor { usage: desc.usage | TextureUsages::RENDER_ATTACHMENT, ..desc };
-                let scope = AllocationErrorScope::new(&renderer.device);
-                let tex = renderer.device.create_texture(&desc);
-                scope.end().map_err(TextureCreationError::TextureAllocationFailed)?;
-
-                let (block_width, _) = texture.format.block_dimensions();
-                let block_size = texture.format.block_copy_size(None).unwrap();
-
-                let scope = AllocationErrorScope::new(&renderer.device);
-                // write first level
-                renderer.queue.write_texture(
-                    ImageCopyTexture {
-                        texture: &tex,
-                        mip_level: 0,
-                        origin: Origin3d::ZERO,
-                        aspect: TextureAspect::All,
-                    },
-                    &texture.data,
-                    ImageDataLayout {
-                        offset: 0,
-                        bytes_per_row: Some(block_size * (size.width / block_width)),
-                        rows_per_image: None,
-                    },
-                    size,
-                );
-                scope.end().map_err(TextureCreationError::WriteTextureFailed)?;
-
-                let mut encoder = renderer.device.create_command_encoder(&CommandEncoderDescriptor::default());
-
-                // generate mipmaps
-                renderer.mipmap_generator.generate_mipmaps(&renderer.device, &mut encoder, &tex, &desc)?;
-
-                (Some(encoder.finish()), tex)
-            }
+        let generation = match texture.mip_source {
+            MipmapSource::Uploaded => None,
+            MipmapSource::Generated => Some(MipmapGeneration::default()),
+            MipmapSource::GeneratedWith(generation) => Some(generation),
         };
+        assert!(!cube || generation.is_none(), "Cannot generate mipmaps from cubemaps currently");
+        let generate_mipmaps = generation.is_some();
+        let desc = match generate_mipmaps {
+            true => TextureDescriptor { usage: desc.usage | TextureUsages::RENDER_ATTACHMENT, ..desc },
+            false => desc,
+        };
+
+        let scope = AllocationErrorScope::new(&renderer.device);
+        let tex = renderer.device.create_texture(&desc);
+        scope.end().map_err(TextureCreationError::TextureAllocationFailed)?;
+
+        // Only the first level is uploaded when the rest are generated from it.
+        let uploaded_mips = if generate_mipmaps { 1 } else { mip_level_count };
+        let mut encoder = renderer.device.create_command_encoder(&CommandEncoderDescriptor::default());
+        let staging = renderer
+            .texture_staging
+            .upload(&renderer.device, &mut encoder, &tex, &desc, uploaded_mips, &texture.data)
+            .map_err(TextureCreationError::WriteTextureFailed)?;
+
+        if let Some(generation) = generation {
+            renderer.mipmap_generator.generate_mipmaps(&renderer.device, &mut encoder, &tex, &desc, generation)?;
+        }
 
         let scope = AllocationErrorScope::new(&renderer.device);
         let view = tex.create_view(&TextureViewDescriptor {
@@ -192,7 +207,43 @@ impl<T: 'static> TextureManager<T> {
         });
         scope.end().map_err(TextureCreationError::TextureViewCreationFailed)?;
 
-        Ok((buffer, InternalTexture { texture: tex, view, desc }))
+        Ok((encoder.finish(), staging, InternalTexture { texture: tex, view, desc }))
+    }
+
+    /// Wraps a texture created outside of rend3, so it can be added without
+    /// copying it.
+    pub fn import(renderer: &Renderer, texture: Texture) -> Result<InternalTexture, TextureCreationError> {
+        // Imported textures are sampled by materials like any other, so they need a filterable float format.
+        validate_texture_format(texture.format(), renderer.features)?;
+
+        let desc = TextureDescriptor {
+            label: None,
+            size: texture.size(),
+            mip_level_count: texture.mip_level_count(),
+            sample_count: texture.sample_count(),
+            dimension: texture.dimension(),
+            format: texture.format(),
+            usage: texture.usage(),
+            view_formats: &[],
+        };
+
+        if desc.dimension != TextureDimension::D2 || desc.size.depth_or_array_layers != 1 || desc.sample_count != 1 {
+            return Err(TextureCreationError::ImportedTextureNot2D {
+                dimension: desc.dimension,
+                layers: desc.size.depth_or_array_layers,
+                samples: desc.sample_count,
+            });
+        }
+        if !desc.usage.contains(TextureUsages::TEXTURE_BINDING) {
+            return Err(TextureCreationError::ImportedTextureNotBindable { usage: desc.usage });
+        }
+
+        let scope = AllocationErrorScope::new(&renderer.device);
+        let view = texture
+            .create_view(&TextureViewDescriptor { dimension: Some(TextureViewDimension::D2), ..Default::default() });
+        scope.end().map_err(TextureCreationError::TextureViewCreationFailed)?;
+
+        Ok(InternalTexture { texture, view, desc })
     }
 
     pub fn fill_from_texture(
@@ -263,8 +314,14 @@ impl<T: 'static> TextureManager<T> {
             profiling::scope!("Update GPU Texture Arrays");
 
             if group_dirty {
-                *self.group.as_gpu_mut() =
-                    create_bind_group(device, self.layout.as_gpu(), &self.null_view, &self.data, self.dimension);
+                *self.group.as_gpu_mut() = create_bind_group(
+                    device,
+                    self.layout.as_gpu(),
+                    &self.null_view,
+                    &self.data,
+                    *self.max_textures.as_gpu(),
+                    self.dimension,
+                );
                 *self.group_dirty.as_gpu_mut() = false;
             }
 
@@ -286,6 +343,15 @@ impl<T: 'static> TextureManager<T> {
         &self.null_view
     }
 
+    /// Size of the bindless texture array, or `None` when CpuDriven, where
+    /// there is no array.
+    pub fn max_textures(&self) -> Option<u32> {
+        match self.max_textures {
+            ProfileData::Cpu(()) => None,
+            ProfileData::Gpu(max) => Some(max),
+        }
+    }
+
     pub fn gpu_bgl(&self) -> &BindGroupLayout {
         self.layout.as_gpu()
     }
@@ -322,11 +388,13 @@ fn create_bind_group(
     layout: &BindGroupLayout,
     null_view: &TextureView,
     data: &[Option<InternalTexture>],
+    max_textures: u32,
     dimension: TextureViewDimension,
 ) -> Arc<BindGroup> {
-    let count = data.len();
+    // Handles are never allocated past the limit, but the preallocated data may be longer than the array.
+    let count = data.len().min(max_textures as usize);
     let mut view_array = Vec::with_capacity(count);
-    view_array.extend(data.iter().map(|tex| match tex {
+    view_array.extend(data[..count].iter().map(|tex| match tex {
         Some(t) => &t.view,
         None => null_view,
     }));
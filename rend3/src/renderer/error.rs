@@ -49,4 +49,19 @@ pub enum RendererInitializationError {
     RequestDeviceFailed,
     #[error("Failed to create GpuProfiler")]
     GpuProfilerCreation(#[source] CreationError),
+    #[error("Requested bindless texture arrays of {requested} textures, but the limit is {limit}")]
+    TooManyBindlessTextures { requested: u32, limit: u32 },
+}
+
+/// Reason why creating a pipeline through the renderer failed.
+///
+/// The error may have been caused by another thread using the device at the
+/// same time, see [`ValidationErrorScope`](crate::util::error_scope::ValidationErrorScope).
+#[derive(Error, Debug)]
+#[error("Creating pipeline {label:?} failed validation")]
+pub struct PipelineCreationError {
+    /// Label of the pipeline descriptor.
+    pub label: Option<String>,
+    #[source]
+    pub inner: wgpu::Error,
 }
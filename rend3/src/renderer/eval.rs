@@ -22,9 +22,13 @@ pub fn evaluate_instructions(renderer: &Renderer) -> InstructionEvaluationOutput
     let mut data_core = renderer.data_core.lock();
     let data_core = &mut *data_core;
 
+    data_core.viewport_camera_state.begin_frame();
+    // The uploads evaluated last frame have been submitted, so their staging memory can be reused.
+    renderer.texture_staging.begin_frame();
+
     {
         profiling::scope!("Instruction Processing");
-        for Instruction { kind, location: _ } in instructions.drain(..) {
+        for Instruction { kind, location } in instructions.drain(..) {
             match kind {
                 InstructionKind::AddSkeleton { handle, skeleton } => {
                     profiling::scope!("Add Skeleton");
@@ -36,15 +40,18 @@ pub fn evaluate_instructions(renderer: &Renderer) -> InstructionEvaluationOutput
                     data_core.skeleton_manager.add(handle, *skeleton);
                     data_core.profiler.try_lock().unwrap().end_query(&mut encoder, profiler_query);
                 }
-                InstructionKind::AddTexture2D { handle, internal_texture, cmd_buf } => {
-                    cmd_bufs.extend(cmd_buf);
+                InstructionKind::AddTexture2D { handle, internal_texture, cmd_buf, _staging: _ } => {
+                    cmd_bufs.push(cmd_buf);
                     data_core.d2_texture_manager.fill(handle, internal_texture);
                 }
                 InstructionKind::AddTexture2DFromTexture { handle, texture } => {
                     data_core.d2_texture_manager.fill_from_texture(&renderer.device, &mut encoder, handle, texture)
                 }
-                InstructionKind::AddTextureCube { handle, internal_texture, cmd_buf } => {
-                    cmd_bufs.extend(cmd_buf);
+                InstructionKind::ImportTexture2D { handle, internal_texture } => {
+                    data_core.d2_texture_manager.fill(handle, internal_texture);
+                }
+                InstructionKind::AddTextureCube { handle, internal_texture, cmd_buf, _staging: _ } => {
+                    cmd_bufs.push(cmd_buf);
                     data_core.d2c_texture_manager.fill(handle, internal_texture);
                 }
                 InstructionKind::AddMaterial { handle, fill_invoke } => {
@@ -83,8 +90,19 @@ pub fn evaluate_instructions(renderer: &Renderer) -> InstructionEvaluationOutput
                 InstructionKind::SetObjectTransform { handle, transform } => {
                     data_core.object_manager.set_object_transform(handle, transform);
                 }
+                InstructionKind::SetObjectWorldOffset { handle, world_offset } => {
+                    data_core.object_manager.set_object_world_offset(handle, world_offset);
+                }
+                InstructionKind::SetObjectDebugTint { handle, tint } => {
+                    data_core.object_manager.set_object_debug_tint(handle, tint);
+                }
+                InstructionKind::SetObjectParent { handle, parent } => {
+                    data_core.object_manager.set_object_parent(handle, parent);
+                }
                 InstructionKind::SetSkeletonJointDeltas { handle, joint_matrices } => {
-                    data_core.skeleton_manager.set_joint_matrices(handle, joint_matrices);
+                    if let Err(e) = data_core.skeleton_manager.set_joint_matrices(handle, joint_matrices) {
+                        log::error!("Skeleton joint update from {location} ignored: {e}");
+                    }
                 }
                 InstructionKind::AddDirectionalLight { handle, light } => {
                     data_core.directional_light_manager.add(handle, light);
@@ -104,6 +122,15 @@ pub fn evaluate_instructions(renderer: &Renderer) -> InstructionEvaluationOutput
                 InstructionKind::SetCameraData { data } => {
                     data_core.viewport_camera_state.set_data(data);
                 }
+                InstructionKind::SetRenderOrigin { origin } => {
+                    data_core.object_manager.set_render_origin(origin);
+                }
+                InstructionKind::ReserveObjects { additional } => {
+                    data_core.object_manager.reserve(additional);
+                }
+                InstructionKind::ReserveJoints { additional } => {
+                    data_core.skeleton_manager.reserve_joints(additional);
+                }
                 InstructionKind::DuplicateObject { src_handle, dst_handle, change } => {
                     data_core.object_manager.duplicate_object(
                         &renderer.device,
@@ -115,6 +142,12 @@ pub fn evaluate_instructions(renderer: &Renderer) -> InstructionEvaluationOutput
                         &mut data_core.material_manager,
                     );
                 }
+                InstructionKind::ClearWorld => {
+                    profiling::scope!("Clear World");
+                    data_core.object_manager.clear();
+                    data_core.directional_light_manager.clear();
+                    data_core.point_light_manager.clear();
+                }
                 InstructionKind::DeleteMesh { handle } => {
                     renderer.resource_handle_allocators.mesh.deallocate(handle);
                     renderer.mesh_manager.remove(handle)
@@ -137,6 +170,7 @@ pub fn evaluate_instructions(renderer: &Renderer) -> InstructionEvaluationOutput
                 }
                 InstructionKind::DeleteObject { handle } => {
                     renderer.resource_handle_allocators.object.deallocate(handle);
+                    renderer.resource_handle_allocators.object_ids.remove(handle);
                     data_core.object_manager.remove(handle)
                 }
                 InstructionKind::DeleteDirectionalLight { handle } => {
@@ -157,7 +191,8 @@ pub fn evaluate_instructions(renderer: &Renderer) -> InstructionEvaluationOutput
 
     // Do these in dependency order
     // Level 3
-    data_core.object_manager.evaluate(&renderer.device, &mut encoder, &renderer.scatter, &delayed_object_handles);
+    let objects_uploaded =
+        data_core.object_manager.evaluate(&renderer.device, &mut encoder, &renderer.scatter, &delayed_object_handles);
 
     // Level 2
     let d2_texture = data_core.d2_texture_manager.evaluate(&renderer.device);
@@ -177,11 +212,21 @@ pub fn evaluate_instructions(renderer: &Renderer) -> InstructionEvaluationOutput
     let d2c_texture = data_core.d2c_texture_manager.evaluate(&renderer.device);
     let (shadow_target_size, shadows) =
         data_core.directional_light_manager.evaluate(renderer, &data_core.viewport_camera_state);
-    data_core.point_light_manager.evaluate(renderer);
+    let point_lights_uploaded = data_core.point_light_manager.evaluate(renderer);
+    data_core.skeleton_manager.evaluate(renderer);
     let (mesh_buffer, mesh_cmd_buf) = renderer.mesh_manager.evaluate(&renderer.device);
 
     cmd_bufs.push(mesh_cmd_buf);
     cmd_bufs.push(encoder.finish());
 
-    InstructionEvaluationOutput { cmd_bufs, d2_texture, d2c_texture, shadow_target_size, shadows, mesh_buffer }
+    InstructionEvaluationOutput {
+        cmd_bufs,
+        d2_texture,
+        d2c_texture,
+        shadow_target_size,
+        shadows,
+        mesh_buffer,
+        objects_uploaded,
+        point_lights_uploaded,
+    }
 }
@@ -1,29 +1,35 @@
-use std::{marker::PhantomData, panic::Location, sync::Arc};
+use std::{future::Future, marker::PhantomData, panic::Location, sync::Arc};
 
-use glam::Mat4;
+use glam::{DVec3, Mat4, Vec3, Vec4};
 use parking_lot::Mutex;
 use rend3_types::{
-    GraphDataHandle, GraphDataTag, Handedness, Material, MaterialTag, ObjectChange, PointLight, PointLightChange,
-    PointLightHandle, Skeleton, SkeletonHandle, Texture2DTag, TextureCubeHandle, TextureCubeTag, TextureFromTexture,
-    WasmNotSend,
+    DepthConvention, GraphDataHandle, GraphDataTag, Handedness, Material, MaterialTag, ObjectChange, PointLight,
+    PointLightChange, PointLightHandle, RawResourceHandle, ResourceHandle, Skeleton, SkeletonHandle, Texture2DTag,
+    TextureCubeHandle, TextureCubeTag, TextureFromTexture, WasmNotSend, WasmNotSync,
+};
+use wgpu::{
+    BufferAsyncError, BufferSlice, ComputePipeline, ComputePipelineDescriptor, Device, DownlevelCapabilities, Features,
+    Limits, Maintain, MapMode, Queue, RenderPipeline, RenderPipelineDescriptor,
 };
-use wgpu::{Device, DownlevelCapabilities, Features, Limits, Queue};
 use wgpu_profiler::GpuProfiler;
 
 use crate::{
     graph::{GraphTextureStore, InstructionEvaluationOutput},
-    instruction::{InstructionKind, InstructionStreamPair},
+    instruction::{DeletableRawResourceHandle, InstructionKind, InstructionStreamPair},
     managers::{
         CameraState, DirectionalLightManager, GraphStorage, HandleAllocator, MaterialManager, MeshCreationError,
-        MeshManager, ObjectManager, PointLightManager, SkeletonCreationError, SkeletonManager, TextureCreationError,
-        TextureManager,
+        MeshManager, ObjectIdMap, ObjectManager, PointLightManager, SkeletonCreationError, SkeletonManager,
+        TextureCreationError, TextureManager,
     },
     types::{
         Camera, DirectionalLight, DirectionalLightChange, DirectionalLightHandle, MaterialHandle, Mesh, MeshHandle,
-        Object, ObjectHandle, Texture, Texture2DHandle,
+        Object, ObjectHandle, RawObjectHandle, Texture, Texture2DHandle,
+    },
+    util::{
+        blit::Blitter, error_scope::ValidationErrorScope, mipmap::MipmapGenerator, scatter_copy::ScatterCopy,
+        texture_staging::TextureStagingBelt,
     },
-    util::{mipmap::MipmapGenerator, scatter_copy::ScatterCopy},
-    ExtendedAdapterInfo, InstanceAdapterDevice, RendererInitializationError, RendererProfile,
+    ExtendedAdapterInfo, InstanceAdapterDevice, PipelineCreationError, RendererInitializationError, RendererProfile,
 };
 
 pub mod error;
@@ -52,6 +58,8 @@ pub struct Renderer {
     pub downlevel: DownlevelCapabilities,
     /// Handedness of all parts of this renderer.
     pub handedness: Handedness,
+    /// How depth is mapped onto the depth buffer.
+    pub depth_convention: DepthConvention,
 
     /// Allocators for resource handles
     resource_handle_allocators: HandleAllocators,
@@ -62,8 +70,12 @@ pub struct Renderer {
 
     /// Tool which generates mipmaps from a texture.
     pub mipmap_generator: MipmapGenerator,
+    /// Tool which blits one texture to another.
+    pub blitter: Blitter,
     /// Tool which allows scatter uploads to happen.
     pub scatter: ScatterCopy,
+    /// Staging memory shared by all texture uploads.
+    pub texture_staging: TextureStagingBelt,
 }
 
 /// Handle allocators
@@ -74,20 +86,24 @@ struct HandleAllocators {
     pub d2c_texture: HandleAllocator<TextureCubeTag>,
     pub material: HandleAllocator<MaterialTag>,
     pub object: HandleAllocator<Object>,
+    pub object_ids: ObjectIdMap,
     pub directional_light: HandleAllocator<DirectionalLight>,
     pub point_light: HandleAllocator<PointLight>,
     pub graph_storage: HandleAllocator<GraphDataTag>,
 }
 
-impl Default for HandleAllocators {
-    fn default() -> Self {
+impl HandleAllocators {
+    /// Texture handles are limited to `texture_limit`, the size of the
+    /// bindless texture arrays.
+    fn new(texture_limit: Option<usize>) -> Self {
         Self {
             mesh: HandleAllocator::new(false),
             skeleton: HandleAllocator::new(false),
-            d2_texture: HandleAllocator::new(false),
-            d2c_texture: HandleAllocator::new(false),
+            d2_texture: HandleAllocator::new(false).with_limit(texture_limit),
+            d2c_texture: HandleAllocator::new(false).with_limit(texture_limit),
             material: HandleAllocator::new(false),
             object: HandleAllocator::new(true),
+            object_ids: ObjectIdMap::default(),
             directional_light: HandleAllocator::new(false),
             point_light: HandleAllocator::new(false),
             graph_storage: HandleAllocator::new(false),
@@ -135,7 +151,41 @@ impl Renderer {
         handedness: Handedness,
         aspect_ratio: Option<f32>,
     ) -> Result<Arc<Self>, RendererInitializationError> {
-        setup::create_renderer(iad, handedness, aspect_ratio)
+        setup::create_renderer(iad, handedness, DepthConvention::default(), None, aspect_ratio)
+    }
+
+    /// Create a new renderer with the given IAD, using the given depth
+    /// convention instead of the default reverse depth.
+    ///
+    /// See [`DepthConvention`] for the tradeoffs.
+    pub fn new_with_depth_convention(
+        iad: InstanceAdapterDevice,
+        handedness: Handedness,
+        depth_convention: DepthConvention,
+        aspect_ratio: Option<f32>,
+    ) -> Result<Arc<Self>, RendererInitializationError> {
+        setup::create_renderer(iad, handedness, depth_convention, None, aspect_ratio)
+    }
+
+    /// Create a new renderer with the given IAD and depth convention, with
+    /// bindless texture arrays of `max_bindless_textures` textures.
+    ///
+    /// When GpuDriven, all 2D textures, and all cube textures, are bound in
+    /// one array each, which by default takes a quarter of the device's
+    /// `max_sampled_textures_per_shader_stage`. Adding more textures than fit
+    /// returns [`TextureCreationError::TextureLimitExceeded`]. Requesting
+    /// larger arrays than the device supports is an error, leaving room for
+    /// the [`RESERVED_SAMPLED_TEXTURES`] bound next to the arrays.
+    ///
+    /// [`RESERVED_SAMPLED_TEXTURES`]: crate::managers::RESERVED_SAMPLED_TEXTURES
+    pub fn new_with_max_bindless_textures(
+        iad: InstanceAdapterDevice,
+        handedness: Handedness,
+        depth_convention: DepthConvention,
+        max_bindless_textures: u32,
+        aspect_ratio: Option<f32>,
+    ) -> Result<Arc<Self>, RendererInitializationError> {
+        setup::create_renderer(iad, handedness, depth_convention, Some(max_bindless_textures), aspect_ratio)
     }
 
     /// Adds a 3D mesh to the renderer. This doesn't instantiate it to world. To
@@ -146,7 +196,33 @@ impl Renderer {
     /// the mesh alive.
     #[track_caller]
     pub fn add_mesh(self: &Arc<Self>, mesh: Mesh) -> Result<MeshHandle, MeshCreationError> {
-        let internal_mesh = self.mesh_manager.add(&self.device, mesh)?;
+        self.add_mesh_inner(mesh, false)
+    }
+
+    /// Adds a 3D mesh to the renderer like [`Self::add_mesh`], keeping a cpu
+    /// copy of it which can be retrieved with [`Self::read_mesh`]. Useful for
+    /// building collision shapes from the same geometry.
+    #[track_caller]
+    pub fn add_mesh_retained(self: &Arc<Self>, mesh: Mesh) -> Result<MeshHandle, MeshCreationError> {
+        self.add_mesh_inner(mesh, true)
+    }
+
+    /// Returns the cpu copy of a mesh added with [`Self::add_mesh_retained`].
+    /// Returns `None` for meshes added with [`Self::add_mesh`].
+    pub fn read_mesh(&self, handle: &MeshHandle) -> Option<Arc<Mesh>> {
+        self.mesh_manager.cpu_mesh(handle.get_raw())
+    }
+
+    fn add_mesh_inner(self: &Arc<Self>, mesh: Mesh, retain: bool) -> Result<MeshHandle, MeshCreationError> {
+        let scope = ValidationErrorScope::new(&self.device);
+        let internal_mesh = self.mesh_manager.add(&self.device, &mesh);
+        let validation = scope.end();
+        let mut internal_mesh = internal_mesh?;
+        if let Err(inner) = validation {
+            self.mesh_manager.free(internal_mesh);
+            return Err(MeshCreationError::ValidationFailed { inner });
+        }
+        internal_mesh.cpu_mesh = retain.then(|| Arc::new(mesh));
 
         // Handle allocation must be done _after_ any validation to prevent deletion of a handle that never gets fully added.
         let handle = self.resource_handle_allocators.mesh.allocate(self);
@@ -183,13 +259,18 @@ impl Renderer {
     pub fn add_texture_2d(self: &Arc<Self>, texture: Texture) -> Result<Texture2DHandle, TextureCreationError> {
         profiling::scope!("Add Texture 2D");
 
-        let (cmd_buf, internal_texture) = TextureManager::<Texture2DTag>::add(self, texture, false)?;
+        let scope = ValidationErrorScope::new(&self.device);
+        let added = TextureManager::<Texture2DTag>::add(self, texture, false);
+        scope.end().map_err(TextureCreationError::ValidationFailed)?;
+        let (cmd_buf, staging, internal_texture) = added?;
 
         // Handle allocation must be done _after_ any validation to prevent deletion of a handle that never gets fully added.
-        let handle = self.resource_handle_allocators.d2_texture.allocate(self);
+        let handle = allocate_texture_handle(&self.resource_handle_allocators.d2_texture, self)?;
 
-        self.instructions
-            .push(InstructionKind::AddTexture2D { handle: *handle, internal_texture, cmd_buf }, *Location::caller());
+        self.instructions.push(
+            InstructionKind::AddTexture2D { handle: *handle, internal_texture, cmd_buf, _staging: staging },
+            *Location::caller(),
+        );
 
         Ok(handle)
     }
@@ -200,15 +281,49 @@ impl Renderer {
     /// The handle will keep the texture alive. All materials created with this
     /// texture will also keep the texture alive.
     #[track_caller]
-    pub fn add_texture_2d_from_texture(self: &Arc<Self>, texture: TextureFromTexture) -> Texture2DHandle {
+    pub fn add_texture_2d_from_texture(
+        self: &Arc<Self>,
+        texture: TextureFromTexture,
+    ) -> Result<Texture2DHandle, TextureCreationError> {
         profiling::scope!("Add Texture 2D From Texture");
 
-        let handle = self.resource_handle_allocators.d2_texture.allocate(self);
+        let handle = allocate_texture_handle(&self.resource_handle_allocators.d2_texture, self)?;
 
         self.instructions
             .push(InstructionKind::AddTexture2DFromTexture { handle: *handle, texture }, *Location::caller());
 
-        handle
+        Ok(handle)
+    }
+
+    /// Add a 2D texture created outside of rend3, like one written by a video
+    /// decoder or a compute pass, without copying it. This can be used in a
+    /// [`Material`].
+    ///
+    /// The texture must be a single sampled 2D texture with one layer, made on
+    /// the renderer's device with the `TEXTURE_BINDING` usage, and of a
+    /// filterable float format. Its size, format and mip levels are read from
+    /// the texture.
+    ///
+    /// rend3 takes ownership of the texture, and destroys it once the handle
+    /// and all materials using it are dropped. To keep writing to it, borrow it
+    /// from the texture manager in `data_core` once the add has been evaluated,
+    /// and make sure those writes are submitted before the frames reading them.
+    #[track_caller]
+    pub fn import_texture_2d(
+        self: &Arc<Self>,
+        texture: wgpu::Texture,
+    ) -> Result<Texture2DHandle, TextureCreationError> {
+        profiling::scope!("Import Texture 2D");
+
+        let internal_texture = TextureManager::<Texture2DTag>::import(self, texture)?;
+
+        // Handle allocation must be done _after_ any validation to prevent deletion of a handle that never gets fully added.
+        let handle = allocate_texture_handle(&self.resource_handle_allocators.d2_texture, self)?;
+
+        self.instructions
+            .push(InstructionKind::ImportTexture2D { handle: *handle, internal_texture }, *Location::caller());
+
+        Ok(handle)
     }
 
     /// Adds a Cube texture to the renderer. This can be used as a cube
@@ -219,13 +334,18 @@ impl Renderer {
     pub fn add_texture_cube(self: &Arc<Self>, texture: Texture) -> Result<TextureCubeHandle, TextureCreationError> {
         profiling::scope!("Add Texture Cube");
 
-        let (cmd_buf, internal_texture) = TextureManager::<TextureCubeTag>::add(self, texture, true)?;
+        let scope = ValidationErrorScope::new(&self.device);
+        let added = TextureManager::<TextureCubeTag>::add(self, texture, true);
+        scope.end().map_err(TextureCreationError::ValidationFailed)?;
+        let (cmd_buf, staging, internal_texture) = added?;
 
         // Handle allocation must be done _after_ any validation to prevent deletion of a handle that never gets fully added.
-        let handle = self.resource_handle_allocators.d2c_texture.allocate(self);
+        let handle = allocate_texture_handle(&self.resource_handle_allocators.d2c_texture, self)?;
 
-        self.instructions
-            .push(InstructionKind::AddTextureCube { handle: *handle, internal_texture, cmd_buf }, *Location::caller());
+        self.instructions.push(
+            InstructionKind::AddTextureCube { handle: *handle, internal_texture, cmd_buf, _staging: staging },
+            *Location::caller(),
+        );
 
         Ok(handle)
     }
@@ -265,6 +385,29 @@ impl Renderer {
         )
     }
 
+    /// Modifies a given material in place. Cheaper than
+    /// [`Renderer::update_material`] for small changes made every frame, such
+    /// as animating texture transforms, as unchanged textures don't need to be
+    /// looked at again.
+    ///
+    /// `M` must be the type the material was added as.
+    #[track_caller]
+    pub fn update_material_with<M: Material>(
+        &self,
+        handle: &MaterialHandle,
+        modify: impl FnOnce(&mut M) + WasmNotSend + WasmNotSync + 'static,
+    ) {
+        self.instructions.push(
+            InstructionKind::ChangeMaterial {
+                handle: **handle,
+                change_invoke: Box::new(move |material_manager, device, d2_manager, mat_handle| {
+                    material_manager.update_with(device, d2_manager, mat_handle, modify)
+                }),
+            },
+            *Location::caller(),
+        )
+    }
+
     /// Adds an object to the renderer. This will create a visible object using
     /// the given mesh and materal.
     ///
@@ -278,6 +421,27 @@ impl Renderer {
         handle
     }
 
+    /// Adds an object to the renderer like [`Self::add_object`], also giving it
+    /// an id which stays the same across runs, unlike handles.
+    ///
+    /// The object can be found again with [`Self::object_by_id`]. If a live
+    /// object already has this id, it is no longer found by it.
+    #[track_caller]
+    pub fn add_object_with_id(self: &Arc<Self>, id: u64, object: Object) -> ObjectHandle {
+        let handle = self.add_object(object);
+        self.resource_handle_allocators.object_ids.insert(id, &handle);
+        handle
+    }
+
+    /// Finds an object added with [`Self::add_object_with_id`].
+    ///
+    /// Returns `None` if no object was added with this id, or if all handles
+    /// to the object have been dropped, as the id doesn't keep the object
+    /// alive.
+    pub fn object_by_id(&self, id: u64) -> Option<ObjectHandle> {
+        self.resource_handle_allocators.object_ids.get(id)
+    }
+
     /// Duplicates an existing object in the renderer, returning the new
     /// object's handle. Any changes specified in the `change` struct will be
     /// applied to the duplicated object, and the same mesh, material and
@@ -293,12 +457,82 @@ impl Renderer {
     }
 
     /// Move the given object to a new transform location.
+    ///
+    /// If the object has a parent, the transform is relative to the parent.
     #[track_caller]
     pub fn set_object_transform(&self, handle: &ObjectHandle, transform: Mat4) {
         self.instructions
             .push(InstructionKind::SetObjectTransform { handle: handle.get_raw(), transform }, *Location::caller());
     }
 
+    /// Moves the given object to a new world offset. See
+    /// [`Object::world_offset`].
+    ///
+    /// Parented objects follow their parent, so this does nothing for them.
+    #[track_caller]
+    pub fn set_object_world_offset(&self, handle: &ObjectHandle, world_offset: DVec3) {
+        self.instructions.push(
+            InstructionKind::SetObjectWorldOffset { handle: handle.get_raw(), world_offset },
+            *Location::caller(),
+        );
+    }
+
+    /// Sets the color the given object is multiplied by after shading, or
+    /// removes it with `None`. See [`Object::debug_tint`].
+    #[track_caller]
+    pub fn set_object_debug_tint(&self, handle: &ObjectHandle, tint: Option<Vec4>) {
+        self.instructions
+            .push(InstructionKind::SetObjectDebugTint { handle: handle.get_raw(), tint }, *Location::caller());
+    }
+
+    /// Attaches the given object to a parent object, so it moves along with
+    /// the parent. Passing `None` detaches it again.
+    ///
+    /// The object keeps its current world transform when attached or
+    /// detached. While attached, [`Self::set_object_transform`] sets the
+    /// transform relative to the parent, and world transforms are resolved
+    /// down the hierarchy every frame.
+    ///
+    /// A child keeps its parent alive. Parenting an object to one of its own
+    /// descendants is an error and is ignored.
+    #[track_caller]
+    pub fn set_object_parent(&self, handle: &ObjectHandle, parent: Option<&ObjectHandle>) {
+        self.instructions.push(
+            InstructionKind::SetObjectParent { handle: handle.get_raw(), parent: parent.cloned() },
+            *Location::caller(),
+        );
+    }
+
+    /// Returns the handles of every object currently alive, sorted by index.
+    ///
+    /// This reflects the objects as of the last call to
+    /// [`Self::evaluate_instructions`]. The raw handles don't keep the objects
+    /// alive.
+    pub fn object_handles(&self) -> Vec<RawObjectHandle> {
+        let mut handles: Vec<_> = self.data_core.lock().object_manager.handles().collect();
+        handles.sort_unstable_by_key(|handle| handle.idx);
+        handles
+    }
+
+    /// Returns the world space axis aligned bounding box of the given object as
+    /// `(min, max)`.
+    ///
+    /// This encloses the bounding sphere the culler tests against, so it is
+    /// conservative. Returns `None` if the object hasn't been added by
+    /// [`Self::evaluate_instructions`] yet. Changes to the transform are
+    /// likewise only reflected after the next evaluation.
+    pub fn object_bounds(&self, handle: &ObjectHandle) -> Option<(Vec3, Vec3)> {
+        let sphere = self.data_core.lock().object_manager.bounding_sphere(handle.get_raw())?;
+
+        Some((sphere.center - sphere.radius, sphere.center + sphere.radius))
+    }
+
+    /// Number of joint matrices skeletons of the mesh need, one more than the
+    /// highest joint index of its vertices. `None` if the mesh has no joints.
+    pub fn mesh_joint_count(&self, handle: &MeshHandle) -> Option<u16> {
+        self.mesh_manager.lock_internal_data()[handle.get_raw()].required_joint_count
+    }
+
     /// Sets the joint positions for a skeleton. See
     /// [Renderer::set_skeleton_joint_matrices] to set the vertex
     /// transformations directly, without having to supply two separate
@@ -328,6 +562,10 @@ impl Renderer {
     /// Note that this is not the same as the joint's transformation. See
     /// [Renderer::set_skeleton_joint_transforms] for an alternative method that
     /// allows setting the joint transformation instead.
+    ///
+    /// There must be at least as many matrices as the skeleton's mesh has
+    /// joints, see [`Renderer::mesh_joint_count`]. Otherwise an error is
+    /// logged and the update is ignored when instructions are evaluated.
     #[track_caller]
     pub fn set_skeleton_joint_matrices(&self, handle: &SkeletonHandle, joint_matrices: Vec<Mat4>) {
         self.instructions.push(
@@ -354,6 +592,9 @@ impl Renderer {
     /// fragment in the forward pass is shaded with every point light in the
     /// world.
     ///
+    /// Use [`Renderer::update_point_light`] to move or change the light
+    /// without re-adding it.
+    ///
     /// The handle will keep the light alive.
     #[track_caller]
     pub fn add_point_light(self: &Arc<Self>, light: PointLight) -> PointLightHandle {
@@ -378,6 +619,20 @@ impl Renderer {
             .push(InstructionKind::ChangePointLight { handle: handle.get_raw(), change }, *Location::caller())
     }
 
+    /// Removes every object, directional light, and point light from the
+    /// world, such as when unloading a level.
+    ///
+    /// Outstanding handles stay valid: updating them does nothing, and dropping
+    /// them no longer removes anything. Only things added before this call are
+    /// removed.
+    ///
+    /// Meshes, materials, and textures are reference counted, so they are
+    /// freed once the removed objects and your own handles to them are gone.
+    #[track_caller]
+    pub fn clear_world(&self) {
+        self.instructions.push(InstructionKind::ClearWorld, *Location::caller())
+    }
+
     /// Adds a piece of data for long term storage and convienient use in the RenderGraph
     ///
     /// The handle will keep the data alive.
@@ -405,6 +660,45 @@ impl Renderer {
         self.instructions.push(InstructionKind::SetCameraData { data }, *Location::caller())
     }
 
+    /// Sets the point in world space everything is rendered relative to.
+    /// Defaults to zero.
+    ///
+    /// Object world offsets are subtracted from the render origin in double
+    /// precision before being converted to f32, so objects near the render
+    /// origin don't jitter, no matter how far they are from the world origin.
+    /// Objects stay in place when it moves, but the camera view and light
+    /// positions are relative to the render origin.
+    ///
+    /// For camera relative rendering, set this to the position of the camera
+    /// every frame and give the camera a view without translation.
+    #[track_caller]
+    pub fn set_render_origin(&self, origin: DVec3) {
+        self.instructions.push(InstructionKind::SetRenderOrigin { origin }, *Location::caller())
+    }
+
+    /// Reserves room on the gpu for at least `additional` more objects, like
+    /// [`Vec::reserve`]. Call this before adding many objects at once.
+    ///
+    /// Object buffers have a power of two capacity, doubling whenever they
+    /// run out of room. Each time they grow, they are reallocated and copied,
+    /// so adding thousands of objects over a few frames can otherwise grow
+    /// them several times.
+    #[track_caller]
+    pub fn reserve_objects(&self, additional: usize) {
+        self.instructions.push(InstructionKind::ReserveObjects { additional }, *Location::caller())
+    }
+
+    /// Reserves room on the gpu for at least `additional` more skeleton
+    /// joints, like [`Vec::reserve`]. Call this before adding many skeletons
+    /// at once, such as when spawning a crowd.
+    ///
+    /// The joint matrices of all skeletons share one buffer, which doubles
+    /// whenever it runs out of room and is then uploaded again in full.
+    #[track_caller]
+    pub fn reserve_joints(&self, additional: usize) {
+        self.instructions.push(InstructionKind::ReserveJoints { additional }, *Location::caller())
+    }
+
     /// Swaps the front and back instruction buffer. Any world-modifiying functions
     /// called after this will be recorded for the next frame.
     ///
@@ -421,4 +715,71 @@ impl Renderer {
     pub fn evaluate_instructions(&self) -> InstructionEvaluationOutput {
         eval::evaluate_instructions(self)
     }
+
+    /// Maps a buffer slice, returning a future which completes once the
+    /// mapping is ready to be read or written.
+    ///
+    /// The device is polled without blocking at the end of every
+    /// [`RenderGraph::execute`](crate::graph::RenderGraph::execute), so the
+    /// future completes during a later frame once the gpu is done with the
+    /// buffer, without stalling. Call [`Self::poll`] to check more often.
+    pub fn map_buffer_async(
+        &self,
+        slice: BufferSlice<'_>,
+        mode: MapMode,
+    ) -> impl Future<Output = Result<(), BufferAsyncError>> + WasmNotSend + 'static {
+        let (sender, receiver) = flume::bounded(1);
+        slice.map_async(mode, move |result| {
+            let _ = sender.send(result);
+        });
+        async move {
+            // The callback is always called, so a disconnect can only happen if the device is lost.
+            receiver.recv_async().await.unwrap_or(Err(BufferAsyncError))
+        }
+    }
+
+    /// Polls the device without blocking, running the callbacks of any
+    /// finished buffer mappings. See [`Self::map_buffer_async`].
+    pub fn poll(&self) {
+        profiling::scope!("Renderer::poll");
+        self.device.poll(Maintain::Poll);
+    }
+
+    /// Creates a render pipeline, returning validation errors instead of
+    /// sending them to the device's uncaptured error handler.
+    pub fn create_render_pipeline(
+        &self,
+        desc: &RenderPipelineDescriptor<'_>,
+    ) -> Result<RenderPipeline, PipelineCreationError> {
+        let scope = ValidationErrorScope::new(&self.device);
+        let pipeline = self.device.create_render_pipeline(desc);
+        scope.end().map_err(|inner| PipelineCreationError { label: desc.label.map(str::to_owned), inner })?;
+
+        Ok(pipeline)
+    }
+
+    /// Creates a compute pipeline, returning validation errors instead of
+    /// sending them to the device's uncaptured error handler.
+    pub fn create_compute_pipeline(
+        &self,
+        desc: &ComputePipelineDescriptor<'_>,
+    ) -> Result<ComputePipeline, PipelineCreationError> {
+        let scope = ValidationErrorScope::new(&self.device);
+        let pipeline = self.device.create_compute_pipeline(desc);
+        scope.end().map_err(|inner| PipelineCreationError { label: desc.label.map(str::to_owned), inner })?;
+
+        Ok(pipeline)
+    }
+}
+
+fn allocate_texture_handle<T>(
+    allocator: &HandleAllocator<T>,
+    renderer: &Arc<Renderer>,
+) -> Result<ResourceHandle<T>, TextureCreationError>
+where
+    RawResourceHandle<T>: DeletableRawResourceHandle,
+{
+    allocator
+        .try_allocate(renderer)
+        .ok_or_else(|| TextureCreationError::TextureLimitExceeded { max: allocator.limit().unwrap_or_default() as u32 })
 }
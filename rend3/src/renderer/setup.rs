@@ -1,7 +1,7 @@
 use std::sync::Arc;
 
 use parking_lot::Mutex;
-use rend3_types::{Camera, Handedness, TextureFormat};
+use rend3_types::{Camera, DepthConvention, Handedness, TextureFormat};
 use wgpu::TextureViewDimension;
 use wgpu_profiler::GpuProfilerSettings;
 
@@ -9,17 +9,24 @@ use crate::{
     graph::GraphTextureStore,
     instruction::InstructionStreamPair,
     managers::{
-        CameraState, DirectionalLightManager, GraphStorage, MaterialManager, MeshManager, ObjectManager,
-        PointLightManager, SkeletonManager, TextureManager,
+        default_max_bindless_textures, max_bindless_textures, CameraState, DirectionalLightManager, GraphStorage,
+        MaterialManager, MeshManager, ObjectManager, PointLightManager, SkeletonManager, TextureManager,
     },
     renderer::{HandleAllocators, RendererDataCore},
-    util::{mipmap::MipmapGenerator, scatter_copy::ScatterCopy},
-    InstanceAdapterDevice, Renderer, RendererInitializationError,
+    util::{
+        blit::Blitter,
+        mipmap::MipmapGenerator,
+        scatter_copy::ScatterCopy,
+        texture_staging::{TextureStagingBelt, DEFAULT_CHUNK_SIZE},
+    },
+    InstanceAdapterDevice, Renderer, RendererInitializationError, RendererProfile,
 };
 
 pub fn create_renderer(
     iad: InstanceAdapterDevice,
     handedness: Handedness,
+    depth_convention: DepthConvention,
+    requested_bindless_textures: Option<u32>,
     aspect_ratio: Option<f32>,
 ) -> Result<Arc<Renderer>, RendererInitializationError> {
     profiling::scope!("Renderer::new");
@@ -28,26 +35,30 @@ pub fn create_renderer(
     let limits = iad.device.limits();
     let downlevel = iad.adapter.get_downlevel_capabilities();
 
-    let camera_state = CameraState::new(Camera::default(), handedness, aspect_ratio);
+    let camera_state = CameraState::new(Camera::default(), handedness, depth_convention, aspect_ratio);
 
-    let d2_texture_manager = TextureManager::new(
-        &iad.device,
-        iad.profile,
-        limits.max_sampled_textures_per_shader_stage,
-        TextureViewDimension::D2,
-    );
-    let d2c_texture_manager = TextureManager::new(
-        &iad.device,
-        iad.profile,
-        limits.max_sampled_textures_per_shader_stage,
-        TextureViewDimension::Cube,
-    );
+    let texture_limit = limits.max_sampled_textures_per_shader_stage;
+    let max_textures = match requested_bindless_textures {
+        Some(requested) if requested > max_bindless_textures(texture_limit) => {
+            return Err(RendererInitializationError::TooManyBindlessTextures {
+                requested,
+                limit: max_bindless_textures(texture_limit),
+            });
+        }
+        Some(requested) => requested,
+        None => default_max_bindless_textures(texture_limit),
+    };
+    // Only the GpuDriven profile binds textures in arrays.
+    let handle_limit = (iad.profile == RendererProfile::GpuDriven).then_some(max_textures as usize);
+
+    let d2_texture_manager = TextureManager::new(&iad.device, iad.profile, max_textures, TextureViewDimension::D2);
+    let d2c_texture_manager = TextureManager::new(&iad.device, iad.profile, max_textures, TextureViewDimension::Cube);
     let mesh_manager = MeshManager::new(&iad.device);
     let material_manager = MaterialManager::new(&iad.device);
     let object_manager = ObjectManager::new();
     let directional_light_manager = DirectionalLightManager::new(&iad.device);
     let point_light_manager = PointLightManager::new(&iad.device);
-    let skeleton_manager = SkeletonManager::new();
+    let skeleton_manager = SkeletonManager::new(&iad.device);
     let graph_storage = GraphStorage::new();
 
     let mipmap_generator = MipmapGenerator::new(
@@ -70,8 +81,12 @@ pub fn create_renderer(
         .map_err(RendererInitializationError::GpuProfilerCreation)?,
     );
 
+    let blitter = Blitter::new(&iad.device);
+
     let scatter = ScatterCopy::new(&iad.device);
 
+    let texture_staging = TextureStagingBelt::new(DEFAULT_CHUNK_SIZE);
+
     Ok(Arc::new(Renderer {
         instructions: InstructionStreamPair::new(),
 
@@ -84,8 +99,9 @@ pub fn create_renderer(
         limits,
         downlevel,
         handedness,
+        depth_convention,
 
-        resource_handle_allocators: HandleAllocators::default(),
+        resource_handle_allocators: HandleAllocators::new(handle_limit),
         mesh_manager,
         data_core: Mutex::new(RendererDataCore {
             viewport_camera_state: camera_state,
@@ -102,6 +118,8 @@ pub fn create_renderer(
         }),
 
         mipmap_generator,
+        blitter,
         scatter,
+        texture_staging,
     }))
 }
@@ -3,8 +3,9 @@
 use std::sync::Arc;
 
 use wgpu::{
-    Adapter, AdapterInfo, Backend, Backends, BufferAddress, Device, DeviceDescriptor, DeviceType, Features,
-    Gles3MinorVersion, Instance, InstanceFlags, Limits, Queue,
+    Adapter, AdapterInfo, Backend, Backends, BufferAddress, Device, DeviceDescriptor, DeviceType,
+    DownlevelCapabilities, Features, Gles3MinorVersion, Instance, InstanceFlags, Limits, Queue, TextureFormat,
+    TextureFormatFeatures,
 };
 
 #[allow(unused_imports)]
@@ -107,7 +108,7 @@ pub const CPU_REQUIRED_LIMITS: Limits = Limits {
     max_bind_groups: 4,
     max_dynamic_uniform_buffers_per_pipeline_layout: 0,
     max_dynamic_storage_buffers_per_pipeline_layout: 0,
-    max_sampled_textures_per_shader_stage: 10,
+    max_sampled_textures_per_shader_stage: 16,
     max_samplers_per_shader_stage: 2,
     max_storage_buffers_per_shader_stage: 2,
     max_storage_textures_per_shader_stage: 0,
@@ -321,6 +322,9 @@ pub struct PotentialAdapter<T> {
     pub features: Features,
     pub limits: Limits,
     pub profile: RendererProfile,
+    /// Why the adapter can't use the GpuDriven profile, if it fell back to
+    /// CpuDriven because of it.
+    pub gpu_driven_unsupported: Option<RendererInitializationError>,
 }
 impl<T> PotentialAdapter<T> {
     pub fn new(
@@ -335,16 +339,18 @@ impl<T> PotentialAdapter<T> {
         let mut features = check_features(RendererProfile::GpuDriven, inner_features);
         let mut limits = check_limits(RendererProfile::GpuDriven, &inner_limits);
         let mut profile = RendererProfile::GpuDriven;
+        let mut gpu_driven_unsupported = None;
 
         if (features.is_err() || limits.is_err() || desired_profile == Some(RendererProfile::CpuDriven))
             && desired_profile != Some(RendererProfile::GpuDriven)
         {
+            gpu_driven_unsupported = features.err().or(limits.err());
             features = check_features(RendererProfile::CpuDriven, inner_features);
             limits = check_limits(RendererProfile::CpuDriven, &inner_limits);
             profile = RendererProfile::CpuDriven;
         }
 
-        Ok(PotentialAdapter { inner, info, features: features?, limits: limits?, profile })
+        Ok(PotentialAdapter { inner, info, features: features?, limits: limits?, profile, gpu_driven_unsupported })
     }
 }
 
@@ -400,7 +406,9 @@ impl From<AdapterInfo> for ExtendedAdapterInfo {
 
 /// Container for Instance/Adapter/Device/Queue etc.
 ///
-/// Create these yourself, or call [`create_iad`].
+/// Create these yourself, or call [`create_iad`]. The capabilities of the
+/// chosen device can be queried before creating a renderer, to decide on
+/// options like MSAA, anisotropy, or wireframe rendering.
 #[derive(Clone)]
 pub struct InstanceAdapterDevice {
     pub instance: Arc<Instance>,
@@ -408,12 +416,41 @@ pub struct InstanceAdapterDevice {
     pub device: Arc<Device>,
     pub queue: Arc<Queue>,
     pub profile: RendererProfile,
+    /// Information about the chosen adapter.
     pub info: ExtendedAdapterInfo,
 }
 
+impl InstanceAdapterDevice {
+    /// Features enabled on the device.
+    pub fn features(&self) -> Features {
+        self.device.features()
+    }
+
+    /// Limits of the device.
+    pub fn limits(&self) -> Limits {
+        self.device.limits()
+    }
+
+    /// Capabilities of the adapter beyond the WebGPU baseline.
+    pub fn downlevel_capabilities(&self) -> DownlevelCapabilities {
+        self.adapter.get_downlevel_capabilities()
+    }
+
+    /// Usages and MSAA sample counts the adapter supports for the given
+    /// format.
+    pub fn texture_format_features(&self, format: TextureFormat) -> TextureFormatFeatures {
+        self.adapter.get_texture_format_features(format)
+    }
+}
+
 /// Creates an Instance/Adapter/Device/Queue using the given choices. Tries to
 /// get the best combination.
 ///
+/// If `desired_profile` is `None`, the GpuDriven profile is used where the
+/// adapter supports it, falling back to CpuDriven with a logged warning
+/// otherwise. Pass `Some(RendererProfile::GpuDriven)` to fail instead of
+/// falling back.
+///
 /// **NOTE:** Some adapters will not advertise all of its supported features.
 /// The `additional_features` parameter can be used to explicitly request
 /// additional features during device creation.
@@ -510,6 +547,9 @@ pub async fn create_iad(
             log::debug!("Chosen features: {:#?}", adapter.features);
             log::debug!("Chosen limits: {:#?}", adapter.limits);
             log::debug!("Chosen profile: {:#?}", adapter.profile);
+            if let Some(ref reason) = adapter.gpu_driven_unsupported {
+                log::warn!("Adapter doesn't support the GpuDriven profile, falling back to CpuDriven: {reason}");
+            }
 
             let (device, queue) = adapter
                 .inner
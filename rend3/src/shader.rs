@@ -21,6 +21,8 @@ struct Rend3ShaderSources;
 pub struct ShaderConfig {
     pub profile: Option<RendererProfile>,
     pub position_attribute_offset: usize,
+    /// Set when the renderer uses [`DepthConvention::Forward`](crate::types::DepthConvention::Forward).
+    pub forward_z: bool,
 }
 
 pub struct ShaderVertexBufferConfig {
@@ -328,7 +330,7 @@ mod tests {
         let mut pp = ShaderPreProcessor::new();
         pp.add_shader("simple", "{{include \"other\"}} simple");
         pp.add_shader("other", "other");
-        let config = ShaderConfig { profile: None, position_attribute_offset: 0 };
+        let config = ShaderConfig { profile: None, position_attribute_offset: 0, forward_z: false };
         let output = pp.render_shader("simple", &config, None).unwrap();
 
         assert_eq!(output, "other simple");
@@ -339,7 +341,7 @@ mod tests {
         let mut pp = ShaderPreProcessor::new();
         pp.add_shader("simple", "{{include \"other\"}} simple");
         pp.add_shader("other", "{{include \"simple\"}} other");
-        let config = ShaderConfig { profile: None, position_attribute_offset: 0 };
+        let config = ShaderConfig { profile: None, position_attribute_offset: 0, forward_z: false };
         let output = pp.render_shader("simple", &config, None).unwrap();
 
         assert_eq!(output, " other simple");
@@ -349,7 +351,7 @@ mod tests {
     fn error_include() {
         let mut pp = ShaderPreProcessor::new();
         pp.add_shader("simple", "{{include \"other\"}} simple");
-        let config = ShaderConfig { profile: None, position_attribute_offset: 0 };
+        let config = ShaderConfig { profile: None, position_attribute_offset: 0, forward_z: false };
         let output = pp.render_shader("simple", &config, None);
 
         assert!(output.is_err(), "Expected error, got {output:?}");
@@ -359,7 +361,7 @@ mod tests {
     fn no_arg_include() {
         let mut pp = ShaderPreProcessor::new();
         pp.add_shader("simple", "{{include}} simple");
-        let config = ShaderConfig { profile: None, position_attribute_offset: 0 };
+        let config = ShaderConfig { profile: None, position_attribute_offset: 0, forward_z: false };
         let output = pp.render_shader("simple", &config, None);
 
         assert!(output.is_err(), "Expected error, got {output:?}");
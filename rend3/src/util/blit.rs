@@ -0,0 +1,105 @@
+//! Full-screen texture blitting.
+
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+use rend3_types::TextureFormat;
+use wgpu::{
+    BindGroupLayout, BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingType, ColorTargetState, ColorWrites,
+    Device, FragmentState, FrontFace, MultisampleState, PipelineLayout, PipelineLayoutDescriptor, PolygonMode,
+    PrimitiveState, PrimitiveTopology, RenderPipeline, RenderPipelineDescriptor, SamplerBindingType, ShaderModule,
+    ShaderStages, TextureSampleType, TextureViewDimension, VertexState,
+};
+
+use crate::{format_sso, util::typedefs::FastHashMap};
+
+/// Pipelines which copy one texture to another with a full-screen triangle,
+/// filtering with a user provided sampler.
+///
+/// Used by [`RenderGraph::add_blit`](crate::graph::RenderGraph::add_blit).
+pub struct Blitter {
+    pub(crate) texture_bgl: BindGroupLayout,
+    pub(crate) sampler_bgl: BindGroupLayout,
+    sm: ShaderModule,
+    pll: PipelineLayout,
+    pipelines: RwLock<FastHashMap<TextureFormat, Arc<RenderPipeline>>>,
+}
+
+impl Blitter {
+    pub fn new(device: &Device) -> Self {
+        profiling::scope!("Blitter::new");
+
+        let texture_bgl = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("blit texture bgl"),
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Texture {
+                    sample_type: TextureSampleType::Float { filterable: true },
+                    view_dimension: TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            }],
+        });
+
+        let sampler_bgl = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("blit sampler bgl"),
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                count: None,
+            }],
+        });
+
+        // The box filtered mipmap entry point is a plain sampled full-screen blit.
+        let sm = device.create_shader_module(wgpu::include_wgsl!("../../shaders/mipmap.wgsl"));
+
+        let pll = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("blit pipeline layout"),
+            bind_group_layouts: &[&texture_bgl, &sampler_bgl],
+            push_constant_ranges: &[],
+        });
+
+        Self { texture_bgl, sampler_bgl, sm, pll, pipelines: RwLock::new(FastHashMap::default()) }
+    }
+
+    /// Get the pipeline which blits into a target of the given format,
+    /// creating it if needed.
+    pub fn pipeline(&self, device: &Device, format: TextureFormat) -> Arc<RenderPipeline> {
+        if let Some(pipeline) = self.pipelines.read().get(&format) {
+            return Arc::clone(pipeline);
+        }
+
+        let pipeline = Arc::new(self.build_pipeline(device, format));
+        Arc::clone(self.pipelines.write().entry(format).or_insert(pipeline))
+    }
+
+    fn build_pipeline(&self, device: &Device, format: TextureFormat) -> RenderPipeline {
+        let label = format_sso!("blit pipeline {:?}", format);
+        profiling::scope!("blit pipeline");
+        device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some(&label),
+            layout: Some(&self.pll),
+            vertex: VertexState { module: &self.sm, entry_point: "vs_main", buffers: &[] },
+            primitive: PrimitiveState {
+                topology: PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: FrontFace::Cw,
+                cull_mode: None,
+                unclipped_depth: false,
+                polygon_mode: PolygonMode::Fill,
+                conservative: false,
+            },
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+            fragment: Some(FragmentState {
+                module: &self.sm,
+                entry_point: "fs_box",
+                targets: &[Some(ColorTargetState { format, blend: None, write_mask: ColorWrites::all() })],
+            }),
+            multiview: None,
+        })
+    }
+}
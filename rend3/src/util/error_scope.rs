@@ -30,20 +30,7 @@ impl<'a> AllocationErrorScope<'a> {
         // End has been called, no need to error.
         self.ended = true;
 
-        // The future we get from wgpu will always be immedately ready on webgl/native. We can't
-        // reasonably handle failures on webgpu. As such we don't want to wait
-        // for the future to complete, just manually poll it once.
-
-        let mut future = self.device.pop_error_scope();
-        let pin = Pin::new(&mut future);
-        match pin.poll(&mut Context::from_waker(&noop_waker::noop_waker())) {
-            // We got an error, so return an error.
-            Poll::Ready(Some(error)) => Err(error),
-            // We got no error, so return success.
-            Poll::Ready(None) => Ok(()),
-            // We're on webgpu, pretend everything always works.
-            Poll::Pending => Ok(()),
-        }
+        pop_error_scope(self.device)
     }
 }
 
@@ -54,3 +41,62 @@ impl<'a> Drop for AllocationErrorScope<'a> {
         }
     }
 }
+
+/// Helper for working with validation error scopes, turning what would go to
+/// the device's uncaptured error handler into an error we can return.
+///
+/// Like [`AllocationErrorScope`], this will always return success on WebGPU.
+///
+/// Error scopes belong to the whole device, not to the thread pushing them.
+/// While a scope is open, it also captures errors caused by other threads
+/// using the device, and scopes pushed and popped concurrently can swap
+/// errors. Scopes are not serialized, as they are held across whole texture
+/// uploads, so when resources are created from several threads a
+/// validation error may be reported by a different call than the one that
+/// caused it.
+#[must_use = "All error scopes must end in a call to `end`"]
+pub struct ValidationErrorScope<'a> {
+    device: &'a Device,
+    /// Used to communicate with the destructor if `end` was called on this or not.
+    ended: bool,
+}
+
+impl<'a> ValidationErrorScope<'a> {
+    /// Create a new ValidationErrorScope on this device.
+    pub fn new(device: &'a Device) -> Self {
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+        Self { device, ended: false }
+    }
+
+    pub fn end(mut self) -> Result<(), wgpu::Error> {
+        // End has been called, no need to error.
+        self.ended = true;
+
+        pop_error_scope(self.device)
+    }
+}
+
+impl<'a> Drop for ValidationErrorScope<'a> {
+    fn drop(&mut self) {
+        if !self.ended {
+            log::error!("ValidationErrorScope dropped without calling `end`");
+        }
+    }
+}
+
+fn pop_error_scope(device: &Device) -> Result<(), wgpu::Error> {
+    // The future we get from wgpu will always be immedately ready on webgl/native. We can't
+    // reasonably handle failures on webgpu. As such we don't want to wait
+    // for the future to complete, just manually poll it once.
+
+    let mut future = device.pop_error_scope();
+    let pin = Pin::new(&mut future);
+    match pin.poll(&mut Context::from_waker(&noop_waker::noop_waker())) {
+        // We got an error, so return an error.
+        Poll::Ready(Some(error)) => Err(error),
+        // We got no error, so return success.
+        Poll::Ready(None) => Ok(()),
+        // We're on webgpu, pretend everything always works.
+        Poll::Pending => Ok(()),
+    }
+}
@@ -46,20 +46,31 @@ impl FreelistDerivedBuffer {
     }
 
     pub fn use_index(&mut self, index: usize) {
-        if index > self.reserved_count {
-            self.reserved_count = index.next_power_of_two();
-        }
+        self.reserve(index + 1);
 
         self.stale.push(index);
     }
 
+    /// Makes room for at least `count` entries when the buffer is next
+    /// applied. The capacity is always a power of two, so it at least doubles
+    /// each time it grows, and reserving ahead of adding many entries avoids
+    /// growing over several frames.
+    pub fn reserve(&mut self, count: usize) {
+        if count > self.reserved_count {
+            self.reserved_count = count.next_power_of_two();
+        }
+    }
+
+    /// Writes every index used since the last call, returning how many
+    /// entries were written. Nothing is uploaded if no index was used.
     pub fn apply<T, F>(
         &mut self,
         device: &Device,
         encoder: &mut CommandEncoder,
         scatter: &ScatterCopy,
         mut get_value: F,
-    ) where
+    ) -> usize
+    where
         T: ShaderSize + WriteInto + 'static,
         F: FnMut(usize) -> T,
     {
@@ -86,15 +97,22 @@ impl FreelistDerivedBuffer {
         }
 
         if self.stale.is_empty() {
-            return;
+            return 0;
         }
 
+        // An index may be used many times in a frame, it only needs writing once.
+        self.stale.sort_unstable();
+        self.stale.dedup();
+        let written = self.stale.len();
+
         let data = self.stale.drain(..).map(|idx| {
             let data = get_value(idx);
             ScatterData { word_offset: u32::try_from((idx as u64 * self.rounded_size) / 4).unwrap(), data }
         });
 
         scatter.execute_copy(device, encoder, &self.inner, data);
+
+        written
     }
 }
 
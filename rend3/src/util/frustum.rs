@@ -4,6 +4,7 @@
 
 use encase::ShaderType;
 use glam::{Mat4, Vec3, Vec3A, Vec4Swizzles};
+use rend3_types::DepthConvention;
 
 /// Represents a point in space and a radius from that point.
 #[derive(Debug, Default, Clone, Copy, ShaderType)]
@@ -94,6 +95,12 @@ pub struct Frustum {
 
 impl Frustum {
     pub fn from_matrix(matrix: Mat4) -> Self {
+        Self::from_matrix_with_convention(matrix, DepthConvention::Reverse)
+    }
+
+    /// Creates a frustum from a projection matrix using the given depth
+    /// convention to find the near plane.
+    pub fn from_matrix_with_convention(matrix: Mat4, depth_convention: DepthConvention) -> Self {
         let mat_arr = matrix.to_cols_array_2d();
 
         let left = Plane::new(
@@ -126,14 +133,18 @@ impl Frustum {
 
         // no far plane as we have infinite depth
 
-        // this is the far plane in the algorithm, but we're using inverse Z, so near
-        // and far get flipped.
-        let near = Plane::new(
-            mat_arr[0][3] - mat_arr[0][2],
-            mat_arr[1][3] - mat_arr[1][2],
-            mat_arr[2][3] - mat_arr[2][2],
-            mat_arr[3][3] - mat_arr[3][2],
-        );
+        let near = match depth_convention {
+            // this is the far plane in the algorithm, but we're using inverse Z, so near
+            // and far get flipped.
+            DepthConvention::Reverse => Plane::new(
+                mat_arr[0][3] - mat_arr[0][2],
+                mat_arr[1][3] - mat_arr[1][2],
+                mat_arr[2][3] - mat_arr[2][2],
+                mat_arr[3][3] - mat_arr[3][2],
+            ),
+            // With a [0, 1] depth range, the near plane is just the z row.
+            DepthConvention::Forward => Plane::new(mat_arr[0][2], mat_arr[1][2], mat_arr[2][2], mat_arr[3][2]),
+        };
 
         Self {
             left: left.normalize(),
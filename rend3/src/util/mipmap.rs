@@ -2,7 +2,7 @@
 
 use arrayvec::ArrayVec;
 use parking_lot::RwLock;
-use rend3_types::TextureFormat;
+use rend3_types::{MipmapFilter, MipmapGeneration, TextureFormat};
 use thiserror::Error;
 use wgpu::{
     AddressMode, BindGroup, BindGroupLayout, BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingType, Color,
@@ -36,7 +36,7 @@ pub struct MipmapGenerator {
     sampler_bg: BindGroup,
     sm: ShaderModule,
     pll: PipelineLayout,
-    pipelines: RwLock<FastHashMap<TextureFormat, RenderPipeline>>,
+    pipelines: RwLock<FastHashMap<(TextureFormat, MipmapGeneration), RenderPipeline>>,
 }
 
 impl MipmapGenerator {
@@ -98,7 +98,10 @@ impl MipmapGenerator {
 
         let pipelines = default_formats
             .iter()
-            .map(|&format| (format, Self::build_blit_pipeline(device, format, &pll, &sm)))
+            .map(|&format| {
+                let generation = MipmapGeneration::default();
+                ((format, generation), Self::build_blit_pipeline(device, format, generation, &pll, &sm))
+            })
             .collect();
 
         Self { texture_bgl, sampler_bg, sm, pll, pipelines: RwLock::new(pipelines) }
@@ -107,10 +110,19 @@ impl MipmapGenerator {
     fn build_blit_pipeline(
         device: &Device,
         format: TextureFormat,
+        generation: MipmapGeneration,
         pll: &PipelineLayout,
         sm: &ShaderModule,
     ) -> RenderPipeline {
-        let label = format_sso!("mipmap pipeline {:?}", format);
+        let label = format_sso!("mipmap pipeline {:?} {:?}", format, generation.filter);
+        let entry_point = match (generation.filter, generation.renormalize_normals) {
+            (MipmapFilter::Box, false) => "fs_box",
+            (MipmapFilter::Box, true) => "fs_box_normal",
+            (MipmapFilter::Triangle, false) => "fs_triangle",
+            (MipmapFilter::Triangle, true) => "fs_triangle_normal",
+            (MipmapFilter::Kaiser, false) => "fs_kaiser",
+            (MipmapFilter::Kaiser, true) => "fs_kaiser_normal",
+        };
         profiling::scope!("mipmap pipeline");
         device.create_render_pipeline(&RenderPipelineDescriptor {
             label: Some(&label),
@@ -129,7 +141,7 @@ impl MipmapGenerator {
             multisample: MultisampleState::default(),
             fragment: Some(FragmentState {
                 module: sm,
-                entry_point: "fs_main",
+                entry_point,
                 targets: &[Some(ColorTargetState { format, blend: None, write_mask: ColorWrites::all() })],
             }),
             multiview: None,
@@ -142,6 +154,7 @@ impl MipmapGenerator {
         encoder: &mut CommandEncoder,
         texture: &Texture,
         desc: &TextureDescriptor,
+        generation: MipmapGeneration,
     ) -> Result<(), MipmapGenerationError> {
         profiling::scope!("generating mipmaps");
         let mips: Result<ArrayVec<_, 14>, _> = (0..desc.size.max_mips(desc.dimension))
@@ -161,18 +174,19 @@ impl MipmapGenerator {
         let mips = mips?;
 
         let mut read_pipelines = self.pipelines.read();
-        let pipeline = match read_pipelines.get(&desc.format) {
+        let key = (desc.format, generation);
+        let pipeline = match read_pipelines.get(&key) {
             Some(p) => p,
             None => {
                 drop(read_pipelines);
 
                 self.pipelines
                     .write()
-                    .insert(desc.format, Self::build_blit_pipeline(device, desc.format, &self.pll, &self.sm));
+                    .insert(key, Self::build_blit_pipeline(device, desc.format, generation, &self.pll, &self.sm));
 
                 read_pipelines = self.pipelines.read();
 
-                read_pipelines.get(&desc.format).unwrap()
+                read_pipelines.get(&key).unwrap()
             }
         };
 
@@ -0,0 +1,297 @@
+//! Staging memory shared by texture uploads.
+//!
+//! Uploading textures through [`wgpu::Queue::write_texture`] allocates
+//! staging memory for every texture, which spikes memory usage when many or
+//! large textures are added at once. The [`TextureStagingBelt`] instead keeps
+//! a set of mapped chunks which uploads are packed into, and reuses each chunk
+//! once the copies out of it have been submitted.
+
+use std::sync::Arc;
+
+use parking_lot::{Mutex, RwLock};
+use wgpu::{
+    util::align_to, Buffer, BufferDescriptor, BufferUsages, CommandEncoder, Device, ImageCopyBuffer, ImageCopyTexture,
+    ImageDataLayout, MapMode, Origin3d, Texture, TextureAspect, TextureDescriptor, TextureDimension,
+    COPY_BYTES_PER_ROW_ALIGNMENT,
+};
+
+use crate::util::error_scope::AllocationErrorScope;
+
+/// Size of the chunks uploads are packed into. Textures larger than this get
+/// a chunk of their own, which is freed instead of reused.
+pub const DEFAULT_CHUNK_SIZE: u64 = 1 << 24;
+
+struct Chunk {
+    buffer: Arc<Buffer>,
+    size: u64,
+    offset: u64,
+    /// One reference per upload whose copies haven't been evaluated yet.
+    users: Arc<()>,
+    /// Held shared by uploads while they copy into the mapping, and
+    /// exclusively while unmapping the chunk.
+    mapping: Arc<RwLock<()>>,
+}
+
+impl Chunk {
+    fn in_use(&self) -> bool {
+        Arc::strong_count(&self.users) > 1
+    }
+}
+
+#[derive(Default)]
+struct BeltState {
+    /// Mapped chunks which can be written to.
+    free: Vec<Chunk>,
+    /// Unmapped chunks, waiting for the copies out of them to be submitted.
+    closed: Vec<Chunk>,
+}
+
+/// Keeps the chunk an upload was staged in from being reused. Drop it once
+/// the commands copying out of the chunk have been evaluated.
+pub struct StagingAllocation {
+    _users: Arc<()>,
+}
+
+/// Ring of mapped staging buffers texture uploads are packed into.
+///
+/// Uploads may be staged from any thread. The belt is only locked while space
+/// is reserved, so several threads can copy their data into the staging
+/// memory at once. The copies out of a chunk must be
+/// submitted before the next call to [`Self::begin_frame`], which the
+/// renderer calls when evaluating instructions.
+pub struct TextureStagingBelt {
+    chunk_size: u64,
+    state: Mutex<BeltState>,
+    sender: flume::Sender<Chunk>,
+    receiver: flume::Receiver<Chunk>,
+}
+
+impl TextureStagingBelt {
+    pub fn new(chunk_size: u64) -> Self {
+        let (sender, receiver) = flume::unbounded();
+        Self { chunk_size, state: Mutex::new(BeltState::default()), sender, receiver }
+    }
+
+    /// Stages the first `mip_levels` levels of `data`, laid out like
+    /// [`wgpu::util::DeviceExt::create_texture_with_data`] with layer major
+    /// order, and records copies of them into `texture`.
+    pub fn upload(
+        &self,
+        device: &Device,
+        encoder: &mut CommandEncoder,
+        texture: &Texture,
+        desc: &TextureDescriptor<'_>,
+        mip_levels: u32,
+        data: &[u8],
+    ) -> Result<StagingAllocation, wgpu::Error> {
+        profiling::scope!("TextureStagingBelt::upload");
+
+        let copies = copy_layout(desc, mip_levels);
+        let staged_size = copies.last().map_or(0, |copy| copy.staging_offset + copy.staged_size());
+
+        let mut state = self.state.lock();
+        let chunk = self.allocate(&mut state, device, staged_size)?;
+        let base_offset = chunk.offset;
+        chunk.offset = align_to(base_offset + staged_size, COPY_BYTES_PER_ROW_ALIGNMENT as u64);
+        let buffer = Arc::clone(&chunk.buffer);
+        let users = Arc::clone(&chunk.users);
+        let mapping_lock = Arc::clone(&chunk.mapping);
+        // Keeps the chunk mapped until the data is copied, without holding up other uploads.
+        let mapped = mapping_lock.read();
+        drop(state);
+
+        {
+            let mut mapping = buffer.slice(base_offset..base_offset + staged_size).get_mapped_range_mut();
+            for copy in &copies {
+                let rows = data[copy.data_offset..][..copy.data_size()].chunks_exact(copy.row_bytes as usize);
+                let staged_rows =
+                    mapping[copy.staging_offset as usize..].chunks_exact_mut(copy.padded_row_bytes as usize);
+                for (staged_row, row) in staged_rows.zip(rows) {
+                    staged_row[..row.len()].copy_from_slice(row);
+                }
+            }
+        }
+        drop(mapped);
+
+        for copy in &copies {
+            encoder.copy_buffer_to_texture(
+                ImageCopyBuffer {
+                    buffer: &buffer,
+                    layout: ImageDataLayout {
+                        offset: base_offset + copy.staging_offset,
+                        bytes_per_row: Some(copy.padded_row_bytes),
+                        rows_per_image: Some(copy.rows),
+                    },
+                },
+                ImageCopyTexture {
+                    texture,
+                    mip_level: copy.mip_level,
+                    origin: Origin3d { x: 0, y: 0, z: copy.layer },
+                    aspect: TextureAspect::All,
+                },
+                copy.size,
+            );
+        }
+
+        Ok(StagingAllocation { _users: users })
+    }
+
+    /// Finds a mapped chunk with `size` bytes free, creating one if needed.
+    fn allocate<'a>(&self, state: &'a mut BeltState, device: &Device, size: u64) -> Result<&'a mut Chunk, wgpu::Error> {
+        state.free.extend(self.receiver.try_iter());
+
+        if let Some(idx) = state.free.iter().position(|chunk| chunk.offset + size <= chunk.size) {
+            return Ok(&mut state.free[idx]);
+        }
+
+        let chunk_size = size.max(self.chunk_size);
+        let scope = AllocationErrorScope::new(device);
+        let buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("texture staging belt chunk"),
+            size: chunk_size,
+            usage: BufferUsages::MAP_WRITE | BufferUsages::COPY_SRC,
+            mapped_at_creation: true,
+        });
+        scope.end()?;
+
+        state.free.push(Chunk {
+            buffer: Arc::new(buffer),
+            size: chunk_size,
+            offset: 0,
+            users: Arc::new(()),
+            mapping: Arc::new(RwLock::new(())),
+        });
+        Ok(state.free.last_mut().unwrap())
+    }
+
+    /// Unmaps chunks with staged uploads so their copies can be submitted,
+    /// and maps chunks whose copies were all submitted again for reuse.
+    ///
+    /// Every upload evaluated before this call must have been submitted.
+    pub fn begin_frame(&self) {
+        profiling::scope!("TextureStagingBelt::begin_frame");
+
+        let mut state = self.state.lock();
+        let state = &mut *state;
+
+        let (done, waiting) = state.closed.drain(..).partition::<Vec<_>, _>(|chunk| !chunk.in_use());
+        state.closed = waiting;
+        for chunk in done {
+            // Oversized chunks only held a single texture, so they aren't worth keeping around.
+            if chunk.size > self.chunk_size {
+                continue;
+            }
+            let sender = self.sender.clone();
+            let buffer = Arc::clone(&chunk.buffer);
+            buffer.slice(..).map_async(MapMode::Write, move |result| {
+                if result.is_ok() {
+                    let _ = sender.send(Chunk { offset: 0, ..chunk });
+                }
+            });
+        }
+
+        let (used, unused) = state.free.drain(..).partition::<Vec<_>, _>(|chunk| chunk.offset != 0);
+        state.free = unused;
+        for chunk in used {
+            {
+                // Wait for uploads still copying into the chunk.
+                let _mapped = chunk.mapping.write();
+                chunk.buffer.unmap();
+            }
+            state.closed.push(chunk);
+        }
+    }
+}
+
+/// Copy of a single mip level of a single layer out of the staging buffer.
+struct StagedCopy {
+    layer: u32,
+    mip_level: u32,
+    size: wgpu::Extent3d,
+    rows: u32,
+    row_bytes: u32,
+    padded_row_bytes: u32,
+    data_offset: usize,
+    staging_offset: u64,
+}
+
+impl StagedCopy {
+    fn data_size(&self) -> usize {
+        self.row_bytes as usize * self.rows as usize * self.size.depth_or_array_layers as usize
+    }
+
+    fn staged_size(&self) -> u64 {
+        self.padded_row_bytes as u64 * self.rows as u64 * self.size.depth_or_array_layers as u64
+    }
+}
+
+/// Where each level of the source data goes in the staging buffer. Rows are
+/// padded to the alignment buffer to texture copies require.
+fn copy_layout(desc: &TextureDescriptor<'_>, mip_levels: u32) -> Vec<StagedCopy> {
+    let (block_width, block_height) = desc.format.block_dimensions();
+    let block_size = desc.format.block_copy_size(None).unwrap();
+    let layers = match desc.dimension {
+        TextureDimension::D3 => 1,
+        _ => desc.array_layer_count(),
+    };
+
+    let mut copies = Vec::with_capacity((layers * mip_levels) as usize);
+    let mut data_offset = 0;
+    let mut staging_offset = 0;
+    for layer in 0..layers {
+        for mip_level in 0..mip_levels {
+            let mut size = desc.mip_level_size(mip_level).unwrap();
+            if desc.dimension != TextureDimension::D3 {
+                size.depth_or_array_layers = 1;
+            }
+            let size = size.physical_size(desc.format);
+            let row_bytes = size.width / block_width * block_size;
+
+            let copy = StagedCopy {
+                layer,
+                mip_level,
+                size,
+                rows: size.height / block_height,
+                row_bytes,
+                padded_row_bytes: align_to(row_bytes, COPY_BYTES_PER_ROW_ALIGNMENT),
+                data_offset,
+                staging_offset,
+            };
+            data_offset += copy.data_size();
+            staging_offset = align_to(staging_offset + copy.staged_size(), COPY_BYTES_PER_ROW_ALIGNMENT as u64);
+            copies.push(copy);
+        }
+    }
+    copies
+}
+
+#[cfg(test)]
+mod tests {
+    use wgpu::{Extent3d, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages};
+
+    use super::copy_layout;
+
+    #[test]
+    fn rows_are_padded() {
+        let desc = TextureDescriptor {
+            label: None,
+            size: Extent3d { width: 100, height: 4, depth_or_array_layers: 6 },
+            mip_level_count: 2,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::Rgba8Unorm,
+            usage: TextureUsages::COPY_DST,
+            view_formats: &[],
+        };
+
+        let copies = copy_layout(&desc, 2);
+        assert_eq!(copies.len(), 12);
+
+        let (first, second) = (&copies[0], &copies[1]);
+        assert_eq!((first.layer, first.mip_level, first.rows), (0, 0, 4));
+        assert_eq!((first.row_bytes, first.padded_row_bytes), (400, 512));
+        assert_eq!((second.layer, second.mip_level, second.rows), (0, 1, 2));
+        assert_eq!((second.data_offset, second.staging_offset), (1600, 2048));
+        assert_eq!((copies[2].layer, copies[2].data_offset, copies[2].staging_offset), (1, 2000, 2560));
+    }
+}